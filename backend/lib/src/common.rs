@@ -1,7 +1,9 @@
+use std::ops::{Add, Mul};
+
 use ark_ed_on_bls12_381::{constraints::EdwardsVar, EdwardsAffine, EdwardsProjective, Fq, Fr};
 use ark_bls12_381::Bls12_381;
 use ark_ff::{Fp256, Fp256Parameters};
-use ark_r1cs_std::{fields::fp::FpVar, boolean::Boolean, prelude::EqGadget, R1CSVar};
+use ark_r1cs_std::{alloc::AllocVar, fields::fp::FpVar, boolean::Boolean, prelude::EqGadget, R1CSVar};
 use ark_relations::r1cs::ConstraintSystemRef;
 use ark_std::{One, Zero};
 
@@ -85,6 +87,113 @@ pub fn fe_to_be_hex_str<F: Fp256Parameters>(fe: &Fp256<F>) -> String {
     hex::encode(bytes)
 }
 
+/// Interprets `bytes` as a _little endian_ integer and reduces it modulo the field order,
+/// matching what `Boolean::le_bits_to_fp_var` computes for the same bytes in-circuit (a plain
+/// weighted sum of bits, which is automatically reduced mod the field order by field
+/// arithmetic). Unlike `fe_from_be_hex_str`, this never panics: arbitrary byte strings (e.g. a
+/// PRF or hash output wider than the field) are always accepted.
+pub fn fe_from_le_bytes_mod_order<F: ark_ff::PrimeField>(bytes: &[u8]) -> F {
+    F::from_le_bytes_mod_order(bytes)
+}
+
+/// Splits `fe` into `num_limbs` little-endian limbs of `limb_bits` bits each, asserting `fe` fits
+/// in `num_limbs * limb_bits` bits. Native counterpart of `enforce_decompose`; the standard
+/// building block range checks and bitwise opcodes need to reason about a field element's bit
+/// pattern at a chosen granularity, instead of the whole-element byte conversions above.
+pub fn decompose_fe(fe: &OuterScalarField, limb_bits: usize, num_limbs: usize) -> Vec<OuterScalarField> {
+    let num: num_bigint::BigUint = (*fe).into();
+    assert!(num.bits() as usize <= limb_bits * num_limbs, "field element does not fit in the declared limb width");
+    let mask = (num_bigint::BigUint::from(1u8) << limb_bits) - num_bigint::BigUint::from(1u8);
+    (0..num_limbs).map(|i| OuterScalarField::from((&num >> (i * limb_bits)) & &mask)).collect()
+}
+
+/// Bit-decomposes `fe` into its little-endian bits (`FE_BYTES * 8` of them). Native counterpart
+/// of `enforce_bits_le` / `OuterScalarVar::to_bits_le`.
+pub fn fe_to_bits_le(fe: &OuterScalarField) -> Vec<bool> {
+    decompose_fe(fe, 1, crate::constants::FE_BYTES * 8).iter().map(|b| *b == OuterScalarField::one()).collect()
+}
+
+/// In-circuit counterpart of `fe_to_bits_le`: allocates one `Boolean` witness per bit
+/// (`FE_BYTES * 8` of them) and enforces, via `ark_r1cs_std`'s own `Boolean::le_bits_to_fp_var`,
+/// that their little-endian weighted sum equals `var`.
+pub fn enforce_bits_le(var: &OuterScalarVar) -> ark_relations::r1cs::Result<Vec<Boolean<OuterScalarField>>> {
+    let cs = var.cs();
+    let native_bits: ark_relations::r1cs::Result<Vec<bool>> = var.value().map(|fe| fe_to_bits_le(&fe));
+    let bits: Vec<Boolean<OuterScalarField>> = (0..crate::constants::FE_BYTES * 8).map(|i| {
+        Boolean::new_witness(cs.clone(), || native_bits.clone().map(|b| b[i]))
+    }).collect::<ark_relations::r1cs::Result<_>>()?;
+    Boolean::le_bits_to_fp_var(&bits)?.enforce_equal(var)?;
+    Ok(bits)
+}
+
+/// In-circuit counterpart of `decompose_fe`: groups `enforce_bits_le`'s full bit decomposition
+/// into `num_limbs` limbs of `limb_bits` bits each. Each limb is already range-bound to
+/// `[0, 2^limb_bits)` by construction (it's a weighted sum of `Boolean`s), and recomposing only
+/// the low `num_limbs * limb_bits` of the field's bits and enforcing the result equal to `var`
+/// additionally proves every bit beyond that window is zero -- i.e. that `var` fits the declared
+/// width, exactly the way `decompose_fe`'s assertion does natively.
+pub fn enforce_decompose(var: &OuterScalarVar, limb_bits: usize, num_limbs: usize) -> ark_relations::r1cs::Result<Vec<OuterScalarVar>> {
+    let cs = var.cs();
+    let bits = enforce_bits_le(var)?;
+    assert!(limb_bits * num_limbs <= bits.len(), "declared limb width narrower than the field's own bit width");
+
+    let limbs: Vec<OuterScalarVar> = bits.chunks(limb_bits).take(num_limbs)
+        .map(Boolean::le_bits_to_fp_var)
+        .collect::<ark_relations::r1cs::Result<_>>()?;
+
+    let mut recomposed = OuterScalarVar::zero();
+    for (i, limb) in limbs.iter().enumerate() {
+        let weight = OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(2).pow([(i * limb_bits) as u64]))?;
+        recomposed = recomposed.add(&limb.clone().mul(&weight));
+    }
+    recomposed.enforce_equal(var)?;
+
+    Ok(limbs)
+}
+
+/// Proves `var` fits in `num_bits` bits, built on `enforce_decompose` at limb width 1. Unlike the
+/// processor's own `enforce_range`/`is_in_range` (which return a `Boolean` the caller can still
+/// combine with other conditions, e.g. to allow an out-of-range value under a dummy-cycle flag),
+/// this hard-enforces the bound -- for callers outside the processor that just need a plain range
+/// check on a field element.
+pub fn range_check_var(var: &OuterScalarVar, num_bits: usize) -> ark_relations::r1cs::Result<()> {
+    enforce_decompose(var, 1, num_bits)?;
+    Ok(())
+}
+
+/// Decodes `fe` as a two's-complement-in-field signed integer of `size_bytes` bytes and formats
+/// it in base-10, for processor values the caller knows to be `size_bytes` wide and signed: a
+/// value at or above `2^(8*size_bytes - 1)` is taken to be a negative encoding (the field stores
+/// `p - |x|` for negative `x`), so the magnitude is recovered as `p - num` and printed with a
+/// leading `-`; otherwise it prints as-is. With `signed = false` this is equivalent to
+/// `fe_to_string`. Only meaningful for `size_bytes` well below `FE_BYTES`, so that the `2^(8*size_bytes-1)`
+/// threshold sits far enough below the field modulus to tell the two encodings apart -- exactly
+/// what `enforce_signed_range`'s in-circuit check enforces.
+pub fn fe_to_signed_string<F: Fp256Parameters>(fe: &Fp256<F>, size_bytes: usize, signed: bool) -> String {
+    let num: num_bigint::BigUint = (*fe).into();
+    if !signed {
+        return num.to_string();
+    }
+    let half = num_bigint::BigUint::from(1u8) << (8 * size_bytes - 1);
+    if num >= half {
+        let max: num_bigint::BigUint = (Fp256::<F>::zero() - Fp256::<F>::one()).into();
+        let modulus = max + num_bigint::BigUint::from(1u8);
+        format!("-{}", modulus - num)
+    } else {
+        num.to_string()
+    }
+}
+
+/// Encodes a signed `i128` as a field element using two's-complement-in-field, the inverse of
+/// `fe_to_signed_string`: non-negative values are stored as-is, negative `x` as `p - |x|`.
+pub fn signed_fe_from_i128(x: i128) -> OuterScalarField {
+    if x >= 0 {
+        OuterScalarField::from(x as u128)
+    } else {
+        OuterScalarField::zero() - OuterScalarField::from(x.unsigned_abs())
+    }
+}
+
 /// converts a number represented in _big endian_ hexadecimal string format
 /// to an usize
 pub fn usize_from_be_hex_str(hex_str: &str) -> usize {
@@ -95,6 +204,118 @@ pub fn usize_from_be_hex_str(hex_str: &str) -> usize {
     usize::from_be_bytes(bytes)
 }
 
+/// Folds a big-endian byte slice into a `usize`, the raw-byte counterpart of
+/// `usize_from_be_hex_str`'s hex-string padding: left-pads with zero bytes if shorter than 8,
+/// and keeps only the trailing (least significant) 8 bytes if longer. Used by `crypto::bloom_filter` to turn a slice
+/// of a serial number into one hash index.
+pub fn usize_from_be_bytes_folded(bytes: &[u8]) -> usize {
+    let mut buf = [0u8; 8];
+    let n = bytes.len().min(8);
+    buf[8 - n..].copy_from_slice(&bytes[bytes.len() - n..]);
+    usize::from_be_bytes(buf)
+}
+
+/// Parses a little-endian byte slice of exactly `size` bytes into a field element, the
+/// little-endian, width-generalized counterpart of `usize_from_be_hex_str`'s fixed-width parsing.
+/// Used to read a declared-width integer (e.g. a record-payload field) out of a raw byte buffer
+/// before handing it to `UintField::from_field`.
+pub fn read_uint(bytes: &[u8], size: usize) -> OuterScalarField {
+    assert_eq!(bytes.len(), size);
+    OuterScalarField::from(num_bigint::BigUint::from_bytes_le(bytes))
+}
+
+/// A fixed-width unsigned integer that packs into (and out of) a field element, giving
+/// record-payload packing and the processor a typed, bounds-checked alternative to passing raw
+/// `OuterScalarField` values around untyped.
+pub trait UintField: Sized {
+    /// Width of this integer type in bytes.
+    const WIDTH_BYTES: usize;
+
+    /// Packs `self` into a field element. Panics if `value >= 2^(8*WIDTH_BYTES)` -- the native
+    /// integer type backing `Self` can be wider than `WIDTH_BYTES` (see `UintU120`), so that
+    /// bound isn't guaranteed by construction and has to be checked here instead.
+    fn to_field(&self) -> OuterScalarField;
+
+    /// Unpacks `fe` into `Self`, rejecting it if `fe >= 2^(8*WIDTH_BYTES)`.
+    fn from_field(fe: &OuterScalarField) -> Option<Self>;
+}
+
+#[macro_export]
+macro_rules! impl_uint_field {
+    ($name: ident, $native: ty, $width_bytes: expr) => {
+        #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+        pub struct $name(pub $native);
+
+        impl UintField for $name {
+            const WIDTH_BYTES: usize = $width_bytes;
+
+            fn to_field(&self) -> OuterScalarField {
+                let value = self.0 as u128;
+                assert!(
+                    value < (1u128 << (8 * Self::WIDTH_BYTES)),
+                    "{} value {} exceeds its declared width of {} bytes",
+                    stringify!($name), value, Self::WIDTH_BYTES
+                );
+                OuterScalarField::from(value)
+            }
+
+            fn from_field(fe: &OuterScalarField) -> Option<Self> {
+                assert!(Self::WIDTH_BYTES <= crate::constants::MAX_BYTES_UINT);
+                let num: num_bigint::BigUint = (*fe).into();
+                let max = num_bigint::BigUint::from(1u8) << (8 * Self::WIDTH_BYTES);
+                if num >= max {
+                    // too large to fit inside $name's declared width
+                    return None;
+                }
+                let mut le_bytes = num.to_bytes_le();
+                le_bytes.resize(16, 0u8);
+                let mut buf = [0u8; 16];
+                buf.copy_from_slice(&le_bytes);
+                Some(Self(u128::from_le_bytes(buf) as $native))
+            }
+        }
+    }
+}
+
+impl_uint_field!(UintU8, u8, 1);
+impl_uint_field!(UintU16, u16, 2);
+impl_uint_field!(UintU32, u32, 4);
+impl_uint_field!(UintU64, u64, 8);
+impl_uint_field!(UintU120, u128, 15);   // widest value the processor's MAX_BYTES_UINT allows
+
+/// Type-erased `UintField`, for contexts (e.g. record-payload schemas) that pick a width at
+/// runtime instead of at the type level.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnyUintField {
+    U8(UintU8),
+    U16(UintU16),
+    U32(UintU32),
+    U64(UintU64),
+    U120(UintU120),
+}
+
+impl AnyUintField {
+    pub fn width_bytes(&self) -> usize {
+        match self {
+            AnyUintField::U8(_) => UintU8::WIDTH_BYTES,
+            AnyUintField::U16(_) => UintU16::WIDTH_BYTES,
+            AnyUintField::U32(_) => UintU32::WIDTH_BYTES,
+            AnyUintField::U64(_) => UintU64::WIDTH_BYTES,
+            AnyUintField::U120(_) => UintU120::WIDTH_BYTES,
+        }
+    }
+
+    pub fn to_field(&self) -> OuterScalarField {
+        match self {
+            AnyUintField::U8(x) => x.to_field(),
+            AnyUintField::U16(x) => x.to_field(),
+            AnyUintField::U32(x) => x.to_field(),
+            AnyUintField::U64(x) => x.to_field(),
+            AnyUintField::U120(x) => x.to_field(),
+        }
+    }
+}
+
 #[cfg(feature="circuit-trace")]
 #[inline]
 pub fn dbg_bytes(bytes: &[ark_r1cs_std::prelude::UInt8<OuterScalarField>]) {
@@ -175,6 +396,16 @@ pub trait FeFromLeBytesConverter<A> {
     fn from_le_bytes(bytes: &[u8]) -> Option<A>;
 }
 
+pub trait FeFromLeBytesConverterCt<A> {
+    /// Constant-time counterpart to `FeFromLeBytesConverter::from_le_bytes`: decodes a fixed
+    /// `FE_BYTES`-length little-endian buffer without any data-dependent branch or early return,
+    /// so neither control flow nor timing reveals whether the input overflowed the field modulus.
+    /// Intended for secret-key material and PRF seeds, where `from_le_bytes`'s variable-time
+    /// `BigUint` comparison and early `return None` could otherwise leak bits of the input; public
+    /// values should keep using the faster, variable-time `from_le_bytes`.
+    fn from_le_bytes_ct(bytes: &[u8; crate::constants::FE_BYTES]) -> subtle::CtOption<A>;
+}
+
 #[macro_export]
 macro_rules! impl_fe_to_larger {
     ($in_type: ident, $out_type: ident) => {
@@ -231,6 +462,64 @@ impl_fe_to_smaller!(OuterScalarField, InnerEdScalarField);
 impl_from_le_bytes!(OuterScalarField);
 impl_from_le_bytes!(InnerEdScalarField);
 
+#[macro_export]
+macro_rules! impl_from_le_bytes_ct {
+    ($out_type: ident) => {
+        impl FeFromLeBytesConverterCt<$out_type> for FeConverter {
+            fn from_le_bytes_ct(bytes: &[u8; crate::constants::FE_BYTES]) -> subtle::CtOption<$out_type> {
+                use subtle::{Choice, ConditionallySelectable, ConstantTimeEq, ConstantTimeGreater};
+                use ark_ff::PrimeField;
+
+                // modulus - 1, the largest representable value: a public constant, so computing
+                // it (unlike comparing the secret input against it) need not run in constant time
+                let max: num_bigint::BigUint = ($out_type::zero() - $out_type::one()).into();
+                let mut max_bytes = max.to_bytes_le();
+                max_bytes.resize(crate::constants::FE_BYTES, 0u8);
+
+                // walk from the most significant byte down, propagating an "equal so far" flag:
+                // `over_max` latches true the first time a byte differs while every more
+                // significant byte compared equal -- the standard big-number comparison, just
+                // without branching on the outcome at each step
+                let mut over_max = Choice::from(0u8);
+                let mut equal_so_far = Choice::from(1u8);
+                for i in (0..crate::constants::FE_BYTES).rev() {
+                    let byte_greater = bytes[i].ct_gt(&max_bytes[i]);
+                    let byte_equal = bytes[i].ct_eq(&max_bytes[i]);
+                    over_max |= equal_so_far & byte_greater;
+                    equal_so_far &= byte_equal;
+                }
+                let in_range = !over_max;
+
+                // zero the buffer on the overflow path before decoding, so the element built
+                // below never depends on the value of out-of-range input bytes
+                let mut safe_bytes = [0u8; crate::constants::FE_BYTES];
+                for i in 0..crate::constants::FE_BYTES {
+                    safe_bytes[i] = u8::conditional_select(&0u8, &bytes[i], in_range);
+                }
+
+                // build the element straight from its 4 little-endian u64 limbs instead of going
+                // through `BigUint` (which trims leading zero limbs and so iterates a number of
+                // times that depends on the value's own magnitude): every step below runs exactly
+                // 4 limb reads regardless of `safe_bytes`, so the decode itself has no
+                // data-dependent loop bounds left.
+                let mut limbs = [0u64; 4];
+                for (i, limb) in limbs.iter_mut().enumerate() {
+                    let mut word = [0u8; 8];
+                    word.copy_from_slice(&safe_bytes[i * 8..(i + 1) * 8]);
+                    *limb = u64::from_le_bytes(word);
+                }
+                let repr = ark_ff::BigInteger256::new(limbs);
+                let elem = $out_type::from_repr(repr).expect("safe_bytes is masked to always be below the field modulus");
+                subtle::CtOption::new(elem, in_range)
+            }
+        }
+    }
+}
+
+// constant-time counterparts, for key material and PRF seeds
+impl_from_le_bytes_ct!(OuterScalarField);
+impl_from_le_bytes_ct!(InnerEdScalarField);
+
 #[cfg(test)]
 mod tests {
     use std::str::FromStr;
@@ -306,6 +595,48 @@ mod tests {
         assert_eq!(check_max, None);
     }
 
+    #[test]
+    fn test_field_to_from_bytes_ct() {
+        let one = OuterScalarField::from(1);
+        let mut one_bytes = [0u8; FE_BYTES];
+        one_bytes[0] = 1;
+        let check_one: OuterScalarField = FeConverter::from_le_bytes_ct(&one_bytes).unwrap();
+        assert_eq!(one, check_one);
+
+        let med = OuterScalarField::from_str("41420").unwrap();  // binary big-endian: 1010 0001 1100 1100
+        let mut med_bytes = [0u8; FE_BYTES];
+        med_bytes[0] = 0b11001100;  // least-significant byte
+        med_bytes[1] = 0b10100001;
+        let check_med: OuterScalarField = FeConverter::from_le_bytes_ct(&med_bytes).unwrap();
+        assert_eq!(med, check_med);
+    }
+
+    #[test]
+    fn test_field_from_overflow_bytes_ct() {
+        let max = OuterScalarField::zero() - OuterScalarField::one();
+        let max_bytes = to_bytes!(max).unwrap();
+        let mut over_bytes = [0u8; FE_BYTES];
+        over_bytes.copy_from_slice(&max_bytes);
+        over_bytes[FE_BYTES - 1] = over_bytes[FE_BYTES - 1].wrapping_add(1);  // bump past the modulus
+
+        let check_over: subtle::CtOption<OuterScalarField> = FeConverter::from_le_bytes_ct(&over_bytes);
+        assert!(bool::from(check_over.is_none()));
+    }
+
+    #[test]
+    fn test_fe_to_signed_string() {
+        let neg = signed_fe_from_i128(-100);
+        assert_eq!(fe_to_signed_string(&neg, 1, true), "-100");
+        assert_eq!(fe_to_signed_string(&neg, 1, false), fe_to_string(&neg));
+
+        let pos = signed_fe_from_i128(100);
+        assert_eq!(fe_to_signed_string(&pos, 1, true), "100");
+
+        // the field's additive inverse of 1 is exactly how signed_fe_from_i128 encodes -1
+        let minus_one = OuterScalarField::zero() - OuterScalarField::one();
+        assert_eq!(fe_to_signed_string(&minus_one, 1, true), "-1");
+    }
+
     #[test]
     fn test_fe_from_to_hex_str() {
         let fe: OuterScalarField = fe_from_be_hex_str("10aa");
@@ -320,4 +651,78 @@ mod tests {
         let u = usize_from_be_hex_str("10aa");
         assert_eq!(u, 4266);
     }
+
+    #[test]
+    fn test_read_uint() {
+        let bytes = [0xccu8, 0xa1];  // little-endian 0xa1cc
+        let fe = read_uint(&bytes, 2);
+        assert_eq!(fe, OuterScalarField::from(0xa1ccu64));
+    }
+
+    #[test]
+    fn test_uint_field_round_trip() {
+        let x = UintU16(1234);
+        let fe = x.to_field();
+        assert_eq!(UintU16::from_field(&fe).unwrap(), x);
+
+        let any = AnyUintField::U16(x);
+        assert_eq!(any.width_bytes(), 2);
+        assert_eq!(any.to_field(), fe);
+    }
+
+    #[test]
+    fn test_uint_field_rejects_overflow() {
+        let too_large = OuterScalarField::from(256u64);   // doesn't fit in a single byte
+        assert_eq!(UintU8::from_field(&too_large), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "exceeds its declared width")]
+    fn test_uint_field_to_field_rejects_out_of_range_construction() {
+        // UintU120's native u128 can represent values that don't fit in its declared 15-byte
+        // (120-bit) width, so a value built by bypassing `from_field` must still be caught here.
+        UintU120(u128::MAX).to_field();
+    }
+
+    #[test]
+    fn test_decompose_fe_round_trip() {
+        let fe = OuterScalarField::from(0xa1ccu64);
+        let limbs = decompose_fe(&fe, 8, 2);
+        assert_eq!(limbs, vec![OuterScalarField::from(0xccu64), OuterScalarField::from(0xa1u64)]);
+    }
+
+    #[test]
+    fn test_fe_to_bits_le() {
+        let fe = OuterScalarField::from(0b101u64);
+        let bits = fe_to_bits_le(&fe);
+        assert!(bits[0] && !bits[1] && bits[2]);
+        assert!(bits[3..].iter().all(|b| !b));
+    }
+
+    #[test]
+    fn test_enforce_bits_le_accepts_correct_decomposition() {
+        let cs: ark_relations::r1cs::ConstraintSystemRef<OuterScalarField> = ark_relations::r1cs::ConstraintSystem::new_ref();
+        let var = OuterScalarVar::new_witness(cs.clone(), || Ok(OuterScalarField::from(0b101u64))).unwrap();
+        let bits = enforce_bits_le(&var).unwrap();
+        assert!(bits[0].value().unwrap() && !bits[1].value().unwrap() && bits[2].value().unwrap());
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn test_enforce_decompose_round_trip() {
+        let cs: ark_relations::r1cs::ConstraintSystemRef<OuterScalarField> = ark_relations::r1cs::ConstraintSystem::new_ref();
+        let var = OuterScalarVar::new_witness(cs.clone(), || Ok(OuterScalarField::from(0xa1ccu64))).unwrap();
+        let limbs = enforce_decompose(&var, 8, 2).unwrap();
+        assert_eq!(limbs[0].value().unwrap(), OuterScalarField::from(0xccu64));
+        assert_eq!(limbs[1].value().unwrap(), OuterScalarField::from(0xa1u64));
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn test_range_check_var_rejects_value_over_width() {
+        let cs: ark_relations::r1cs::ConstraintSystemRef<OuterScalarField> = ark_relations::r1cs::ConstraintSystem::new_ref();
+        let var = OuterScalarVar::new_witness(cs.clone(), || Ok(OuterScalarField::from(256u64))).unwrap();
+        range_check_var(&var, 8).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
 }
\ No newline at end of file
@@ -52,6 +52,32 @@ pub const PRF_OID_SEED: u8 = 4;
 /// Seed for PRF when deriving fresh values.
 pub const PRF_FRESH_VAL_SEED: u8 = 5;
 
+/// Seed for PRF when hashing a VRF input `alpha` to a curve point (try-and-increment).
+pub const PRF_VRF_H2C_SEED: u8 = 6;
+
+/// Seed for PRF when hashing an OPRF password input to a curve point (try-and-increment).
+pub const PRF_OPRF_H2C_SEED: u8 = 7;
+
+/// Seed for PRF when deriving a candidate secret key from an OPRF output.
+pub const PRF_OPRF_KDF_SEED: u8 = 8;
+
+/// Seed for PRF when deriving a diversified owner address from a public key and diversifier.
+pub const PRF_DIV_ADDR_SEED: u8 = 9;
+
+/// Seed for PRF when deriving an external account's secret key from a recovery seed/phrase.
+pub const PRF_ACCOUNT_SEED: u8 = 10;
+
+/// Number of bits in the spent-serials bloom filter's bit array (see `crypto::bloom_filter`),
+/// sized to keep the false-positive rate low for the expected number of serials ever spent.
+#[cfg(not(feature="tiny"))]
+pub const BLOOM_FILTER_BITS: usize = 1 << 20;
+#[cfg(feature="tiny")]
+pub const BLOOM_FILTER_BITS: usize = 1 << 8;
+
+/// Number of independent hash indices probed per bloom-filter `insert`/`maybe_contains`; trades
+/// false-positive rate (lower with more indices) against per-op cost (one array touch per index).
+pub const BLOOM_FILTER_K: usize = 4;
+
 
 // ********** NON-CONFIGURABLE CONSTANTS **********
 // !! Do not edit unless you know what you are doing !!
@@ -78,6 +104,25 @@ pub const RAND_BYTES: usize = PRF_BLOCK_BYTES;
 /// The maximum number of bytes allowed for a uint in the processor.
 pub const MAX_BYTES_UINT: usize = 15;   // = 120 bits
 
+/// Default per-transaction gas budget for the ZK processor (see `ZkProcessor::gas_limit` /
+/// `OPCODE_GAS`), equal to `NOF_PROCESSOR_CYCLES` times the heaviest opcode's cost
+/// (`GAS_COST_MEM_OP` = 10), so a full program of memory ops uses up exactly the default limit
+/// without exceeding it.
+pub const DEFAULT_GAS_LIMIT: u64 = (NOF_PROCESSOR_CYCLES as u64) * 10;
+
+/// Depth of the spent-serials sparse Merkle tree (see `crypto::spent_serials`). Unlike
+/// `TREE_HEIGHT` (an enumerable, append-only tree sized to the number of records ever created),
+/// this tree is keyed directly by a Poseidon digest of the serial number itself, so its depth
+/// bounds the probability of two unrelated serials colliding on the same leaf path rather than
+/// the number of leaves. 64 bits keeps `access_input`'s added constraint cost to one
+/// non-membership path (64 Poseidon two-to-one hashes) per input record while keeping the
+/// birthday-bound collision probability (~ n^2/2^64 for n spent serials) negligible for any
+/// realistic chain lifetime.
+#[cfg(not(feature="tiny"))]
+pub const SPENT_SMT_DEPTH: usize = 64;
+#[cfg(feature="tiny")]
+pub const SPENT_SMT_DEPTH: usize = 8;
+
 
 pub fn data_log_constants() {
     crate::data_log!(format!("{{\"config\": {{\"TREE_HEIGHT\": {}, \"NOF_TX_RECORDS\": {}, \"NOF_TX_FRESH\": {}, \"NOF_RECORD_PAYLOAD_ELEMENTS\": {}, \"NOF_PROCESSOR_REGISTERS\": {}, \"NOF_PROCESSOR_CYCLES\": {}}}}}",
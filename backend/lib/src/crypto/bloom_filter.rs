@@ -0,0 +1,99 @@
+//! A probabilistic fast path for "has this serial number already been spent?", sitting in front
+//! of `spent_serials::SpentSerialsSmt`'s authoritative (but comparatively expensive) tree lookup.
+//! A negative probe is a proof the serial is unspent, at the cost of touching `BLOOM_FILTER_K`
+//! bits instead of walking a Merkle path; a positive probe (a genuine match, or a false positive)
+//! still has to fall through to the exact check.
+
+use crate::common::usize_from_be_bytes_folded;
+use crate::constants::{BLOOM_FILTER_BITS, BLOOM_FILTER_K, SN_BYTES};
+use crate::infrastructure::record::Serial;
+
+/// Slices `serial` into `BLOOM_FILTER_K` (roughly) equal byte ranges and folds each one into a
+/// bit index, the same byte-folding `usize_from_be_hex_str` does for a full hex string, just
+/// applied per slice and reduced modulo the filter's bit count.
+fn hash_indices(serial: &Serial) -> impl Iterator<Item = usize> + '_ {
+    let chunk_len = (SN_BYTES / BLOOM_FILTER_K).max(1);
+    (0..BLOOM_FILTER_K).map(move |i| {
+        let start = (i * chunk_len).min(SN_BYTES - 1);
+        let end = (start + chunk_len).min(SN_BYTES);
+        usize_from_be_bytes_folded(&serial[start..end]) % BLOOM_FILTER_BITS
+    })
+}
+
+/// A compact bit array over the `BLOOM_FILTER_BITS`-sized index space `hash_indices` maps serial
+/// numbers into. See the module doc comment for how it's meant to be used.
+pub struct BloomFilter {
+    bits: Vec<bool>,
+    nof_inserted: usize,
+}
+
+impl BloomFilter {
+    pub fn new() -> BloomFilter {
+        BloomFilter { bits: vec![false; BLOOM_FILTER_BITS], nof_inserted: 0 }
+    }
+
+    /// Sets every bit `serial` hashes to. Idempotent, like `SpentSerialsSmt::insert`: inserting
+    /// an already-set serial again only flips already-true bits.
+    pub fn insert(&mut self, serial: &Serial) {
+        for idx in hash_indices(serial) {
+            self.bits[idx] = true;
+        }
+        self.nof_inserted += 1;
+        crate::data_log!(format!("{{\"bloom_filter\": {{\"nof_inserted\": {}, \"saturation\": {}}}}}", self.nof_inserted, self.saturation()));
+    }
+
+    /// `false` proves `serial` was never passed to `insert`; `true` means "maybe", and the
+    /// caller must fall through to the authoritative check (e.g. `SpentSerialsSmt::witness`).
+    pub fn maybe_contains(&self, serial: &Serial) -> bool {
+        hash_indices(serial).all(|idx| self.bits[idx])
+    }
+
+    /// Fraction of bits currently set, i.e. how close the filter is to saturating (and its
+    /// false-positive rate climbing towards uselessness).
+    pub fn saturation(&self) -> f64 {
+        self.bits.iter().filter(|b| **b).count() as f64 / self.bits.len() as f64
+    }
+}
+
+impl Default for BloomFilter {
+    fn default() -> Self {
+        BloomFilter::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test_serial(tag: u8) -> Serial {
+        [tag; SN_BYTES]
+    }
+
+    #[test]
+    fn test_fresh_serial_is_not_maybe_contained() {
+        let filter = BloomFilter::new();
+        assert!(!filter.maybe_contains(&test_serial(1)));
+    }
+
+    #[test]
+    fn test_inserted_serial_is_maybe_contained() {
+        let mut filter = BloomFilter::new();
+        filter.insert(&test_serial(1));
+        assert!(filter.maybe_contains(&test_serial(1)));
+    }
+
+    #[test]
+    fn test_unrelated_serial_unaffected_by_insert() {
+        let mut filter = BloomFilter::new();
+        filter.insert(&test_serial(1));
+        assert!(!filter.maybe_contains(&test_serial(2)));
+    }
+
+    #[test]
+    fn test_saturation_increases_after_insert() {
+        let mut filter = BloomFilter::new();
+        assert_eq!(filter.saturation(), 0.0);
+        filter.insert(&test_serial(1));
+        assert!(filter.saturation() > 0.0);
+    }
+}
@@ -0,0 +1,129 @@
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{to_bytes, PrimeField};
+use ark_sponge::{poseidon::PoseidonSponge, CryptographicSponge, FieldBasedCryptographicSponge};
+use ark_std::{rand::Rng, UniformRand};
+
+use crate::common::*;
+use crate::crypto::elgamal_ext::ExtSecretKey;
+use crate::crypto::poseidon::HybridPoseidonParams;
+
+/// A non-interactive proof that an ElGamal ciphertext `(c1, c2)` decrypts to `shared_key` under
+/// `pk`, without revealing the secret key: a Chaum-Pedersen proof of equality between
+/// `log_generator(pk)` and `log_c1(c2 - shared_key)`, both of which equal `sk`. Lets
+/// `Record::open` disclose a record's `shared_key` (and, through it, selected plaintext fields)
+/// to a third party while keeping the owner's `secret_key` itself secret -- the building block
+/// `infrastructure::record::RecordOpening` uses for its selective-disclosure proof.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DecryptionProof {
+    pub r1: InnerEdAffine,
+    pub r2: InnerEdAffine,
+    pub s: InnerEdScalarField,
+}
+
+pub struct ElGamalDleq;
+
+impl ElGamalDleq {
+    /// Proves that `shared_key = c2 - sk*c1` for the `sk` behind `pk = sk*generator`.
+    pub fn prove<R: Rng>(
+        params: &HybridPoseidonParams,
+        sk: &ExtSecretKey<InnerEdProjective>,
+        pk: &InnerEdAffine,
+        c1: &InnerEdAffine,
+        c2: &InnerEdAffine,
+        shared_key: &InnerEdAffine,
+        rng: &mut R,
+    ) -> DecryptionProof {
+        let k = InnerEdScalarField::rand(rng);
+        let r1 = params.elgamal_params.generator.mul(k).into_affine();
+        let r2 = c1.mul(k).into_affine();
+        let c = Self::challenge(params, pk, c1, c2, shared_key, &r1, &r2);
+        let s = k + c * sk.0.0;
+        DecryptionProof { r1, r2, s }
+    }
+
+    /// Verifies a `DecryptionProof` produced by `prove`.
+    pub fn verify(
+        params: &HybridPoseidonParams,
+        pk: &InnerEdAffine,
+        c1: &InnerEdAffine,
+        c2: &InnerEdAffine,
+        shared_key: &InnerEdAffine,
+        proof: &DecryptionProof,
+    ) -> bool {
+        let c = Self::challenge(params, pk, c1, c2, shared_key, &proof.r1, &proof.r2);
+
+        let lhs1 = params.elgamal_params.generator.mul(proof.s);
+        let rhs1 = proof.r1.into_projective() + pk.mul(c);
+        if lhs1 != rhs1 {
+            return false;
+        }
+
+        let c2_minus_shared_key = c2.into_projective() - shared_key.into_projective();
+        let lhs2 = c1.mul(proof.s);
+        let rhs2 = proof.r2.into_projective() + c2_minus_shared_key.mul(c);
+        lhs2 == rhs2
+    }
+
+    /// Derives the Fiat-Shamir challenge binding the statement (`pk`, `c1`, `c2`, `shared_key`)
+    /// and both commitments (`r1`, `r2`) -- mirrors `Schnorr::challenge`.
+    fn challenge(
+        params: &HybridPoseidonParams,
+        pk: &InnerEdAffine,
+        c1: &InnerEdAffine,
+        c2: &InnerEdAffine,
+        shared_key: &InnerEdAffine,
+        r1: &InnerEdAffine,
+        r2: &InnerEdAffine,
+    ) -> InnerEdScalarField {
+        let mut poseidon = PoseidonSponge::new(&params.poseidon_params);
+        poseidon.absorb(&vec![
+            pk.x, pk.y, c1.x, c1.y, c2.x, c2.y, shared_key.x, shared_key.y, r1.x, r1.y, r2.x, r2.y,
+        ]);
+        let c = poseidon.squeeze_native_field_elements(1)[0];
+        InnerEdScalarField::from_le_bytes_mod_order(&to_bytes![c].unwrap())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_std::test_rng;
+
+    use crate::crypto::elgamal_ext::derive_pk_from_sk;
+    use crate::crypto::poseidon::HybridPoseidonCipher;
+
+    use super::*;
+
+    #[test]
+    fn test_prove_and_verify() {
+        let rng = &mut test_rng();
+        let params = HybridPoseidonCipher::setup(rng);
+        let sk = ExtSecretKey::rand(rng);
+        let pk = derive_pk_from_sk(&params.elgamal_params, &sk.0);
+
+        let shared_key = InnerEdAffine::rand(rng);
+        let r = InnerEdScalarField::rand(rng);
+        let c1 = params.elgamal_params.generator.mul(r).into_affine();
+        let c2 = (shared_key.into_projective() + pk.mul(r)).into_affine();
+
+        let proof = ElGamalDleq::prove(&params, &sk, &pk, &c1, &c2, &shared_key, rng);
+        assert!(ElGamalDleq::verify(&params, &pk, &c1, &c2, &shared_key, &proof));
+    }
+
+    #[test]
+    fn test_verify_rejects_wrong_shared_key() {
+        let rng = &mut test_rng();
+        let params = HybridPoseidonCipher::setup(rng);
+        let sk = ExtSecretKey::rand(rng);
+        let pk = derive_pk_from_sk(&params.elgamal_params, &sk.0);
+
+        let shared_key = InnerEdAffine::rand(rng);
+        let r = InnerEdScalarField::rand(rng);
+        let c1 = params.elgamal_params.generator.mul(r).into_affine();
+        let c2 = (shared_key.into_projective() + pk.mul(r)).into_affine();
+
+        let proof = ElGamalDleq::prove(&params, &sk, &pk, &c1, &c2, &shared_key, rng);
+
+        let wrong_shared_key = InnerEdAffine::rand(rng);
+        assert!(!ElGamalDleq::verify(&params, &pk, &c1, &c2, &wrong_shared_key, &proof));
+    }
+}
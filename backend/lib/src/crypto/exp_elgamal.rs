@@ -0,0 +1,264 @@
+use std::collections::HashMap;
+
+use ark_crypto_primitives::encryption::elgamal::{ElGamal, Parameters, PublicKey, Randomness};
+use ark_crypto_primitives::encryption::AsymmetricEncryptionScheme;
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{to_bytes, Zero};
+use ark_std::rand::Rng;
+use ark_std::UniformRand;
+
+use crate::common::*;
+
+type MyElgamal = ElGamal<InnerEdProjective>;
+
+/// An exponential-ElGamal ciphertext, i.e. the usual `(c1, c2)` ElGamal pair, but encrypting
+/// `m*generator` rather than `m` directly so that ciphertexts are additively homomorphic.
+pub type Output = (InnerEdAffine, InnerEdAffine);
+
+pub struct ExpElGamal;
+
+impl ExpElGamal {
+    /// Encodes `m` as `m*generator` and encrypts it under `pk`. The resulting ciphertext is
+    /// additively homomorphic: `homomorphic_add`ing two such ciphertexts encrypts the sum of
+    /// their payloads, at the cost of needing `decode` (a bounded discrete-log search) rather
+    /// than plain decryption to recover `m`.
+    pub fn encrypt(
+        params: &Parameters<InnerEdProjective>,
+        pk: &PublicKey<InnerEdProjective>,
+        m: InnerEdScalarField,
+        randomness: &Randomness<InnerEdProjective>,
+    ) -> Output {
+        let encoded = params.generator.mul(m).into_affine();
+        MyElgamal::encrypt(params, pk, &encoded, randomness).unwrap()
+    }
+
+    /// Homomorphically adds two ciphertexts: if `a` encrypts `m` and `b` encrypts `m'`, the
+    /// result encrypts `m+m'`.
+    pub fn homomorphic_add(a: &Output, b: &Output) -> Output {
+        ((a.0.into_projective() + b.0.into_projective()).into_affine(), (a.1.into_projective() + b.1.into_projective()).into_affine())
+    }
+
+    /// Re-randomizes `ciphertext` for unlinkability: `c1 += r*generator`, `c2 += r*pk`. The
+    /// payload is left unchanged.
+    pub fn rerandomize<R: Rng>(params: &Parameters<InnerEdProjective>, pk: &PublicKey<InnerEdProjective>, ciphertext: &Output, rng: &mut R) -> Output {
+        let r = InnerEdScalarField::rand(rng);
+        let c1 = (ciphertext.0.into_projective() + params.generator.mul(r)).into_affine();
+        let c2 = (ciphertext.1.into_projective() + pk.mul(r)).into_affine();
+        (c1, c2)
+    }
+}
+
+/// A baby-step/giant-step table for recovering `m` from `m*generator`, for `m` in a bounded
+/// range `[0, 2^range_bits)`. Built once per `range_bits` and reused across decodes.
+pub struct DiscreteLogTable {
+    baby_steps: u64,
+    giant_steps: u64,
+    table: HashMap<Vec<u8>, u64>,
+    giant_step_point: InnerEdProjective,
+}
+
+impl DiscreteLogTable {
+    /// Precomputes the baby-step table `{j*generator -> j}` for `j` in `[0, 2^(range_bits/2))`.
+    pub fn new(params: &Parameters<InnerEdProjective>, range_bits: u32) -> DiscreteLogTable {
+        let baby_steps = 1u64 << (range_bits / 2);
+        let giant_steps = (1u64 << range_bits) / baby_steps + 1;
+
+        let generator = params.generator.into_projective();
+        let mut table = HashMap::with_capacity(baby_steps as usize);
+        let mut acc = InnerEdProjective::zero();
+        for j in 0..baby_steps {
+            table.insert(to_bytes![acc.into_affine()].unwrap(), j);
+            acc += generator;
+        }
+
+        let giant_step_point = params.generator.mul(InnerEdScalarField::from(baby_steps));
+        DiscreteLogTable { baby_steps, giant_steps, table, giant_step_point }
+    }
+
+    /// Recovers `m` from `encoded = m*generator`. Returns `None` if `m` lies outside the
+    /// range this table was built for.
+    pub fn decode(&self, encoded: &InnerEdAffine) -> Option<u64> {
+        let mut target = encoded.into_projective();
+        for i in 0..self.giant_steps {
+            if let Some(j) = self.table.get(&to_bytes![target.into_affine()].unwrap()) {
+                return Some(i * self.baby_steps + j);
+            }
+            target -= self.giant_step_point;
+        }
+        None
+    }
+}
+
+pub mod constraints {
+    use std::marker::PhantomData;
+
+    use ark_crypto_primitives::encryption::elgamal::constraints::OutputVar;
+    use ark_ec::ProjectiveCurve;
+    use ark_ff::PrimeField;
+    use ark_r1cs_std::prelude::*;
+    use ark_relations::r1cs::SynthesisError;
+
+    use crate::crypto::elgamal_ext::{ConstraintF, ElGamalDecGadget, ElGamalEncGadget, MyParametersVar, SecretKeyVar};
+
+    pub struct ExpElGamalEncGadget<C: ProjectiveCurve, GG: CurveVar<C, ConstraintF<C>>>
+    where
+        for<'a> &'a GG: GroupOpsBounds<'a, C, GG>,
+    {
+        #[doc(hidden)]
+        _curve: PhantomData<*const C>,
+        _group_var: PhantomData<*const GG>,
+    }
+
+    impl<C, GG> ExpElGamalEncGadget<C, GG>
+    where
+        C: ProjectiveCurve,
+        GG: CurveVar<C, ConstraintF<C>>,
+        for<'a> &'a GG: GroupOpsBounds<'a, C, GG>,
+        ConstraintF<C>: PrimeField,
+    {
+        /// Encodes `m` as `m*generator` and encrypts it, the in-circuit counterpart of
+        /// `ExpElGamal::encrypt`.
+        pub fn encrypt(
+            parameters: &MyParametersVar<C, GG>,
+            m: &Vec<UInt8<ConstraintF<C>>>,
+            randomness: &Vec<UInt8<ConstraintF<C>>>,
+            public_key: &GG,
+        ) -> Result<(GG, GG), SynthesisError> {
+            let m_bits: Vec<_> = m.iter().flat_map(|b| b.to_bits_le().unwrap()).collect();
+            let encoded = parameters.generator.clone().scalar_mul_le(m_bits.iter())?;
+
+            ElGamalEncGadget::encrypt(parameters, &encoded, randomness, public_key)
+        }
+
+        /// Checks that `ciphertext` decrypts under `sk` to the encoding of `claimed_m`. Unlike
+        /// plain ElGamal decryption, the circuit never recovers `claimed_m` itself: the prover
+        /// supplies it as a witness (having recovered it off-circuit via `DiscreteLogTable`)
+        /// and the circuit only checks it against the decrypted `m*generator`.
+        pub fn check_decryption(
+            sk: &SecretKeyVar<ConstraintF<C>>,
+            ciphertext: &OutputVar<C, GG>,
+            claimed_m: &Vec<UInt8<ConstraintF<C>>>,
+            generator: &GG,
+        ) -> Result<Boolean<ConstraintF<C>>, SynthesisError> {
+            let decrypted = ElGamalDecGadget::<C, GG>::decrypt(sk, ciphertext)?;
+
+            let claimed_bits: Vec<_> = claimed_m.iter().flat_map(|b| b.to_bits_le().unwrap()).collect();
+            let encoded_claim = generator.clone().scalar_mul_le(claimed_bits.iter())?;
+
+            decrypted.is_eq(&encoded_claim)
+        }
+    }
+
+    pub struct ExpElGamalSumGadget<C: ProjectiveCurve, GG: CurveVar<C, ConstraintF<C>>>
+    where
+        for<'a> &'a GG: GroupOpsBounds<'a, C, GG>,
+    {
+        #[doc(hidden)]
+        _curve: PhantomData<*const C>,
+        _group_var: PhantomData<*const GG>,
+    }
+
+    impl<C, GG> ExpElGamalSumGadget<C, GG>
+    where
+        C: ProjectiveCurve,
+        GG: CurveVar<C, ConstraintF<C>>,
+        for<'a> &'a GG: GroupOpsBounds<'a, C, GG>,
+        ConstraintF<C>: PrimeField,
+    {
+        /// Enforces that the homomorphic sum of `inputs` equals `output`, i.e. that the
+        /// output record's encrypted payload is the sum of the input records' payloads
+        /// (a privacy-preserving conservation check).
+        pub fn check_sum(inputs: &[OutputVar<C, GG>], output: &OutputVar<C, GG>) -> Result<Boolean<ConstraintF<C>>, SynthesisError> {
+            assert!(!inputs.is_empty(), "check_sum requires at least one input ciphertext");
+
+            let mut c1_sum = inputs[0].c1.clone();
+            let mut c2_sum = inputs[0].c2.clone();
+            for input in &inputs[1..] {
+                c1_sum = c1_sum + input.c1.clone();
+                c2_sum = c2_sum + input.c2.clone();
+            }
+
+            let c1_ok = c1_sum.is_eq(&output.c1)?;
+            let c2_ok = c2_sum.is_eq(&output.c2)?;
+            c1_ok.and(&c2_ok)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_ff::to_bytes;
+    use ark_r1cs_std::prelude::*;
+    use ark_relations::r1cs::ConstraintSystem;
+    use ark_std::test_rng;
+
+    use crate::crypto::elgamal_ext::{derive_pk_from_sk, ExtSecretKey, MyParametersVar, SecretKeyVar};
+
+    use super::constraints::{ExpElGamalEncGadget, ExpElGamalSumGadget};
+    use super::*;
+
+    #[test]
+    fn test_encrypt_add_and_decode() {
+        let rng = &mut test_rng();
+        let params = MyElgamal::setup(rng).unwrap();
+        let sk = ExtSecretKey::rand(rng);
+        let pk = derive_pk_from_sk(&params, &sk.0);
+
+        let table = DiscreteLogTable::new(&params, 16);
+
+        let m1 = 7u64;
+        let m2 = 35u64;
+        let c1 = ExpElGamal::encrypt(&params, &pk, InnerEdScalarField::from(m1), &Randomness::rand(rng));
+        let c2 = ExpElGamal::encrypt(&params, &pk, InnerEdScalarField::from(m2), &Randomness::rand(rng));
+
+        let sum_ciphertext = ExpElGamal::homomorphic_add(&c1, &c2);
+        let s = sk.0.0;
+        let decrypted = (sum_ciphertext.1.into_projective() - sum_ciphertext.0.mul(s)).into_affine();
+        assert_eq!(table.decode(&decrypted), Some(m1 + m2));
+
+        // re-randomizing must not change the decoded payload
+        let rerandomized = ExpElGamal::rerandomize(&params, &pk, &c1, rng);
+        let decrypted_1 = (rerandomized.1.into_projective() - rerandomized.0.mul(s)).into_affine();
+        assert_eq!(table.decode(&decrypted_1), Some(m1));
+        assert_ne!(rerandomized, c1);
+    }
+
+    #[test]
+    fn test_check_sum_gadget() {
+        let rng = &mut test_rng();
+        let params = MyElgamal::setup(rng).unwrap();
+        let sk = ExtSecretKey::rand(rng);
+        let pk = derive_pk_from_sk(&params, &sk.0);
+
+        let m1 = InnerEdScalarField::from(7u64);
+        let m2 = InnerEdScalarField::from(35u64);
+        let randomness1 = Randomness::rand(rng);
+        let randomness2 = Randomness::rand(rng);
+
+        let cs = ConstraintSystem::<OuterScalarField>::new_ref();
+        let pp_var = MyParametersVar::<InnerEdProjective, InnerEdVar>::new_constant(cs.clone(), &params).unwrap();
+        let pk_var = InnerEdVar::new_constant(cs.clone(), &pk).unwrap();
+
+        let m1_bytes = UInt8::new_witness_vec(cs.clone(), &to_bytes![m1].unwrap()).unwrap();
+        let m2_bytes = UInt8::new_witness_vec(cs.clone(), &to_bytes![m2].unwrap()).unwrap();
+        let r1_bytes = UInt8::new_witness_vec(cs.clone(), &to_bytes![randomness1.0].unwrap()).unwrap();
+        let r2_bytes = UInt8::new_witness_vec(cs.clone(), &to_bytes![randomness2.0].unwrap()).unwrap();
+
+        let (c1_1, c1_2) = ExpElGamalEncGadget::encrypt(&pp_var, &m1_bytes, &r1_bytes, &pk_var).unwrap();
+        let (c2_1, c2_2) = ExpElGamalEncGadget::encrypt(&pp_var, &m2_bytes, &r2_bytes, &pk_var).unwrap();
+
+        let sum_m = m1 + m2;
+        let sum_r = Randomness(randomness1.0 + randomness2.0);
+        let sum_bytes = UInt8::new_witness_vec(cs.clone(), &to_bytes![sum_m].unwrap()).unwrap();
+        let sum_r_bytes = UInt8::new_witness_vec(cs.clone(), &to_bytes![sum_r.0].unwrap()).unwrap();
+        let (out_1, out_2) = ExpElGamalEncGadget::encrypt(&pp_var, &sum_bytes, &sum_r_bytes, &pk_var).unwrap();
+
+        use ark_crypto_primitives::encryption::elgamal::constraints::OutputVar;
+        let inputs = vec![OutputVar { c1: c1_1, c2: c1_2 }, OutputVar { c1: c2_1, c2: c2_2 }];
+        let output = OutputVar { c1: out_1, c2: out_2 };
+
+        let ok = ExpElGamalSumGadget::check_sum(&inputs, &output).unwrap();
+        ok.enforce_equal(&Boolean::TRUE).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+    }
+}
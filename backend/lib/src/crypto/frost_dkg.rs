@@ -0,0 +1,180 @@
+//! FROST-style distributed key generation (DKG) for object-account ElGamal keys: `n` parties
+//! jointly generate a `t`-of-`n`-controlled secret key without any single party (not even a
+//! dealer, unlike `threshold_elgamal::ThresholdElGamal::split`) ever learning it.
+//!
+//! Each party `j` runs [`generate_round1`] to sample its own degree-`(t-1)` polynomial `f_j` and
+//! publish a Feldman commitment to its coefficients (`a_{j,0}*G, ..., a_{j,t-1}*G`, the first
+//! being `f_j(0)*G`); `f_j(0)` is `j`'s secret
+//! contribution and never leaves the party. Party `j` then privately sends [`Party::share_for`]`(k)`
+//! `= f_j(k)` to every party `k` (including itself). Once party `k` has received and verified
+//! (via `ThresholdElGamal::verify_share`, against each sender's published commitment) one share
+//! from every party, it sums them with [`aggregate_share`] into its final secret share
+//! `s_k = Σ_j f_j(k)` -- a valid Shamir share of the aggregate secret key `sk = Σ_j f_j(0)`, usable
+//! directly with `ThresholdElGamal::partial_decrypt`/`combine`. The aggregate public key
+//! `pk = Σ_j f_j(0)*G` is recovered from the published commitments alone, via
+//! [`aggregate_public_key`], without anyone computing `sk`.
+//!
+//! Because an object account's address is `pk.x` (see `derivations::get_addr_for_pk`), the
+//! parties must, exactly like `derivations::derive_fresh_object_sk`'s single-party retry loop,
+//! restart round 1 with fresh polynomials if [`is_usable_group_key`] rejects the aggregate `pk`.
+//! `derive_fresh_object_sk` itself remains the `n = t = 1` special case of this protocol: a lone
+//! party's degree-0 "polynomial" is just its secret key, and its "Feldman commitment" is just its
+//! public key.
+
+use ark_crypto_primitives::encryption::elgamal::Parameters;
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{UniformRand, Zero};
+use ark_std::rand::Rng;
+
+use crate::common::*;
+use crate::crypto::threshold_elgamal::{FeldmanCommitment, Share, ThresholdElGamal};
+use crate::infrastructure::derivations::{get_addr_for_pk, is_external_account, is_reconstructable};
+
+/// One party's state across DKG round 1 (its own polynomial `f_j`, kept private) and round 2
+/// (computing the share it sends to each other party).
+pub struct Party {
+    index: u8,
+    coeffs: Vec<InnerEdScalarField>,
+}
+
+impl Party {
+    /// Round 1: party `index` samples a fresh degree-`(t-1)` polynomial `f_j` and returns both
+    /// its own state (to be retained privately for round 2) and the Feldman commitment to
+    /// publish to the other `n - 1` parties.
+    pub fn generate_round1<R: Rng>(params: &Parameters<InnerEdProjective>, index: u8, t: usize, rng: &mut R) -> (Party, FeldmanCommitment) {
+        let coeffs: Vec<_> = (0..t).map(|_| InnerEdScalarField::rand(rng)).collect();
+        let commitment = FeldmanCommitment {
+            coeffs: coeffs.iter().map(|a_j| params.generator.mul(*a_j).into_affine()).collect(),
+        };
+        (Party { index, coeffs }, commitment)
+    }
+
+    /// Round 2: the share `f_j(recipient_index)` this party sends privately to `recipient_index`
+    /// (which the recipient must verify against this party's published commitment, via
+    /// `ThresholdElGamal::verify_share`, before accepting it).
+    pub fn share_for(&self, recipient_index: u8) -> Share {
+        Share {
+            index: recipient_index,
+            sk_share: ThresholdElGamal::eval_poly(&self.coeffs, InnerEdScalarField::from(recipient_index as u64)),
+        }
+    }
+}
+
+/// Round 3: sums the `n` shares party `k` received (one `f_j(k)` from each party `j`, including
+/// itself), all already individually verified against their sender's commitment, into `k`'s final
+/// secret share `s_k = Σ_j f_j(k)` of the aggregate secret key.
+pub fn aggregate_share(shares: &[Share]) -> Share {
+    let index = shares[0].index;
+    assert!(shares.iter().all(|s| s.index == index), "all shares being aggregated must be addressed to the same party");
+    let sk_share = shares.iter().fold(InnerEdScalarField::zero(), |acc, s| acc + s.sk_share);
+    Share { index, sk_share }
+}
+
+/// Recovers the aggregate object-account public key `pk = Σ_j f_j(0)*G` from every party's
+/// published commitment alone -- no party ever computes, or needs to know, `sk = Σ_j f_j(0)`.
+pub fn aggregate_public_key(commitments: &[FeldmanCommitment]) -> InnerEdAffine {
+    commitments.iter()
+        .fold(InnerEdProjective::zero(), |acc, c| acc + c.coeffs[0].into_projective())
+        .into_affine()
+}
+
+/// Whether `pk` is usable as an object account's group key: its address (`pk.x`) must be
+/// reconstructable back to `pk` (see `derivations::is_reconstructable`) and must not collide with
+/// an external account's address space. If this rejects the aggregate public key, every party
+/// must discard its round-1 polynomial and restart from `generate_round1` with fresh randomness,
+/// exactly like the single-party retry loop in `derivations::derive_fresh_object_sk`.
+pub fn is_usable_group_key(pk: &InnerEdAffine) -> bool {
+    is_reconstructable(pk) && !is_external_account(&get_addr_for_pk(pk))
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_crypto_primitives::encryption::elgamal::{ElGamal, Randomness, SecretKey as ElgamalSecretKey};
+    use ark_crypto_primitives::encryption::AsymmetricEncryptionScheme;
+    use ark_std::test_rng;
+
+    use crate::crypto::elgamal_ext::derive_pk_from_sk;
+
+    use super::*;
+
+    type MyElgamal = ElGamal<InnerEdProjective>;
+
+    /// Runs all `n` parties' round 1 and round 2 locally (there being no real network in a unit
+    /// test) and returns each party's final aggregated share, plus the commitments used to derive
+    /// the group public key.
+    fn run_dkg<R: Rng>(params: &Parameters<InnerEdProjective>, t: usize, n: u8, rng: &mut R) -> (Vec<Share>, Vec<FeldmanCommitment>) {
+        let mut parties = vec![];
+        let mut commitments = vec![];
+        for index in 1..=n {
+            let (party, commitment) = Party::generate_round1(params, index, t, rng);
+            parties.push(party);
+            commitments.push(commitment);
+        }
+
+        for (party, commitment) in parties.iter().zip(commitments.iter()) {
+            for recipient in 1..=n {
+                let share = party.share_for(recipient);
+                assert!(ThresholdElGamal::verify_share(params, commitment, &share));
+            }
+        }
+
+        let final_shares = (1..=n).map(|recipient| {
+            let received: Vec<_> = parties.iter().map(|party| party.share_for(recipient)).collect();
+            aggregate_share(&received)
+        }).collect();
+
+        (final_shares, commitments)
+    }
+
+    #[test]
+    fn test_dkg_produces_usable_threshold_key() {
+        let rng = &mut test_rng();
+        let params = MyElgamal::setup(rng).unwrap();
+
+        let (shares, commitments) = run_dkg(&params, 3, 5, rng);
+        let pk = aggregate_public_key(&commitments);
+
+        let msg = InnerEdProjective::rand(rng).into();
+        let randomness = Randomness::rand(rng);
+        let ciphertext = MyElgamal::encrypt(&params, &pk, &msg, &randomness).unwrap();
+
+        let partials: Vec<_> = shares[..3].iter().map(|s| (s.index, ThresholdElGamal::partial_decrypt(s, &ciphertext))).collect();
+        let recovered = ThresholdElGamal::combine(&partials, &ciphertext);
+        assert_eq!(recovered, msg);
+    }
+
+    #[test]
+    fn test_dkg_no_single_party_knows_the_secret_key() {
+        // the aggregate secret key is Σ_j f_j(0); reconstruct it out-of-band here only to check
+        // it differs from any one party's own polynomial constant term
+        let rng = &mut test_rng();
+        let params = MyElgamal::setup(rng).unwrap();
+
+        let mut parties = vec![];
+        let mut commitments = vec![];
+        for index in 1..=3u8 {
+            let (party, commitment) = Party::generate_round1(&params, index, 2, rng);
+            parties.push(party);
+            commitments.push(commitment);
+        }
+        let sk: InnerEdScalarField = parties.iter().fold(InnerEdScalarField::zero(), |acc, p| acc + p.coeffs[0]);
+        let pk = aggregate_public_key(&commitments);
+        let expected_pk = derive_pk_from_sk(&params, &ElgamalSecretKey(sk));
+        assert_eq!(pk, expected_pk);
+
+        for party in &parties {
+            assert_ne!(party.coeffs[0], sk);
+        }
+    }
+
+    #[test]
+    fn test_is_usable_group_key_matches_single_party_reconstructability_check() {
+        let rng = &mut test_rng();
+        let params = MyElgamal::setup(rng).unwrap();
+        let (_, commitments) = run_dkg(&params, 2, 3, rng);
+        let pk = aggregate_public_key(&commitments);
+        // not asserting a specific outcome (a random key may or may not be reconstructable), just
+        // that the check runs and agrees with its building blocks
+        assert_eq!(is_usable_group_key(&pk), is_reconstructable(&pk) && !is_external_account(&get_addr_for_pk(&pk)));
+    }
+}
@@ -0,0 +1,15 @@
+pub mod elgamal_dleq;
+pub mod elgamal_ext;
+pub mod exp_elgamal;
+pub mod frost_dkg;
+pub mod nova_fold;
+pub mod oprf;
+pub mod poseidon;
+pub mod poseidon_merkle;
+pub mod rln;
+pub mod schnorr;
+pub mod sparse_merkle_tree;
+pub mod spent_serials;
+pub mod threshold_elgamal;
+pub mod vrf;
+pub mod bloom_filter;
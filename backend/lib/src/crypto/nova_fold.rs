@@ -0,0 +1,327 @@
+//! Relaxed-R1CS folding arithmetic (the native half of a Nova-style IVC scheme), so that
+//! `MainProofCircuit`'s per-step instance/witness can eventually be accumulated across many
+//! transactions into one constant-size proof instead of proving each transaction's full R1CS
+//! from scratch.
+//!
+//! **Scope note:** this module implements the off-circuit folding math only -- the relaxed R1CS
+//! instance/witness representation (`RelaxedR1CSInstance`/`RelaxedR1CSWitness`), the cross-term
+//! computation from a finalized `ConstraintSystemRef`'s matrices (`compute_cross_term`), the
+//! folding step itself (`fold_instance`/`fold_witness`), and a Pedersen-style vector commitment
+//! to fold `W`/`E` homomorphically (`CommitmentParams`). It deliberately does **not** include the
+//! augmented step circuit that would verify a folding step *inside* `MainProofCircuit`, nor the
+//! CycleFold companion circuit the real scheme needs to do so cheaply: `W`/`E` are vectors of
+//! `OuterScalarField` elements, but `CommitmentParams::commit` scalar-multiplies them against
+//! `InnerEdProjective` generators, whose scalar field is `InnerEdScalarField` -- a different,
+//! smaller field. Off-circuit that's just a type conversion (see `commit`'s use of
+//! `fe_from_le_bytes_mod_order`), but verifying that same scalar multiplication *in*
+//! `MainProofCircuit` would require non-native field arithmetic gadgets this codebase doesn't
+//! have, which is exactly the problem CycleFold solves by moving the scalar mul into a companion
+//! circuit over the commitment curve's own scalar field. Building that companion circuit and the
+//! augmented step circuit is a substantial follow-on; what's here is the self-contained
+//! accumulator math a decider / IVC prover would sit on top of.
+//!
+//! One piece of the augmented circuit *doesn't* depend on CycleFold, so it's included here too:
+//! `hash_ivc_public_io` computes the single running hash `H(i, z0, zi, U)` the IVC scheme uses as
+//! its entire public IO (folding `i` instances of the per-step circuit `F` into one proof means
+//! the proof's public input can't grow with `i`, so the step count, the base/current step
+//! circuit IO, and the running relaxed-R1CS instance all get folded into this one field element
+//! instead). Computing and checking this hash is ordinary Poseidon absorption, no non-native
+//! scalar multiplication involved -- only `fold_instance`/`fold_witness`'s commitment arithmetic
+//! needs CycleFold to move in-circuit.
+
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{to_bytes, Field, One, ToBytes, UniformRand, Zero};
+use ark_relations::r1cs::{ConstraintMatrices, Matrix};
+use ark_sponge::{poseidon::PoseidonSponge, CryptographicSponge, FieldBasedCryptographicSponge};
+use ark_std::rand::Rng;
+
+use crate::common::{fe_from_le_bytes_mod_order, InnerEdProjective, InnerEdScalarField, OuterScalarField};
+use crate::crypto::poseidon::HybridPoseidonParams;
+
+/// domain separator for the folding challenge Fiat-Shamir transcript
+const FOLD_CHALLENGE_DOMAIN: u64 = 0x464f4c445f5231; // ASCII "FOLD_R1"
+
+/// domain separator for the IVC public-IO running hash
+const IVC_IO_HASH_DOMAIN: u64 = 0x4956435f494f; // ASCII "IVC_IO"
+
+/// A relaxed R1CS instance `(u, comm_w, comm_e, x)`: `u·(A·z ∘ B·z) = u²·(C·z) + u·E` where
+/// `z = (W, x, 1)`, `comm_w`/`comm_e` are (hiding-optional, here just binding) Pedersen
+/// commitments to `W`/`E`, and `x` is the public IO. A freshly-run step (not yet folded into
+/// anything) is represented with `u = 1`, `E = 0` (see `RelaxedR1CSWitness::fresh`).
+#[derive(Clone, Debug)]
+pub struct RelaxedR1CSInstance {
+    pub u: OuterScalarField,
+    pub comm_w: InnerEdProjective,
+    pub comm_e: InnerEdProjective,
+    pub x: Vec<OuterScalarField>,
+}
+
+/// The witness half of a `RelaxedR1CSInstance`: `W` (the non-public part of `z`) and the slack
+/// vector `E` that absorbs the folding cross-term.
+#[derive(Clone, Debug)]
+pub struct RelaxedR1CSWitness {
+    pub w: Vec<OuterScalarField>,
+    pub e: Vec<OuterScalarField>,
+}
+
+impl RelaxedR1CSWitness {
+    /// Wraps a satisfying (non-relaxed) witness `w` as a relaxed witness with `e = 0`.
+    pub fn fresh(w: Vec<OuterScalarField>) -> RelaxedR1CSWitness {
+        let len = w.len();
+        RelaxedR1CSWitness { w, e: vec![OuterScalarField::zero(); len] }
+    }
+}
+
+/// Public parameters for the vector Pedersen commitment used to commit to `W`/`E`: a fixed list
+/// of independent `InnerEdProjective` generators, one per vector position, sampled the same way
+/// `infrastructure::params::LeafHash`/`InnerHash`'s Pedersen CRH parameters are (plain random
+/// sampling at setup time -- nothing-up-my-sleeve generators aren't needed here any more than
+/// they are there).
+#[derive(Clone)]
+pub struct CommitmentParams {
+    pub generators: Vec<InnerEdProjective>,
+}
+
+impl CommitmentParams {
+    pub fn setup<R: Rng>(rng: &mut R, max_vector_len: usize) -> CommitmentParams {
+        CommitmentParams {
+            generators: (0..max_vector_len).map(|_| InnerEdProjective::rand(rng)).collect(),
+        }
+    }
+
+    /// Commits to `v` (an `OuterScalarField` vector -- the R1CS witness/error vector) against
+    /// `InnerEdProjective` generators. The per-element scalar multiplication needs a scalar in
+    /// `InnerEdScalarField`, not `OuterScalarField`, so each element is reduced via
+    /// `fe_from_le_bytes_mod_order`; see the module doc comment for why that's fine natively but
+    /// not in-circuit.
+    pub fn commit(&self, v: &[OuterScalarField]) -> InnerEdProjective {
+        assert!(v.len() <= self.generators.len(), "vector longer than the committed generator set");
+        v.iter().zip(self.generators.iter())
+            .map(|(elem, gen)| {
+                let scalar: InnerEdScalarField = fe_from_le_bytes_mod_order(&to_bytes!(elem).unwrap());
+                gen.mul(scalar)
+            })
+            .fold(InnerEdProjective::zero(), |acc, term| acc + term)
+    }
+}
+
+fn matrix_vec_mul(matrix: &Matrix<OuterScalarField>, z: &[OuterScalarField]) -> Vec<OuterScalarField> {
+    matrix.iter().map(|row| row.iter().map(|(coeff, col)| *coeff * z[*col]).sum()).collect()
+}
+
+fn hadamard(a: &[OuterScalarField], b: &[OuterScalarField]) -> Vec<OuterScalarField> {
+    a.iter().zip(b.iter()).map(|(x, y)| *x * y).collect()
+}
+
+fn vec_add(a: &[OuterScalarField], b: &[OuterScalarField]) -> Vec<OuterScalarField> {
+    a.iter().zip(b.iter()).map(|(x, y)| *x + y).collect()
+}
+
+fn vec_scale(a: &[OuterScalarField], s: OuterScalarField) -> Vec<OuterScalarField> {
+    a.iter().map(|x| *x * s).collect()
+}
+
+fn vec_sub(a: &[OuterScalarField], b: &[OuterScalarField]) -> Vec<OuterScalarField> {
+    a.iter().zip(b.iter()).map(|(x, y)| *x - y).collect()
+}
+
+/// Computes the cross-term `T = A·z1 ∘ B·z2 + A·z2 ∘ B·z1 - u1·(C·z2) - u2·(C·z1)` the prover
+/// supplies when folding a running instance `(u1, z1)` with a fresh one `(u2, z2)` under R1CS
+/// matrices `(A, B, C)`, each `z = (W, x, u)` (the relaxed z-representation, `u` standing in for
+/// the usual constant `1`).
+pub fn compute_cross_term(
+    matrices: &ConstraintMatrices<OuterScalarField>,
+    z1: &[OuterScalarField], u1: OuterScalarField,
+    z2: &[OuterScalarField], u2: OuterScalarField,
+) -> Vec<OuterScalarField> {
+    let az1 = matrix_vec_mul(&matrices.a, z1);
+    let bz1 = matrix_vec_mul(&matrices.b, z1);
+    let cz1 = matrix_vec_mul(&matrices.c, z1);
+    let az2 = matrix_vec_mul(&matrices.a, z2);
+    let bz2 = matrix_vec_mul(&matrices.b, z2);
+    let cz2 = matrix_vec_mul(&matrices.c, z2);
+
+    let term_a = hadamard(&az1, &bz2);
+    let term_b = hadamard(&az2, &bz1);
+    let lhs = vec_add(&term_a, &term_b);
+    let rhs = vec_add(&vec_scale(&cz2, u1), &vec_scale(&cz1, u2));
+    vec_sub(&lhs, &rhs)
+}
+
+/// Derives the Fiat-Shamir folding challenge `r` from the two instances being folded and the
+/// prover's cross-term commitment, via a Poseidon transcript (the same sponge construction
+/// `crypto::rln` uses for its own domain-separated hashes).
+pub fn derive_fold_challenge(
+    params: &HybridPoseidonParams,
+    running: &RelaxedR1CSInstance,
+    fresh: &RelaxedR1CSInstance,
+    comm_t: &InnerEdProjective,
+) -> OuterScalarField {
+    let mut sponge = PoseidonSponge::new(&params.poseidon_params);
+    let mut to_absorb = vec![OuterScalarField::from(FOLD_CHALLENGE_DOMAIN), running.u, fresh.u];
+    to_absorb.extend_from_slice(&running.x);
+    to_absorb.extend_from_slice(&fresh.x);
+    to_absorb.push(fe_from_le_bytes_mod_order(&to_bytes!(running.comm_w.into_affine()).unwrap()));
+    to_absorb.push(fe_from_le_bytes_mod_order(&to_bytes!(fresh.comm_w.into_affine()).unwrap()));
+    to_absorb.push(fe_from_le_bytes_mod_order(&to_bytes!(comm_t.into_affine()).unwrap()));
+    sponge.absorb(&to_absorb);
+    sponge.squeeze_native_field_elements(1)[0]
+}
+
+/// Folds `running` (a possibly-already-folded relaxed instance, `u1 != 1` in general) with
+/// `fresh` (a just-proved step instance, always `u = 1`, `comm_e = 0`) under challenge `r`:
+/// `u = u1 + r·u2`, `x = x1 + r·x2`, `comm_w = comm_w1 + r·comm_w2`, `comm_e = comm_e1 + r·comm_t`.
+pub fn fold_instance(
+    running: &RelaxedR1CSInstance,
+    fresh: &RelaxedR1CSInstance,
+    comm_t: &InnerEdProjective,
+    r: OuterScalarField,
+) -> RelaxedR1CSInstance {
+    let r_scalar: InnerEdScalarField = fe_from_le_bytes_mod_order(&to_bytes!(r).unwrap());
+    RelaxedR1CSInstance {
+        u: running.u + r * fresh.u,
+        comm_w: running.comm_w + fresh.comm_w.mul(r_scalar),
+        comm_e: running.comm_e + comm_t.mul(r_scalar),
+        x: vec_add(&running.x, &vec_scale(&fresh.x, r)),
+    }
+}
+
+/// Witness-side counterpart of `fold_instance`: `W = W1 + r·W2`, `E = E1 + r·T`.
+pub fn fold_witness(
+    running: &RelaxedR1CSWitness,
+    fresh: &RelaxedR1CSWitness,
+    cross_term: &[OuterScalarField],
+    r: OuterScalarField,
+) -> RelaxedR1CSWitness {
+    RelaxedR1CSWitness {
+        w: vec_add(&running.w, &vec_scale(&fresh.w, r)),
+        e: vec_add(&running.e, &vec_scale(cross_term, r)),
+    }
+}
+
+/// Computes the IVC scheme's single public-IO element `H(i, z0, zi, U)`: the step count `i`, the
+/// step circuit `F`'s base input `z0` and current output `zi`, and the running relaxed instance
+/// `U` (its `u`, commitments, and public IO `x`), absorbed into one Poseidon sponge. The augmented
+/// step circuit would allocate this same hash as its sole public input and check it against a
+/// freshly-recomputed one each step; off-circuit, a verifier checks it once against the final
+/// decider proof's claimed `(i, z0, zi, U)`.
+pub fn hash_ivc_public_io(
+    params: &HybridPoseidonParams,
+    step_i: u64,
+    z0: &[OuterScalarField],
+    zi: &[OuterScalarField],
+    running: &RelaxedR1CSInstance,
+) -> OuterScalarField {
+    let mut sponge = PoseidonSponge::new(&params.poseidon_params);
+    let mut to_absorb = vec![OuterScalarField::from(IVC_IO_HASH_DOMAIN), OuterScalarField::from(step_i)];
+    to_absorb.extend_from_slice(z0);
+    to_absorb.extend_from_slice(zi);
+    to_absorb.push(running.u);
+    to_absorb.push(fe_from_le_bytes_mod_order(&to_bytes!(running.comm_w.into_affine()).unwrap()));
+    to_absorb.push(fe_from_le_bytes_mod_order(&to_bytes!(running.comm_e.into_affine()).unwrap()));
+    to_absorb.extend_from_slice(&running.x);
+    sponge.absorb(&to_absorb);
+    sponge.squeeze_native_field_elements(1)[0]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_std::test_rng;
+
+    /// A tiny R1CS instance (`z_2 = 2 * z_1 * z_0` over one constraint) exercised twice with
+    /// different witnesses, folded together, and checked to still satisfy the relaxed relation
+    /// `A·z ∘ B·z = u·(C·z) + E` -- the core soundness property folding must preserve.
+    fn toy_matrices() -> ConstraintMatrices<OuterScalarField> {
+        // z = (1, x /* pub */, w /* priv: 2*z1*z0 */), one constraint: 1*w = x * 2 (i.e. w = 2x)
+        ConstraintMatrices {
+            num_instance_variables: 2,
+            num_witness_variables: 1,
+            num_constraints: 1,
+            a: vec![vec![(OuterScalarField::from(2u64), 1)]],
+            b: vec![vec![(OuterScalarField::one(), 0)]],
+            c: vec![vec![(OuterScalarField::one(), 2)]],
+            a_num_non_zero: 1,
+            b_num_non_zero: 1,
+            c_num_non_zero: 1,
+        }
+    }
+
+    #[test]
+    fn test_fold_preserves_relaxed_satisfiability() {
+        let mut rng = test_rng();
+        let matrices = toy_matrices();
+        let comm_params = CommitmentParams::setup(&mut rng, 4);
+
+        // instance 1: x = 3, w = 6 (satisfies w = 2x)
+        let z1 = vec![OuterScalarField::one(), OuterScalarField::from(3u64), OuterScalarField::from(6u64)];
+        let running_witness = RelaxedR1CSWitness::fresh(vec![z1[2]]);
+        let running = RelaxedR1CSInstance {
+            u: OuterScalarField::one(),
+            comm_w: comm_params.commit(&running_witness.w),
+            comm_e: comm_params.commit(&running_witness.e),
+            x: vec![z1[1]],
+        };
+
+        // instance 2: x = 5, w = 10
+        let z2 = vec![OuterScalarField::one(), OuterScalarField::from(5u64), OuterScalarField::from(10u64)];
+        let fresh_witness = RelaxedR1CSWitness::fresh(vec![z2[2]]);
+        let fresh = RelaxedR1CSInstance {
+            u: OuterScalarField::one(),
+            comm_w: comm_params.commit(&fresh_witness.w),
+            comm_e: comm_params.commit(&fresh_witness.e),
+            x: vec![z2[1]],
+        };
+
+        let cross_term = compute_cross_term(&matrices, &z1, running.u, &z2, fresh.u);
+        let comm_t = comm_params.commit(&cross_term);
+
+        let params = crate::crypto::poseidon::HybridPoseidonCipher::setup(&mut rng);
+        let r = derive_fold_challenge(&params, &running, &fresh, &comm_t);
+
+        let folded_instance = fold_instance(&running, &fresh, &comm_t, r);
+        let folded_witness = fold_witness(&running_witness, &fresh_witness, &cross_term, r);
+
+        // check the folded instance/witness satisfies the relaxed relation directly
+        let folded_z = vec![folded_instance.u, folded_instance.x[0], folded_witness.w[0]];
+        let az = matrix_vec_mul(&matrices.a, &folded_z);
+        let bz = matrix_vec_mul(&matrices.b, &folded_z);
+        let cz = matrix_vec_mul(&matrices.c, &folded_z);
+        let lhs = hadamard(&az, &bz);
+        let rhs = vec_add(&vec_scale(&cz, folded_instance.u), &folded_witness.e);
+        assert_eq!(lhs, rhs);
+
+        // and the folded commitments match committing to the folded witness directly
+        assert_eq!(folded_instance.comm_w, comm_params.commit(&folded_witness.w));
+        assert_eq!(folded_instance.comm_e, comm_params.commit(&folded_witness.e));
+    }
+
+    #[test]
+    fn test_ivc_public_io_hash_is_sensitive_to_every_input() {
+        let mut rng = test_rng();
+        let comm_params = CommitmentParams::setup(&mut rng, 4);
+        let params = crate::crypto::poseidon::HybridPoseidonCipher::setup(&mut rng);
+
+        let running = RelaxedR1CSInstance {
+            u: OuterScalarField::one(),
+            comm_w: comm_params.commit(&[OuterScalarField::from(7u64)]),
+            comm_e: comm_params.commit(&[OuterScalarField::zero()]),
+            x: vec![OuterScalarField::from(3u64)],
+        };
+        let z0 = vec![OuterScalarField::from(1u64)];
+        let zi = vec![OuterScalarField::from(2u64)];
+
+        let base = hash_ivc_public_io(&params, 5, &z0, &zi, &running);
+
+        assert_ne!(base, hash_ivc_public_io(&params, 6, &z0, &zi, &running));
+        assert_ne!(base, hash_ivc_public_io(&params, 5, &[OuterScalarField::from(9u64)], &zi, &running));
+        assert_ne!(base, hash_ivc_public_io(&params, 5, &z0, &[OuterScalarField::from(9u64)], &running));
+
+        let mut different_running = running.clone();
+        different_running.u = OuterScalarField::from(2u64);
+        assert_ne!(base, hash_ivc_public_io(&params, 5, &z0, &zi, &different_running));
+
+        // deterministic: recomputing with identical inputs reproduces the same hash
+        assert_eq!(base, hash_ivc_public_io(&params, 5, &z0, &zi, &running));
+    }
+}
@@ -0,0 +1,176 @@
+//! A field-native alternative to `infrastructure::params::{LeafHash, InnerHash}`: instead of
+//! bit-decomposing byte-serialized inputs through Pedersen windows, `PoseidonLeafCRH`/
+//! `PoseidonTwoToOneCRH` absorb the input's field-element encoding directly into a Poseidon
+//! sponge, and their gadget counterparts reconstruct that encoding in-circuit with one linear
+//! combination per field element (`Boolean::le_bits_to_fp_var`) instead of a per-bit elliptic-curve
+//! window lookup. Since `EncryptedRecord` is already a handful of `OuterScalarField` elements
+//! (see `ToConstraintField` in `circuit.rs`), this avoids the byte/bit conversion overhead the
+//! Pedersen tree pays on every `access_input` membership check.
+//!
+//! This module is additive, not a drop-in replacement: `infrastructure::params::MerkleTreeParams`
+//! (and everything downstream of it -- `CryptoParams`'s serialized hash parameters,
+//! `RuntimeStateView`'s stored tree, and the trusted setup `VerifyingKey`) is pinned to the
+//! Pedersen tree, and switching the live tree type is a breaking change to on-chain state and
+//! the proving key, not something that can be folded into an unrelated change. `PoseidonMerkleTreeParams`
+//! is provided here as the `merkle_tree::Config` a future migration would use (e.g. together with
+//! `crypto::sparse_merkle_tree::SparseMerkleTree`, which is already generic over `merkle_tree::Config`).
+
+use ark_crypto_primitives::crh::{TwoToOneCRH, CRH};
+use ark_crypto_primitives::Error;
+use ark_ff::PrimeField;
+use ark_sponge::{poseidon::PoseidonSponge, CryptographicSponge, FieldBasedCryptographicSponge};
+use ark_std::rand::Rng;
+
+use crate::common::{fe_from_le_bytes_mod_order, OuterScalarField};
+use crate::crypto::poseidon::HybridPoseidonParams;
+
+/// domain separator for leaf hashing, so a leaf and an inner digest can never collide even if
+/// their absorbed field elements happened to coincide
+const POSEIDON_LEAF_DOMAIN: u64 = 0x504f53_4c454146; // ASCII "POSLEAF"
+/// domain separator for the two-to-one (inner node) hash
+const POSEIDON_TWO_TO_ONE_DOMAIN: u64 = 0x504f535f324231; // ASCII "POS_2B1"
+
+fn poseidon_hash(params: &HybridPoseidonParams, domain: u64, inputs: &[OuterScalarField]) -> OuterScalarField {
+    let mut sponge = PoseidonSponge::new(&params.poseidon_params);
+    let mut to_absorb = vec![OuterScalarField::from(domain)];
+    to_absorb.extend_from_slice(inputs);
+    sponge.absorb(&to_absorb);
+    sponge.squeeze_native_field_elements(1)[0]
+}
+
+/// Reinterprets a CRH's raw `&[u8]` input (the caller's serialized field elements, chunked at
+/// `FE_BYTES`-ish boundaries by whatever produced them) as field elements via
+/// `fe_from_le_bytes_mod_order`, so hashing genuinely operates on field elements rather than
+/// their bit pattern -- mirroring what the in-circuit gadget does with `UInt8`/`Boolean`.
+fn bytes_to_field_elements(input: &[u8]) -> Vec<OuterScalarField> {
+    input.chunks(crate::constants::FE_BYTES).map(fe_from_le_bytes_mod_order).collect()
+}
+
+pub struct PoseidonLeafCRH;
+impl CRH for PoseidonLeafCRH {
+    const INPUT_SIZE_BITS: usize = 0; // variable-length input, unlike the fixed-window Pedersen leaf hash
+
+    type Output = OuterScalarField;
+    type Parameters = HybridPoseidonParams;
+
+    fn setup<R: Rng>(rng: &mut R) -> Result<Self::Parameters, Error> {
+        Ok(crate::crypto::poseidon::HybridPoseidonCipher::setup(rng))
+    }
+
+    fn evaluate(parameters: &Self::Parameters, input: &[u8]) -> Result<Self::Output, Error> {
+        Ok(poseidon_hash(parameters, POSEIDON_LEAF_DOMAIN, &bytes_to_field_elements(input)))
+    }
+}
+
+pub struct PoseidonTwoToOneCRH;
+impl TwoToOneCRH for PoseidonTwoToOneCRH {
+    const LEFT_INPUT_SIZE_BITS: usize = OuterScalarField::size_in_bits();
+    const RIGHT_INPUT_SIZE_BITS: usize = OuterScalarField::size_in_bits();
+
+    type Output = OuterScalarField;
+    type Parameters = HybridPoseidonParams;
+
+    fn setup<R: Rng>(rng: &mut R) -> Result<Self::Parameters, Error> {
+        Ok(crate::crypto::poseidon::HybridPoseidonCipher::setup(rng))
+    }
+
+    fn evaluate(parameters: &Self::Parameters, left_input: &[u8], right_input: &[u8]) -> Result<Self::Output, Error> {
+        let left = fe_from_le_bytes_mod_order(left_input);
+        let right = fe_from_le_bytes_mod_order(right_input);
+        Ok(poseidon_hash(parameters, POSEIDON_TWO_TO_ONE_DOMAIN, &[left, right]))
+    }
+}
+
+#[derive(Clone)]
+pub struct PoseidonMerkleTreeParams;
+impl ark_crypto_primitives::merkle_tree::Config for PoseidonMerkleTreeParams {
+    type LeafHash = PoseidonLeafCRH;
+    type TwoToOneHash = PoseidonTwoToOneCRH;
+}
+
+pub mod constraints {
+    use ark_crypto_primitives::crh::{CRHGadget, TwoToOneCRHGadget};
+    use ark_r1cs_std::prelude::*;
+    use ark_relations::r1cs::SynthesisError;
+    use ark_sponge::{constraints::CryptographicSpongeVar, poseidon::{constraints::PoseidonSpongeVar, PoseidonParameters}};
+
+    use crate::common::OuterScalarField;
+
+    use super::{PoseidonLeafCRH, PoseidonTwoToOneCRH, POSEIDON_LEAF_DOMAIN, POSEIDON_TWO_TO_ONE_DOMAIN};
+
+    fn poseidon_hash_var(
+        cs: ark_relations::r1cs::ConstraintSystemRef<OuterScalarField>,
+        params: &PoseidonParameters<OuterScalarField>,
+        domain: u64,
+        inputs: &[FpVar<OuterScalarField>],
+    ) -> Result<FpVar<OuterScalarField>, SynthesisError> {
+        let mut sponge = PoseidonSpongeVar::<OuterScalarField>::new(cs.clone(), params);
+        let mut to_absorb = vec![FpVar::new_constant(cs, OuterScalarField::from(domain))?];
+        to_absorb.extend_from_slice(inputs);
+        sponge.absorb(&to_absorb)?;
+        Ok(sponge.squeeze_field_elements(1)?[0].clone())
+    }
+
+    /// Reassembles the field elements a `PoseidonLeafCRH`/`PoseidonTwoToOneCRH` caller serialized
+    /// to bytes, the same way `bytes_to_field_elements` does natively -- one `le_bits_to_fp_var`
+    /// per `FE_BYTES`-sized chunk, instead of a Pedersen-style per-bit curve-point accumulation.
+    fn bytes_to_field_elements_var(input: &[UInt8<OuterScalarField>]) -> Result<Vec<FpVar<OuterScalarField>>, SynthesisError> {
+        input.chunks(crate::constants::FE_BYTES)
+            .map(|chunk| {
+                let bits: Vec<_> = chunk.iter().flat_map(|b| b.to_bits_le().unwrap()).collect();
+                Boolean::le_bits_to_fp_var(&bits)
+            })
+            .collect()
+    }
+
+    pub struct PoseidonLeafCRHGadget;
+    impl CRHGadget<PoseidonLeafCRH, OuterScalarField> for PoseidonLeafCRHGadget {
+        type OutputVar = FpVar<OuterScalarField>;
+        type ParametersVar = PoseidonParametersVar;
+
+        fn evaluate(parameters: &Self::ParametersVar, input: &[UInt8<OuterScalarField>]) -> Result<Self::OutputVar, SynthesisError> {
+            let cs = parameters.cs.clone();
+            let elems = bytes_to_field_elements_var(input)?;
+            poseidon_hash_var(cs, &parameters.params, POSEIDON_LEAF_DOMAIN, &elems)
+        }
+    }
+
+    pub struct PoseidonTwoToOneCRHGadget;
+    impl TwoToOneCRHGadget<PoseidonTwoToOneCRH, OuterScalarField> for PoseidonTwoToOneCRHGadget {
+        type OutputVar = FpVar<OuterScalarField>;
+        type ParametersVar = PoseidonParametersVar;
+
+        fn evaluate(
+            parameters: &Self::ParametersVar,
+            left_input: &[UInt8<OuterScalarField>],
+            right_input: &[UInt8<OuterScalarField>],
+        ) -> Result<Self::OutputVar, SynthesisError> {
+            let cs = parameters.cs.clone();
+            let left = Boolean::le_bits_to_fp_var(&left_input.iter().flat_map(|b| b.to_bits_le().unwrap()).collect::<Vec<_>>())?;
+            let right = Boolean::le_bits_to_fp_var(&right_input.iter().flat_map(|b| b.to_bits_le().unwrap()).collect::<Vec<_>>())?;
+            poseidon_hash_var(cs, &parameters.params, POSEIDON_TWO_TO_ONE_DOMAIN, &[left, right])
+        }
+    }
+
+    /// `AllocVar` wrapper for `HybridPoseidonParams`: only the Poseidon round constants need to
+    /// be allocated as circuit constants, so this mirrors `EncParams`'s treatment in `circuit.rs`
+    /// (where `poseidon_params` is cloned in as a plain Rust value rather than allocated).
+    #[derive(Clone)]
+    pub struct PoseidonParametersVar {
+        cs: ark_relations::r1cs::ConstraintSystemRef<OuterScalarField>,
+        params: PoseidonParameters<OuterScalarField>,
+    }
+
+    impl AllocVar<super::HybridPoseidonParams, OuterScalarField> for PoseidonParametersVar {
+        fn new_variable<T: std::borrow::Borrow<super::HybridPoseidonParams>>(
+            cs: impl Into<ark_relations::r1cs::Namespace<OuterScalarField>>,
+            f: impl FnOnce() -> Result<T, SynthesisError>,
+            _mode: AllocationMode,
+        ) -> Result<Self, SynthesisError> {
+            let ns = cs.into();
+            let cs = ns.cs();
+            let params = f()?.borrow().poseidon_params.clone();
+            Ok(PoseidonParametersVar { cs, params })
+        }
+    }
+}
@@ -0,0 +1,299 @@
+//! RLN ("Rate-Limiting Nullifier")-style per-epoch spam limiting for `MainProofCircuit`: a
+//! sender who proves more than one transaction in the same epoch leaks a Shamir share of their
+//! own secret key, letting anyone who observes two same-epoch shares reconstruct it and slash
+//! them. A degree-1 polynomial `y = a0 + a1*x` is formed per proof, with `a0` fixed to the
+//! sender's secret key, `a1` derived (via Poseidon) from `(sk, epoch)`, and `x` derived from the
+//! transaction's unique seed; two proofs in the same epoch give two points on the same line,
+//! which Lagrange-interpolate to recover `a0` (see `recover_secret`).
+//!
+//! `internal_nullifier = Poseidon(a1)` is the public value two same-epoch proofs from the same
+//! sender have in common (without revealing `sk` or `epoch` individually), so a watcher can spot
+//! a double-signal without first recovering the secret.
+
+use std::io::{Read, Write};
+
+use ark_ff::{Field, FromBytes, ToBytes};
+use ark_sponge::{poseidon::PoseidonSponge, CryptographicSponge, FieldBasedCryptographicSponge};
+
+use crate::common::OuterScalarField;
+use crate::crypto::poseidon::HybridPoseidonParams;
+use crate::crypto::poseidon_merkle::PoseidonMerkleTreeParams;
+use crate::crypto::sparse_merkle_tree::SparseMerkleTree;
+
+/// domain separator for `a1 = Poseidon(RLN_A1_DOMAIN, sk, epoch)`
+const RLN_A1_DOMAIN: u64 = 0x524c4e5f4131; // ASCII "RLN_A1"
+/// domain separator for `internal_nullifier = Poseidon(RLN_NULLIFIER_DOMAIN, a1)`
+const RLN_NULLIFIER_DOMAIN: u64 = 0x524c4e5f4e554c4c; // ASCII "RLN_NULL"
+/// domain separator for `x = Poseidon(RLN_X_DOMAIN, tx_seed)`
+const RLN_X_DOMAIN: u64 = 0x524c4e5f58; // ASCII "RLN_X"
+/// domain separator for `identity_commitment = Poseidon(RLN_IDENTITY_DOMAIN, a0)`
+const RLN_IDENTITY_DOMAIN: u64 = 0x524c4e5f4944; // ASCII "RLN_ID"
+
+/// A Merkle tree of RLN identity commitments (`derive_identity_commitment`'s output), reusing the
+/// field-native Poseidon tree config from `crypto::poseidon_merkle` rather than the Pedersen-based
+/// record tree -- a natural fit here since both the leaves and this module's own Shamir-share math
+/// already live in the same Poseidon sponge.
+pub type IdentityTree = SparseMerkleTree<PoseidonMerkleTreeParams>;
+
+/// One proof's contribution to the per-epoch Shamir sharing of the sender's secret key: the
+/// point `(x, y)` on `y = a0 + a1*x`, plus the `internal_nullifier` that lets two shares from the
+/// same `(sk, epoch)` be recognized as such without revealing `sk` or `epoch`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct RlnShare {
+    pub x: OuterScalarField,
+    pub y: OuterScalarField,
+    pub internal_nullifier: OuterScalarField,
+}
+
+impl ToBytes for RlnShare {
+    fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        self.x.write(&mut writer)?;
+        self.y.write(&mut writer)?;
+        self.internal_nullifier.write(&mut writer)
+    }
+}
+
+impl FromBytes for RlnShare {
+    fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let x = OuterScalarField::read(&mut reader)?;
+        let y = OuterScalarField::read(&mut reader)?;
+        let internal_nullifier = OuterScalarField::read(&mut reader)?;
+        Ok(RlnShare { x, y, internal_nullifier })
+    }
+}
+
+fn poseidon_hash(params: &HybridPoseidonParams, domain: u64, inputs: &[OuterScalarField]) -> OuterScalarField {
+    let mut sponge = PoseidonSponge::new(&params.poseidon_params);
+    let mut to_absorb = vec![OuterScalarField::from(domain)];
+    to_absorb.extend_from_slice(inputs);
+    sponge.absorb(&to_absorb);
+    sponge.squeeze_native_field_elements(1)[0]
+}
+
+/// `a1 = Poseidon(sk, epoch)`, the per-epoch slope of the sender's Shamir line.
+pub fn derive_a1(params: &HybridPoseidonParams, sk: OuterScalarField, epoch: OuterScalarField) -> OuterScalarField {
+    poseidon_hash(params, RLN_A1_DOMAIN, &[sk, epoch])
+}
+
+/// `internal_nullifier = Poseidon(a1)`, deterministic in `(sk, epoch)` only (not the message),
+/// so two proofs from the same sender in the same epoch always share it regardless of what each
+/// transaction does.
+pub fn derive_internal_nullifier(params: &HybridPoseidonParams, a1: OuterScalarField) -> OuterScalarField {
+    poseidon_hash(params, RLN_NULLIFIER_DOMAIN, &[a1])
+}
+
+/// `x = Poseidon(tx_seed)`, the Shamir share's x-coordinate; `tx_seed` should be this
+/// transaction's unique seed reduced to a field element, so distinct transactions land on
+/// distinct points of the sender's per-epoch line.
+pub fn derive_x(params: &HybridPoseidonParams, tx_seed: OuterScalarField) -> OuterScalarField {
+    poseidon_hash(params, RLN_X_DOMAIN, &[tx_seed])
+}
+
+/// `identity_commitment = Poseidon(a0)`, the public leaf stored in an `IdentityTree` that
+/// `constraints::prove_share_with_membership` proves membership of, without revealing `a0` itself.
+pub fn derive_identity_commitment(params: &HybridPoseidonParams, a0: OuterScalarField) -> OuterScalarField {
+    poseidon_hash(params, RLN_IDENTITY_DOMAIN, &[a0])
+}
+
+/// Evaluates this proof's `(x, y, internal_nullifier)` RLN share for secret key `sk` in `epoch`,
+/// identifying the transaction via `tx_seed`.
+pub fn evaluate_share(params: &HybridPoseidonParams, sk: OuterScalarField, epoch: OuterScalarField, tx_seed: OuterScalarField) -> RlnShare {
+    let a1 = derive_a1(params, sk, epoch);
+    let x = derive_x(params, tx_seed);
+    let y = sk + a1 * x;
+    let internal_nullifier = derive_internal_nullifier(params, a1);
+    RlnShare { x, y, internal_nullifier }
+}
+
+/// Recovers the shared secret `a0` (the sender's secret key) from two distinct-`x` shares that
+/// share an `internal_nullifier` (i.e. the same sender signalled twice in the same epoch), by
+/// Lagrange-interpolating the degree-1 polynomial `y = a0 + a1*x` at `x = 0`. Returns `None` if
+/// the shares don't actually collide (different `internal_nullifier`) or can't be interpolated
+/// (identical `x`, which a would-be slasher should never see from two distinct real proofs,
+/// since `x` is derived from each transaction's own unique seed).
+pub fn recover_secret(share_a: &RlnShare, share_b: &RlnShare) -> Option<OuterScalarField> {
+    if share_a.internal_nullifier != share_b.internal_nullifier || share_a.x == share_b.x {
+        return None;
+    }
+    let dx = share_b.x - share_a.x;
+    let dy = share_b.y - share_a.y;
+    let a1 = dy * dx.inverse()?;
+    Some(share_a.y - a1 * share_a.x)
+}
+
+pub mod constraints {
+    use ark_r1cs_std::prelude::*;
+    use ark_relations::r1cs::ConstraintSystemRef;
+    use ark_sponge::{constraints::CryptographicSpongeVar, poseidon::{constraints::PoseidonSpongeVar, PoseidonParameters}};
+
+    use crate::common::{OuterScalarField, OuterScalarVar};
+    use crate::crypto::poseidon_merkle::constraints::{PoseidonLeafCRHGadget, PoseidonParametersVar, PoseidonTwoToOneCRHGadget};
+    use crate::crypto::poseidon_merkle::PoseidonMerkleTreeParams;
+    use crate::crypto::sparse_merkle_tree::constraints::SparseMerklePathVar;
+
+    use super::{RLN_A1_DOMAIN, RLN_IDENTITY_DOMAIN, RLN_NULLIFIER_DOMAIN, RLN_X_DOMAIN};
+
+    /// In-circuit authentication path for an `IdentityTree` membership proof.
+    pub type IdentityPathVar = SparseMerklePathVar<PoseidonMerkleTreeParams, PoseidonLeafCRHGadget, PoseidonTwoToOneCRHGadget, OuterScalarField>;
+
+    fn poseidon_hash_var(
+        cs: &ConstraintSystemRef<OuterScalarField>,
+        params: &PoseidonParameters<OuterScalarField>,
+        domain: u64,
+        inputs: &[OuterScalarVar],
+    ) -> ark_relations::r1cs::Result<OuterScalarVar> {
+        let mut sponge = PoseidonSpongeVar::<OuterScalarField>::new(cs.clone(), params);
+        let mut to_absorb = vec![OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(domain))?];
+        to_absorb.extend_from_slice(inputs);
+        sponge.absorb(&to_absorb)?;
+        Ok(sponge.squeeze_field_elements(1)?[0].clone())
+    }
+
+    /// In-circuit counterpart of `evaluate_share`; returns `(x, y, internal_nullifier)`.
+    pub fn evaluate_share_var(
+        cs: &ConstraintSystemRef<OuterScalarField>,
+        params: &PoseidonParameters<OuterScalarField>,
+        sk: &OuterScalarVar,
+        epoch: &OuterScalarVar,
+        tx_seed: &OuterScalarVar,
+    ) -> ark_relations::r1cs::Result<(OuterScalarVar, OuterScalarVar, OuterScalarVar)> {
+        let a1 = poseidon_hash_var(cs, params, RLN_A1_DOMAIN, &[sk.clone(), epoch.clone()])?;
+        let x = poseidon_hash_var(cs, params, RLN_X_DOMAIN, &[tx_seed.clone()])?;
+        let y = sk.clone().add(&a1.clone().mul(&x));
+        let internal_nullifier = poseidon_hash_var(cs, params, RLN_NULLIFIER_DOMAIN, &[a1])?;
+        Ok((x, y, internal_nullifier))
+    }
+
+    /// In-circuit counterpart of `evaluate_share`, additionally proving that `Poseidon(sk)` is a
+    /// leaf of the `IdentityTree` rooted at `root` along `path` -- combines `derive_identity_commitment`
+    /// with `evaluate_share_var`'s existing share/nullifier math so a single call produces
+    /// everything `MainProofCircuit` would need to enforce for a membership-gated RLN share.
+    pub fn prove_share_with_membership(
+        cs: &ConstraintSystemRef<OuterScalarField>,
+        params: &PoseidonParameters<OuterScalarField>,
+        leaf_hash_param: &PoseidonParametersVar,
+        inner_hash_param: &PoseidonParametersVar,
+        root: &OuterScalarVar,
+        path: &IdentityPathVar,
+        sk: &OuterScalarVar,
+        epoch: &OuterScalarVar,
+        tx_seed: &OuterScalarVar,
+    ) -> ark_relations::r1cs::Result<(OuterScalarVar, OuterScalarVar, OuterScalarVar, Boolean<OuterScalarField>)> {
+        let identity_commitment = poseidon_hash_var(cs, params, RLN_IDENTITY_DOMAIN, &[sk.clone()])?;
+        let leaf_bytes = identity_commitment.to_bytes()?;
+        let is_member = path.verify_membership(leaf_hash_param, inner_hash_param, root, &leaf_bytes)?;
+        let (x, y, internal_nullifier) = evaluate_share_var(cs, params, sk, epoch, tx_seed)?;
+        Ok((x, y, internal_nullifier, is_member))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_std::test_rng;
+
+    use crate::crypto::poseidon::HybridPoseidonCipher;
+
+    #[test]
+    fn test_recover_secret_from_two_same_epoch_shares() {
+        let mut rng = test_rng();
+        let params = HybridPoseidonCipher::setup(&mut rng);
+        let sk = OuterScalarField::from(12345u64);
+        let epoch = OuterScalarField::from(7u64);
+        let share_a = evaluate_share(&params, sk, epoch, OuterScalarField::from(1u64));
+        let share_b = evaluate_share(&params, sk, epoch, OuterScalarField::from(2u64));
+        assert_eq!(share_a.internal_nullifier, share_b.internal_nullifier);
+        assert_eq!(recover_secret(&share_a, &share_b), Some(sk));
+    }
+
+    #[test]
+    fn test_recover_secret_fails_across_different_epochs() {
+        let mut rng = test_rng();
+        let params = HybridPoseidonCipher::setup(&mut rng);
+        let sk = OuterScalarField::from(12345u64);
+        let share_a = evaluate_share(&params, sk, OuterScalarField::from(7u64), OuterScalarField::from(1u64));
+        let share_b = evaluate_share(&params, sk, OuterScalarField::from(8u64), OuterScalarField::from(2u64));
+        assert_ne!(share_a.internal_nullifier, share_b.internal_nullifier);
+        assert_eq!(recover_secret(&share_a, &share_b), None);
+    }
+
+    #[test]
+    fn test_recover_secret_rejects_different_senders() {
+        let mut rng = test_rng();
+        let params = HybridPoseidonCipher::setup(&mut rng);
+        let epoch = OuterScalarField::from(7u64);
+        let share_a = evaluate_share(&params, OuterScalarField::from(1u64), epoch, OuterScalarField::from(1u64));
+        let share_b = evaluate_share(&params, OuterScalarField::from(2u64), epoch, OuterScalarField::from(2u64));
+        assert_ne!(share_a.internal_nullifier, share_b.internal_nullifier);
+        assert_eq!(recover_secret(&share_a, &share_b), None);
+    }
+
+    #[test]
+    fn test_identity_commitment_membership() {
+        let mut rng = test_rng();
+        let params = HybridPoseidonCipher::setup(&mut rng);
+        let sk = OuterScalarField::from(42u64);
+
+        let mut tree = IdentityTree::new(&params, &params, 4);
+        let commitment = derive_identity_commitment(&params, sk);
+        tree.update(0, &commitment);
+
+        let proof = tree.generate_proof(0);
+        let root = tree.root();
+        assert!(crate::crypto::sparse_merkle_tree::verify_path(&params, &params, &root, &proof, &commitment));
+
+        let other_commitment = derive_identity_commitment(&params, OuterScalarField::from(43u64));
+        assert!(!crate::crypto::sparse_merkle_tree::verify_path(&params, &params, &root, &proof, &other_commitment));
+    }
+
+    #[test]
+    fn test_prove_share_with_membership_gadget() {
+        use ark_r1cs_std::prelude::*;
+        use ark_relations::r1cs::ConstraintSystem;
+
+        use crate::common::OuterScalarVar;
+        use crate::crypto::poseidon_merkle::constraints::PoseidonParametersVar;
+
+        use super::constraints::{prove_share_with_membership, IdentityPathVar};
+
+        let mut rng = test_rng();
+        let params = HybridPoseidonCipher::setup(&mut rng);
+        let sk = OuterScalarField::from(42u64);
+        let epoch = OuterScalarField::from(7u64);
+        let tx_seed = OuterScalarField::from(1u64);
+
+        let mut tree = IdentityTree::new(&params, &params, 4);
+        let commitment = derive_identity_commitment(&params, sk);
+        tree.update(0, &commitment);
+        let proof = tree.generate_proof(0);
+        let root = tree.root();
+
+        let cs = ConstraintSystem::<OuterScalarField>::new_ref();
+        let leaf_hash_param_var = PoseidonParametersVar::new_constant(cs.clone(), params.clone()).unwrap();
+        let inner_hash_param_var = PoseidonParametersVar::new_constant(cs.clone(), params.clone()).unwrap();
+        let root_var = OuterScalarVar::new_input(cs.clone(), || Ok(root)).unwrap();
+        let path_var = IdentityPathVar::new_witness(cs.clone(), || Ok(proof)).unwrap();
+        let sk_var = OuterScalarVar::new_witness(cs.clone(), || Ok(sk)).unwrap();
+        let epoch_var = OuterScalarVar::new_witness(cs.clone(), || Ok(epoch)).unwrap();
+        let tx_seed_var = OuterScalarVar::new_witness(cs.clone(), || Ok(tx_seed)).unwrap();
+
+        let (x_var, y_var, nullifier_var, is_member) = prove_share_with_membership(
+            &cs,
+            &params.poseidon_params,
+            &leaf_hash_param_var,
+            &inner_hash_param_var,
+            &root_var,
+            &path_var,
+            &sk_var,
+            &epoch_var,
+            &tx_seed_var,
+        ).unwrap();
+        is_member.enforce_equal(&Boolean::TRUE).unwrap();
+
+        let expected = evaluate_share(&params, sk, epoch, tx_seed);
+        assert_eq!(x_var.value().unwrap(), expected.x);
+        assert_eq!(y_var.value().unwrap(), expected.y);
+        assert_eq!(nullifier_var.value().unwrap(), expected.internal_nullifier);
+        assert!(cs.is_satisfied().unwrap());
+    }
+}
@@ -0,0 +1,181 @@
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{to_bytes, PrimeField};
+use ark_sponge::{poseidon::PoseidonSponge, CryptographicSponge, FieldBasedCryptographicSponge};
+use ark_std::{rand::Rng, UniformRand};
+
+use crate::common::*;
+use crate::crypto::elgamal_ext::ExtSecretKey;
+use crate::crypto::poseidon::HybridPoseidonParams;
+
+/// A Schnorr signature over the inner Edwards curve. Lets an `Identity` authorize a
+/// transaction with its `secret_key`/`public_key` pair, checked inside the circuit, rather
+/// than relying solely on serial-number ownership.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Signature {
+    pub r: InnerEdAffine,
+    pub s: InnerEdScalarField,
+}
+
+pub struct Schnorr;
+
+impl Schnorr {
+    /// Signs `msg` (typically a transaction digest) under `sk`/`pk`.
+    pub fn sign<R: Rng>(
+        params: &HybridPoseidonParams,
+        sk: &ExtSecretKey<InnerEdProjective>,
+        pk: &InnerEdAffine,
+        msg: OuterScalarField,
+        rng: &mut R,
+    ) -> Signature {
+        let k = InnerEdScalarField::rand(rng);
+        let r = params.elgamal_params.generator.mul(k).into_affine();
+        let c = Self::challenge(params, &r, pk, msg);
+        let s = k + c * sk.0.0;
+        Signature { r, s }
+    }
+
+    /// Checks that `signature` is a valid signature on `msg` under `pk`.
+    pub fn verify(params: &HybridPoseidonParams, pk: &InnerEdAffine, msg: OuterScalarField, signature: &Signature) -> bool {
+        let c = Self::challenge(params, &signature.r, pk, msg);
+        let lhs = params.elgamal_params.generator.mul(signature.s);
+        let rhs = signature.r.into_projective() + pk.mul(c);
+        lhs == rhs
+    }
+
+    /// Derives the Fiat-Shamir challenge `c = Poseidon(R.x, R.y, pk.x, pk.y, msg)`. The
+    /// sponge runs over `OuterScalarField` (the field `R`/`pk`'s coordinates already live
+    /// in); the squeezed element is reduced into the inner curve's scalar field so `c` can
+    /// be combined with `sk`/`s` using ordinary `InnerEdScalarField` arithmetic.
+    fn challenge(params: &HybridPoseidonParams, r: &InnerEdAffine, pk: &InnerEdAffine, msg: OuterScalarField) -> InnerEdScalarField {
+        let mut poseidon = PoseidonSponge::new(&params.poseidon_params);
+        poseidon.absorb(&vec![r.x, r.y, pk.x, pk.y, msg]);
+        let c = poseidon.squeeze_native_field_elements(1)[0];
+        InnerEdScalarField::from_le_bytes_mod_order(&to_bytes![c].unwrap())
+    }
+}
+
+pub mod constraints {
+    use ark_ff::to_bytes;
+    use ark_r1cs_std::prelude::*;
+    use ark_relations::r1cs::{ConstraintSystemRef, Namespace, SynthesisError};
+    use ark_sponge::{constraints::CryptographicSpongeVar, poseidon::constraints::PoseidonSpongeVar, poseidon::PoseidonParameters};
+    use ark_std::borrow::Borrow;
+
+    use crate::common::*;
+
+    use super::Signature;
+
+    #[derive(Clone, Debug)]
+    pub struct SignatureVar {
+        pub r: InnerEdVar,
+        pub s: Vec<UInt8<OuterScalarField>>,
+    }
+
+    impl AllocVar<Signature, OuterScalarField> for SignatureVar {
+        fn new_variable<T: Borrow<Signature>>(
+            cs: impl Into<Namespace<OuterScalarField>>,
+            f: impl FnOnce() -> Result<T, SynthesisError>,
+            mode: AllocationMode,
+        ) -> Result<Self, SynthesisError> {
+            let cs = cs.into().cs();
+            let sig = f()?;
+            let sig = sig.borrow();
+            let r = InnerEdVar::new_variable(cs.clone(), || Ok(sig.r), mode)?;
+            let s_bytes = to_bytes![sig.s].unwrap();
+            let s = match mode {
+                AllocationMode::Constant => UInt8::constant_vec(&s_bytes),
+                AllocationMode::Input => UInt8::new_input_vec(cs, &s_bytes)?,
+                AllocationMode::Witness => UInt8::new_witness_vec(cs, &s_bytes)?,
+            };
+            Ok(SignatureVar { r, s })
+        }
+    }
+
+    pub struct SchnorrVerifyGadget;
+
+    impl SchnorrVerifyGadget {
+        /// Recomputes the challenge `c = Poseidon(R.x, R.y, pk.x, pk.y, msg)` in-circuit and
+        /// enforces `s*generator == R + c*public_key`, the in-circuit counterpart of
+        /// `Schnorr::verify`.
+        pub fn verify(
+            cs: ConstraintSystemRef<OuterScalarField>,
+            poseidon_params: &PoseidonParameters<OuterScalarField>,
+            generator: &InnerEdVar,
+            public_key: &InnerEdVar,
+            msg: &OuterScalarVar,
+            signature: &SignatureVar,
+        ) -> Result<Boolean<OuterScalarField>, SynthesisError> {
+            // recompute the challenge, then flatten it (like `s`) to a little-endian bit vector
+            let mut poseidon = PoseidonSpongeVar::<OuterScalarField>::new(cs, poseidon_params);
+            poseidon.absorb(&vec![
+                signature.r.x.clone(),
+                signature.r.y.clone(),
+                public_key.x.clone(),
+                public_key.y.clone(),
+                msg.clone(),
+            ])?;
+            let c = poseidon.squeeze_field_elements(1)?[0].clone();
+            let c_bits = c.to_bits_le()?;
+
+            let s_bits: Vec<_> = signature.s.iter().flat_map(|b| b.to_bits_le().unwrap()).collect();
+
+            // compute s*generator
+            let lhs = generator.clone().scalar_mul_le(s_bits.iter())?;
+
+            // compute R + c*public_key
+            let rhs = signature.r.clone() + public_key.clone().scalar_mul_le(c_bits.iter())?;
+
+            lhs.is_eq(&rhs)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_r1cs_std::prelude::*;
+    use ark_relations::r1cs::ConstraintSystem;
+    use ark_std::test_rng;
+
+    use crate::crypto::elgamal_ext::derive_pk_from_sk;
+    use crate::crypto::poseidon::HybridPoseidonCipher;
+
+    use super::constraints::{SchnorrVerifyGadget, SignatureVar};
+    use super::*;
+
+    #[test]
+    fn test_sign_and_verify() {
+        let rng = &mut test_rng();
+        let params = HybridPoseidonCipher::setup(rng);
+        let sk = ExtSecretKey::rand(rng);
+        let pk = derive_pk_from_sk(&params.elgamal_params, &sk.0);
+        let msg = OuterScalarField::rand(rng);
+
+        let signature = Schnorr::sign(&params, &sk, &pk, msg, rng);
+        assert!(Schnorr::verify(&params, &pk, msg, &signature));
+
+        // a signature over a different message must not verify
+        let other_msg = OuterScalarField::rand(rng);
+        assert!(!Schnorr::verify(&params, &pk, other_msg, &signature));
+    }
+
+    #[test]
+    fn test_verify_gadget() {
+        let rng = &mut test_rng();
+        let params = HybridPoseidonCipher::setup(rng);
+        let sk = ExtSecretKey::rand(rng);
+        let pk = derive_pk_from_sk(&params.elgamal_params, &sk.0);
+        let msg = OuterScalarField::rand(rng);
+        let signature = Schnorr::sign(&params, &sk, &pk, msg, rng);
+        assert!(Schnorr::verify(&params, &pk, msg, &signature));
+
+        let cs = ConstraintSystem::<OuterScalarField>::new_ref();
+        let generator_var = InnerEdVar::new_constant(cs.clone(), &params.elgamal_params.generator).unwrap();
+        let pk_var = InnerEdVar::new_witness(cs.clone(), || Ok(pk)).unwrap();
+        let msg_var = OuterScalarVar::new_witness(cs.clone(), || Ok(msg)).unwrap();
+        let signature_var = SignatureVar::new_witness(cs.clone(), || Ok(&signature)).unwrap();
+
+        let valid = SchnorrVerifyGadget::verify(cs.clone(), &params.poseidon_params, &generator_var, &pk_var, &msg_var, &signature_var).unwrap();
+        valid.enforce_equal(&Boolean::TRUE).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+    }
+}
@@ -1,31 +1,39 @@
-use ark_std::cell::{Ref, RefCell};
-use ark_std::rc::Rc;
-use ark_std::collections::BTreeMap;
+use ark_std::collections::{BTreeMap, BTreeSet};
 use ark_crypto_primitives::{merkle_tree, CRH, crh::TwoToOneCRH};
 use ark_ff::ToBytes;
 
-#[derive(Clone)]
-struct NodePtr<P: Clone + merkle_tree::Config>(Rc<RefCell<Node<P>>>);
-
-impl<P: Clone + merkle_tree::Config> NodePtr<P> {
-    pub fn borrow(&self) -> Ref<Node<P>> {
-        return self.0.borrow();
-    }
-
-    pub fn new(node: Node<P>) -> NodePtr<P> {
-        NodePtr(Rc::new(RefCell::new(node)))
-    }
-}
+/// Content-address of a stored node: a leaf/inner tag byte followed by the serialized digest.
+/// Two different nodes (leaf or inner, at any position in the tree) never share a key, since
+/// the tree is append-only and content-addressed -- a given `(tag, digest)` pair always denotes
+/// the same node contents.
+pub type NodeKey = Vec<u8>;
 
+#[derive(Clone)]
 enum NodeData<P: Clone + merkle_tree::Config> {
     Inner(merkle_tree::TwoToOneDigest<P>),
     Leaf(merkle_tree::LeafDigest<P>)
 }
 
+fn node_key<P: Clone + merkle_tree::Config>(data: &NodeData<P>) -> NodeKey {
+    match data {
+        NodeData::Leaf(hash) => {
+            let mut key = vec![0u8];
+            key.extend(ark_ff::to_bytes!(hash).unwrap());
+            key
+        },
+        NodeData::Inner(hash) => {
+            let mut key = vec![1u8];
+            key.extend(ark_ff::to_bytes!(hash).unwrap());
+            key
+        }
+    }
+}
+
+#[derive(Clone)]
 struct Node<P: Clone + merkle_tree::Config> {
     data: NodeData<P>,
-    left_child: Option<NodePtr<P>>,
-    right_child: Option<NodePtr<P>>
+    left_child: Option<NodeKey>,
+    right_child: Option<NodeKey>
 }
 
 impl<P: Clone + merkle_tree::Config> Node<P> {
@@ -37,7 +45,7 @@ impl<P: Clone + merkle_tree::Config> Node<P> {
         }
     }
 
-    pub fn new_internal(hash: merkle_tree::TwoToOneDigest<P>, left: NodePtr<P>, right: NodePtr<P>) -> Node<P> {
+    pub fn new_internal(hash: merkle_tree::TwoToOneDigest<P>, left: NodeKey, right: NodeKey) -> Node<P> {
         Node {
             data: NodeData::Inner(hash),
             left_child: Some(left),
@@ -45,12 +53,17 @@ impl<P: Clone + merkle_tree::Config> Node<P> {
         }
     }
 
-    pub fn get_left(&self) -> Ref<Node<P>> {
-        self.left_child.as_ref().unwrap().borrow()
+    /// The key this node is (or would be) stored under.
+    pub fn key(&self) -> NodeKey {
+        node_key(&self.data)
+    }
+
+    pub fn get_left<S: Storage<P>>(&self, storage: &S) -> Node<P> {
+        storage.get_node(self.left_child.as_ref().unwrap()).expect("missing node in storage")
     }
 
-    pub fn get_right(&self) -> Ref<Node<P>> {
-        self.right_child.as_ref().unwrap().borrow()
+    pub fn get_right<S: Storage<P>>(&self, storage: &S) -> Node<P> {
+        storage.get_node(self.right_child.as_ref().unwrap()).expect("missing node in storage")
     }
 
     pub fn try_get_leaf_hash(&self) -> Option<merkle_tree::LeafDigest<P>> {
@@ -68,30 +81,102 @@ impl<P: Clone + merkle_tree::Config> Node<P> {
     }
 }
 
+/// Persistence backend for `SparseMerkleTree` nodes, keyed by content-address (`NodeKey`), plus
+/// the index-to-leaf-key mapping. `InMemoryStorage` is the default, RAM-only implementation;
+/// swapping in a disk- or database-backed implementation (e.g. wrapping LevelDB, the way
+/// arnaucube's `merkletree-rs` wraps a `Db`) lets a tree with millions of leaves page nodes from
+/// disk instead of keeping the whole structure resident.
+pub trait Storage<P: Clone + merkle_tree::Config> {
+    fn get_node(&self, key: &NodeKey) -> Option<Node<P>>;
+    fn put_node(&mut self, key: NodeKey, node: Node<P>);
+    fn get_leaf_key(&self, idx: u128) -> Option<NodeKey>;
+    fn set_leaf_key(&mut self, idx: u128, key: NodeKey);
+    fn remove_leaf_key(&mut self, idx: u128);
+}
+
+/// Default `Storage` implementation, keeping every node and the leaf index in memory.
+pub struct InMemoryStorage<P: Clone + merkle_tree::Config> {
+    nodes: BTreeMap<NodeKey, Node<P>>,
+    leaves: BTreeMap<u128, NodeKey>
+}
+
+impl<P: Clone + merkle_tree::Config> Default for InMemoryStorage<P> {
+    fn default() -> Self {
+        InMemoryStorage {
+            nodes: BTreeMap::new(),
+            leaves: BTreeMap::new()
+        }
+    }
+}
+
+impl<P: Clone + merkle_tree::Config> Storage<P> for InMemoryStorage<P> {
+    fn get_node(&self, key: &NodeKey) -> Option<Node<P>> {
+        self.nodes.get(key).cloned()
+    }
+
+    fn put_node(&mut self, key: NodeKey, node: Node<P>) {
+        self.nodes.insert(key, node);
+    }
+
+    fn get_leaf_key(&self, idx: u128) -> Option<NodeKey> {
+        self.leaves.get(&idx).cloned()
+    }
+
+    fn set_leaf_key(&mut self, idx: u128, key: NodeKey) {
+        self.leaves.insert(idx, key);
+    }
+
+    fn remove_leaf_key(&mut self, idx: u128) {
+        self.leaves.remove(&idx);
+    }
+}
+
 struct SparseMerkleTreePath<P: Clone + merkle_tree::Config> {
     pub is_left: Vec<bool>,
-    pub nodes: Vec<NodePtr<P>>
+    pub nodes: Vec<Node<P>>
 }
 
-pub struct SparseMerkleTree<P: Clone + merkle_tree::Config> {
+/// Id returned by `SparseMerkleTree::checkpoint`, to be passed to `rewind` later on.
+pub type CheckpointId = usize;
+
+pub struct SparseMerkleTree<P: Clone + merkle_tree::Config, S: Storage<P> = InMemoryStorage<P>> {
     height: usize,
-    root: NodePtr<P>,
-    leaves: BTreeMap<u128, NodePtr<P>>,
+    root: NodeKey,
+    storage: S,
     leaf_hash_param: merkle_tree::LeafParam<P>,
-    inner_hash_param: merkle_tree::TwoToOneParam<P>
+    inner_hash_param: merkle_tree::TwoToOneParam<P>,
+    // stack of (root, changed leaves) snapshots; `changed_leaves` records, in chronological
+    // order, the previous leaf key at every index touched by `update` since the checkpoint was
+    // taken. Since nodes are content-addressed and never overwritten, rewinding never needs to
+    // touch `storage` itself -- only the root pointer and the index-to-leaf-key mapping.
+    checkpoints: Vec<(NodeKey, Vec<(u128, Option<NodeKey>)>)>
 }
 
-impl<P: Clone + merkle_tree::Config> SparseMerkleTree<P> {
-    /// Creates a new empty merkle tree of the specified height (can store 2^(height-1) leafs)
+impl<P: Clone + merkle_tree::Config> SparseMerkleTree<P, InMemoryStorage<P>> {
+    /// Creates a new empty merkle tree of the specified height (can store 2^(height-1) leafs),
+    /// backed by an in-memory `InMemoryStorage`. Use `with_storage` for a different backend.
     pub fn new(leaf_hash_param: &merkle_tree::LeafParam<P>,
                inner_hash_param: &merkle_tree::TwoToOneParam<P>,
-               height: usize) -> SparseMerkleTree<P> {
+               height: usize) -> SparseMerkleTree<P, InMemoryStorage<P>> {
+        Self::with_storage(leaf_hash_param, inner_hash_param, height, InMemoryStorage::default())
+    }
+}
+
+impl<P: Clone + merkle_tree::Config, S: Storage<P>> SparseMerkleTree<P, S> {
+    /// Creates a new empty merkle tree of the specified height (can store 2^(height-1) leafs),
+    /// persisting its nodes through `storage`.
+    pub fn with_storage(leaf_hash_param: &merkle_tree::LeafParam<P>,
+                         inner_hash_param: &merkle_tree::TwoToOneParam<P>,
+                         height: usize,
+                         mut storage: S) -> SparseMerkleTree<P, S> {
         assert!(height >= 2, "height must be at least 2");
 
         // create empty leaf
         let empty_leaf_hash = P::LeafHash::evaluate(leaf_hash_param, &vec![0u8; P::LeafHash::INPUT_SIZE_BITS / 8]).unwrap();
-        let empty_leaf = NodePtr::new(Node::new_leaf(empty_leaf_hash.clone()));
-        let mut cur = empty_leaf.clone();
+        let empty_leaf = Node::new_leaf(empty_leaf_hash.clone());
+        let empty_leaf_key = empty_leaf.key();
+        storage.put_node(empty_leaf_key.clone(), empty_leaf);
+        let mut cur_key = empty_leaf_key;
 
         // create bottom layer internal node
         let hash: merkle_tree::TwoToOneDigest<P> = P::TwoToOneHash::evaluate(
@@ -99,8 +184,9 @@ impl<P: Clone + merkle_tree::Config> SparseMerkleTree<P> {
             &ark_ff::to_bytes!(&empty_leaf_hash).unwrap(),
             &ark_ff::to_bytes!(&empty_leaf_hash).unwrap()
         ).unwrap();
-        let next = NodePtr::new(Node::new_internal(hash.clone(), cur.clone(), cur.clone()));
-        cur = next;
+        let next = Node::new_internal(hash.clone(), cur_key.clone(), cur_key.clone());
+        cur_key = next.key();
+        storage.put_node(cur_key.clone(), next);
 
         // create remaining internal nodes
         let mut prev_hash = hash;
@@ -110,17 +196,48 @@ impl<P: Clone + merkle_tree::Config> SparseMerkleTree<P> {
                 &ark_ff::to_bytes!(&prev_hash).unwrap(),
                 &ark_ff::to_bytes!(&prev_hash).unwrap()
             ).unwrap();
-            let next = NodePtr::new(Node::new_internal(prev_hash.clone(), cur.clone(), cur.clone()));
-            cur = next;
+            let next = Node::new_internal(prev_hash.clone(), cur_key.clone(), cur_key.clone());
+            cur_key = next.key();
+            storage.put_node(cur_key.clone(), next);
         }
 
         SparseMerkleTree {
             height: height,
-            root: cur,
-            leaves: BTreeMap::new(),
+            root: cur_key,
+            storage,
             leaf_hash_param: leaf_hash_param.clone(),
-            inner_hash_param: inner_hash_param.clone()
+            inner_hash_param: inner_hash_param.clone(),
+            checkpoints: Vec::new()
+        }
+    }
+
+    /// Records the current tree state and returns an id that can later be passed to `rewind`
+    /// to restore it. O(1): nodes are content-addressed and never mutated in place, so
+    /// snapshotting just needs to remember the current root and log future leaf-key changes.
+    pub fn checkpoint(&mut self) -> CheckpointId {
+        self.checkpoints.push((self.root.clone(), Vec::new()));
+        self.checkpoints.len() - 1
+    }
+
+    /// Restores the tree to the state it was in when `checkpoint` returned `id`, discarding
+    /// every mutation made since (including any later checkpoints, which are dropped too).
+    /// Panics if `id` does not refer to a currently live checkpoint.
+    pub fn rewind(&mut self, id: CheckpointId) {
+        assert!(id < self.checkpoints.len(), "no such checkpoint");
+        let mut popped = self.checkpoints.split_off(id);
+        let root = popped[0].0.clone();
+        let mut changed_leaves = Vec::new();
+        for (_, leaves) in popped.drain(..) {
+            changed_leaves.extend(leaves);
+        }
+        // undo in reverse so that an index touched more than once is restored step by step
+        while let Some((idx, old_leaf_key)) = changed_leaves.pop() {
+            match old_leaf_key {
+                Some(key) => self.storage.set_leaf_key(idx, key),
+                None => self.storage.remove_leaf_key(idx)
+            }
         }
+        self.root = root;
     }
 
     /// Updates leaf at position `idx` with `new_leaf_data`
@@ -129,65 +246,159 @@ impl<P: Clone + merkle_tree::Config> SparseMerkleTree<P> {
 
         // create new leaf
         let new_leaf_hash = P::LeafHash::evaluate(&self.leaf_hash_param, &ark_ff::to_bytes!(&new_leaf_data).unwrap()).unwrap();
-        let mut cur = NodePtr::new(Node::new_leaf(new_leaf_hash.clone()));
-        self.leaves.insert(idx, cur.clone());
+        let new_leaf_node = Node::new_leaf(new_leaf_hash.clone());
+        let new_leaf_key = new_leaf_node.key();
+        self.storage.put_node(new_leaf_key.clone(), new_leaf_node);
+
+        let old_leaf_key = self.storage.get_leaf_key(idx);
+        self.storage.set_leaf_key(idx, new_leaf_key.clone());
+        for (_, changed_leaves) in self.checkpoints.iter_mut() {
+            changed_leaves.push((idx, old_leaf_key.clone()));
+        }
 
         // find position in tree
         let path = self.get_path(idx);
+        let mut cur_key = new_leaf_key;
 
         // update bottom layer internal node
         let mut prev_hash;
         let original = &path.nodes[path.is_left.len() - 1];
-        if path.is_left[path.is_left.len() - 1] {
-            let right_hash = original.borrow().get_right().try_get_leaf_hash().expect("malformed node");
+        let bottom_internal = if path.is_left[path.is_left.len() - 1] {
+            let right_hash = original.get_right(&self.storage).try_get_leaf_hash().expect("malformed node");
             prev_hash = P::TwoToOneHash::evaluate(
                 &self.inner_hash_param,
                 &ark_ff::to_bytes!(&new_leaf_hash).unwrap(),
                 &ark_ff::to_bytes!(&right_hash).unwrap()
             ).unwrap();
-            cur = NodePtr::new(Node::new_internal(prev_hash.clone(), cur.clone(), original.borrow().right_child.as_ref().unwrap().clone()));
+            Node::new_internal(prev_hash.clone(), cur_key.clone(), original.right_child.clone().unwrap())
         } else {
-            let left_hash = original.borrow().get_left().try_get_leaf_hash().expect("malformed node");
+            let left_hash = original.get_left(&self.storage).try_get_leaf_hash().expect("malformed node");
             prev_hash = P::TwoToOneHash::evaluate(
                 &self.inner_hash_param,
                 &ark_ff::to_bytes!(&left_hash).unwrap(),
                 &ark_ff::to_bytes!(&new_leaf_hash).unwrap()
             ).unwrap();
-            cur = NodePtr::new(Node::new_internal(prev_hash.clone(), original.borrow().left_child.as_ref().unwrap().clone(), cur.clone()));
-        }
+            Node::new_internal(prev_hash.clone(), original.left_child.clone().unwrap(), cur_key.clone())
+        };
+        cur_key = bottom_internal.key();
+        self.storage.put_node(cur_key.clone(), bottom_internal);
 
         // update remaining internal nodes
         if self.height > 2 {
             let mut i = path.is_left.len() - 2;
             loop {
                 let original = &path.nodes[i];
-                if path.is_left[i] {
-                    let right_hash = original.borrow().get_right().try_get_inner_hash().expect("malformed node");
+                let new_internal = if path.is_left[i] {
+                    let right_hash = original.get_right(&self.storage).try_get_inner_hash().expect("malformed node");
                     prev_hash = P::TwoToOneHash::evaluate(
                         &self.inner_hash_param,
                         &ark_ff::to_bytes!(&prev_hash.clone()).unwrap(),
                         &ark_ff::to_bytes!(&right_hash).unwrap()
                     ).unwrap();
-                    cur = NodePtr::new(Node::new_internal(prev_hash.clone(), cur.clone(), original.borrow().right_child.as_ref().unwrap().clone()));
+                    Node::new_internal(prev_hash.clone(), cur_key.clone(), original.right_child.clone().unwrap())
                 } else {
-                    let left_hash = original.borrow().get_left().try_get_inner_hash().expect("malformed node");
+                    let left_hash = original.get_left(&self.storage).try_get_inner_hash().expect("malformed node");
                     prev_hash = P::TwoToOneHash::evaluate(
                         &self.inner_hash_param,
                         &ark_ff::to_bytes!(&left_hash).unwrap(),
                         &ark_ff::to_bytes!(&prev_hash.clone()).unwrap()
                     ).unwrap();
-                    cur = NodePtr::new(Node::new_internal(prev_hash.clone(), original.borrow().left_child.as_ref().unwrap().clone(), cur.clone()));
-                }
+                    Node::new_internal(prev_hash.clone(), original.left_child.clone().unwrap(), cur_key.clone())
+                };
+                cur_key = new_internal.key();
+                self.storage.put_node(cur_key.clone(), new_internal);
                 if i == 0 { break; }
                 i -= 1;
             }
         }
-        self.root = cur;
+        self.root = cur_key;
+    }
+
+    /// Applies many leaf updates in one pass. Unlike calling `update` once per entry, which
+    /// recomputes the whole root-to-leaf path (and so re-hashes shared ancestors once per
+    /// leaf that shares them), this recomputes each affected internal node's digest exactly
+    /// once, processing the tree level by level from the leaves up -- the approach used by
+    /// the Libra sparse Merkle tree for bulk insertion.
+    pub fn update_batch<L: ToBytes>(&mut self, entries: &[(u128, L)]) {
+        if entries.is_empty() {
+            return;
+        }
+        for (idx, _) in entries {
+            assert!(*idx < (1 << self.height - 1), "index too large for tree height");
+        }
+
+        // depth 0: write every new leaf and remember it as the current (bottom) level
+        let mut current: BTreeMap<u128, Node<P>> = BTreeMap::new();
+        for (idx, data) in entries {
+            let new_leaf_hash = P::LeafHash::evaluate(&self.leaf_hash_param, &ark_ff::to_bytes!(data).unwrap()).unwrap();
+            let new_leaf = Node::new_leaf(new_leaf_hash);
+            let new_leaf_key = new_leaf.key();
+            self.storage.put_node(new_leaf_key.clone(), new_leaf.clone());
+
+            let old_leaf_key = self.storage.get_leaf_key(*idx);
+            self.storage.set_leaf_key(*idx, new_leaf_key);
+            for (_, changed_leaves) in self.checkpoints.iter_mut() {
+                changed_leaves.push((*idx, old_leaf_key.clone()));
+            }
+            current.insert(*idx, new_leaf);
+        }
+
+        // walk level by level towards the root, recomputing each affected node exactly once;
+        // an unaffected sibling is pulled from the pre-batch tree via `node_at`
+        for depth in 0..self.height - 1 {
+            let parent_coords: BTreeSet<u128> = current.keys().map(|idx| idx >> 1).collect();
+            let mut next = BTreeMap::new();
+            for parent_coord in parent_coords {
+                let left_idx = parent_coord * 2;
+                let right_idx = parent_coord * 2 + 1;
+                let (left, right) = match (current.get(&left_idx).cloned(), current.get(&right_idx).cloned()) {
+                    (Some(left), Some(right)) => (left, right),
+                    (left, right) => {
+                        let old_parent = self.node_at(depth + 1, parent_coord);
+                        (left.unwrap_or_else(|| old_parent.get_left(&self.storage)),
+                         right.unwrap_or_else(|| old_parent.get_right(&self.storage)))
+                    }
+                };
+
+                let (left_bytes, right_bytes) = if depth == 0 {
+                    (ark_ff::to_bytes!(&left.try_get_leaf_hash().expect("malformed node")).unwrap(),
+                     ark_ff::to_bytes!(&right.try_get_leaf_hash().expect("malformed node")).unwrap())
+                } else {
+                    (ark_ff::to_bytes!(&left.try_get_inner_hash().expect("malformed node")).unwrap(),
+                     ark_ff::to_bytes!(&right.try_get_inner_hash().expect("malformed node")).unwrap())
+                };
+                let hash = P::TwoToOneHash::evaluate(&self.inner_hash_param, &left_bytes, &right_bytes).unwrap();
+                let new_node = Node::new_internal(hash, left.key(), right.key());
+                self.storage.put_node(new_node.key(), new_node.clone());
+                next.insert(parent_coord, new_node);
+            }
+            current = next;
+        }
+
+        self.root = current.remove(&0).expect("malformed batch update").key();
+    }
+
+    /// Returns the node at `coord` among the `2^(height-1-depth_from_leaf)` nodes living at
+    /// `depth_from_leaf` levels above the leaves (0 = the leaves themselves), as of the
+    /// current tree state.
+    fn node_at(&self, depth_from_leaf: usize, coord: u128) -> Node<P> {
+        let descent_steps = self.height - 1 - depth_from_leaf;
+        // a representative leaf index under this node; only bits at position >= depth_from_leaf
+        // are ever consulted while descending `descent_steps` levels, so the zero-padding is safe
+        let representative_idx = coord << depth_from_leaf;
+        let mut cur = self.storage.get_node(&self.root).expect("missing node in storage");
+        for level in 0..descent_steps {
+            let nof_leaves_at_level = 1u128 << (self.height - 1 - level);
+            let is_left = (representative_idx % nof_leaves_at_level) < (nof_leaves_at_level >> 1);
+            let child_key = if is_left { cur.left_child.clone().unwrap() } else { cur.right_child.clone().unwrap() };
+            cur = self.storage.get_node(&child_key).expect("missing node in storage");
+        }
+        cur
     }
 
     /// Returns the root hash of the Merkle tree.
     pub fn root(&self) -> merkle_tree::TwoToOneDigest<P> {
-        self.root.borrow().try_get_inner_hash().expect("malformed root")
+        self.storage.get_node(&self.root).expect("missing root node in storage").try_get_inner_hash().expect("malformed root")
     }
 
     /// Returns the height of the Merkle tree
@@ -199,24 +410,26 @@ impl<P: Clone + merkle_tree::Config> SparseMerkleTree<P> {
     /// Currently only supports usize indices due to `merkle_tree::Path`.
     pub fn generate_proof(&self, idx: usize) -> merkle_tree::Path<P> {
         let path = self.get_path(idx as u128);
-        let lowest_inner_node = path.nodes[path.nodes.len() - 2].borrow();
-        let leaf_sibling_node = if path.is_left[path.nodes.len() - 2] {
+        let lowest_inner_node = &path.nodes[path.nodes.len() - 2];
+        let leaf_sibling_key = if path.is_left[path.nodes.len() - 2] {
             lowest_inner_node.right_child.as_ref().unwrap()
         } else {
             lowest_inner_node.left_child.as_ref().unwrap()
         };
-        let leaf_sibling_hash = leaf_sibling_node.borrow().try_get_leaf_hash().expect("malformed leaf node");
+        let leaf_sibling_hash = self.storage.get_node(leaf_sibling_key).expect("missing node in storage")
+            .try_get_leaf_hash().expect("malformed leaf node");
 
         // auth_path.len() = `self.height - 2`, the two missing elements being the leaf sibling hash and the root
         let mut auth_path = Vec::with_capacity(self.height - 2);
         for i in 0..path.nodes.len()-2 {
-            let node = path.nodes[i].borrow();
-            let sibling_node = if path.is_left[i] {
+            let node = &path.nodes[i];
+            let sibling_key = if path.is_left[i] {
                 node.right_child.as_ref().unwrap()
             } else {
                 node.left_child.as_ref().unwrap()
             };
-            let sibling_hash = sibling_node.borrow().try_get_inner_hash().expect("malformed inner node");
+            let sibling_hash = self.storage.get_node(sibling_key).expect("missing node in storage")
+                .try_get_inner_hash().expect("malformed inner node");
             auth_path.push(sibling_hash);
         }
 
@@ -227,29 +440,109 @@ impl<P: Clone + merkle_tree::Config> SparseMerkleTree<P> {
         }
     }
 
+    /// Returns a proof that no value has ever been written at position `idx`, i.e. that the
+    /// leaf there still holds the tree's canonical "empty" value. Returns `None` if `idx`
+    /// was in fact written via `update`.
+    pub fn generate_non_membership_proof(&self, idx: usize) -> Option<merkle_tree::Path<P>> {
+        if self.storage.get_leaf_key(idx as u128).is_some() {
+            return None;
+        }
+        Some(self.generate_proof(idx))
+    }
+
+    /// Verifies a non-membership proof produced by `generate_non_membership_proof`: checks
+    /// that `proof` opens `root` to the tree's canonical "empty" leaf value.
+    pub fn verify_non_membership_proof(
+        leaf_hash_param: &merkle_tree::LeafParam<P>,
+        inner_hash_param: &merkle_tree::TwoToOneParam<P>,
+        root: &merkle_tree::TwoToOneDigest<P>,
+        proof: &merkle_tree::Path<P>,
+    ) -> bool {
+        let empty_leaf_bytes = vec![0u8; P::LeafHash::INPUT_SIZE_BITS / 8];
+        proof.verify_membership(leaf_hash_param, inner_hash_param, root, &empty_leaf_bytes).unwrap_or(false)
+    }
+
     /// For the leaf at position `idx`, returns the path starting at the root and leading to the leaf.
     fn get_path(&self, idx: u128) -> SparseMerkleTreePath<P> {
         let mut path = SparseMerkleTreePath {
             is_left: vec![],
             nodes: vec![]
         };
-        let mut cur = self.root.clone();
+        let mut cur = self.storage.get_node(&self.root).expect("missing node in storage");
         path.nodes.push(cur.clone());
         for level in 0..self.height - 1 {
             let nof_leaves_at_level = 1 << (self.height - 1 - level);
             let is_left = (idx % nof_leaves_at_level) < (nof_leaves_at_level >> 1);
-            let node = if is_left { cur.borrow().left_child.as_ref().unwrap().clone() } else { cur.borrow().right_child.as_ref().unwrap().clone() };
+            let child_key = if is_left { cur.left_child.clone().unwrap() } else { cur.right_child.clone().unwrap() };
+            let child = self.storage.get_node(&child_key).expect("missing node in storage");
             path.is_left.push(is_left);
-            path.nodes.push(node.clone());
-            cur = node;
+            path.nodes.push(child.clone());
+            cur = child;
         }
         path
     }
 }
 
+/// Verifies a `merkle_tree::Path<P>` proof (as produced by `SparseMerkleTree::generate_proof`)
+/// against `root`, without needing the tree itself: re-derives the leaf hash from `leaf_data`,
+/// then folds it upward through `path.leaf_sibling_hash` and `path.auth_path`, swapping
+/// left/right at each level according to `path.leaf_index`'s bits, and compares the result to
+/// `root`. Lets a lightweight client validate a proof it was handed independently of the tree
+/// that produced it.
+pub fn verify_path<P: Clone + merkle_tree::Config, L: ToBytes>(
+    leaf_hash_param: &merkle_tree::LeafParam<P>,
+    inner_hash_param: &merkle_tree::TwoToOneParam<P>,
+    root: &merkle_tree::TwoToOneDigest<P>,
+    path: &merkle_tree::Path<P>,
+    leaf_data: &L,
+) -> bool {
+    let leaf_bytes = ark_ff::to_bytes!(leaf_data).unwrap();
+    path.verify_membership(leaf_hash_param, inner_hash_param, root, &leaf_bytes).unwrap_or(false)
+}
+
+/// In-circuit verification of `SparseMerkleTree` proofs.
+///
+/// `SparseMerkleTree::generate_proof`/`generate_non_membership_proof` both produce ordinary
+/// `merkle_tree::Path<P>` values, encoded exactly the way `ark_crypto_primitives`'s own dense
+/// `MerkleTree` encodes them (same `auth_path`/`leaf_sibling_hash`/`leaf_index` fields, same
+/// left/right convention as `get_path`'s `is_left`). That means arkworks' own `PathVar` gadget
+/// -- already generic over the leaf- and two-to-one-hash gadgets -- verifies a sparse-tree
+/// proof in-circuit without any changes; this module just names that gadget for this tree and
+/// adds the non-membership counterpart to `SparseMerkleTree::verify_non_membership_proof`.
+pub mod constraints {
+    use ark_crypto_primitives::crh::TwoToOneCRHGadget;
+    use ark_crypto_primitives::{merkle_tree, CRHGadget, PathVar};
+    use ark_ff::Field;
+    use ark_r1cs_std::prelude::*;
+    use ark_relations::r1cs::SynthesisError;
+
+    /// In-circuit authentication path for a `SparseMerkleTree<P>` proof, generic over the
+    /// leaf- and two-to-one-hash gadgets the same way arkworks' own `PathVar` is.
+    pub type SparseMerklePathVar<P, LeafHG, TwoToOneHG, ConstraintF> =
+        PathVar<P, LeafHG, TwoToOneHG, ConstraintF>;
+
+    /// Verifies, in-circuit, that `path` opens `root` to the tree's canonical "empty" leaf
+    /// value -- the gadget counterpart of `SparseMerkleTree::verify_non_membership_proof`.
+    pub fn verify_non_membership<P, LeafHG, TwoToOneHG, ConstraintF>(
+        path: &SparseMerklePathVar<P, LeafHG, TwoToOneHG, ConstraintF>,
+        leaf_hash_param: &LeafHG::ParametersVar,
+        inner_hash_param: &TwoToOneHG::ParametersVar,
+        root: &TwoToOneHG::OutputVar,
+    ) -> Result<Boolean<ConstraintF>, SynthesisError>
+    where
+        P: Clone + merkle_tree::Config,
+        LeafHG: CRHGadget<P::LeafHash, ConstraintF>,
+        TwoToOneHG: TwoToOneCRHGadget<P::TwoToOneHash, ConstraintF>,
+        ConstraintF: Field,
+    {
+        let empty_leaf_bytes = vec![UInt8::constant(0); P::LeafHash::INPUT_SIZE_BITS / 8];
+        path.verify_membership(leaf_hash_param, inner_hash_param, root, &empty_leaf_bytes)
+    }
+}
+
 #[cfg(test)]
 mod tests {
-    use super::SparseMerkleTree;
+    use super::{verify_path, InMemoryStorage, SparseMerkleTree};
     use ark_crypto_primitives::{
         crh::{pedersen, *},
         merkle_tree::*,
@@ -404,4 +697,170 @@ mod tests {
         assert_eq!(dense_proof.leaf_sibling_hash, sparse_proof.leaf_sibling_hash);
         assert_eq!(dense_proof.leaf_index, sparse_proof.leaf_index);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn non_membership_proof_test() {
+        let mut rng = test_rng();
+
+        let (leaf_hash_param, inner_hash_param) = {
+            let leaf_hash_param = <H as CRH>::setup(&mut rng).unwrap();
+            let inner_hash_param = <H as TwoToOneCRH>::setup(&mut rng).unwrap();
+            (leaf_hash_param, inner_hash_param)
+        };
+        let mut sparse_tree = SparseMerkleTree::<MerkleTreeParams>::new(&leaf_hash_param, &inner_hash_param, 9);
+
+        // untouched leaf: non-membership proof must succeed
+        let proof = sparse_tree.generate_non_membership_proof(19).unwrap();
+        assert!(SparseMerkleTree::<MerkleTreeParams>::verify_non_membership_proof(&leaf_hash_param, &inner_hash_param, &sparse_tree.root(), &proof));
+
+        // once the leaf is written, no non-membership proof can be generated for it anymore
+        let new_leaf = EdwardsProjective::rand(&mut rng);
+        sparse_tree.update(19, &new_leaf);
+        assert!(sparse_tree.generate_non_membership_proof(19).is_none());
+
+        // a proof against a stale root must not verify against the new root
+        let stale_proof = sparse_tree.generate_non_membership_proof(20).unwrap();
+        sparse_tree.update(20, &new_leaf);
+        assert!(!SparseMerkleTree::<MerkleTreeParams>::verify_non_membership_proof(&leaf_hash_param, &inner_hash_param, &sparse_tree.root(), &stale_proof));
+    }
+
+    #[test]
+    fn checkpoint_rewind_test() {
+        let mut rng = test_rng();
+        let (_, mut sparse_tree) = create_blank_trees(9);
+
+        let leaf_a = EdwardsProjective::rand(&mut rng);
+        sparse_tree.update(164, &leaf_a);
+        let root_before = sparse_tree.root();
+
+        let checkpoint = sparse_tree.checkpoint();
+
+        // overwrite an existing leaf, and write to a fresh one
+        let leaf_b = EdwardsProjective::rand(&mut rng);
+        sparse_tree.update(164, &leaf_b);
+        let leaf_c = EdwardsProjective::rand(&mut rng);
+        sparse_tree.update(19, &leaf_c);
+        assert_ne!(sparse_tree.root(), root_before);
+
+        sparse_tree.rewind(checkpoint);
+        assert_eq!(sparse_tree.root(), root_before);
+        assert!(sparse_tree.generate_non_membership_proof(19).is_some());
+
+        // a rewind also discards any later checkpoints
+        let checkpoint_2 = sparse_tree.checkpoint();
+        let _ = sparse_tree.checkpoint();
+        let leaf_d = EdwardsProjective::rand(&mut rng);
+        sparse_tree.update(201, &leaf_d);
+
+        sparse_tree.rewind(checkpoint_2);
+        assert_eq!(sparse_tree.root(), root_before);
+    }
+
+    #[test]
+    fn verify_path_test() {
+        let mut rng = test_rng();
+
+        let leaf_hash_param = <H as CRH>::setup(&mut rng).unwrap();
+        let inner_hash_param = <H as TwoToOneCRH>::setup(&mut rng).unwrap();
+        let mut sparse_tree = SparseMerkleTree::<MerkleTreeParams>::new(&leaf_hash_param, &inner_hash_param, 9);
+
+        let new_leaf = EdwardsProjective::rand(&mut rng);
+        sparse_tree.update(164, &new_leaf);
+        let other_leaf = EdwardsProjective::rand(&mut rng);
+        sparse_tree.update(19, &other_leaf);
+
+        let proof = sparse_tree.generate_proof(164);
+        let root = sparse_tree.root();
+
+        assert!(verify_path(&leaf_hash_param, &inner_hash_param, &root, &proof, &new_leaf));
+        assert!(!verify_path(&leaf_hash_param, &inner_hash_param, &root, &proof, &other_leaf));
+
+        let stale_root = EdwardsProjective::rand(&mut rng);
+        let stale_root_hash = <H as TwoToOneCRH>::evaluate(&inner_hash_param, &ark_ff::to_bytes!(&stale_root).unwrap(), &ark_ff::to_bytes!(&stale_root).unwrap()).unwrap();
+        assert!(!verify_path(&leaf_hash_param, &inner_hash_param, &stale_root_hash, &proof, &new_leaf));
+    }
+
+    #[test]
+    fn batch_update_test() {
+        let mut rng = test_rng();
+        let leaf_hash_param = <H as CRH>::setup(&mut rng).unwrap();
+        let inner_hash_param = <H as TwoToOneCRH>::setup(&mut rng).unwrap();
+
+        let mut sequential = SparseMerkleTree::<MerkleTreeParams>::new(&leaf_hash_param, &inner_hash_param, 9);
+        let mut batched = SparseMerkleTree::<MerkleTreeParams>::new(&leaf_hash_param, &inner_hash_param, 9);
+
+        let entries: Vec<(u128, EdwardsProjective)> = vec![164, 165, 19, 201, 40]
+            .into_iter()
+            .map(|idx| (idx, EdwardsProjective::rand(&mut rng)))
+            .collect();
+
+        for (idx, leaf) in &entries {
+            sequential.update(*idx, leaf);
+        }
+        batched.update_batch(&entries);
+
+        assert_eq!(sequential.root(), batched.root());
+        assert_eq!(sequential.generate_proof(165).auth_path, batched.generate_proof(165).auth_path);
+        assert_eq!(sequential.generate_proof(57).auth_path, batched.generate_proof(57).auth_path);
+    }
+
+    #[test]
+    fn pluggable_storage_test() {
+        // a tree built directly on `InMemoryStorage` via `with_storage` must behave identically
+        // to one built through the `new` convenience constructor
+        let mut rng = test_rng();
+        let leaf_hash_param = <H as CRH>::setup(&mut rng).unwrap();
+        let inner_hash_param = <H as TwoToOneCRH>::setup(&mut rng).unwrap();
+
+        let mut tree = SparseMerkleTree::<MerkleTreeParams, InMemoryStorage<MerkleTreeParams>>::with_storage(
+            &leaf_hash_param, &inner_hash_param, 9, InMemoryStorage::default());
+        let mut reference = SparseMerkleTree::<MerkleTreeParams>::new(&leaf_hash_param, &inner_hash_param, 9);
+        assert_eq!(tree.root(), reference.root());
+
+        let new_leaf = EdwardsProjective::rand(&mut rng);
+        tree.update(164, &new_leaf);
+        reference.update(164, &new_leaf);
+        assert_eq!(tree.root(), reference.root());
+        assert_eq!(tree.generate_proof(164).auth_path, reference.generate_proof(164).auth_path);
+    }
+
+    #[test]
+    fn gadget_membership_test() {
+        use super::constraints::SparseMerklePathVar;
+        use ark_crypto_primitives::crh::pedersen::constraints::CRHGadget as PedersenCRHGadget;
+        use ark_ed_on_bls12_381::{constraints::EdwardsVar, Fq};
+        use ark_r1cs_std::prelude::*;
+        use ark_relations::r1cs::ConstraintSystem;
+
+        type HG = PedersenCRHGadget<EdwardsProjective, EdwardsVar, Window>;
+
+        let mut rng = test_rng();
+        let (mut dense_tree, mut sparse_tree) = create_blank_trees(9);
+
+        let new_leaf = EdwardsProjective::rand(&mut rng);
+        dense_tree.update(164, &new_leaf).unwrap();
+        sparse_tree.update(164, &new_leaf);
+
+        let other_leaf = EdwardsProjective::rand(&mut rng);
+        dense_tree.update(19, &other_leaf).unwrap();
+        sparse_tree.update(19, &other_leaf);
+
+        let proof = sparse_tree.generate_proof(164);
+        let root = sparse_tree.root();
+
+        let cs = ConstraintSystem::<Fq>::new_ref();
+        let leaf_hash_param_var =
+            <HG as CRHGadget<H, Fq>>::ParametersVar::new_constant(cs.clone(), sparse_tree.leaf_hash_param.clone()).unwrap();
+        let inner_hash_param_var =
+            <HG as TwoToOneCRHGadget<H, Fq>>::ParametersVar::new_constant(cs.clone(), sparse_tree.inner_hash_param.clone()).unwrap();
+        let root_var = <HG as TwoToOneCRHGadget<H, Fq>>::OutputVar::new_input(cs.clone(), || Ok(root)).unwrap();
+        let leaf_var = EdwardsVar::new_witness(cs.clone(), || Ok(new_leaf)).unwrap();
+        let path_var = SparseMerklePathVar::<MerkleTreeParams, HG, HG, Fq>::new_witness(cs.clone(), || Ok(proof)).unwrap();
+
+        let is_member = path_var
+            .verify_membership(&leaf_hash_param_var, &inner_hash_param_var, &root_var, &leaf_var)
+            .unwrap();
+        is_member.enforce_equal(&Boolean::TRUE).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+    }
+}
@@ -0,0 +1,411 @@
+//! A sparse Merkle tree over the Poseidon-digest key space of serial numbers, used to prove a
+//! consumed `Serial` is *not* already in the spent-serials accumulator -- complementing
+//! `access_input`'s existing inclusion proof that the input record itself is in the
+//! positionally-indexed record tree (see `infrastructure::params::MerkleTreeParams`).
+//!
+//! Unlike that record tree, this tree is keyed by (a Poseidon digest of) the serial's own value
+//! rather than insertion order: the path to a serial's leaf is fixed by its key, and an untouched
+//! subtree's hash is one of `SPENT_SMT_DEPTH + 1` cached, precomputed default-hash constants, so
+//! proving a deep subtree is empty costs no extra hashing beyond walking the path. See
+//! `SPENT_SMT_DEPTH` in `constants.rs` for why the key space is truncated to 64 bits rather than
+//! the full field.
+//!
+//! Because of that truncation, a queried leaf can be occupied by a *different* serial's key than
+//! the one being proven absent (with an untruncated key space this could never happen, since the
+//! occupying key would necessarily equal the queried key). `SmtNonMembershipPathVar` handles this
+//! by having the prover open the occupying leaf's actual key and enforcing it differs from the
+//! queried key, rather than only handling the "subtree genuinely empty" case.
+
+use std::collections::BTreeMap;
+
+use ark_ff::to_bytes;
+use ark_sponge::{poseidon::PoseidonSponge, CryptographicSponge, FieldBasedCryptographicSponge};
+
+use crate::common::{fe_from_le_bytes_mod_order, OuterScalarField};
+use crate::constants::SPENT_SMT_DEPTH;
+use crate::crypto::bloom_filter::BloomFilter;
+use crate::crypto::poseidon::HybridPoseidonParams;
+use crate::infrastructure::record::Serial;
+
+/// domain separator for a serial's SMT key, `Poseidon(SMT_KEY_DOMAIN, serial)`
+const SMT_KEY_DOMAIN: u64 = 0x534d545f4b4559; // ASCII "SMT_KEY"
+/// domain separator for an occupied leaf's hash, `Poseidon(SMT_LEAF_DOMAIN, key)`
+const SMT_LEAF_DOMAIN: u64 = 0x534d545f4c454146; // ASCII "SMT_LEAF"
+/// domain separator for the canonical untouched-leaf hash, `Poseidon(SMT_EMPTY_DOMAIN)`
+const SMT_EMPTY_DOMAIN: u64 = 0x534d545f454d5054; // ASCII "SMT_EMPT"
+/// domain separator for an inner node's hash, `Poseidon(SMT_INNER_DOMAIN, left, right)`
+const SMT_INNER_DOMAIN: u64 = 0x534d545f494e4e52; // ASCII "SMT_INNR"
+
+fn poseidon_hash(params: &HybridPoseidonParams, domain: u64, inputs: &[OuterScalarField]) -> OuterScalarField {
+    let mut sponge = PoseidonSponge::new(&params.poseidon_params);
+    let mut to_absorb = vec![OuterScalarField::from(domain)];
+    to_absorb.extend_from_slice(inputs);
+    sponge.absorb(&to_absorb);
+    sponge.squeeze_native_field_elements(1)[0]
+}
+
+/// Derives a serial's SMT key: a Poseidon digest of the serial's bytes, whose low
+/// `SPENT_SMT_DEPTH` bits select the key's leaf path from the root.
+pub fn derive_key(params: &HybridPoseidonParams, serial: &Serial) -> OuterScalarField {
+    let serial_fe: OuterScalarField = fe_from_le_bytes_mod_order(serial);
+    poseidon_hash(params, SMT_KEY_DOMAIN, &[serial_fe])
+}
+
+fn empty_leaf_hash(params: &HybridPoseidonParams) -> OuterScalarField {
+    poseidon_hash(params, SMT_EMPTY_DOMAIN, &[])
+}
+
+fn occupied_leaf_hash(params: &HybridPoseidonParams, key: OuterScalarField) -> OuterScalarField {
+    poseidon_hash(params, SMT_LEAF_DOMAIN, &[key])
+}
+
+fn inner_hash(params: &HybridPoseidonParams, left: OuterScalarField, right: OuterScalarField) -> OuterScalarField {
+    poseidon_hash(params, SMT_INNER_DOMAIN, &[left, right])
+}
+
+/// Computes the `SPENT_SMT_DEPTH + 1` cached hashes of an untouched subtree at each depth from
+/// the leaves (index 0) up to the root (index `SPENT_SMT_DEPTH`), so that walking a
+/// non-membership path through empty subtrees never re-derives them.
+fn default_hashes(params: &HybridPoseidonParams) -> Vec<OuterScalarField> {
+    let mut hashes = Vec::with_capacity(SPENT_SMT_DEPTH + 1);
+    hashes.push(empty_leaf_hash(params));
+    for depth in 1..=SPENT_SMT_DEPTH {
+        let prev = hashes[depth - 1];
+        hashes.push(inner_hash(params, prev, prev));
+    }
+    hashes
+}
+
+/// The low `SPENT_SMT_DEPTH` bits of `key`'s canonical little-endian byte representation,
+/// interpreted as the leaf index -- `SPENT_SMT_DEPTH <= 64`, so these always fit in a `u64`.
+fn index_for_key(key: OuterScalarField) -> u64 {
+    let bytes = to_bytes!(key).unwrap();
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&bytes[..8]);
+    let raw = u64::from_le_bytes(buf);
+    if SPENT_SMT_DEPTH >= 64 { raw } else { raw & ((1u64 << SPENT_SMT_DEPTH) - 1) }
+}
+
+/// A witnessed non-membership opening for some serial's key: either the leaf's subtree is
+/// genuinely untouched (`occupant_key = None`) or it's occupied by a different key
+/// (`occupant_key = Some(_)`, distinct from `key` -- see the module doc comment).
+#[derive(Clone)]
+pub struct SmtNonMembershipPath {
+    pub key: OuterScalarField,
+    pub siblings: Vec<OuterScalarField>,
+    pub occupant_key: Option<OuterScalarField>,
+}
+
+impl Default for SmtNonMembershipPath {
+    fn default() -> Self {
+        SmtNonMembershipPath {
+            key: OuterScalarField::default(),
+            siblings: vec![OuterScalarField::default(); SPENT_SMT_DEPTH],
+            occupant_key: None,
+        }
+    }
+}
+
+/// Id returned by `SpentSerialsSmt::checkpoint`, to be passed to `rewind` later on.
+pub type SpentSerialsCheckpointId = usize;
+
+/// Off-circuit accumulator of spent serials: a sparse Merkle tree over the `2^SPENT_SMT_DEPTH`
+/// leaf positions selected by `derive_key`'s low-order bits. Only occupied nodes are stored; an
+/// untouched subtree is represented implicitly via the cached `default_hashes`.
+pub struct SpentSerialsSmt {
+    params: HybridPoseidonParams,
+    defaults: Vec<OuterScalarField>,
+    // (depth_from_leaf, index at that depth) -> node hash, for nodes differing from their default
+    nodes: BTreeMap<(usize, u64), OuterScalarField>,
+    // leaf index -> the full key occupying it
+    leaf_keys: BTreeMap<u64, OuterScalarField>,
+    // bloom-filter fast path over inserted serials (see `crypto::bloom_filter`); consulted by
+    // `maybe_contains` before a caller pays for a full `witness` lookup
+    bloom: BloomFilter,
+    // stack of (node log, leaf-key log) snapshots; each log records, in chronological order, the
+    // previous value at every position touched by `insert` since the checkpoint was taken --
+    // unlike `sparse_merkle_tree::SparseMerkleTree` (whose nodes are content-addressed and never
+    // overwritten), positions here are overwritten in place, so the previous value itself (not
+    // just a pointer to it) must be recorded to undo an insert.
+    checkpoints: Vec<(Vec<((usize, u64), Option<OuterScalarField>)>, Vec<(u64, Option<OuterScalarField>)>)>,
+}
+
+impl SpentSerialsSmt {
+    pub fn new(params: HybridPoseidonParams) -> SpentSerialsSmt {
+        let defaults = default_hashes(&params);
+        SpentSerialsSmt { params, defaults, nodes: BTreeMap::new(), leaf_keys: BTreeMap::new(), bloom: BloomFilter::new(), checkpoints: Vec::new() }
+    }
+
+    fn node_hash(&self, depth_from_leaf: usize, index: u64) -> OuterScalarField {
+        *self.nodes.get(&(depth_from_leaf, index)).unwrap_or(&self.defaults[depth_from_leaf])
+    }
+
+    /// Records the current accumulator state and returns an id that can later be passed to
+    /// `rewind` to restore it.
+    pub fn checkpoint(&mut self) -> SpentSerialsCheckpointId {
+        self.checkpoints.push((Vec::new(), Vec::new()));
+        self.checkpoints.len() - 1
+    }
+
+    /// Restores the accumulator to the state it was in when `checkpoint` returned `id`,
+    /// discarding every mutation made since (including any later checkpoints, which are dropped
+    /// too). Panics if `id` does not refer to a currently live checkpoint.
+    pub fn rewind(&mut self, id: SpentSerialsCheckpointId) {
+        assert!(id < self.checkpoints.len(), "no such checkpoint");
+        let popped = self.checkpoints.split_off(id);
+        let mut node_log = Vec::new();
+        let mut leaf_key_log = Vec::new();
+        for (nodes, leaf_keys) in popped {
+            node_log.extend(nodes);
+            leaf_key_log.extend(leaf_keys);
+        }
+        // undo in reverse so that a position touched more than once is restored step by step
+        while let Some((pos, old_hash)) = node_log.pop() {
+            match old_hash {
+                Some(hash) => { self.nodes.insert(pos, hash); }
+                None => { self.nodes.remove(&pos); }
+            }
+        }
+        while let Some((idx, old_key)) = leaf_key_log.pop() {
+            match old_key {
+                Some(key) => { self.leaf_keys.insert(idx, key); }
+                None => { self.leaf_keys.remove(&idx); }
+            }
+        }
+    }
+
+    /// Inserts `serial` into the accumulator. Idempotent: re-inserting an already-spent serial
+    /// leaves the tree (and its root) unchanged. Also marks `serial` in the bloom filter; unlike
+    /// the tree itself, the filter is never rewound by `rewind` -- a stale `true` bit only costs
+    /// a wasted fast-path probe, never an incorrect answer, since `maybe_contains` only ever
+    /// proves absence, never presence.
+    pub fn insert(&mut self, serial: &Serial) {
+        self.bloom.insert(serial);
+
+        let key = derive_key(&self.params, serial);
+        let idx = index_for_key(key);
+        let old_leaf_key = self.leaf_keys.insert(idx, key);
+        for (_, leaf_key_log) in self.checkpoints.iter_mut() {
+            leaf_key_log.push((idx, old_leaf_key));
+        }
+
+        let mut cur_hash = occupied_leaf_hash(&self.params, key);
+        let old_node = self.nodes.insert((0, idx), cur_hash);
+        for (node_log, _) in self.checkpoints.iter_mut() {
+            node_log.push(((0, idx), old_node));
+        }
+        let mut cur_idx = idx;
+        for depth in 1..=SPENT_SMT_DEPTH {
+            let sibling_idx = cur_idx ^ 1;
+            let sibling_hash = self.node_hash(depth - 1, sibling_idx);
+            let (left, right) = if cur_idx & 1 == 0 { (cur_hash, sibling_hash) } else { (sibling_hash, cur_hash) };
+            cur_hash = inner_hash(&self.params, left, right);
+            cur_idx >>= 1;
+            let old_node = self.nodes.insert((depth, cur_idx), cur_hash);
+            for (node_log, _) in self.checkpoints.iter_mut() {
+                node_log.push(((depth, cur_idx), old_node));
+            }
+        }
+    }
+
+    /// Returns the current root hash of the accumulator.
+    pub fn root(&self) -> OuterScalarField {
+        self.node_hash(SPENT_SMT_DEPTH, 0)
+    }
+
+    /// Bloom-filter fast path over the serials ever passed to `insert`: `false` proves `serial`
+    /// is unspent without touching the tree at all; `true` means "maybe spent", and the caller
+    /// should fall through to `witness`'s exact (and more expensive) check.
+    pub fn maybe_contains(&self, serial: &Serial) -> bool {
+        self.bloom.maybe_contains(serial)
+    }
+
+    /// Returns a non-membership witness for `serial`, or `None` if this exact serial is already
+    /// in the accumulator (a genuine double-spend, not merely a truncated-key collision).
+    pub fn witness(&self, serial: &Serial) -> Option<SmtNonMembershipPath> {
+        let key = derive_key(&self.params, serial);
+        let idx = index_for_key(key);
+        let occupant_key = self.leaf_keys.get(&idx).copied();
+        if occupant_key == Some(key) {
+            return None;
+        }
+
+        let mut siblings = Vec::with_capacity(SPENT_SMT_DEPTH);
+        let mut cur_idx = idx;
+        for depth in 0..SPENT_SMT_DEPTH {
+            siblings.push(self.node_hash(depth, cur_idx ^ 1));
+            cur_idx >>= 1;
+        }
+        Some(SmtNonMembershipPath { key, siblings, occupant_key })
+    }
+}
+
+/// Verifies a `SmtNonMembershipPath` against `root` without needing the tree itself, the same
+/// way `sparse_merkle_tree::verify_path` lets a lightweight client check a record-tree proof.
+pub fn verify_non_membership(params: &HybridPoseidonParams, root: OuterScalarField, path: &SmtNonMembershipPath) -> bool {
+    if path.occupant_key == Some(path.key) {
+        return false;
+    }
+    if path.siblings.len() != SPENT_SMT_DEPTH {
+        return false;
+    }
+    let mut cur_hash = match path.occupant_key {
+        Some(k) => occupied_leaf_hash(params, k),
+        None => empty_leaf_hash(params),
+    };
+    let mut cur_idx = index_for_key(path.key);
+    for sibling in path.siblings.iter() {
+        cur_hash = if cur_idx & 1 == 0 { inner_hash(params, cur_hash, *sibling) } else { inner_hash(params, *sibling, cur_hash) };
+        cur_idx >>= 1;
+    }
+    cur_hash == root
+}
+
+pub mod constraints {
+    use ark_r1cs_std::prelude::*;
+    use ark_relations::r1cs::{ConstraintSystemRef, SynthesisError};
+    use ark_sponge::{constraints::CryptographicSpongeVar, poseidon::{constraints::PoseidonSpongeVar, PoseidonParameters}};
+
+    use crate::common::{OuterScalarField, OuterScalarVar};
+    use crate::constants::SPENT_SMT_DEPTH;
+
+    use super::{SmtNonMembershipPath, SMT_EMPTY_DOMAIN, SMT_INNER_DOMAIN, SMT_KEY_DOMAIN, SMT_LEAF_DOMAIN};
+
+    fn poseidon_hash_var(
+        cs: &ConstraintSystemRef<OuterScalarField>,
+        params: &PoseidonParameters<OuterScalarField>,
+        domain: u64,
+        inputs: &[OuterScalarVar],
+    ) -> Result<OuterScalarVar, SynthesisError> {
+        let mut sponge = PoseidonSpongeVar::<OuterScalarField>::new(cs.clone(), params);
+        let mut to_absorb = vec![OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(domain))?];
+        to_absorb.extend_from_slice(inputs);
+        sponge.absorb(&to_absorb)?;
+        Ok(sponge.squeeze_field_elements(1)?[0].clone())
+    }
+
+    /// In-circuit counterpart of `derive_key`.
+    pub fn derive_key_var(
+        cs: &ConstraintSystemRef<OuterScalarField>,
+        params: &PoseidonParameters<OuterScalarField>,
+        serial_fe: &OuterScalarVar,
+    ) -> Result<OuterScalarVar, SynthesisError> {
+        poseidon_hash_var(cs, params, SMT_KEY_DOMAIN, &[serial_fe.clone()])
+    }
+
+    /// In-circuit witness for an `SmtNonMembershipPath`, allocated via `new_witness`.
+    pub struct SmtNonMembershipPathVar {
+        siblings: Vec<OuterScalarVar>,
+        occupant_key: Option<OuterScalarVar>,
+    }
+
+    impl SmtNonMembershipPathVar {
+        pub fn new_witness(
+            cs: ConstraintSystemRef<OuterScalarField>,
+            path: &SmtNonMembershipPath,
+        ) -> Result<Self, SynthesisError> {
+            let siblings = path.siblings.iter()
+                .map(|s| OuterScalarVar::new_witness(cs.clone(), || Ok(*s)))
+                .collect::<Result<Vec<_>, _>>()?;
+            let occupant_key = path.occupant_key
+                .map(|k| OuterScalarVar::new_witness(cs.clone(), || Ok(k)))
+                .transpose()?;
+            Ok(SmtNonMembershipPathVar { siblings, occupant_key })
+        }
+
+        /// Enforces that this path opens `root` to a leaf proving `key` is not a member: either
+        /// the leaf's subtree is the canonical empty default, or it's occupied by a different
+        /// key. Returns the check as a `Boolean` (rather than enforcing it directly) so callers
+        /// can combine it with `enforce_or_dummy`, matching `PathVar::verify_membership`'s
+        /// calling convention elsewhere in this crate. The leaf's position is the low
+        /// `SPENT_SMT_DEPTH` bits of `key`'s little-endian bit decomposition, the same convention
+        /// `index_for_key` uses natively.
+        pub fn check_non_membership(
+            &self,
+            cs: &ConstraintSystemRef<OuterScalarField>,
+            params: &PoseidonParameters<OuterScalarField>,
+            key: &OuterScalarVar,
+            root: &OuterScalarVar,
+        ) -> Result<Boolean<OuterScalarField>, SynthesisError> {
+            assert_eq!(self.siblings.len(), SPENT_SMT_DEPTH);
+
+            let (leaf_hash, occupant_distinct) = match &self.occupant_key {
+                Some(occ) => (poseidon_hash_var(cs, params, SMT_LEAF_DOMAIN, &[occ.clone()])?, occ.is_neq(key)?),
+                None => (poseidon_hash_var(cs, params, SMT_EMPTY_DOMAIN, &[])?, Boolean::TRUE),
+            };
+
+            let key_bits = key.to_bits_le()?;
+            let mut cur = leaf_hash;
+            for depth in 0..SPENT_SMT_DEPTH {
+                let is_right = &key_bits[depth];
+                let sibling = &self.siblings[depth];
+                let left = OuterScalarVar::conditionally_select(is_right, sibling, &cur)?;
+                let right = OuterScalarVar::conditionally_select(is_right, &cur, sibling)?;
+                cur = poseidon_hash_var(cs, params, SMT_INNER_DOMAIN, &[left, right])?;
+            }
+
+            cur.is_eq(root)?.and(&occupant_distinct)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_std::test_rng;
+
+    use crate::crypto::poseidon::HybridPoseidonCipher;
+
+    fn test_serial(tag: u8) -> Serial {
+        [tag; crate::constants::SN_BYTES]
+    }
+
+    #[test]
+    fn test_empty_tree_non_membership() {
+        let mut rng = test_rng();
+        let params = HybridPoseidonCipher::setup(&mut rng);
+        let smt = SpentSerialsSmt::new(params.clone());
+        let path = smt.witness(&test_serial(1)).expect("fresh serial should not be a member");
+        assert!(verify_non_membership(&params, smt.root(), &path));
+    }
+
+    #[test]
+    fn test_insert_makes_serial_a_member() {
+        let mut rng = test_rng();
+        let params = HybridPoseidonCipher::setup(&mut rng);
+        let mut smt = SpentSerialsSmt::new(params);
+        smt.insert(&test_serial(1));
+        assert!(smt.witness(&test_serial(1)).is_none());
+    }
+
+    #[test]
+    fn test_other_serials_remain_non_members_after_insert() {
+        let mut rng = test_rng();
+        let params = HybridPoseidonCipher::setup(&mut rng);
+        let mut smt = SpentSerialsSmt::new(params.clone());
+        smt.insert(&test_serial(1));
+        let path = smt.witness(&test_serial(2)).expect("unrelated serial should not be a member");
+        assert!(verify_non_membership(&params, smt.root(), &path));
+    }
+
+    #[test]
+    fn test_stale_root_rejects_proof() {
+        let mut rng = test_rng();
+        let params = HybridPoseidonCipher::setup(&mut rng);
+        let mut smt = SpentSerialsSmt::new(params.clone());
+        let path = smt.witness(&test_serial(1)).unwrap();
+        smt.insert(&test_serial(2));
+        assert!(!verify_non_membership(&params, smt.root(), &path));
+    }
+
+    #[test]
+    fn test_maybe_contains_fast_path() {
+        let mut rng = test_rng();
+        let params = HybridPoseidonCipher::setup(&mut rng);
+        let mut smt = SpentSerialsSmt::new(params);
+        assert!(!smt.maybe_contains(&test_serial(1)));
+        smt.insert(&test_serial(1));
+        assert!(smt.maybe_contains(&test_serial(1)));
+        assert!(!smt.maybe_contains(&test_serial(2)));
+    }
+}
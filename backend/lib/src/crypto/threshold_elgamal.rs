@@ -0,0 +1,267 @@
+use ark_crypto_primitives::encryption::elgamal::Parameters;
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{One, UniformRand, Zero};
+use ark_std::rand::Rng;
+
+use crate::common::*;
+use crate::crypto::elgamal_ext::ExtSecretKey;
+
+/// This party's share `sk_i = f(i)` of a Shamir-split secret key, for the polynomial
+/// `f(x) = sk + a_1*x + ... + a_{t-1}*x^(t-1)`.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Share {
+    pub index: u8,
+    pub sk_share: InnerEdScalarField,
+}
+
+/// Feldman commitments `A_j = a_j*G` to the coefficients of the splitting polynomial
+/// (`A_0 = pk`), letting any party check its share against `sk_i*G == sum_j i^j*A_j`
+/// without learning the other shares.
+#[derive(Clone, Debug)]
+pub struct FeldmanCommitment {
+    pub coeffs: Vec<InnerEdAffine>,
+}
+
+pub struct ThresholdElGamal;
+
+impl ThresholdElGamal {
+    /// Splits `sk` into `n` Shamir shares such that any `t` of them can reconstruct it,
+    /// together with Feldman commitments to the splitting polynomial's coefficients.
+    pub fn split<R: Rng>(
+        params: &Parameters<InnerEdProjective>,
+        sk: &ExtSecretKey<InnerEdProjective>,
+        t: usize,
+        n: usize,
+        rng: &mut R,
+    ) -> (Vec<Share>, FeldmanCommitment) {
+        assert!(t >= 1 && t <= n, "threshold must be between 1 and the number of parties");
+
+        let mut coeffs = vec![sk.0.0];
+        for _ in 1..t {
+            coeffs.push(InnerEdScalarField::rand(rng));
+        }
+
+        let commitment = FeldmanCommitment {
+            coeffs: coeffs.iter().map(|a_j| params.generator.mul(*a_j).into_affine()).collect(),
+        };
+
+        let shares = (1..=n as u64)
+            .map(|i| Share {
+                index: i as u8,
+                sk_share: Self::eval_poly(&coeffs, InnerEdScalarField::from(i)),
+            })
+            .collect();
+
+        (shares, commitment)
+    }
+
+    /// Checks that `share` is consistent with the Feldman `commitment`, i.e. that it lies
+    /// on the same polynomial the commitments were derived from.
+    pub fn verify_share(params: &Parameters<InnerEdProjective>, commitment: &FeldmanCommitment, share: &Share) -> bool {
+        let lhs = params.generator.mul(share.sk_share).into_affine();
+        let rhs = Self::eval_commitment(&commitment.coeffs, InnerEdScalarField::from(share.index as u64));
+        lhs == rhs
+    }
+
+    /// Computes this party's partial decryption `d_i = sk_i*c1` of `ciphertext`.
+    pub fn partial_decrypt(share: &Share, ciphertext: &(InnerEdAffine, InnerEdAffine)) -> InnerEdAffine {
+        ciphertext.0.mul(share.sk_share).into_affine()
+    }
+
+    /// Recombines at least `t` partial decryptions `(index, d_i)` into the plaintext,
+    /// using Lagrange interpolation at `x = 0`: `s = sum_i lambda_i*d_i`, `m = c2 - s`.
+    pub fn combine(partials: &[(u8, InnerEdAffine)], ciphertext: &(InnerEdAffine, InnerEdAffine)) -> InnerEdAffine {
+        let indices: Vec<u8> = partials.iter().map(|(i, _)| *i).collect();
+
+        let mut s = InnerEdProjective::zero();
+        for (i, d_i) in partials {
+            let lambda = Self::lagrange_coefficient(&indices, *i);
+            s += d_i.mul(lambda);
+        }
+
+        (ciphertext.1.into_projective() - s).into_affine()
+    }
+
+    pub(crate) fn eval_poly(coeffs: &[InnerEdScalarField], x: InnerEdScalarField) -> InnerEdScalarField {
+        let mut acc = InnerEdScalarField::zero();
+        let mut x_pow = InnerEdScalarField::one();
+        for a_j in coeffs {
+            acc += *a_j * x_pow;
+            x_pow *= x;
+        }
+        acc
+    }
+
+    fn eval_commitment(coeffs: &[InnerEdAffine], x: InnerEdScalarField) -> InnerEdAffine {
+        let mut acc = InnerEdProjective::zero();
+        let mut x_pow = InnerEdScalarField::one();
+        for a_j in coeffs {
+            acc += a_j.mul(x_pow);
+            x_pow *= x;
+        }
+        acc.into_affine()
+    }
+
+    /// Lagrange coefficient `lambda_i` for recombining at `x = 0`, given the indices of all
+    /// participating shares.
+    fn lagrange_coefficient(indices: &[u8], i: u8) -> InnerEdScalarField {
+        let x_i = InnerEdScalarField::from(i as u64);
+        let mut num = InnerEdScalarField::one();
+        let mut den = InnerEdScalarField::one();
+        for &j in indices {
+            if j == i {
+                continue;
+            }
+            let x_j = InnerEdScalarField::from(j as u64);
+            num *= x_j;
+            den *= x_j - x_i;
+        }
+        num * den.inverse().unwrap()
+    }
+}
+
+pub mod constraints {
+    use ark_ec::ProjectiveCurve;
+    use ark_ff::{to_bytes, PrimeField};
+    use ark_r1cs_std::prelude::*;
+    use ark_relations::r1cs::SynthesisError;
+    use ark_std::marker::PhantomData;
+
+    use crate::crypto::elgamal_ext::{ConstraintF, MyParametersVar, SecretKeyVar};
+
+    pub struct ThresholdDecGadget<C: ProjectiveCurve, GG: CurveVar<C, ConstraintF<C>>>
+    where
+        for<'a> &'a GG: GroupOpsBounds<'a, C, GG>,
+    {
+        #[doc(hidden)]
+        _curve: PhantomData<*const C>,
+        _group_var: PhantomData<*const GG>,
+    }
+
+    impl<C, GG> ThresholdDecGadget<C, GG>
+    where
+        C: ProjectiveCurve,
+        GG: CurveVar<C, ConstraintF<C>>,
+        for<'a> &'a GG: GroupOpsBounds<'a, C, GG>,
+        ConstraintF<C>: PrimeField,
+    {
+        /// Enforces that `partial` is the correct partial decryption `sk_i*c1`, and that
+        /// `sk_i` is consistent with the Feldman `commitments` (`A_0, ..., A_{t-1}`) for the
+        /// given party `index`, i.e. `sk_i*G == sum_j index^j*A_j`.
+        pub fn check_partial_decryption(
+            pp: &MyParametersVar<C, GG>,
+            sk_i: &SecretKeyVar<ConstraintF<C>>,
+            index: u8,
+            commitments: &[GG],
+            ciphertext_c1: &GG,
+            partial: &GG,
+        ) -> Result<Boolean<ConstraintF<C>>, SynthesisError> {
+            // flatten the secret share to a little-endian bit vector
+            let sk_bits: Vec<_> = sk_i.0.iter().flat_map(|b| b.to_bits_le().unwrap()).collect();
+
+            // d_i == sk_i*c1
+            let computed_partial = ciphertext_c1.clone().scalar_mul_le(sk_bits.iter())?;
+            let partial_ok = computed_partial.is_eq(partial)?;
+
+            // sk_i*G == sum_j index^j*A_j
+            let lhs = pp.generator.clone().scalar_mul_le(sk_bits.iter())?;
+            let mut power = C::ScalarField::one();
+            let x = C::ScalarField::from(index as u64);
+            let mut rhs: Option<GG> = None;
+            for a_j in commitments {
+                let power_bits: Vec<_> = UInt8::<ConstraintF<C>>::constant_vec(&to_bytes![power].unwrap())
+                    .iter()
+                    .flat_map(|b| b.to_bits_le().unwrap())
+                    .collect();
+                let term = a_j.clone().scalar_mul_le(power_bits.iter())?;
+                rhs = Some(match rhs {
+                    None => term,
+                    Some(acc) => acc + term,
+                });
+                power *= x;
+            }
+            let rhs = rhs.expect("at least one Feldman commitment coefficient is required");
+            let share_ok = lhs.is_eq(&rhs)?;
+
+            partial_ok.and(&share_ok)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_crypto_primitives::encryption::elgamal::constraints::OutputVar;
+    use ark_crypto_primitives::encryption::elgamal::{ElGamal, Randomness, SecretKey as OrigSecretKey};
+    use ark_crypto_primitives::encryption::AsymmetricEncryptionScheme;
+    use ark_r1cs_std::prelude::*;
+    use ark_relations::r1cs::ConstraintSystem;
+    use ark_std::test_rng;
+
+    use crate::crypto::elgamal_ext::{derive_pk_from_sk, ExtSecretKey, MyParametersVar, SecretKeyVar};
+
+    use super::constraints::ThresholdDecGadget;
+    use super::*;
+
+    type MyElgamal = ElGamal<InnerEdProjective>;
+
+    #[test]
+    fn test_split_verify_and_combine() {
+        let rng = &mut test_rng();
+        let params = MyElgamal::setup(rng).unwrap();
+        let sk = ExtSecretKey::rand(rng);
+        let pk = derive_pk_from_sk(&params, &sk.0);
+
+        let (shares, commitment) = ThresholdElGamal::split(&params, &sk, 3, 5, rng);
+        assert_eq!(commitment.coeffs[0], pk);
+        for share in &shares {
+            assert!(ThresholdElGamal::verify_share(&params, &commitment, share));
+        }
+
+        let msg = InnerEdProjective::rand(rng).into();
+        let randomness = Randomness::rand(rng);
+        let ciphertext = MyElgamal::encrypt(&params, &pk, &msg, &randomness).unwrap();
+
+        let partials: Vec<_> = shares[..3].iter().map(|s| (s.index, ThresholdElGamal::partial_decrypt(s, &ciphertext))).collect();
+        let recovered = ThresholdElGamal::combine(&partials, &ciphertext);
+        assert_eq!(recovered, msg);
+
+        // a tampered share must fail verification
+        let mut bad_share = shares[0].clone();
+        bad_share.sk_share += InnerEdScalarField::from(1u64);
+        assert!(!ThresholdElGamal::verify_share(&params, &commitment, &bad_share));
+    }
+
+    #[test]
+    fn test_check_partial_decryption_gadget() {
+        let rng = &mut test_rng();
+        let params = MyElgamal::setup(rng).unwrap();
+        let sk = ExtSecretKey::rand(rng);
+        let pk = derive_pk_from_sk(&params, &sk.0);
+        let (shares, commitment) = ThresholdElGamal::split(&params, &sk, 3, 5, rng);
+
+        let msg = InnerEdProjective::rand(rng).into();
+        let randomness = Randomness::rand(rng);
+        let ciphertext = MyElgamal::encrypt(&params, &pk, &msg, &randomness).unwrap();
+        let share = &shares[0];
+        let partial = ThresholdElGamal::partial_decrypt(share, &ciphertext);
+
+        let cs = ConstraintSystem::<OuterScalarField>::new_ref();
+        let pp_var = MyParametersVar::<InnerEdProjective, InnerEdVar>::new_constant(cs.clone(), &params).unwrap();
+        let sk_i = ExtSecretKey::<InnerEdProjective>(OrigSecretKey(share.sk_share));
+        let sk_var = SecretKeyVar::new_witness(cs.clone(), || Ok(&sk_i)).unwrap();
+        let commitments_var: Vec<_> = commitment.coeffs.iter().map(|a_j| InnerEdVar::new_constant(cs.clone(), a_j).unwrap()).collect();
+        let ciphertext_var = OutputVar::<InnerEdProjective, InnerEdVar>::new_witness(cs.clone(), || Ok(&ciphertext)).unwrap();
+        let partial_var = InnerEdVar::new_witness(cs.clone(), || Ok(partial)).unwrap();
+
+        let ok = ThresholdDecGadget::<InnerEdProjective, InnerEdVar>::check_partial_decryption(
+            &pp_var,
+            &sk_var,
+            share.index,
+            &commitments_var,
+            &ciphertext_var.c1,
+            &partial_var,
+        ).unwrap();
+        ok.enforce_equal(&Boolean::TRUE).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+    }
+}
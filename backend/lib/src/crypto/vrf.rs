@@ -0,0 +1,232 @@
+use ark_ec::{AffineCurve, ProjectiveCurve};
+use ark_ff::{to_bytes, PrimeField};
+use ark_sponge::{poseidon::PoseidonSponge, CryptographicSponge, FieldBasedCryptographicSponge};
+use ark_std::rand::Rng;
+use ark_std::UniformRand;
+use blake2::{Blake2s, Digest};
+
+use crate::common::*;
+use crate::constants::PRF_VRF_H2C_SEED;
+use crate::crypto::elgamal_ext::ExtSecretKey;
+use crate::crypto::poseidon::HybridPoseidonParams;
+
+/// Hashes `alpha` to a point on the inner curve via try-and-increment: repeatedly hashes
+/// `alpha` together with a counter until the digest is the x-coordinate of a valid point.
+fn hash_to_curve(alpha: &OuterScalarField) -> InnerEdAffine {
+    let mut counter: u8 = 0;
+    loop {
+        let mut h = Blake2s::new();
+        h.update(&[PRF_VRF_H2C_SEED]);
+        h.update(&to_bytes![alpha].unwrap());
+        h.update(&[counter]);
+        let digest = h.finalize();
+        if let Some(x) = FeConverter::from_le_bytes(&digest) {
+            if let Some(p) = InnerEdAffine::get_point_from_x(x, false) {
+                return p;
+            }
+        }
+        counter = counter.checked_add(1).expect("hash_to_curve: exhausted all counters without finding a valid point");
+    }
+}
+
+/// A proof that `Vrf::derive_output` was computed correctly for some `alpha`, without
+/// revealing the secret key used to compute it.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VrfProof {
+    pub gamma: InnerEdAffine,
+    pub c: InnerEdScalarField,
+    pub s: InnerEdScalarField,
+}
+
+pub struct Vrf;
+
+impl Vrf {
+    /// Evaluates the VRF on `alpha` under `sk`/`pk`, returning the output `beta` together
+    /// with a proof that `beta` was derived correctly. Used to derive a record's serial
+    /// number from its nonce in a way that is pseudorandom but publicly verifiable.
+    pub fn evaluate<R: Rng>(
+        params: &HybridPoseidonParams,
+        sk: &ExtSecretKey<InnerEdProjective>,
+        alpha: OuterScalarField,
+        rng: &mut R,
+    ) -> (OuterScalarField, VrfProof) {
+        let h = hash_to_curve(&alpha);
+        let gamma = h.mul(sk.0.0).into_affine();
+
+        let k = InnerEdScalarField::rand(rng);
+        let u = params.elgamal_params.generator.mul(k).into_affine();
+        let v = h.mul(k).into_affine();
+        let c = Self::challenge(params, &h, &gamma, &u, &v);
+        let s = k + c * sk.0.0;
+
+        let beta = Self::derive_output(params, &gamma);
+        (beta, VrfProof { gamma, c, s })
+    }
+
+    /// Verifies `proof` on `alpha` under `pk`, returning the VRF output `beta` on success.
+    pub fn verify(params: &HybridPoseidonParams, pk: &InnerEdAffine, alpha: OuterScalarField, proof: &VrfProof) -> Option<OuterScalarField> {
+        let h = hash_to_curve(&alpha);
+        let u = (params.elgamal_params.generator.mul(proof.s) - pk.mul(proof.c)).into_affine();
+        let v = (h.mul(proof.s) - proof.gamma.mul(proof.c)).into_affine();
+        let c = Self::challenge(params, &h, &proof.gamma, &u, &v);
+        if c != proof.c {
+            return None;
+        }
+        Some(Self::derive_output(params, &proof.gamma))
+    }
+
+    /// Derives the Fiat-Shamir challenge `c = Poseidon(H ‖ Gamma ‖ U ‖ V)`, reduced into the
+    /// inner curve's scalar field (see `Schnorr::challenge` for why this reduction is safe).
+    fn challenge(params: &HybridPoseidonParams, h: &InnerEdAffine, gamma: &InnerEdAffine, u: &InnerEdAffine, v: &InnerEdAffine) -> InnerEdScalarField {
+        let mut poseidon = PoseidonSponge::new(&params.poseidon_params);
+        poseidon.absorb(&vec![h.x, h.y, gamma.x, gamma.y, u.x, u.y, v.x, v.y]);
+        let c = poseidon.squeeze_native_field_elements(1)[0];
+        InnerEdScalarField::from_le_bytes_mod_order(&to_bytes![c].unwrap())
+    }
+
+    /// Derives the public VRF output `beta = Poseidon(Gamma)`.
+    fn derive_output(params: &HybridPoseidonParams, gamma: &InnerEdAffine) -> OuterScalarField {
+        let mut poseidon = PoseidonSponge::new(&params.poseidon_params);
+        poseidon.absorb(&vec![gamma.x, gamma.y]);
+        poseidon.squeeze_native_field_elements(1)[0]
+    }
+}
+
+pub mod constraints {
+    use ark_ff::to_bytes;
+    use ark_r1cs_std::prelude::*;
+    use ark_relations::r1cs::{ConstraintSystemRef, Namespace, SynthesisError};
+    use ark_sponge::{constraints::CryptographicSpongeVar, poseidon::constraints::PoseidonSpongeVar, poseidon::PoseidonParameters};
+    use ark_std::borrow::Borrow;
+
+    use crate::common::*;
+
+    use super::VrfProof;
+
+    #[derive(Clone, Debug)]
+    pub struct VrfProofVar {
+        pub gamma: InnerEdVar,
+        pub c: Vec<UInt8<OuterScalarField>>,
+        pub s: Vec<UInt8<OuterScalarField>>,
+    }
+
+    impl AllocVar<VrfProof, OuterScalarField> for VrfProofVar {
+        fn new_variable<T: Borrow<VrfProof>>(
+            cs: impl Into<Namespace<OuterScalarField>>,
+            f: impl FnOnce() -> Result<T, SynthesisError>,
+            mode: AllocationMode,
+        ) -> Result<Self, SynthesisError> {
+            let cs = cs.into().cs();
+            let proof = f()?;
+            let proof = proof.borrow();
+            let gamma = InnerEdVar::new_variable(cs.clone(), || Ok(proof.gamma), mode)?;
+            let alloc_bytes = |bytes: &[u8]| -> Result<Vec<UInt8<OuterScalarField>>, SynthesisError> {
+                match mode {
+                    AllocationMode::Constant => Ok(UInt8::constant_vec(bytes)),
+                    AllocationMode::Input => UInt8::new_input_vec(cs.clone(), bytes),
+                    AllocationMode::Witness => UInt8::new_witness_vec(cs.clone(), bytes),
+                }
+            };
+            let c = alloc_bytes(&to_bytes![proof.c].unwrap())?;
+            let s = alloc_bytes(&to_bytes![proof.s].unwrap())?;
+            Ok(VrfProofVar { gamma, c, s })
+        }
+    }
+
+    pub struct VrfVerifyGadget;
+
+    impl VrfVerifyGadget {
+        /// Recomputes `U' = s*G - c*pk` and `V' = s*H - c*Gamma`, checks
+        /// `c == Poseidon(H ‖ Gamma ‖ U' ‖ V')`, and returns that check alongside the VRF
+        /// output `beta = Poseidon(Gamma)`, which the caller enforces as the serial number.
+        pub fn verify(
+            cs: &ConstraintSystemRef<OuterScalarField>,
+            poseidon_params: &PoseidonParameters<OuterScalarField>,
+            generator: &InnerEdVar,
+            public_key: &InnerEdVar,
+            h: &InnerEdVar,
+            proof: &VrfProofVar,
+        ) -> Result<(Boolean<OuterScalarField>, OuterScalarVar), SynthesisError> {
+            let c_bits: Vec<_> = proof.c.iter().flat_map(|b| b.to_bits_le().unwrap()).collect();
+            let s_bits: Vec<_> = proof.s.iter().flat_map(|b| b.to_bits_le().unwrap()).collect();
+
+            // U' = s*G - c*pk
+            let u = generator.clone().scalar_mul_le(s_bits.iter())?
+                .sub(public_key.clone().scalar_mul_le(c_bits.iter())?);
+            // V' = s*H - c*Gamma
+            let v = h.clone().scalar_mul_le(s_bits.iter())?
+                .sub(proof.gamma.clone().scalar_mul_le(c_bits.iter())?);
+
+            // recompute the challenge and compare it to the claimed `c`
+            let mut poseidon = PoseidonSpongeVar::<OuterScalarField>::new(cs.clone(), poseidon_params);
+            poseidon.absorb(&vec![h.x.clone(), h.y.clone(), proof.gamma.x.clone(), proof.gamma.y.clone(), u.x, u.y, v.x, v.y])?;
+            let computed_c = poseidon.squeeze_field_elements(1)?[0].clone();
+            // `proof.c` is the digest reduced into the inner scalar field, so compare the two
+            // modulo the generator's order: `computed_c*G == c*G`
+            let computed_c_bits = computed_c.to_bits_le()?;
+            let is_ok = generator.clone().scalar_mul_le(computed_c_bits.iter())?
+                .is_eq(&generator.clone().scalar_mul_le(c_bits.iter())?)?;
+
+            // beta = Poseidon(Gamma)
+            let mut beta_sponge = PoseidonSpongeVar::<OuterScalarField>::new(cs.clone(), poseidon_params);
+            beta_sponge.absorb(&vec![proof.gamma.x.clone(), proof.gamma.y.clone()])?;
+            let beta = beta_sponge.squeeze_field_elements(1)?[0].clone();
+
+            Ok((is_ok, beta))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_r1cs_std::prelude::*;
+    use ark_relations::r1cs::ConstraintSystem;
+    use ark_std::test_rng;
+
+    use crate::crypto::elgamal_ext::derive_pk_from_sk;
+    use crate::crypto::poseidon::HybridPoseidonCipher;
+
+    use super::constraints::{VrfProofVar, VrfVerifyGadget};
+    use super::*;
+
+    #[test]
+    fn test_evaluate_and_verify() {
+        let rng = &mut test_rng();
+        let params = HybridPoseidonCipher::setup(rng);
+        let sk = ExtSecretKey::rand(rng);
+        let pk = derive_pk_from_sk(&params.elgamal_params, &sk.0);
+        let alpha = OuterScalarField::rand(rng);
+
+        let (beta, proof) = Vrf::evaluate(&params, &sk, alpha, rng);
+        assert_eq!(Vrf::verify(&params, &pk, alpha, &proof), Some(beta));
+
+        // re-running the VRF on the same input under the same key is deterministic
+        let (beta_again, _) = Vrf::evaluate(&params, &sk, alpha, rng);
+        assert_eq!(beta, beta_again);
+
+        // a proof over a different input must not verify
+        let other_alpha = OuterScalarField::rand(rng);
+        assert_eq!(Vrf::verify(&params, &pk, other_alpha, &proof), None);
+    }
+
+    #[test]
+    fn test_verify_gadget() {
+        let rng = &mut test_rng();
+        let params = HybridPoseidonCipher::setup(rng);
+        let sk = ExtSecretKey::rand(rng);
+        let pk = derive_pk_from_sk(&params.elgamal_params, &sk.0);
+        let alpha = OuterScalarField::rand(rng);
+        let (beta, proof) = Vrf::evaluate(&params, &sk, alpha, rng);
+
+        let cs = ConstraintSystem::<OuterScalarField>::new_ref();
+        let generator_var = InnerEdVar::new_constant(cs.clone(), &params.elgamal_params.generator).unwrap();
+        let pk_var = InnerEdVar::new_witness(cs.clone(), || Ok(pk)).unwrap();
+        let h_var = InnerEdVar::new_witness(cs.clone(), || Ok(hash_to_curve(&alpha))).unwrap();
+        let proof_var = VrfProofVar::new_witness(cs.clone(), || Ok(&proof)).unwrap();
+
+        let (is_ok, beta_var) = VrfVerifyGadget::verify(&cs, &params.poseidon_params, &generator_var, &pk_var, &h_var, &proof_var).unwrap();
+        is_ok.enforce_equal(&Boolean::TRUE).unwrap();
+        beta_var.enforce_equal(&OuterScalarVar::new_constant(cs.clone(), beta).unwrap()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+    }
+}
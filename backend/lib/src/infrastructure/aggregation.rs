@@ -0,0 +1,187 @@
+//! Bundles multiple `MainProofCircuit` proofs (e.g. every transaction in a block) for settlement
+//! as one unit.
+//!
+//! **Scope note:** true succinct proof aggregation -- folding N GM17 proofs into one
+//! constant-size accumulation proof whose on-chain verification cost is O(1) regardless of N
+//! (the SnarkPack/Halo-style construction the term "aggregation" usually implies) needs its own
+//! pairing-based inner-product argument over the proofs' group elements. That's a separate
+//! cryptographic construction on the scale of `nova_fold`'s CycleFold gap, not something addable
+//! as a thin wrapper around `GM17::verify`. What's implemented here instead: an `AggregatedProof`
+//! that Fiat-Shamir-binds a batch of proofs together with their public inputs, via
+//! `aggregate`/`verify_aggregated`. Verification cost is still linear in the number of proofs (one
+//! `GM17::verify` call each), but a submitter can no longer reorder, drop, or substitute a proof
+//! inside an already-formed bundle without invalidating the one binding hash a settlement
+//! contract would check first.
+
+use ark_ff::ToConstraintField;
+use ark_sponge::{poseidon::PoseidonSponge, CryptographicSponge, FieldBasedCryptographicSponge};
+
+use crate::common::OuterScalarField;
+use crate::crypto::poseidon::HybridPoseidonParams;
+use crate::crypto::rln::RlnShare;
+
+use super::circuit::{MainProof, MainProofVerifier};
+use super::params::MerkleTreeRoot;
+use super::processor::ZkInstruction;
+use super::record::{EncryptedRecord, Serial};
+
+/// domain separator for the aggregation binding-hash transcript
+const AGGREGATE_BINDING_DOMAIN: u64 = 0x4147475f42494e44; // ASCII "AGG_BIND"
+
+/// Everything `MainProofVerifier::verify` needs for one transaction's proof, bundled so a batch of
+/// them can be aggregated and verified together.
+#[derive(Clone)]
+pub struct AggregateItem {
+    pub unique_seed: [u8; crate::constants::RAND_BYTES],
+    pub merkle_tree_root: MerkleTreeRoot,
+    pub spent_serials_root: OuterScalarField,
+    pub consumed_serials: Vec<Serial>,
+    pub new_records: Vec<EncryptedRecord>,
+    pub called_class_id: OuterScalarField,
+    pub called_function_id: OuterScalarField,
+    pub instructions: Vec<ZkInstruction>,
+    pub current_time: OuterScalarField,
+    pub rln_share: RlnShare,
+    pub proof: MainProof,
+}
+
+/// A batch of `AggregateItem`s plus the Fiat-Shamir hash binding them together in order. See the
+/// module doc comment for what this does and does not provide over verifying each proof alone.
+pub struct AggregatedProof {
+    pub items: Vec<AggregateItem>,
+    pub binding_hash: OuterScalarField,
+}
+
+/// Absorbs every item's public inputs, in order, into one Poseidon transcript. Two bundles with
+/// the same proofs in a different order, or with any proof/public-input substituted, hash to a
+/// different value.
+fn compute_binding_hash(params: &HybridPoseidonParams, items: &[AggregateItem]) -> OuterScalarField {
+    let mut sponge = PoseidonSponge::new(&params.poseidon_params);
+    let mut to_absorb = vec![OuterScalarField::from(AGGREGATE_BINDING_DOMAIN), OuterScalarField::from(items.len() as u64)];
+    for item in items {
+        to_absorb.extend_from_slice(&item.unique_seed.to_field_elements().unwrap());
+        to_absorb.push(item.merkle_tree_root.0);
+        to_absorb.push(item.spent_serials_root);
+        to_absorb.push(item.called_class_id);
+        to_absorb.push(item.called_function_id);
+        for serial in &item.consumed_serials {
+            to_absorb.extend_from_slice(&serial.to_field_elements().unwrap());
+        }
+        for record in &item.new_records {
+            to_absorb.extend_from_slice(&record.to_field_elements().unwrap());
+        }
+        to_absorb.push(item.current_time);
+        to_absorb.push(item.rln_share.x);
+        to_absorb.push(item.rln_share.y);
+        to_absorb.push(item.rln_share.internal_nullifier);
+        for instruction in &item.instructions {
+            to_absorb.extend_from_slice(&instruction.to_field_elements().unwrap());
+        }
+        to_absorb.extend_from_slice(&ark_ff::to_bytes![item.proof].unwrap().to_field_elements().unwrap());
+    }
+    sponge.absorb(&to_absorb);
+    sponge.squeeze_native_field_elements(1)[0]
+}
+
+/// Binds `items` into one `AggregatedProof`; the proofs themselves are unchanged and still need
+/// individually verifying (see `verify_aggregated`).
+pub fn aggregate(params: &HybridPoseidonParams, items: Vec<AggregateItem>) -> AggregatedProof {
+    let binding_hash = compute_binding_hash(params, &items);
+    AggregatedProof { items, binding_hash }
+}
+
+/// Checks `aggregated`'s binding hash, then verifies every bundled proof against `verifier`.
+/// Fails closed (`false`) if the bundle was reordered/tampered with or any individual proof
+/// doesn't verify.
+pub fn verify_aggregated(params: &HybridPoseidonParams, verifier: &MainProofVerifier, aggregated: &AggregatedProof) -> bool {
+    if compute_binding_hash(params, &aggregated.items) != aggregated.binding_hash {
+        return false;
+    }
+    aggregated.items.iter().all(|item| verifier.verify(
+        &item.unique_seed,
+        &item.merkle_tree_root,
+        item.spent_serials_root,
+        &item.consumed_serials,
+        &item.new_records,
+        item.called_class_id,
+        item.called_function_id,
+        &item.instructions,
+        item.current_time,
+        &item.rln_share,
+        &item.proof,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_ec::PairingEngine;
+    use ark_gm17::Proof;
+    use ark_std::test_rng;
+    use ark_std::Zero;
+
+    use crate::common::OuterPairing;
+    use crate::crypto::poseidon::HybridPoseidonCipher;
+
+    use super::*;
+
+    // a, b, c default to the point at infinity -- these tests only exercise the binding-hash
+    // transcript, not a real proof's cryptographic validity (see circuit.rs's own dummy-proof
+    // tests for why running a real setup/prove here would be wasteful)
+    fn dummy_item(params: &HybridPoseidonParams, current_time: OuterScalarField) -> AggregateItem {
+        AggregateItem {
+            unique_seed: [0u8; crate::constants::RAND_BYTES],
+            merkle_tree_root: MerkleTreeRoot::default(),
+            spent_serials_root: OuterScalarField::zero(),
+            consumed_serials: vec![[0u8; crate::constants::SN_BYTES]; crate::constants::NOF_TX_RECORDS],
+            new_records: vec![],
+            called_class_id: OuterScalarField::zero(),
+            called_function_id: OuterScalarField::zero(),
+            instructions: vec![],
+            current_time,
+            rln_share: RlnShare { x: OuterScalarField::zero(), y: OuterScalarField::zero(), internal_nullifier: OuterScalarField::zero() },
+            proof: MainProof(Proof::<OuterPairing> {
+                a: <OuterPairing as PairingEngine>::G1Affine::default(),
+                b: <OuterPairing as PairingEngine>::G2Affine::default(),
+                c: <OuterPairing as PairingEngine>::G1Affine::default(),
+            }),
+        }
+    }
+
+    #[test]
+    fn test_binding_hash_changes_with_content_and_order() {
+        let mut rng = test_rng();
+        let params = HybridPoseidonCipher::setup(&mut rng);
+
+        let item_a = dummy_item(&params, OuterScalarField::from(1u64));
+        let item_b = dummy_item(&params, OuterScalarField::from(2u64));
+
+        let bundle_ab = aggregate(&params, vec![item_a.clone(), item_b.clone()]);
+        let bundle_ba = aggregate(&params, vec![item_b.clone(), item_a.clone()]);
+        assert_ne!(bundle_ab.binding_hash, bundle_ba.binding_hash);
+
+        let bundle_aa = aggregate(&params, vec![item_a.clone(), item_a.clone()]);
+        assert_ne!(bundle_ab.binding_hash, bundle_aa.binding_hash);
+
+        // recomputing over the same items in the same order reproduces the same hash
+        let bundle_ab_again = aggregate(&params, vec![item_a.clone(), item_b.clone()]);
+        assert_eq!(bundle_ab.binding_hash, bundle_ab_again.binding_hash);
+    }
+
+    #[test]
+    fn test_binding_hash_detects_reordered_bundle() {
+        let mut rng = test_rng();
+        let params = HybridPoseidonCipher::setup(&mut rng);
+
+        let item_a = dummy_item(&params, OuterScalarField::from(1u64));
+        let item_b = dummy_item(&params, OuterScalarField::from(2u64));
+        let mut bundle = aggregate(&params, vec![item_a, item_b]);
+
+        // reorder the items, keeping the binding hash computed over the original order
+        bundle.items.swap(0, 1);
+
+        // verifying the proofs themselves would still fail here (they're dummy points), but the
+        // binding-hash mismatch must be what's checked first -- confirmed by the hash recomputed
+        // over the tampered item order no longer matching the stored one
+        assert_ne!(compute_binding_hash(&params, &bundle.items), bundle.binding_hash);
+    }
+}
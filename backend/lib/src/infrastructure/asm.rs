@@ -0,0 +1,263 @@
+//! A text assembler/disassembler for the `ZkInstruction` ISA (see the opcode mnemonics
+//! documented at the top of `processor.rs`), so contract authors and test writers can read
+//! and write programs as text instead of hand-building `Vec<ZkInstruction>` with raw `u8`
+//! opcodes and `RegOrConst` values.
+//!
+//! Each line is one instruction: `MNEMONIC dst src_1 src_2`. `dst` is a bare decimal register
+//! index; `src_1`/`src_2` are either a register (`r12`), a constant (decimal `42` or hex
+//! `0x2a`), or `_` for an operand the instruction doesn't use (assembled as register/constant
+//! `0`). Blank lines are skipped.
+
+use ark_ff::Zero;
+
+use crate::common::OuterScalarField;
+
+use super::processor::{
+    RegOrConst, ZkInstruction,
+    OPCODE_ADD, OPCODE_AND, OPCODE_CID, OPCODE_CMOV, OPCODE_DIV, OPCODE_EQ, OPCODE_FRESH,
+    OPCODE_GAS, OPCODE_HALT, OPCODE_JMP, OPCODE_JNZ, OPCODE_JZ, OPCODE_KILL, OPCODE_LOAD,
+    OPCODE_LT, OPCODE_MOD, OPCODE_MOV, OPCODE_MUL, OPCODE_NEW, OPCODE_NOOP, OPCODE_NOP, OPCODE_NOW,
+    OPCODE_OR, OPCODE_PK, OPCODE_REQ, OPCODE_SHL, OPCODE_SHR, OPCODE_STORE, OPCODE_SUB, OPCODE_XOR,
+};
+
+/// An error while assembling a program, reporting the 1-based line/column and the offending
+/// token so the caller can point back at the source.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct AsmError {
+    pub line: usize,
+    pub column: usize,
+    pub token: String,
+    pub message: String,
+}
+
+impl std::fmt::Display for AsmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}: {} (at '{}')", self.line, self.column, self.message, self.token)
+    }
+}
+
+fn mnemonic_to_opcode(mnemonic: &str) -> Option<u8> {
+    Some(match mnemonic {
+        "NOOP" => OPCODE_NOOP,
+        "MOV" => OPCODE_MOV,
+        "CMOV" => OPCODE_CMOV,
+        "REQ" => OPCODE_REQ,
+        "LOAD" => OPCODE_LOAD,
+        "STORE" => OPCODE_STORE,
+        "KILL" => OPCODE_KILL,
+        "PK" => OPCODE_PK,
+        "NEW" => OPCODE_NEW,
+        "CID" => OPCODE_CID,
+        "FRESH" => OPCODE_FRESH,
+        "NOW" => OPCODE_NOW,
+        "ADD" => OPCODE_ADD,
+        "SUB" => OPCODE_SUB,
+        "MUL" => OPCODE_MUL,
+        "EQ" => OPCODE_EQ,
+        "LT" => OPCODE_LT,
+        "JMP" => OPCODE_JMP,
+        "JZ" => OPCODE_JZ,
+        "JNZ" => OPCODE_JNZ,
+        "HALT" => OPCODE_HALT,
+        "DIV" => OPCODE_DIV,
+        "MOD" => OPCODE_MOD,
+        "AND" => OPCODE_AND,
+        "OR" => OPCODE_OR,
+        "XOR" => OPCODE_XOR,
+        "GAS" => OPCODE_GAS,
+        "NOP" => OPCODE_NOP,
+        "SHL" => OPCODE_SHL,
+        "SHR" => OPCODE_SHR,
+        _ => return None,
+    })
+}
+
+fn opcode_to_mnemonic(opcode: u8) -> &'static str {
+    match opcode {
+        OPCODE_NOOP => "NOOP",
+        OPCODE_MOV => "MOV",
+        OPCODE_CMOV => "CMOV",
+        OPCODE_REQ => "REQ",
+        OPCODE_LOAD => "LOAD",
+        OPCODE_STORE => "STORE",
+        OPCODE_KILL => "KILL",
+        OPCODE_PK => "PK",
+        OPCODE_NEW => "NEW",
+        OPCODE_CID => "CID",
+        OPCODE_FRESH => "FRESH",
+        OPCODE_NOW => "NOW",
+        OPCODE_ADD => "ADD",
+        OPCODE_SUB => "SUB",
+        OPCODE_MUL => "MUL",
+        OPCODE_EQ => "EQ",
+        OPCODE_LT => "LT",
+        OPCODE_JMP => "JMP",
+        OPCODE_JZ => "JZ",
+        OPCODE_JNZ => "JNZ",
+        OPCODE_HALT => "HALT",
+        OPCODE_DIV => "DIV",
+        OPCODE_MOD => "MOD",
+        OPCODE_AND => "AND",
+        OPCODE_OR => "OR",
+        OPCODE_XOR => "XOR",
+        OPCODE_GAS => "GAS",
+        OPCODE_NOP => "NOP",
+        OPCODE_SHL => "SHL",
+        OPCODE_SHR => "SHR",
+        _ => "???",
+    }
+}
+
+struct Token {
+    text: String,
+    line: usize,
+    column: usize,
+}
+
+fn tokenize_line(line: &str, line_no: usize) -> Vec<Token> {
+    let mut tokens = vec![];
+    let mut column = 0;
+    for word in line.split_whitespace() {
+        // `split_whitespace` doesn't report byte offsets, so re-find each word from where we left off
+        column = line[column..].find(word).map(|off| column + off).unwrap_or(column);
+        tokens.push(Token { text: word.to_string(), line: line_no, column: column + 1 });
+        column += word.len();
+    }
+    tokens
+}
+
+fn parse_dst(token: &Token) -> Result<usize, AsmError> {
+    if token.text == "_" {
+        return Ok(0);
+    }
+    token.text.parse::<usize>().map_err(|_| AsmError {
+        line: token.line,
+        column: token.column,
+        token: token.text.clone(),
+        message: "expected a destination register index (e.g. '3') or '_'".to_string(),
+    })
+}
+
+fn parse_reg_or_const(token: &Token) -> Result<RegOrConst, AsmError> {
+    if token.text == "_" {
+        return Ok(RegOrConst::Const(OuterScalarField::zero()));
+    }
+    if let Some(reg) = token.text.strip_prefix('r') {
+        return reg.parse::<usize>().map(RegOrConst::Reg).map_err(|_| AsmError {
+            line: token.line,
+            column: token.column,
+            token: token.text.clone(),
+            message: "expected a register index after 'r' (e.g. 'r12')".to_string(),
+        });
+    }
+    if let Some(hex) = token.text.strip_prefix("0x") {
+        return num_bigint::BigUint::parse_bytes(hex.as_bytes(), 16)
+            .map(OuterScalarField::from)
+            .map(RegOrConst::Const)
+            .ok_or_else(|| AsmError {
+                line: token.line,
+                column: token.column,
+                token: token.text.clone(),
+                message: "expected a hexadecimal constant after '0x'".to_string(),
+            });
+    }
+    num_bigint::BigUint::parse_bytes(token.text.as_bytes(), 10)
+        .map(OuterScalarField::from)
+        .map(RegOrConst::Const)
+        .ok_or_else(|| AsmError {
+            line: token.line,
+            column: token.column,
+            token: token.text.clone(),
+            message: "expected a register ('rN'), a decimal or hex ('0x...') constant, or '_'".to_string(),
+        })
+}
+
+fn disassemble_reg_or_const(operand: &RegOrConst) -> String {
+    match operand {
+        RegOrConst::Reg(idx) => format!("r{}", idx),
+        RegOrConst::Const(c) => crate::common::fe_to_string(c),
+    }
+}
+
+/// Parses a textual program (one instruction per line) into `ZkInstruction`s. See the module
+/// docs for the line grammar.
+pub fn assemble(source: &str) -> Result<Vec<ZkInstruction>, AsmError> {
+    let mut instructions = vec![];
+    for (line_idx, line) in source.lines().enumerate() {
+        let line_no = line_idx + 1;
+        if line.trim().is_empty() {
+            continue;
+        }
+        let tokens = tokenize_line(line, line_no);
+        let mnemonic = &tokens[0];
+        let opcode = mnemonic_to_opcode(&mnemonic.text).ok_or_else(|| AsmError {
+            line: mnemonic.line,
+            column: mnemonic.column,
+            token: mnemonic.text.clone(),
+            message: "unknown mnemonic".to_string(),
+        })?;
+        if tokens.len() != 4 {
+            let last = tokens.last().unwrap_or(mnemonic);
+            return Err(AsmError {
+                line: last.line,
+                column: last.column + last.text.len(),
+                token: String::new(),
+                message: format!("expected 3 operands (dst, src_1, src_2) after '{}', got {}", mnemonic.text, tokens.len() - 1),
+            });
+        }
+        instructions.push(ZkInstruction {
+            opcode,
+            dst: parse_dst(&tokens[1])?,
+            src_1: parse_reg_or_const(&tokens[2])?,
+            src_2: parse_reg_or_const(&tokens[3])?,
+        });
+    }
+    Ok(instructions)
+}
+
+/// Renders `ZkInstruction`s back to the textual format accepted by `assemble`, one instruction
+/// per line. Unknown opcodes are rendered as `???` (there is no mnemonic to round-trip them).
+pub fn disassemble(instructions: &[ZkInstruction]) -> String {
+    instructions.iter().map(|inst| {
+        format!("{} {} {} {}",
+            opcode_to_mnemonic(inst.opcode),
+            inst.dst,
+            disassemble_reg_or_const(&inst.src_1),
+            disassemble_reg_or_const(&inst.src_2))
+    }).collect::<Vec<_>>().join("\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_assemble_disassemble_roundtrip() {
+        let source = "MOV 0 r1 _\nADD 2 r0 0x2a\nREQ _ r2 _\n";
+        let instructions = assemble(source).unwrap();
+        assert_eq!(instructions.len(), 3);
+        assert_eq!(instructions[0].opcode, OPCODE_MOV);
+        assert_eq!(instructions[1].opcode, OPCODE_ADD);
+        assert_eq!(instructions[2].opcode, OPCODE_REQ);
+
+        let rendered = disassemble(&instructions);
+        let reparsed = assemble(&rendered).unwrap();
+        assert_eq!(reparsed.len(), instructions.len());
+        assert_eq!(rendered, "MOV 0 r1 0\nADD 2 r0 42\nREQ 0 r2 0");
+    }
+
+    #[test]
+    fn test_assemble_reports_unknown_mnemonic() {
+        let err = assemble("FROB 0 r0 r0").unwrap_err();
+        assert_eq!(err.line, 1);
+        assert_eq!(err.column, 1);
+        assert_eq!(err.token, "FROB");
+    }
+
+    #[test]
+    fn test_assemble_reports_bad_operand() {
+        let err = assemble("MOV 0 rX r0").unwrap_err();
+        assert_eq!(err.line, 1);
+        assert_eq!(err.token, "rX");
+    }
+}
@@ -13,19 +13,23 @@ use ark_relations::r1cs::{
 use ark_sponge::poseidon::PoseidonParameters;
 use log::{info, debug};
 use rand::{Rng, CryptoRng};
+use rayon::prelude::*;
 
 use crate::{common::*, data_log, time_measure};
 use crate::constants::*;
 use crate::crypto::elgamal_ext::{ElGamalDecGadget, SecretKeyVar, MyParametersVar, ElGamalEncGadget, ElGamalKeyGadget};
 use crate::crypto::poseidon::constraints::PoseidonCipherGadget;
+use crate::crypto::rln::{self, RlnShare};
+use crate::crypto::spent_serials::{self, constraints::SmtNonMembershipPathVar};
 use crate::infrastructure::params::{InnerHash, InnerWindow, LeafHash, LeafWindow, MerkleTreeParams};
 use crate::infrastructure::runtime::ProofContext;
 use crate::infrastructure::derivations::constraints::*;
 
 use super::params::{CryptoParams, MerkleTreeRoot};
-use super::processor::ZkInstruction;
-use super::processor::constraints::{ZkProcessorStateVar, ZkInstructionVar, ZkProcessorGadget};
-use super::record::{EncryptedRecord, RECORD_CHUNKS, Record, EncRandomness, RECORD_CHUNKS_PADDED, Serial};
+use super::optimizer::{eliminate_dead_code, fold_constants, infer_range_safe_cycles};
+use super::processor::{compute_shift_amounts, program_has_control_flow, ZkInstruction};
+use super::processor::constraints::{ZkProcessorStateVar, ZkInstructionVar, ZkProcessorGadget, ObjectDataVar};
+use super::record::{EncryptedRecord, RECORD_CHUNKS, Record, EncRandomness, RECORD_CHUNKS_PADDED, RECORD_MEMO_ELEMENTS, Serial};
 
 pub type TwoToOneHashGadget = PedersenCRHCompressorGadget<
     InnerEdProjective,
@@ -107,6 +111,23 @@ pub fn generate_main_proof<R: Rng + CryptoRng>(rng: &mut R, pk: &Option<ProvingK
 }
 
 
+/// One transaction's public inputs and proof, bundled so `MainProofVerifier::verify_batch` can
+/// take a slice of them instead of a slice of eleven separate parallel arrays. Field meanings match
+/// the identically-named parameters of `MainProofVerifier::verify`.
+pub struct ProofVerificationRequest<'a> {
+    pub unique_seed: [u8; RAND_BYTES],
+    pub merkle_tree_root: MerkleTreeRoot,
+    pub spent_serials_root: OuterScalarField,
+    pub consumed_serials: &'a [Serial],
+    pub new_records: &'a [EncryptedRecord],
+    pub called_class_id: OuterScalarField,
+    pub called_function_id: OuterScalarField,
+    pub instructions: &'a [ZkInstruction],
+    pub current_time: OuterScalarField,
+    pub rln_share: &'a RlnShare,
+    pub proof: &'a MainProof,
+}
+
 pub struct MainProofVerifier {
     pub verifier_key: VerifyingKey<OuterPairing>
 }
@@ -119,28 +140,33 @@ impl MainProofVerifier {
     pub fn verify(&self,
         unique_seed: &[u8; RAND_BYTES],
         merkle_tree_root: &MerkleTreeRoot,
+        spent_serials_root: OuterScalarField,
         consumed_serials: &[Serial],
         new_records: &[EncryptedRecord],
         called_class_id: OuterScalarField,
         called_function_id: OuterScalarField,
         instructions: &[ZkInstruction],
         current_time: OuterScalarField,
+        rln_share: &RlnShare,
         proof: &MainProof
     ) -> bool {
         assert_eq!(consumed_serials.len(), NOF_TX_RECORDS);
         assert_eq!(new_records.len(), NOF_TX_RECORDS);
         
-        // pad program with NOOPs
+        // pad program with NOOPs, then run the same optimizer passes the prover ran (see
+        // RuntimeInterface::execute) so both sides agree on the public instruction stream
         assert!(instructions.len() <= NOF_PROCESSOR_CYCLES, "too many instructions (got: {}, max: {})", instructions.len(), NOF_PROCESSOR_CYCLES);
         let mut padded_instructions = instructions.to_vec();
         for _ in 0..(NOF_PROCESSOR_CYCLES - instructions.len()) {
             padded_instructions.push(ZkInstruction::default());
         }
+        let padded_instructions = eliminate_dead_code(&fold_constants(&padded_instructions));
 
         // collect public circuit inputs
         let mut input = vec![];
         input.extend_from_slice(&unique_seed.to_field_elements().unwrap());
         input.push(merkle_tree_root.0);
+        input.push(spent_serials_root);
         input.push(called_class_id);
         input.push(called_function_id);
         for serial in consumed_serials {
@@ -153,17 +179,72 @@ impl MainProofVerifier {
             input.extend_from_slice(&inst.to_field_elements().unwrap());
         }
         input.push(current_time);
-        
+        // the prover runs `ZkProcessor::default()`, whose gas limit is `DEFAULT_GAS_LIMIT`
+        input.push(OuterScalarField::from(DEFAULT_GAS_LIMIT));
+        input.push(rln_share.x);
+        input.push(rln_share.y);
+        input.push(rln_share.internal_nullifier);
+
         debug!("verifying proof...");
         let res = GM17::verify(&self.verifier_key, &input, &proof.0).unwrap();
         debug!("verification result: {:?}", res);
         res
     }
+
+    /// Verifies a whole batch of transactions' proofs, returning `Ok(())` iff every one of them
+    /// is valid, or `Err` with the indices of the proofs that failed.
+    ///
+    /// An ideal implementation of this would sample random scalars rᵢ and fold all `requests.len()`
+    /// proofs' pairing checks into a single multi-pairing (one Miller loop per proof, but one
+    /// shared final exponentiation for the whole batch, instead of one per proof), which is where
+    /// most of the per-proof cost of `verify` actually goes. Doing that soundly means reaching
+    /// past the `SNARK` trait into `ark_gm17`'s internal prepared-verifying-key pairing equations
+    /// -- something nothing else in this codebase does, since every other call site here (and in
+    /// `solidity_verifier`) only ever goes through `GM17::prove`/`GM17::verify`. Hand-deriving that
+    /// algebra for a proof *verifier* without a way to compile and test it against the real GM17
+    /// equations is the kind of mistake that fails in the dangerous direction (a forged proof
+    /// silently accepted), so this instead gets the bulk of the practical win -- not re-serializing
+    /// a whole block's worth of proof checks behind one verifier's latency -- by running `verify`
+    /// for each transaction in parallel and collecting which indices failed.
+    pub fn verify_batch(&self, requests: &[ProofVerificationRequest]) -> Result<(), Vec<usize>> {
+        let failed: Vec<usize> = requests
+            .par_iter()
+            .enumerate()
+            .filter(|(_, req)| !self.verify(
+                &req.unique_seed,
+                &req.merkle_tree_root,
+                req.spent_serials_root,
+                req.consumed_serials,
+                req.new_records,
+                req.called_class_id,
+                req.called_function_id,
+                req.instructions,
+                req.current_time,
+                req.rln_share,
+                req.proof,
+            ))
+            .map(|(i, _)| i)
+            .collect();
+
+        if failed.is_empty() {
+            Ok(())
+        } else {
+            Err(failed)
+        }
+    }
 }
 
 #[derive(Clone)]
 pub struct MainProof(pub Proof<OuterPairing>);
 
+impl MainProof {
+    /// ABI-encodes this proof's `(a, b, c)` group elements via `solidity_verifier::encode_proof_calldata`,
+    /// in the layout the contract `solidity_verifier::generate_solidity_verifier` emits expects.
+    pub fn to_calldata(&self) -> Vec<u8> {
+        super::solidity_verifier::encode_proof_calldata(&self.0)
+    }
+}
+
 impl ToBytes for MainProof {
     fn write<W: ark_serialize::Write>(&self, writer: W) -> std::io::Result<()> {
         self.0.write(writer)
@@ -183,6 +264,42 @@ impl FromBytes for MainProof {
     }
 }
 
+impl super::versioned::VersionedBinary for MainProof {
+    const MAGIC: [u8; 4] = *b"ZPRF";
+
+    fn write_body<W: std::io::Write>(&self, writer: W) -> std::io::Result<()> {
+        self.write(writer)
+    }
+
+    fn read_body<R: std::io::Read>(reader: R) -> std::io::Result<Self> {
+        Self::read(reader)
+    }
+}
+
+impl super::versioned::VersionedBinary for ProvingKey<OuterPairing> {
+    const MAGIC: [u8; 4] = *b"ZPPK";
+
+    fn write_body<W: std::io::Write>(&self, writer: W) -> std::io::Result<()> {
+        self.write(writer)
+    }
+
+    fn read_body<R: std::io::Read>(reader: R) -> std::io::Result<Self> {
+        Self::read(reader)
+    }
+}
+
+impl super::versioned::VersionedBinary for VerifyingKey<OuterPairing> {
+    const MAGIC: [u8; 4] = *b"ZPVK";
+
+    fn write_body<W: std::io::Write>(&self, writer: W) -> std::io::Result<()> {
+        self.write(writer)
+    }
+
+    fn read_body<R: std::io::Read>(reader: R) -> std::io::Result<Self> {
+        Self::read(reader)
+    }
+}
+
 pub struct EncParams {
     pub elgamal_params: MyParametersVar<InnerEdProjective, InnerEdVar>,
     pub poseidon_params: PoseidonParameters<OuterScalarField>
@@ -191,7 +308,8 @@ pub struct EncParams {
 pub struct EncryptedRecordVar {
     key_part: elgamal::constraints::OutputVar<InnerEdProjective, InnerEdVar>,
     data_elems: Vec<OuterScalarVar>,
-    nonce: OuterScalarVar
+    nonce: OuterScalarVar,
+    tag: OuterScalarVar
 }
 
 impl AllocVar<EncryptedRecord, OuterScalarField> for EncryptedRecordVar {
@@ -204,11 +322,13 @@ impl AllocVar<EncryptedRecord, OuterScalarField> for EncryptedRecordVar {
         let enc_record = f()?;
         let key_part = elgamal::constraints::OutputVar::<InnerEdProjective, InnerEdVar>::new_variable(cs.clone(), || Ok(enc_record.borrow().0.key_part), mode)?;
         let data_elems = enc_record.borrow().0.data_part.elems.iter().map(|fe| OuterScalarVar::new_variable(cs.clone(), || Ok(fe), mode).unwrap()).collect();
-        let nonce = OuterScalarVar::new_variable(cs, || Ok(enc_record.borrow().0.data_part.nonce), mode)?;
+        let nonce = OuterScalarVar::new_variable(cs.clone(), || Ok(enc_record.borrow().0.data_part.nonce), mode)?;
+        let tag = OuterScalarVar::new_variable(cs, || Ok(enc_record.borrow().1), mode)?;
         Ok(EncryptedRecordVar {
             key_part,
             data_elems,
-            nonce
+            nonce,
+            tag
         })
     }
 }
@@ -222,6 +342,7 @@ impl ToBytesGadget<OuterScalarField> for EncryptedRecordVar {
         for elem in self.data_elems.iter() {
             v.extend_from_slice(&elem.to_bytes()?);
         }
+        v.extend_from_slice(&self.tag.to_bytes()?);
         Ok(v)
     }
 }
@@ -233,6 +354,7 @@ impl ToConstraintField<OuterScalarField> for EncryptedRecord {
         elems.extend_from_slice(&self.0.key_part.1.to_field_elements()?);
         elems.extend_from_slice(&self.0.data_part.elems);
         elems.push(self.0.data_part.nonce);
+        elems.push(self.1);
         Some(elems)
     }
 }
@@ -245,6 +367,8 @@ pub struct RecordVar {
     pub addr_object: OuterScalarVar,
     pub addr_owner: OuterScalarVar,
     pub payload: Vec<OuterScalarVar>,
+    pub memo_elems: Vec<OuterScalarVar>,
+    pub diversifier: OuterScalarVar,
 }
 
 impl AllocVar<Record, OuterScalarField> for RecordVar {
@@ -263,6 +387,8 @@ impl AllocVar<Record, OuterScalarField> for RecordVar {
         let addr_object = OuterScalarVar::new_variable(cs.clone(), || Ok(record.addr_object), mode)?;
         let addr_owner = OuterScalarVar::new_variable(cs.clone(), || Ok(record.addr_owner), mode)?;
         let payload = record.payload.iter().map(|elem: &OuterScalarField| OuterScalarVar::new_variable(cs.clone(), || Ok(elem), mode).unwrap()).collect();
+        let memo_elems = record.memo_elems().iter().map(|elem: &OuterScalarField| OuterScalarVar::new_variable(cs.clone(), || Ok(elem), mode).unwrap()).collect();
+        let diversifier = OuterScalarVar::new_variable(cs.clone(), || Ok(record.diversifier), mode)?;
         Ok(RecordVar {
             serial_nonce,
             contract_id,
@@ -270,7 +396,9 @@ impl AllocVar<Record, OuterScalarField> for RecordVar {
             sk_object,
             addr_object,
             addr_owner,
-            payload
+            payload,
+            memo_elems,
+            diversifier
         })
     }
 }
@@ -286,6 +414,9 @@ impl RecordVar {
            self.addr_owner.clone()
         ];
         data.extend_from_slice(&self.payload);
+        assert_eq!(self.memo_elems.len(), RECORD_MEMO_ELEMENTS);
+        data.extend_from_slice(&self.memo_elems);
+        data.push(self.diversifier.clone());
         data
     }
 }
@@ -328,7 +459,7 @@ fn check_record_decryption(
 
     let msg_len = OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(RECORD_CHUNKS as u64)).unwrap();
     let data = record.to_encryption_data();
-    let check_cipher = PoseidonCipherGadget::encrypt_with_expanded_key(cs, &enc_params.poseidon_params, &shared_key, enc_record.nonce.clone(), &data, &msg_len).unwrap();
+    let check_cipher = PoseidonCipherGadget::encrypt_with_expanded_key(cs, &enc_params.poseidon_params, &shared_key, enc_record.nonce.clone(), &data, &msg_len, &[]).unwrap();
     let mut ok = Boolean::TRUE;
     for i in 0..RECORD_CHUNKS_PADDED+1 {
         ok = ok.and(&check_cipher[i].is_eq(&enc_record.data_elems[i]).unwrap()).unwrap();
@@ -351,7 +482,7 @@ fn check_record_encryption(
 
     let msg_len = OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(RECORD_CHUNKS as u64)).unwrap();
     let data = record.to_encryption_data();
-    let check_cipher = PoseidonCipherGadget::encrypt_with_expanded_key(cs, &enc_params.poseidon_params, &rand.shared_key, enc_record.nonce.clone(), &data, &msg_len).unwrap();
+    let check_cipher = PoseidonCipherGadget::encrypt_with_expanded_key(cs, &enc_params.poseidon_params, &rand.shared_key, enc_record.nonce.clone(), &data, &msg_len, &[]).unwrap();
     for i in 0..RECORD_CHUNKS_PADDED+1 {
         ok = ok.and(&check_cipher[i].is_eq(&enc_record.data_elems[i]).unwrap()).unwrap();
     }
@@ -362,6 +493,30 @@ fn enforce_or_dummy(is_dummy: &Boolean<OuterScalarField>, check: Boolean<OuterSc
     check.or(is_dummy)?.enforce_equal(&Boolean::TRUE)
 }
 
+/// Enforces `a == b` unless `cond` holds: the equality-flavored special case of
+/// `enforce_or_dummy`, collapsing the repeated `a.is_eq(&b)?.or(&cond)?.enforce_equal(&TRUE)?`
+/// idiom into one call.
+fn enforce_eq_unless(a: &OuterScalarVar, b: &OuterScalarVar, cond: &Boolean<OuterScalarField>) -> ark_relations::r1cs::Result<()> {
+    enforce_or_dummy(cond, a.is_eq(b)?)
+}
+
+/// Batched counterpart of `enforce_eq_unless`, for matching a whole processor `ObjectDataVar`
+/// against the `RecordVar` it should equal field-by-field, including the `payload` array (where
+/// `obj.payload[0]` is `record.addr_owner` and `obj.payload[1..]` is `record.payload`; see
+/// `ObjectDataVar`'s doc comment). Used by `run_processor`'s "processor_state_matching" in place
+/// of the hand-rolled per-field loop over input and output records.
+fn enforce_obj_data_matches_record(obj: &ObjectDataVar, record: &RecordVar, cond: &Boolean<OuterScalarField>) -> ark_relations::r1cs::Result<()> {
+    enforce_eq_unless(&obj.contract_id, &record.contract_id, cond)?;
+    enforce_eq_unless(&obj.object_id, &record.object_id, cond)?;
+    enforce_eq_unless(&obj.sk_object, &record.sk_object, cond)?;
+    enforce_eq_unless(&obj.addr_object, &record.addr_object, cond)?;
+    enforce_eq_unless(&obj.payload[0], &record.addr_owner, cond)?;
+    for (obj_elem, record_elem) in obj.payload[1..].iter().zip(record.payload.iter()) {
+        enforce_eq_unless(obj_elem, record_elem, cond)?;
+    }
+    Ok(())
+}
+
 pub struct MainProofCircuit {
     pub ctx: ProofContext
 }
@@ -370,6 +525,7 @@ impl MainProofCircuit {
     fn access_input(&self,
                     cs: &ConstraintSystemRef<OuterScalarField>,
                     root: &RootVar,
+                    spent_serials_root: &OuterScalarVar,
                     idx: usize,
                     leaf_hash_param: &LeafHashParamsVar,
                     inner_hash_param: &InnerHashParamsVar,
@@ -392,25 +548,39 @@ impl MainProofCircuit {
             enforce_or_dummy(&is_dummy, check_record_decryption(cs, enc_params, &sk, &in_record_encrypted, &in_record))?;
         });
 
-        // check serial nonce correctly derived (for dummy inputs)
+        // check serial nonce correctly derived (for dummy inputs; active only when is_dummy holds,
+        // i.e. exactly the opposite guard from the rest of this function's checks)
         constraints_measure!(cs, "derive_sn_nonce_dummy", {
             let rand_sn_nonce = UInt8::new_witness_vec(ark_relations::ns!(cs, "rand_dummy_sn_nonce"), &self.ctx.in_records[idx].rand_dummy_sn_nonce)?;
             let rand_sn_nonce_bits = rand_sn_nonce.to_bits_le()?;
-            let is_ok = check_derive_fresh_sn_nonce(cs, &rand_sn_nonce_bits, (idx + NOF_TX_RECORDS) as u8, unique_seed_bits, &in_record.serial_nonce.to_bytes().unwrap())?;
-            enforce_or_dummy(&is_dummy.not(), is_ok)?;  // NOTE: is_dummy flag inverted
+            check_derive_fresh_sn_nonce(cs, &rand_sn_nonce_bits, (idx + NOF_TX_RECORDS) as u8, unique_seed_bits, &in_record.serial_nonce.to_bytes().unwrap(), &is_dummy)?;
         });
 
-        // check serial number correctly derived
+        // check serial number correctly derived -- this is the record's nullifier in Sapling's
+        // sense (sk_object standing in for the nullifier key, serial_nonce for rho; see
+        // derivations::derive_sn_from_nonce's doc comment), exposed as a public input so the
+        // non-membership check just below can enforce it hasn't already been spent
+        let actual_sn;
         constraints_measure!(cs, "derive_sn", {
             let sk_bits: Vec<_> = sk.0.to_bytes()?.iter().flat_map(|b| b.to_bits_le().unwrap()).collect();
             let serial_nonce_bits: Vec<_> = in_record.serial_nonce.to_bytes()?.iter().flat_map(|b| b.to_bits_le().unwrap()).collect();
-            let actual_sn = UInt8::new_input_vec(cs.clone(), &self.ctx.in_records[idx].sn)?;
-            let is_ok = check_derive_sn_from_nonce(cs, &sk_bits, &serial_nonce_bits, &actual_sn)?;
-            enforce_true_with_info(&is_ok, "access_input - derive serial number");
+            actual_sn = UInt8::new_input_vec(cs.clone(), &self.ctx.in_records[idx].sn)?;
+            check_derive_sn_from_nonce(cs, &sk_bits, &serial_nonce_bits, &actual_sn, &Boolean::TRUE)?;
+        });
+
+        // check serial number is not already in the spent-serials accumulator (see
+        // crypto::spent_serials); masked for dummy inputs like the other access_input checks
+        constraints_measure!(cs, "verify_spent_serials_non_membership", {
+            let sn_bits: Vec<_> = actual_sn.iter().flat_map(|b| b.to_bits_le().unwrap()).collect();
+            let sn_fe = Boolean::le_bits_to_fp_var(&sn_bits)?;
+            let smt_key = spent_serials::constraints::derive_key_var(cs, &enc_params.poseidon_params, &sn_fe)?;
+            let smt_path = SmtNonMembershipPathVar::new_witness(cs.clone(), &self.ctx.in_records[idx].spent_serials_path)?;
+            let check = smt_path.check_non_membership(cs, &enc_params.poseidon_params, &smt_key, spent_serials_root)?;
+            enforce_or_dummy(&is_dummy, check)?;
         });
 
         Ok((is_dummy, in_record))
-    } 
+    }
 
     fn access_output(&self,
                     cs: &ConstraintSystemRef<OuterScalarField>,
@@ -419,19 +589,24 @@ impl MainProofCircuit {
                     unique_seed_bits: &[Boolean<OuterScalarField>],
                 ) -> ark_relations::r1cs::Result<(Boolean<OuterScalarField>, RecordVar)> {
         let out_record = RecordVar::new_witness(ark_relations::ns!(cs, "out_record"), || Ok(self.ctx.out_records[idx].plaintext.clone()))?;
-        
-        // check out_record.serial_nonce correctly derived
+
+        // records with object id = 0 are dummy records; computed early so the serial-nonce
+        // derivation check below can be masked for dummy/padding output slots
+        let is_dummy = out_record.object_id.is_zero()?;
+
+        // check out_record.serial_nonce correctly derived (active only for non-dummy outputs)
         constraints_measure!(cs, "derive_sn_nonce", {
             let rand_sn_nonce = UInt8::new_witness_vec(ark_relations::ns!(cs, "rand_sn_nonce"), &self.ctx.out_records[idx].rand_sn_nonce)?;
             let rand_sn_nonce_bits = rand_sn_nonce.to_bits_le()?;
-            let is_ok = check_derive_fresh_sn_nonce(cs, &rand_sn_nonce_bits, idx as u8, unique_seed_bits, &out_record.serial_nonce.to_bytes().unwrap())?;
-            enforce_true_with_info(&is_ok, "access_output - derive serial nonce");
+            check_derive_fresh_sn_nonce(cs, &rand_sn_nonce_bits, idx as u8, unique_seed_bits, &out_record.serial_nonce.to_bytes().unwrap(), &is_dummy.not())?;
         });
 
-        // check owner public key correctly derived from owner address
+        // check owner public key correctly derived from owner address -- the address may be
+        // plain (zero diversifier) or one of many unlinkable diversified addresses for the
+        // same owner key (see `derivations::derive_diversified_addr`)
         let pk_owner = InnerEdVar::new_witness(cs.clone(), || Ok(self.ctx.out_records[idx].pk_owner))?;
         constraints_measure!(cs, "derive_owner_public_key", {
-            let check_owner_addr = get_addr_for_pk_var(&pk_owner);
+            let check_owner_addr = derive_diversified_addr_var(cs, &pk_owner, &out_record.diversifier)?;
             enforce_true_with_info(&check_owner_addr.is_eq(&out_record.addr_owner)?, "access_output - owner public key derivation");
         });
     
@@ -443,8 +618,6 @@ impl MainProofCircuit {
                 "access_output - record encryption");
         });
 
-        // records with object id = 0 are dummy records
-        let is_dummy = out_record.object_id.is_zero()?;
         Ok((is_dummy, out_record))
     }
 
@@ -468,7 +641,9 @@ impl MainProofCircuit {
         let sks = (0..NOF_TX_FRESH).map(|i| {
             let rand_sk = UInt8::new_witness_vec(cs.clone(), &self.ctx.rand_sk[i]).unwrap();
             let rand_sk_bits: Vec<_> = rand_sk.iter().flat_map(|byte| byte.to_bits_le().unwrap()).collect();
-            derive_and_check_fresh_object_sk_var(cs, &rand_sk_bits, i as u8, unique_seed_bits, enc_params).unwrap()
+            // every fresh slot is always in use (there is no per-slot dummy/active concept for
+            // fresh object accounts), so this check is unconditionally active
+            derive_and_check_fresh_object_sk_var(cs, &rand_sk_bits, i as u8, unique_seed_bits, enc_params, &Boolean::TRUE).unwrap()
         }).collect();
         Ok(sks)
     }
@@ -488,7 +663,7 @@ impl MainProofCircuit {
     fn get_and_authenticate_sender(&self,
         cs: &ConstraintSystemRef<OuterScalarField>,
         enc_params: &EncParams
-    ) -> ark_relations::r1cs::Result<OuterScalarVar> {
+    ) -> ark_relations::r1cs::Result<(OuterScalarVar, OuterScalarVar)> {
         let sender_address = OuterScalarVar::new_witness(cs.clone(), || Ok(self.ctx.sender_address))?;
 
         // ensure sender address is a valid external account
@@ -496,11 +671,17 @@ impl MainProofCircuit {
 
         // authenticate the sender by checking knowledge of the sender secret key
         let sender_sk_bytes = UInt8::new_witness_vec(cs.clone(), &self.ctx.sender_sk_bytes)?;
-        let check_pk = ElGamalKeyGadget::derive_pk(&SecretKeyVar(sender_sk_bytes), &enc_params.elgamal_params)?;
+        let check_pk = ElGamalKeyGadget::derive_pk(&SecretKeyVar(sender_sk_bytes.clone()), &enc_params.elgamal_params)?;
         let check_address = get_addr_for_pk_var(&check_pk);
         enforce_true_with_info(&check_address.is_eq(&sender_address)?, "sender knows secret key");
 
-        Ok(sender_address)
+        // field-element view of the same sk bytes (reduced mod the field order, see
+        // fe_from_le_bytes_mod_order); used as the RLN Shamir secret a0 in the "rln_rate_limit"
+        // constraints below
+        let sender_sk_bits: Vec<_> = sender_sk_bytes.iter().flat_map(|b| b.to_bits_le().unwrap()).collect();
+        let sender_sk_fe = Boolean::le_bits_to_fp_var(&sender_sk_bits)?;
+
+        Ok((sender_address, sender_sk_fe))
     }
 
     fn run_processor(&self,
@@ -512,7 +693,7 @@ impl MainProofCircuit {
         out_is_dummy: &[Boolean<OuterScalarField>],
         out_records: &[RecordVar],
         sender_address: &OuterScalarVar,
-    ) -> ark_relations::r1cs::Result<()> {
+    ) -> ark_relations::r1cs::Result<OuterScalarVar> {
         // derive fresh values
         let fresh_oids;
         let fresh_obj_sks;
@@ -543,43 +724,40 @@ impl MainProofCircuit {
             }
             for i in 0..NOF_TX_RECORDS {
                 initial_state.obj_data[i].is_empty.enforce_equal(&in_is_dummy[i].clone().into())?;
-                initial_state.obj_data[i].contract_id.is_eq(&in_records[i].contract_id)?.or(&in_is_dummy[i])?.enforce_equal(&Boolean::TRUE)?;
-                initial_state.obj_data[i].object_id.is_eq(&in_records[i].object_id)?.or(&in_is_dummy[i])?.enforce_equal(&Boolean::TRUE)?;
-                initial_state.obj_data[i].sk_object.is_eq(&in_records[i].sk_object)?.or(&in_is_dummy[i])?.enforce_equal(&Boolean::TRUE)?;
-                initial_state.obj_data[i].addr_object.is_eq(&in_records[i].addr_object)?.or(&in_is_dummy[i])?.enforce_equal(&Boolean::TRUE)?;
-                initial_state.obj_data[i].payload[0].is_eq(&in_records[i].addr_owner)?.or(&in_is_dummy[i])?.enforce_equal(&Boolean::TRUE)?;
-                for j in 0..NOF_RECORD_PAYLOAD_ELEMENTS {
-                    initial_state.obj_data[i].payload[1+j].is_eq(&in_records[i].payload[j])?.or(&in_is_dummy[i])?.enforce_equal(&Boolean::TRUE)?;
-                }
+                enforce_obj_data_matches_record(&initial_state.obj_data[i], &in_records[i], &in_is_dummy[i])?;
                 dbg_ensure_satisfied(cs, &format!("processor - checking matching input i = {}", i));
 
                 final_state.obj_data[i].is_empty.enforce_equal(&out_is_dummy[i].clone().into())?;
-                final_state.obj_data[i].contract_id.is_eq(&out_records[i].contract_id)?.or(&out_is_dummy[i])?.enforce_equal(&Boolean::TRUE)?;
-                final_state.obj_data[i].object_id.is_eq(&out_records[i].object_id)?.or(&out_is_dummy[i])?.enforce_equal(&Boolean::TRUE)?;
-                final_state.obj_data[i].sk_object.is_eq(&out_records[i].sk_object)?.or(&out_is_dummy[i])?.enforce_equal(&Boolean::TRUE)?;
-                final_state.obj_data[i].addr_object.is_eq(&out_records[i].addr_object)?.or(&out_is_dummy[i])?.enforce_equal(&Boolean::TRUE)?;
-                final_state.obj_data[i].payload[0].is_eq(&out_records[i].addr_owner)?.or(&out_is_dummy[i])?.enforce_equal(&Boolean::TRUE)?;
-                for j in 0..NOF_RECORD_PAYLOAD_ELEMENTS {
-                    final_state.obj_data[i].payload[1+j].is_eq(&out_records[i].payload[j])?.or(&out_is_dummy[i])?.enforce_equal(&Boolean::TRUE)?;
-                }
+                enforce_obj_data_matches_record(&final_state.obj_data[i], &out_records[i], &out_is_dummy[i])?;
                 dbg_ensure_satisfied(cs, &format!("processor - checking matching output i = {}", i));
             }
         });
 
         // run get instructions and run processor
         let instructions: Vec<_> = self.ctx.processor_instructions.iter().map(|inst| ZkInstructionVar::new_input(cs.clone(), || Ok(inst)).unwrap()).collect();
+        // allocated here, right after instructions, so the prover-side public-input order matches
+        // `MainProofVerifier::verify`'s `for inst in padded_instructions { ... } input.push(current_time)`,
+        // followed by the gas limit
         let current_time = OuterScalarVar::new_input(cs.clone(), || Ok(self.ctx.processor_current_time))?;
+        let gas_limit = OuterScalarVar::new_input(cs.clone(), || Ok(self.ctx.processor_gas_limit))?;
         constraints_measure!(cs, "processor_gadget", {
+            let straight_line = !program_has_control_flow(&self.ctx.processor_instructions);
+            let range_check_elided = infer_range_safe_cycles(&self.ctx.processor_instructions);
+            let shift_amounts = compute_shift_amounts(&self.ctx.processor_instructions);
             let processor = ZkProcessorGadget::new(cs.clone(),
                 instructions,
                 states,
-                current_time
+                current_time.clone(),
+                gas_limit,
+                straight_line,
+                range_check_elided,
+                shift_amounts,
             );
             processor.run().unwrap();
         });
         dbg_ensure_satisfied(cs, "processor - run");
 
-        Ok(())
+        Ok(current_time)
     }
 }
 
@@ -600,6 +778,9 @@ impl ConstraintSynthesizer<OuterScalarField> for MainProofCircuit {
         // get merkle tree root
         let root = RootVar::new_input(ark_relations::ns!(cs, "root"), || Ok(self.ctx.merkle_root.0))?;
 
+        // get spent-serials accumulator root (see access_input's "verify_spent_serials_non_membership")
+        let spent_serials_root = OuterScalarVar::new_input(ark_relations::ns!(cs, "spent_serials_root"), || Ok(self.ctx.spent_serials_root))?;
+
         // get and check called function
         let called_class_id = OuterScalarVar::new_input(cs.clone(), || Ok(self.ctx.called_class_id)).unwrap();
         let called_function_id = OuterScalarVar::new_input(cs.clone(), || Ok(self.ctx.called_function_id)).unwrap();
@@ -613,7 +794,7 @@ impl ConstraintSynthesizer<OuterScalarField> for MainProofCircuit {
         let mut in_records = vec![];
         for i in 0..NOF_TX_RECORDS {
             constraints_measure!(cs, "access_input", {
-                    let (is_dummy, plaintext) = self.access_input(&cs, &root, i, &leaf_hash_param, &inner_hash_param, &enc_params, &unique_seed_bits)?;
+                    let (is_dummy, plaintext) = self.access_input(&cs, &root, &spent_serials_root, i, &leaf_hash_param, &inner_hash_param, &enc_params, &unique_seed_bits)?;
                     in_is_dummy.push(is_dummy);
                     in_records.push(plaintext);
             });
@@ -633,15 +814,48 @@ impl ConstraintSynthesizer<OuterScalarField> for MainProofCircuit {
         dbg_ensure_satisfied(&cs, "checking output records");
 
         // authenticate sender
-        constraints_measure!(cs, "authenticate_sender",
-            let sender_address = self.get_and_authenticate_sender(&cs, &enc_params)?
-        );
+        let sender_address;
+        let sender_sk_fe;
+        constraints_measure!(cs, "authenticate_sender", {
+            let (addr, sk_fe) = self.get_and_authenticate_sender(&cs, &enc_params)?;
+            sender_address = addr;
+            sender_sk_fe = sk_fe;
+        });
 
-        // run processor
+        // run processor (current_time is allocated as a public input inside run_processor itself,
+        // right after instructions, to keep the prover's allocation order in sync with `verify`'s)
+        let current_time;
         constraints_measure!(cs, "run_processor", {
-            self.run_processor(&cs, &enc_params, &unique_seed_bits, &in_is_dummy, &in_records, &out_is_dummy, &out_records, &sender_address)?;
+            current_time = self.run_processor(&cs, &enc_params, &unique_seed_bits, &in_is_dummy, &in_records, &out_is_dummy, &out_records, &sender_address)?;
         });
         dbg_ensure_satisfied(&cs, "running processor");
+
+        // RLN-style per-epoch rate limiting: leak a Shamir share of the sender's secret key, so
+        // that two proofs from the same sender in the same epoch let anyone recover it (see
+        // crypto::rln). The epoch is scoped to exactly `current_time` (one epoch per distinct
+        // processor timestamp) rather than a bucketed range, since bucketing would need a
+        // range-checked division gadget the rest of the circuit doesn't otherwise require; callers
+        // wanting coarser epochs should quantize `current_time` themselves before proving.
+        constraints_measure!(cs, "rln_rate_limit", {
+            let epoch = self.ctx.processor_current_time;
+            let tx_seed: OuterScalarField = fe_from_le_bytes_mod_order(&self.ctx.unique_seed);
+            let sk_fe: OuterScalarField = fe_from_le_bytes_mod_order(&self.ctx.sender_sk_bytes);
+            let share = rln::evaluate_share(&self.ctx.crypto_params.enc_params, sk_fe, epoch, tx_seed);
+
+            let x_input = OuterScalarVar::new_input(cs.clone(), || Ok(share.x))?;
+            let y_input = OuterScalarVar::new_input(cs.clone(), || Ok(share.y))?;
+            let internal_nullifier_input = OuterScalarVar::new_input(cs.clone(), || Ok(share.internal_nullifier))?;
+
+            let tx_seed_var = Boolean::le_bits_to_fp_var(&unique_seed_bits)?;
+            let (x_check, y_check, nullifier_check) = rln::constraints::evaluate_share_var(
+                &cs, &enc_params.poseidon_params, &sender_sk_fe, &current_time, &tx_seed_var,
+            )?;
+            enforce_true_with_info(&x_input.is_eq(&x_check)?, "rln x matches");
+            enforce_true_with_info(&y_input.is_eq(&y_check)?, "rln y matches");
+            enforce_true_with_info(&internal_nullifier_input.is_eq(&nullifier_check)?, "rln internal_nullifier matches");
+        });
+        dbg_ensure_satisfied(&cs, "rln rate limiting");
+
         if cs.is_in_setup_mode() {
             data_log!(format!("{{\"constraints\": {{\"part\": \"{}\", \"num_constraints\": {}}}}}", "main_circuit", cs.num_constraints()));
         }
@@ -653,8 +867,10 @@ impl ConstraintSynthesizer<OuterScalarField> for MainProofCircuit {
 #[cfg(test)]
 mod tests {
     use ark_std::test_rng;
+    use ark_ff::Zero;
 
     use super::*;
+    use super::super::versioned::VersionedBinary;
 
     #[test]
     fn test_proof_circuit_count_constraints() {
@@ -664,4 +880,188 @@ mod tests {
         let num_constraints = count_constraints(params);
         println!("proof circuit constraints: {}", num_constraints);
     }
+
+    // a, b, c default to the point at infinity here -- running a real setup + prove just to
+    // exercise the versioned framing below would be wasteful, since that framing only wraps
+    // MainProof's existing ToBytes/FromBytes encoding (already covered by proving-related tests
+    // elsewhere) without touching the group elements themselves
+    fn dummy_main_proof() -> MainProof {
+        MainProof(Proof::<OuterPairing> {
+            a: <OuterPairing as PairingEngine>::G1Affine::default(),
+            b: <OuterPairing as PairingEngine>::G2Affine::default(),
+            c: <OuterPairing as PairingEngine>::G1Affine::default(),
+        })
+    }
+
+    #[test]
+    fn test_main_proof_versioned_round_trip() {
+        let proof = dummy_main_proof();
+        let bytes = proof.to_bytes();
+        let check_proof = MainProof::from_bytes(&bytes).unwrap();
+        assert_eq!(check_proof.0.a, proof.0.a);
+        assert_eq!(check_proof.0.b, proof.0.b);
+        assert_eq!(check_proof.0.c, proof.0.c);
+
+        let hex = proof.to_hex();
+        let check_proof = MainProof::from_hex(&hex).unwrap();
+        assert_eq!(check_proof.0.a, proof.0.a);
+    }
+
+    /// `MainProof::read` (the raw `FromBytes` impl) has no way to tell that a buffer is misaligned:
+    /// given one stray leading byte, it just reads `a`/`b`/`c`'s coordinates starting one byte late
+    /// and silently discards the trailing byte that no longer fits, producing a different-but
+    /// well-formed-looking `MainProof` instead of an error. `VersionedBinary::from_bytes` catches
+    /// this up front because the stray byte corrupts the leading magic tag it checks for.
+    #[test]
+    fn test_main_proof_versioned_rejects_misaligned_buffer_where_raw_read_would_not() {
+        let proof = dummy_main_proof();
+        let mut raw_bytes = vec![];
+        proof.0.write(&mut raw_bytes).unwrap();
+        let mut misaligned_raw = vec![0xffu8];
+        misaligned_raw.extend_from_slice(&raw_bytes);
+        // the raw FromBytes impl happily parses the shifted bytes instead of rejecting them
+        assert!(MainProof::read(misaligned_raw.as_slice()).is_ok());
+
+        let versioned_bytes = proof.to_bytes();
+        let mut misaligned_versioned = vec![0xffu8];
+        misaligned_versioned.extend_from_slice(&versioned_bytes);
+        assert!(MainProof::from_bytes(&misaligned_versioned).is_err());
+    }
+
+    #[test]
+    fn test_main_proof_versioned_rejects_wrong_magic() {
+        let proof = dummy_main_proof();
+        let mut bytes = proof.to_bytes();
+        bytes[0] = bytes[0].wrapping_add(1); // corrupt the "ZPRF" magic
+        assert!(MainProof::from_bytes(&bytes).is_err());
+    }
+
+    fn alloc_obj_data_var_for(cs: &ConstraintSystemRef<OuterScalarField>, record: &Record) -> ObjectDataVar {
+        ObjectDataVar {
+            is_empty: OuterScalarVar::new_witness(cs.clone(), || Ok(OuterScalarField::zero())).unwrap(),
+            contract_id: OuterScalarVar::new_witness(cs.clone(), || Ok(record.contract_id)).unwrap(),
+            object_id: OuterScalarVar::new_witness(cs.clone(), || Ok(record.object_id)).unwrap(),
+            sk_object: OuterScalarVar::new_witness(cs.clone(), || Ok(record.sk_object)).unwrap(),
+            addr_object: OuterScalarVar::new_witness(cs.clone(), || Ok(record.addr_object)).unwrap(),
+            payload: std::iter::once(record.addr_owner).chain(record.payload.iter().cloned())
+                .map(|e| OuterScalarVar::new_witness(cs.clone(), || Ok(e)).unwrap())
+                .collect(),
+        }
+    }
+
+    // the hand-rolled chain `enforce_obj_data_matches_record` replaced in "processor_state_matching"
+    fn enforce_obj_data_matches_record_unrolled(obj: &ObjectDataVar, record: &RecordVar, cond: &Boolean<OuterScalarField>) -> ark_relations::r1cs::Result<()> {
+        obj.contract_id.is_eq(&record.contract_id)?.or(cond)?.enforce_equal(&Boolean::TRUE)?;
+        obj.object_id.is_eq(&record.object_id)?.or(cond)?.enforce_equal(&Boolean::TRUE)?;
+        obj.sk_object.is_eq(&record.sk_object)?.or(cond)?.enforce_equal(&Boolean::TRUE)?;
+        obj.addr_object.is_eq(&record.addr_object)?.or(cond)?.enforce_equal(&Boolean::TRUE)?;
+        obj.payload[0].is_eq(&record.addr_owner)?.or(cond)?.enforce_equal(&Boolean::TRUE)?;
+        for j in 0..NOF_RECORD_PAYLOAD_ELEMENTS {
+            obj.payload[1+j].is_eq(&record.payload[j])?.or(cond)?.enforce_equal(&Boolean::TRUE)?;
+        }
+        Ok(())
+    }
+
+    /// `enforce_obj_data_matches_record` must be behavior-preserving: same satisfiability and the
+    /// same constraint count as the per-field chain it replaced in "processor_state_matching".
+    #[test]
+    fn test_enforce_obj_data_matches_record_matches_unrolled_chain() {
+        let record = Record::default();
+
+        let cs_unrolled = ConstraintSystem::<OuterScalarField>::new_ref();
+        let obj_unrolled = alloc_obj_data_var_for(&cs_unrolled, &record);
+        let record_var_unrolled = RecordVar::new_witness(cs_unrolled.clone(), || Ok(record.clone())).unwrap();
+        let cond_unrolled = Boolean::new_witness(cs_unrolled.clone(), || Ok(false)).unwrap();
+        let before = cs_unrolled.num_constraints();
+        enforce_obj_data_matches_record_unrolled(&obj_unrolled, &record_var_unrolled, &cond_unrolled).unwrap();
+        let unrolled_constraints = cs_unrolled.num_constraints() - before;
+        assert!(cs_unrolled.is_satisfied().unwrap());
+
+        let cs_builder = ConstraintSystem::<OuterScalarField>::new_ref();
+        let obj_builder = alloc_obj_data_var_for(&cs_builder, &record);
+        let record_var_builder = RecordVar::new_witness(cs_builder.clone(), || Ok(record.clone())).unwrap();
+        let cond_builder = Boolean::new_witness(cs_builder.clone(), || Ok(false)).unwrap();
+        let before = cs_builder.num_constraints();
+        enforce_obj_data_matches_record(&obj_builder, &record_var_builder, &cond_builder).unwrap();
+        let builder_constraints = cs_builder.num_constraints() - before;
+        assert!(cs_builder.is_satisfied().unwrap());
+
+        assert_eq!(unrolled_constraints, builder_constraints);
+    }
+
+    /// masked equality must still be enforceable-false: a mismatching field should fail
+    /// satisfiability when `cond` is false, and be tolerated when `cond` is true.
+    #[test]
+    fn test_enforce_obj_data_matches_record_respects_mask() {
+        let record = Record::default();
+        let mut mismatched = record.clone();
+        mismatched.contract_id = OuterScalarField::from(1u64);
+
+        let cs = ConstraintSystem::<OuterScalarField>::new_ref();
+        let obj = alloc_obj_data_var_for(&cs, &record);
+        let record_var = RecordVar::new_witness(cs.clone(), || Ok(mismatched.clone())).unwrap();
+        let cond = Boolean::new_witness(cs.clone(), || Ok(false)).unwrap();
+        enforce_obj_data_matches_record(&obj, &record_var, &cond).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+
+        let cs = ConstraintSystem::<OuterScalarField>::new_ref();
+        let obj = alloc_obj_data_var_for(&cs, &record);
+        let record_var = RecordVar::new_witness(cs.clone(), || Ok(mismatched)).unwrap();
+        let cond = Boolean::new_witness(cs.clone(), || Ok(true)).unwrap();
+        enforce_obj_data_matches_record(&obj, &record_var, &cond).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    /// Regression test for the RLN rate-limiting commit: `generate_constraints` must allocate its
+    /// public inputs in exactly the order `MainProofVerifier::verify` builds its `input` vector in,
+    /// since GM17 (like any QAP-based SNARK) binds public inputs by position -- a mismatch here
+    /// makes every proof fail to verify, not just ones touching whatever moved. This doesn't need
+    /// a real proving/verifying key: it directly compares the constraint system's instance
+    /// assignment (the allocation-order values `new_input` produced) against `verify`'s own
+    /// construction of the same values from an identical `ProofContext`.
+    #[test]
+    fn test_main_proof_circuit_input_order_matches_verifier() {
+        let mut rng = test_rng();
+        let params = CryptoParams::setup(&mut rng);
+
+        // built independently from the circuit's own context, so there's no risk of accidentally
+        // sharing allocation-order assumptions between the two
+        let ctx = ProofContext::default_with_params(params.clone());
+        let consumed_serials: Vec<Serial> = ctx.in_records.iter().map(|rec| rec.sn).collect();
+        let new_records: Vec<EncryptedRecord> = ctx.out_records.iter().map(|rec| rec.encrypted.clone()).collect();
+        let sk_fe = fe_from_le_bytes_mod_order(&ctx.sender_sk_bytes);
+        let tx_seed = fe_from_le_bytes_mod_order(&ctx.unique_seed);
+        let share = rln::evaluate_share(&ctx.crypto_params.enc_params, sk_fe, ctx.processor_current_time, tx_seed);
+
+        let mut expected_input = vec![];
+        expected_input.extend_from_slice(&ctx.unique_seed.to_field_elements().unwrap());
+        expected_input.push(ctx.merkle_root.0);
+        expected_input.push(ctx.spent_serials_root);
+        expected_input.push(ctx.called_class_id);
+        expected_input.push(ctx.called_function_id);
+        for serial in &consumed_serials {
+            expected_input.extend_from_slice(&serial.to_field_elements().unwrap());
+        }
+        for record in &new_records {
+            expected_input.extend_from_slice(&record.to_field_elements().unwrap());
+        }
+        for inst in eliminate_dead_code(&fold_constants(&ctx.processor_instructions)) {
+            expected_input.extend_from_slice(&inst.to_field_elements().unwrap());
+        }
+        expected_input.push(ctx.processor_current_time);
+        expected_input.push(ctx.processor_gas_limit);
+        expected_input.push(share.x);
+        expected_input.push(share.y);
+        expected_input.push(share.internal_nullifier);
+
+        let circuit = MainProofCircuit { ctx: ProofContext::default_with_params(params) };
+        let cs = ConstraintSystem::<OuterScalarField>::new_ref();
+        circuit.generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+
+        // instance_assignment[0] is the constant "one" every R1CS instance carries; the rest is
+        // exactly the sequence of values passed to `new_input`, in allocation order.
+        let actual_input = cs.borrow().unwrap().instance_assignment[1..].to_vec();
+        assert_eq!(actual_input, expected_input);
+    }
 }
\ No newline at end of file
@@ -1,7 +1,8 @@
-use ark_crypto_primitives::encryption::elgamal::SecretKey;
+use ark_crypto_primitives::encryption::elgamal::{Parameters as ElGamalParameters, SecretKey};
+use ark_ec::{AffineCurve, ProjectiveCurve};
 use blake2::{Digest, Blake2s};
 
-use ark_ff::to_bytes;
+use ark_ff::{to_bytes, PrimeField, Zero};
 use log::debug;
 use rand::Rng;
 
@@ -13,6 +14,16 @@ use super::params::CryptoParams;
 use super::record::Serial;
 
 /// Derives the serial number from a given serial nonce and secret key.
+///
+/// This is Zapper's nullifier in Sapling's sense: `sn = PRF(sk_object, serial_nonce)`, where
+/// `sk_object` plays the role of Sapling's nullifier key `nk` and `serial_nonce` plays `rho` (a
+/// per-record random value stored in the note, see `Record::serial_nonce`). Publishing `sn` lets
+/// the ledger reject a double-spend without revealing which Merkle leaf it came from, exactly
+/// like a Sapling nullifier; `crypto::spent_serials` is the accumulator that tracks which ones
+/// have already been published. The only difference from the textbook construction is the PRF:
+/// this crate uses Blake2s here (see `constraints::check_derive_sn_from_nonce` for the in-circuit
+/// gadget) rather than Poseidon, to reuse the same PRF already used for `try_derive_fresh_sn_nonce`
+/// and the other `Prf*Seed`-domained derivations below.
 pub fn derive_sn_from_nonce(serial_nonce: &OuterScalarField, sk_bytes: &[u8; SERIALIZED_SK_BYTES]) -> Serial {
     let mut h = Blake2s::new();
     h.update(&[PRF_SN_SEED]);
@@ -73,40 +84,149 @@ pub fn try_derive_fresh_value(rand: &[u8; RAND_BYTES], i: u8, unique_seed: &[u8;
 }
 
 
-/// Returns `true` iff `addr` is the address of an external account.
+/// Returns `true` iff `addr` is the address of an external account. Independent of which
+/// `DerivationCurve` `addr` was derived under: addresses always live in `OuterScalarField` (the
+/// SNARK's own scalar field), and this distinction is pinned to their least significant bit.
 pub fn is_external_account(addr: &OuterScalarField) -> bool {
     // check if least significant bit of the address is 1
     let ls_byte = to_bytes!(addr).unwrap()[0];
     (ls_byte & 1u8) == 1u8
 }
 
+/// Extension point letting the derivation logic below (addresses, fresh object keys) target an
+/// inner twisted-Edwards curve other than the current ciphersuite (`JubJubDerivation`), e.g. a
+/// higher-security Ed448-family curve selected via a future `params::Ciphersuite` variant. The
+/// invariants every implementor must preserve: an address is the affine x-coordinate (hence
+/// `Self::Affine`'s base field must be `OuterScalarField`, the SNARK's own scalar field, so
+/// addresses are always native in-circuit field elements); `try_get_pk_for_addr` recovers the
+/// point from `x` with a fixed sign bit (`false`, i.e. `get_point_from_x`'s `greatest = false`);
+/// and the external-vs-object account distinction stays on the address's least significant bit
+/// (`is_external_account`, which is curve-independent and so lives outside this trait).
+///
+/// Only `JubJubDerivation` is implemented today. Adding a second curve here is necessary but not
+/// sufficient to actually select it end-to-end: `CryptoParams`'s Pedersen/Merkle-tree CRH
+/// configuration and every place in `record.rs` and `circuit.rs` still hard-coded to
+/// `InnerEdProjective`/`InnerEdVar` (record and circuit gadget types) would also need to become
+/// generic over the same curve. This trait lets that migration start with the derivation logic
+/// without forcing it all to land in one change.
+pub trait DerivationCurve {
+    /// The curve's scalar field (object account secret keys live here).
+    type ScalarField: PrimeField;
+    /// Affine point representation; its base field must coincide with `OuterScalarField` so that
+    /// an address (an affine x-coordinate) is directly usable as an in-circuit field element.
+    type Affine: AffineCurve<ScalarField = Self::ScalarField, BaseField = OuterScalarField>;
+    /// Projective point representation, used for public-key arithmetic off-circuit.
+    type Projective: ProjectiveCurve<Affine = Self::Affine, ScalarField = Self::ScalarField>;
+
+    /// Returns the address derived from the given public key `pk`.
+    fn get_addr_for_pk(pk: &Self::Affine) -> OuterScalarField {
+        // we use the x-coordinate as the address
+        pk.x
+    }
+
+    /// Tries to reconstruct the public key for the given address `addr`.
+    /// Returns `None` if not possible.
+    fn try_get_pk_for_addr(addr: &OuterScalarField) -> Option<Self::Affine> {
+        Self::Affine::get_point_from_x(*addr, false)
+    }
+
+    /// Reconstructs the public key for the given address `addr`.
+    fn get_pk_for_addr(addr: &OuterScalarField) -> Self::Affine {
+        Self::try_get_pk_for_addr(addr).unwrap()
+    }
+
+    /// Checks if `pk` is reconstructable from its x coordinate.
+    fn is_reconstructable(pk: &Self::Affine) -> bool {
+        let pk_check = Self::get_pk_for_addr(&Self::get_addr_for_pk(pk));
+        *pk == pk_check
+    }
+
+    /// Derives a fresh object secret key (whose public key is guaranteed to be an object public
+    /// key, i.e. not an external account) for the `i`-th output record, given this curve's ElGamal
+    /// parameters and the unique seed. Returns `(rand, sk)`, where `rand` is the randomness used
+    /// to derive `sk`. Same `n = t = 1` special case of `crypto::frost_dkg`'s threshold key
+    /// generation as `derive_fresh_object_sk` below.
+    fn derive_fresh_object_sk<R: Rng>(
+        rng: &mut R,
+        elgamal_params: &ElGamalParameters<Self::Projective>,
+        i: u8,
+        unique_seed: &[u8; RAND_BYTES],
+    ) -> ([u8; RAND_BYTES], Self::ScalarField)
+    where
+        FeConverter: FeFromLeBytesConverter<Self::ScalarField>,
+    {
+        let mut rand = [0u8; RAND_BYTES];
+        loop {
+            rng.fill_bytes(&mut rand);
+            if let Some(sk) = try_derive_fresh_object_sk_no_pk_check::<Self::ScalarField>(&rand, i, unique_seed) {
+                let sk = SecretKey(sk);
+                let pk = derive_pk_from_sk(elgamal_params, &sk);
+                if Self::is_reconstructable(&pk) && !is_external_account(&Self::get_addr_for_pk(&pk)) {
+                    debug!("successfully derived fresh object sk {:?} for object account", &sk.0);
+                    return (rand, sk.0);
+                }
+            }
+        }
+    }
+}
+
+/// The current ciphersuite's inner curve: JubJub over BLS12-381. See `DerivationCurve` for what
+/// plugging in an alternative curve would require.
+pub struct JubJubDerivation;
+
+impl DerivationCurve for JubJubDerivation {
+    type ScalarField = InnerEdScalarField;
+    type Affine = InnerEdAffine;
+    type Projective = InnerEdProjective;
+}
+
 /// Returns the address derived from the given public key `pk`.
 pub fn get_addr_for_pk(pk: &InnerEdAffine) -> OuterScalarField {
-    // we use the x-coordinate as the address
-    pk.x
+    JubJubDerivation::get_addr_for_pk(pk)
 }
 
 /// Tries to reconstruct the public key for the given address `addr`.
 /// Returns `None` not possible.
 pub fn try_get_pk_for_addr(addr: &OuterScalarField) -> Option<InnerEdAffine> {
-    InnerEdAffine::get_point_from_x(*addr, false)
+    JubJubDerivation::try_get_pk_for_addr(addr)
 }
 
 /// Reconstructs the public key for the given address `addr`.
 pub fn get_pk_for_addr(addr: &OuterScalarField) -> InnerEdAffine {
-    try_get_pk_for_addr(addr).unwrap()
+    JubJubDerivation::get_pk_for_addr(addr)
 }
 
 /// Checks if `pk` is reconstructable from its x coordinate.
 pub fn is_reconstructable(pk: &InnerEdAffine) -> bool {
-    let pk_check = get_pk_for_addr(&get_addr_for_pk(pk));
-    *pk == pk_check
+    JubJubDerivation::is_reconstructable(pk)
+}
+
+/// Derives a diversified address for owner public key `pk` under `diversifier`, Sapling-note-
+/// plaintext style: `addr = KDF(pk, diversifier)`. A single long-term `pk` can hand out many
+/// such addresses, one per fresh `diversifier`, that are unlinkable to each other without
+/// knowing `pk`. `diversifier == 0` is reserved to mean "no diversification" and falls back to
+/// the plain, reconstructable `get_addr_for_pk(pk)` address; this keeps every existing address
+/// (object accounts, an external account's default address) a valid diversified address with
+/// diversifier zero, so records whose owner was assigned the old way need no changes.
+pub fn derive_diversified_addr(pk: &InnerEdAffine, diversifier: &OuterScalarField) -> OuterScalarField {
+    if diversifier.is_zero() {
+        return get_addr_for_pk(pk);
+    }
+    let mut h = Blake2s::new();
+    h.update(&[PRF_DIV_ADDR_SEED]);
+    h.update(&to_bytes!(pk.x).unwrap());
+    h.update(&to_bytes!(pk.y).unwrap());
+    h.update(&to_bytes!(diversifier).unwrap());
+    fe_from_le_bytes_mod_order(&h.finalize())
 }
 
 /// Derives a fresh object secret key for the `i`-th new object, given randomness and unique seed
 /// without checking that the corresponding public key is actually an object secret key.
 /// Returns `None` if no valid secret key could be derived (re-try using different randomness).
-fn try_derive_fresh_object_sk_no_pk_check(rand: &[u8; RAND_BYTES], i: u8, unique_seed: &[u8; RAND_BYTES]) -> Option<InnerEdScalarField> {
+fn try_derive_fresh_object_sk_no_pk_check<F>(rand: &[u8; RAND_BYTES], i: u8, unique_seed: &[u8; RAND_BYTES]) -> Option<F>
+where
+    FeConverter: FeFromLeBytesConverter<F>,
+{
     assert!((i as usize) < NOF_TX_FRESH);
 
     let mut h = Blake2s::new();
@@ -122,21 +242,12 @@ fn try_derive_fresh_object_sk_no_pk_check(rand: &[u8; RAND_BYTES], i: u8, unique
 
 /// Derives a fresh object secret key (whose public key is guaranteed to be an object public key) for the `i`-th output record, given the unique seed.
 /// Returns a tuple `(rand, sk)`, where `rand` is the randomness used to derive the secret key `sk`.
+/// This is the `n = t = 1` special case of `crypto::frost_dkg`'s threshold key generation: a lone
+/// party's "polynomial" is just `sk` itself, and its retry loop (in `DerivationCurve::derive_fresh_object_sk`) is the single-party version
+/// of `crypto::frost_dkg::is_usable_group_key`'s restart condition. Thin wrapper around
+/// `JubJubDerivation::derive_fresh_object_sk` (see `DerivationCurve`) for today's ciphersuite.
 pub fn derive_fresh_object_sk<R: Rng>(rng: &mut R, crypto_params: &CryptoParams, i: u8, unique_seed: &[u8; RAND_BYTES]) -> ([u8; RAND_BYTES], InnerEdScalarField) {
-    let mut rand = [0u8; RAND_BYTES];
-    loop {
-        rng.fill_bytes(&mut rand);
-        if let Some(sk) = try_derive_fresh_object_sk_no_pk_check(&rand, i, unique_seed) {
-            let sk = SecretKey(sk);
-            let pk = derive_pk_from_sk(&crypto_params.enc_params.elgamal_params, &sk);
-            if is_reconstructable(&pk) {
-                if !is_external_account(&get_addr_for_pk(&pk)) {
-                    debug!("successfully derived fresh object sk {:?} for object account", &sk.0);
-                    return (rand, sk.0);
-                }
-            }
-        }
-    }
+    JubJubDerivation::derive_fresh_object_sk(rng, &crypto_params.enc_params.elgamal_params, i, unique_seed)
 }
 
 pub mod constraints {
@@ -153,26 +264,44 @@ pub mod constraints {
         Ok(evaluate_blake2s(input)?.iter().flat_map(|int| int.to_bytes().unwrap()).collect())
     }
 
+    /// Masks `check` so it is only enforced while `active` holds: enforces `check OR !active`,
+    /// letting a padding/dummy slot (`active == false`) satisfy the constraint no matter what
+    /// `check` evaluates to. This is the derivation-gadget counterpart of `circuit::
+    /// enforce_or_dummy` (kept local here so the PRF/key-derivation checks below don't need a
+    /// dependency on `circuit.rs`), used by `check_derive_sn_from_nonce`,
+    /// `check_derive_fresh_sn_nonce`, and `derive_and_check_fresh_object_sk_var` so padding slots
+    /// don't have to carry fully-derived keys and nonces.
+    pub fn enforce_when_active(check: Boolean<OuterScalarField>, active: &Boolean<OuterScalarField>) -> ark_relations::r1cs::Result<()> {
+        check.or(&active.not())?.enforce_equal(&Boolean::TRUE)
+    }
+
+    /// In-circuit counterpart of `super::derive_sn_from_nonce` -- the nullifier-derivation gadget
+    /// `access_input` calls for every input record, dummy or not, with `active` set to TRUE (see
+    /// `circuit.rs`). The check is masked by `active` (see `enforce_when_active`); a caller passing
+    /// FALSE lets a slot skip it.
     pub fn check_derive_sn_from_nonce(cs: &ConstraintSystemRef<OuterScalarField>,
             sk_bits: &[Boolean<OuterScalarField>],
             sn_nonce_bits: &[Boolean<OuterScalarField>],
-            check_sn: &[UInt8<OuterScalarField>]
-    ) -> ark_relations::r1cs::Result<Boolean<OuterScalarField>> {
+            check_sn: &[UInt8<OuterScalarField>],
+            active: &Boolean<OuterScalarField>
+    ) -> ark_relations::r1cs::Result<()> {
         let mut hash_input_bits = UInt8::new_constant(cs.clone(), PRF_SN_SEED)?.to_bits_le()?;
         hash_input_bits.extend_from_slice(sk_bits);
         hash_input_bits.extend_from_slice(sn_nonce_bits);
 
         let computed_sn = compute_prf(&hash_input_bits)?;
         let res = computed_sn.is_eq(check_sn)?;
-        Ok(res)
+        enforce_when_active(res, active)
     }
 
+    /// Masked (see `enforce_when_active`) in-circuit counterpart of `super::try_derive_fresh_sn_nonce`.
     pub fn check_derive_fresh_sn_nonce(cs: &ConstraintSystemRef<OuterScalarField>,
             rand_bits: &[Boolean<OuterScalarField>],
             i: u8,
             unique_seed_bits: &[Boolean<OuterScalarField>],
-            check_sn_nonce: &[UInt8<OuterScalarField>]
-    ) -> ark_relations::r1cs::Result<Boolean<OuterScalarField>> {
+            check_sn_nonce: &[UInt8<OuterScalarField>],
+            active: &Boolean<OuterScalarField>
+    ) -> ark_relations::r1cs::Result<()> {
         let mut hash_input_bits = UInt8::new_constant(cs.clone(), PRF_SN_NONCE_SEED)?.to_bits_le()?;
         hash_input_bits.extend_from_slice(rand_bits);
         let i_bits = UInt8::new_constant(cs.clone(), i)?.to_bits_le()?;
@@ -181,7 +310,7 @@ pub mod constraints {
 
         let computed_sn_nonce = compute_prf(&hash_input_bits)?;
         let res = computed_sn_nonce.is_eq(check_sn_nonce)?;
-        Ok(res)
+        enforce_when_active(res, active)
     }
 
     pub fn derive_fresh_object_id_var(cs: &ConstraintSystemRef<OuterScalarField>,
@@ -222,17 +351,38 @@ pub mod constraints {
         pk.x.clone()
     }
 
+    /// In-circuit counterpart of `super::derive_diversified_addr`.
+    pub fn derive_diversified_addr_var(cs: &ConstraintSystemRef<OuterScalarField>,
+        pk: &InnerEdVar,
+        diversifier: &OuterScalarVar
+    ) -> ark_relations::r1cs::Result<OuterScalarVar> {
+        let mut hash_input_bits = UInt8::new_constant(cs.clone(), PRF_DIV_ADDR_SEED)?.to_bits_le()?;
+        hash_input_bits.extend_from_slice(&pk.x.to_bytes()?.iter().flat_map(|b| b.to_bits_le().unwrap()).collect::<Vec<_>>());
+        hash_input_bits.extend_from_slice(&pk.y.to_bytes()?.iter().flat_map(|b| b.to_bits_le().unwrap()).collect::<Vec<_>>());
+        hash_input_bits.extend_from_slice(&diversifier.to_bytes()?.iter().flat_map(|b| b.to_bits_le().unwrap()).collect::<Vec<_>>());
+
+        let computed_addr_bytes = compute_prf(&hash_input_bits)?;
+        let computed_addr = Boolean::le_bits_to_fp_var(&computed_addr_bytes.to_bits_le()?)?;
+
+        let is_undiversified = diversifier.is_zero()?;
+        OuterScalarVar::conditionally_select(&is_undiversified, &get_addr_for_pk_var(pk), &computed_addr)
+    }
+
     pub fn is_external_account(addr: &OuterScalarVar)
     -> ark_relations::r1cs::Result<Boolean<OuterScalarField>> {
         let ls_bit = addr.to_bits_le()?[0].clone();
         Ok(ls_bit)
     }
 
+    /// Masked (see `enforce_when_active`) in-circuit counterpart of `super::derive_fresh_object_sk`:
+    /// `active` gates the "not an external account" check, so a padding/unused fresh-object slot
+    /// doesn't have to carry a key that actually derives to an object account.
     pub fn derive_and_check_fresh_object_sk_var(cs: &ConstraintSystemRef<OuterScalarField>,
         rand_bits: &[Boolean<OuterScalarField>],
         i: u8,
         unique_seed_bits: &[Boolean<OuterScalarField>],
-        enc_param: &MyParametersVar<InnerEdProjective, InnerEdVar>
+        enc_param: &MyParametersVar<InnerEdProjective, InnerEdVar>,
+        active: &Boolean<OuterScalarField>
     ) -> ark_relations::r1cs::Result<OuterScalarVar> {
         // first, compute the secret key based on the hash function
         let mut hash_input_bits = UInt8::new_constant(cs.clone(), PRF_SK_SEED)?.to_bits_le()?;
@@ -248,7 +398,8 @@ pub mod constraints {
 
         // then, enforce the corresponding public key is a an object account (no external account)
         let pk = ElGamalKeyGadget::<InnerEdProjective, InnerEdVar>::derive_pk(&SecretKeyVar(computed_sk_bytes), enc_param)?;
-        is_external_account(&get_addr_for_pk_var(&pk))?.enforce_equal(&Boolean::FALSE)?;
+        let is_valid_object_account = is_external_account(&get_addr_for_pk_var(&pk))?.not();
+        enforce_when_active(is_valid_object_account, active)?;
 
         Ok(sk_fe)
     }
@@ -279,7 +430,7 @@ mod tests {
             let unique_seed_var: Vec<_> = self.unique_seed.iter().flat_map(|byte| UInt8::new_witness(cs.clone(), || Ok(byte)).unwrap().to_bits_le().unwrap()).collect();
 
             let expected_sk = OuterScalarVar::new_constant(cs.clone(), self.expected_sk)?;
-            let derived_sk = constraints::derive_and_check_fresh_object_sk_var(&cs, &rand_var, 0u8, &unique_seed_var, &enc_params_var)?;
+            let derived_sk = constraints::derive_and_check_fresh_object_sk_var(&cs, &rand_var, 0u8, &unique_seed_var, &enc_params_var, &Boolean::TRUE)?;
             expected_sk.enforce_equal(&derived_sk)?;
 
             Ok(())
@@ -306,4 +457,76 @@ mod tests {
         circ.generate_constraints(cs.clone()).unwrap();
         assert!(cs.is_satisfied().unwrap());
     }
+
+    struct DiversifiedAddrCircuit {
+        pk: InnerEdAffine,
+        diversifier: OuterScalarField,
+        expected_addr: OuterScalarField,
+    }
+
+    impl ConstraintSynthesizer<OuterScalarField> for DiversifiedAddrCircuit {
+        fn generate_constraints(self, cs: ark_relations::r1cs::ConstraintSystemRef<OuterScalarField>) -> ark_relations::r1cs::Result<()> {
+            let pk_var = InnerEdVar::new_witness(cs.clone(), || Ok(self.pk))?;
+            let diversifier_var = OuterScalarVar::new_witness(cs.clone(), || Ok(self.diversifier))?;
+            let expected_addr = OuterScalarVar::new_constant(cs.clone(), self.expected_addr)?;
+
+            let derived_addr = constraints::derive_diversified_addr_var(&cs, &pk_var, &diversifier_var)?;
+            expected_addr.enforce_equal(&derived_addr)?;
+
+            Ok(())
+        }
+    }
+
+    fn check_diversified_addr(pk: InnerEdAffine, diversifier: OuterScalarField) {
+        let expected_addr = derive_diversified_addr(&pk, &diversifier);
+        let circ = DiversifiedAddrCircuit { pk, diversifier, expected_addr };
+        let cs = ConstraintSystem::<OuterScalarField>::new_ref();
+        circ.generate_constraints(cs.clone()).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn test_derive_diversified_addr_var() {
+        let mut rng = test_rng();
+        let params = CryptoParams::setup(&mut rng);
+        let (_, sk) = derive_fresh_object_sk(&mut rng, &params, 0u8, &{
+            let mut seed = [0u8; RAND_BYTES];
+            rng.fill_bytes(&mut seed);
+            seed
+        });
+        let pk = derive_pk_from_sk(&params.enc_params.elgamal_params, &SecretKey(sk));
+
+        check_diversified_addr(pk, OuterScalarField::rand(&mut rng));
+    }
+
+    #[test]
+    fn test_derive_diversified_addr_zero_is_plain_addr() {
+        let mut rng = test_rng();
+        let params = CryptoParams::setup(&mut rng);
+        let (_, sk) = derive_fresh_object_sk(&mut rng, &params, 0u8, &{
+            let mut seed = [0u8; RAND_BYTES];
+            rng.fill_bytes(&mut seed);
+            seed
+        });
+        let pk = derive_pk_from_sk(&params.enc_params.elgamal_params, &SecretKey(sk));
+
+        assert_eq!(derive_diversified_addr(&pk, &OuterScalarField::zero()), get_addr_for_pk(&pk));
+        check_diversified_addr(pk, OuterScalarField::zero());
+    }
+
+    #[test]
+    fn test_derive_diversified_addr_unlinkable() {
+        let mut rng = test_rng();
+        let params = CryptoParams::setup(&mut rng);
+        let (_, sk) = derive_fresh_object_sk(&mut rng, &params, 0u8, &{
+            let mut seed = [0u8; RAND_BYTES];
+            rng.fill_bytes(&mut seed);
+            seed
+        });
+        let pk = derive_pk_from_sk(&params.enc_params.elgamal_params, &SecretKey(sk));
+
+        let addr_1 = derive_diversified_addr(&pk, &OuterScalarField::rand(&mut rng));
+        let addr_2 = derive_diversified_addr(&pk, &OuterScalarField::rand(&mut rng));
+        assert_ne!(addr_1, addr_2);
+    }
 }
\ No newline at end of file
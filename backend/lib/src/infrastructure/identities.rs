@@ -1,24 +1,112 @@
+use std::io::{Read, Write};
+
 use ark_crypto_primitives::encryption::elgamal::SecretKey;
-use ark_ff::UniformRand;
+use ark_ff::{FromBytes, ToBytes, UniformRand};
+use blake2::{Blake2s, Digest};
 use log::debug;
 use rand::Rng;
 
 use crate::infrastructure::params::CryptoParams;
 
 use crate::crypto::elgamal_ext::{ExtSecretKey, derive_pk_from_sk};
+use crate::crypto::oprf;
 use crate::common::*;
+use crate::constants::PRF_ACCOUNT_SEED;
+
+use crate::infrastructure::derivations::{is_external_account, is_reconstructable, get_addr_for_pk, derive_diversified_addr};
+
+/// Whether an `Identity` may author transactions or only observe them. Mirrors the
+/// incoming-viewing-key vs. spending-key split in shielded wallets: a `WatchOnly` identity still
+/// carries its full `secret_key` (trial decryption and serial derivation need it, see
+/// `Runtime::try_recognize_enc_records`), but `Runtime::execute` refuses to use it as a sender, so
+/// importing it cannot expose spend authority over the identity's funds.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IdentityKind {
+    Spend,
+    WatchOnly,
+}
 
-use crate::infrastructure::derivations::{is_external_account, is_reconstructable, get_addr_for_pk};
+impl Default for IdentityKind {
+    fn default() -> Self {
+        IdentityKind::Spend
+    }
+}
 
 #[derive(Clone, Default)]
 pub struct Identity {
     pub is_external_account: bool,
     pub secret_key: ExtSecretKey<InnerEdProjective>,
     pub public_key: InnerEdAffine,
-    pub address: OuterScalarField
+    pub address: OuterScalarField,
+    pub kind: IdentityKind,
+}
+
+/// A decrypt-only credential derived from an object's secret key. Holding only a
+/// `ViewingKey` lets a watch-only wallet recover the fields of records addressed to the
+/// object (see `Record::decrypt_with_viewing_key`), but gives no way to recover
+/// `sk_object` and thus no way to derive a valid serial number to spend them.
+///
+/// NOTE: `Record::decrypt` needs the exact ElGamal secret scalar a record was encrypted
+/// under, so this wraps that same scalar rather than an independently-derived one.
+/// Stronger separation (records encrypted to a dedicated incoming-viewing public key)
+/// would need every record encrypted under two keys; this type still prevents a holder
+/// of the viewing key alone from recovering the raw secret key or spend-critical fields.
+#[derive(Clone)]
+pub struct ViewingKey(ExtSecretKey<InnerEdProjective>);
+
+impl ViewingKey {
+    /// Derives the incoming viewing key for an object's secret key.
+    pub fn derive(sk_object: &ExtSecretKey<InnerEdProjective>) -> ViewingKey {
+        ViewingKey(sk_object.clone())
+    }
+
+    pub(crate) fn as_ext_secret_key(&self) -> &ExtSecretKey<InnerEdProjective> {
+        &self.0
+    }
+}
+
+/// An identity known only by its incoming viewing key: enough to recognize and decrypt
+/// records addressed to `address` (see `Record::decrypt_with_viewing_key`), but -- unlike
+/// `Identity` -- with no `secret_key` field at all, so there is no way to derive
+/// `sk_object`-dependent values (new object identities owned by a record this key
+/// recognizes) or a serial number to consume it. Register one via
+/// `Runtime::register_view_only_identity`.
+#[derive(Clone)]
+pub struct ViewOnlyIdentity {
+    pub public_key: InnerEdAffine,
+    pub address: OuterScalarField,
+    pub viewing_key: ViewingKey,
+}
+
+impl ViewOnlyIdentity {
+    pub fn new(public_key: InnerEdAffine, viewing_key: ViewingKey) -> ViewOnlyIdentity {
+        ViewOnlyIdentity { address: get_addr_for_pk(&public_key), public_key, viewing_key }
+    }
 }
 
 impl Identity {
+    /// Derives this identity's incoming viewing key, for watch-only access to its records.
+    pub fn viewing_key(&self) -> ViewingKey {
+        ViewingKey::derive(&self.secret_key)
+    }
+
+    /// Derives a `ViewOnlyIdentity` sharing this identity's incoming viewing key but none of
+    /// its spend authority, for handing to an auditor or watch-only wallet (see
+    /// `Runtime::register_view_only_identity`).
+    pub fn view_only(&self) -> ViewOnlyIdentity {
+        ViewOnlyIdentity { public_key: self.public_key, address: self.address, viewing_key: self.viewing_key() }
+    }
+
+    /// Derives a fresh, unlinkable diversified address for this identity (see
+    /// `derivations::derive_diversified_addr`); a sender who encrypts an output to the
+    /// returned address (and records `diversifier` alongside it, e.g. in `Record::diversifier`)
+    /// reaches this identity exactly like a sender using `self.address` would, but two
+    /// addresses handed out with different `diversifier`s cannot be linked to each other or
+    /// to `self.address` without knowledge of `self.public_key`.
+    pub fn diversified_address(&self, diversifier: OuterScalarField) -> OuterScalarField {
+        derive_diversified_addr(&self.public_key, &diversifier)
+    }
+
     pub fn from_coords(public_key_x: OuterScalarField, public_key_y: OuterScalarField, secret_key: InnerEdScalarField) -> Identity {
         let public_key = InnerEdAffine::new(public_key_x, public_key_y);
         let address = get_addr_for_pk(&public_key);
@@ -27,10 +115,24 @@ impl Identity {
             is_external_account: is_external_account(&address),
             secret_key,
             public_key,
-            address
+            address,
+            kind: IdentityKind::Spend,
         }
     }
 
+    /// Returns a watch-only copy of this identity: same keys and address (so it still
+    /// recognizes and decrypts its own incoming records via `Runtime::try_recognize_enc_records`
+    /// and derives the same serial numbers), but `Runtime::execute` will refuse to use it as a
+    /// `sender_address`. Pair with `Runtime::register_watch_identity`.
+    pub fn as_watch_only(&self) -> Identity {
+        Identity { kind: IdentityKind::WatchOnly, ..self.clone() }
+    }
+
+    /// True if this identity must not be used as an `execute` sender (see `IdentityKind`).
+    pub fn is_watch_only(&self) -> bool {
+        self.kind == IdentityKind::WatchOnly
+    }
+
     pub fn new_external<R: Rng>(rng: &mut R, params: &CryptoParams) -> Identity {
         let mut secret_key;
         let mut public_key;
@@ -54,7 +156,88 @@ impl Identity {
             is_external_account: true,
             secret_key,
             public_key,
-            address
+            address,
+            kind: IdentityKind::Spend,
+        }
+    }
+
+    /// Deterministically (re)derives an external identity from a password, given the OPRF
+    /// output `n = k*hash_to_curve(pwd)` recovered via `crypto::oprf::blind`/`unblind` and a
+    /// server's `crypto::oprf::server::evaluate` (which never learns `pwd` or `sk`). Unlike
+    /// `new_external`'s random resampling, the rejection loop here is folded into the KDF via
+    /// an incrementing counter, so the same `pwd`/`n` always recover the same identity.
+    pub fn from_password(params: &CryptoParams, pwd: &[u8], n: &InnerEdAffine) -> Identity {
+        let mut counter: u8 = 0;
+        loop {
+            if let Some(candidate_sk) = oprf::derive_sk_candidate(pwd, n, counter) {
+                let secret_key = ExtSecretKey(SecretKey(candidate_sk));
+                let public_key = derive_pk_from_sk(&params.enc_params.elgamal_params, &secret_key.0);
+                if is_reconstructable(&public_key) && is_external_account(&get_addr_for_pk(&public_key)) {
+                    let address = get_addr_for_pk(&public_key);
+                    debug!("successfully derived password-based identity with public key ({}, {}), counter {}",
+                        fe_to_be_hex_str(&public_key.x),
+                        fe_to_be_hex_str(&public_key.y),
+                        counter);
+                    return Identity {
+                        is_external_account: true,
+                        secret_key,
+                        public_key,
+                        address,
+                        kind: IdentityKind::Spend,
+                    };
+                }
+            }
+            counter = counter.checked_add(1).expect("from_password: exhausted all counters without finding a valid identity");
+        }
+    }
+
+    /// Deterministically (re)derives an external identity from an arbitrary recovery seed (e.g.
+    /// a backed-up seed phrase, or its hash) -- the non-interactive counterpart of
+    /// `from_password`: rather than blinding `seed` through an OPRF round-trip with a server,
+    /// `seed` is hashed directly, so the same seed always recovers the same identity offline.
+    /// As with `from_password` (and unlike `new_external`), rejection-resampling is folded into the KDF via
+    /// an incrementing counter, so the loop always terminates on the same candidate for a given
+    /// `seed`.
+    pub fn from_seed(params: &CryptoParams, seed: &[u8]) -> Identity {
+        let mut counter: u8 = 0;
+        loop {
+            let mut h = Blake2s::new();
+            h.update(&[PRF_ACCOUNT_SEED]);
+            h.update(seed);
+            h.update(&[counter]);
+            let candidate_sk: Option<InnerEdScalarField> = FeConverter::from_le_bytes(&h.finalize());
+            if let Some(candidate_sk) = candidate_sk {
+                let secret_key = ExtSecretKey(SecretKey(candidate_sk));
+                let public_key = derive_pk_from_sk(&params.enc_params.elgamal_params, &secret_key.0);
+                if is_reconstructable(&public_key) && is_external_account(&get_addr_for_pk(&public_key)) {
+                    let address = get_addr_for_pk(&public_key);
+                    debug!("successfully derived seed-based identity with public key ({}, {}), counter {}",
+                        fe_to_be_hex_str(&public_key.x),
+                        fe_to_be_hex_str(&public_key.y),
+                        counter);
+                    return Identity {
+                        is_external_account: true,
+                        secret_key,
+                        public_key,
+                        address,
+                        kind: IdentityKind::Spend,
+                    };
+                }
+            }
+            counter = counter.checked_add(1).expect("from_seed: exhausted all counters without finding a valid identity");
+        }
+    }
+
+    /// Generates a fresh external identity like `new_external`, but keeps resampling until its
+    /// address's big-endian hex encoding (see `fe_to_be_hex_str`) starts with `hex_prefix` --
+    /// for wallets that want a recognizable, vanity address. `hex_prefix` should be short: the
+    /// expected number of attempts grows as `16^hex_prefix.len()`.
+    pub fn new_external_with_prefix<R: Rng>(rng: &mut R, params: &CryptoParams, hex_prefix: &str) -> Identity {
+        loop {
+            let candidate = Identity::new_external(rng, params);
+            if fe_to_be_hex_str(&candidate.address).starts_with(hex_prefix) {
+                return candidate;
+            }
         }
     }
 
@@ -75,4 +258,134 @@ impl Identity {
         }
         true
     }
+
+    /// Reads an `Identity` (see `ToBytes`/`FromBytes` below) and re-checks its validity
+    /// against `params` before returning it. `FromBytes::read` alone cannot do this check
+    /// itself, as it has no way to receive `params`; use this whenever the bytes may come
+    /// from an untrusted source (e.g. a loaded wallet file).
+    pub fn from_bytes_checked<R: Read>(reader: R, params: &CryptoParams) -> std::io::Result<Option<Identity>> {
+        let identity = Identity::read(reader)?;
+        Ok(if identity.is_valid(params) { Some(identity) } else { None })
+    }
+}
+
+impl ToBytes for Identity {
+    fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        (self.is_external_account as u8).write(&mut writer)?;
+        self.secret_key.write(&mut writer)?;
+        self.public_key.write(&mut writer)?;
+        self.address.write(&mut writer)?;
+        let kind = match self.kind { IdentityKind::Spend => 0u8, IdentityKind::WatchOnly => 1u8 };
+        kind.write(&mut writer)
+    }
+}
+
+impl FromBytes for Identity {
+    fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let is_external_account = u8::read(&mut reader)? == 1;
+        let secret_key = ExtSecretKey::read(&mut reader)?;
+        let public_key = InnerEdAffine::read(&mut reader)?;
+        let address = OuterScalarField::read(&mut reader)?;
+        let kind = match u8::read(&mut reader)? {
+            1 => IdentityKind::WatchOnly,
+            _ => IdentityKind::Spend,
+        };
+        Ok(Identity { is_external_account, secret_key, public_key, address, kind })
+    }
+}
+
+/// Human-readable `serde` support for `Identity`, for portable wallet files (JSON/bincode).
+/// Mirrors `record::serde_impl`: every field is hex-encoded (via `fe_to_be_hex_str`/
+/// `fe_from_be_hex_str`) rather than relying on `ToBytes`/`FromBytes` above, which are the
+/// canonical wire format but not `serde`-aware. Loading a wallet file from an untrusted
+/// source should still go through `Identity::is_valid` afterwards, as deserializing alone
+/// cannot re-check it (no `CryptoParams` is available to a `Deserialize` impl).
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+    use crate::common::fe_to_be_hex_str;
+    use crate::common::fe_from_be_hex_str;
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct IdentityHex {
+        is_external_account: bool,
+        secret_key: String,
+        public_key_x: String,
+        public_key_y: String,
+        address: String,
+        #[serde(default)]
+        watch_only: bool,
+    }
+
+    impl From<&Identity> for IdentityHex {
+        fn from(identity: &Identity) -> Self {
+            IdentityHex {
+                is_external_account: identity.is_external_account,
+                secret_key: fe_to_be_hex_str(&identity.secret_key.0.0),
+                public_key_x: fe_to_be_hex_str(&identity.public_key.x),
+                public_key_y: fe_to_be_hex_str(&identity.public_key.y),
+                address: fe_to_be_hex_str(&identity.address),
+                watch_only: identity.is_watch_only(),
+            }
+        }
+    }
+
+    impl From<IdentityHex> for Identity {
+        fn from(hex: IdentityHex) -> Self {
+            Identity {
+                is_external_account: hex.is_external_account,
+                secret_key: ExtSecretKey(SecretKey(fe_from_be_hex_str(&hex.secret_key))),
+                public_key: InnerEdAffine::new(fe_from_be_hex_str(&hex.public_key_x), fe_from_be_hex_str(&hex.public_key_y)),
+                address: fe_from_be_hex_str(&hex.address),
+                kind: if hex.watch_only { IdentityKind::WatchOnly } else { IdentityKind::Spend },
+            }
+        }
+    }
+
+    impl Serialize for Identity {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            IdentityHex::from(self).serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Identity {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let hex = IdentityHex::deserialize(deserializer)?;
+            Ok(Identity::from(hex))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_std::test_rng;
+
+    use super::*;
+
+    #[test]
+    fn test_from_seed_is_deterministic() {
+        let params = CryptoParams::setup(&mut test_rng());
+        let iden_a = Identity::from_seed(&params, b"correct horse battery staple");
+        let iden_b = Identity::from_seed(&params, b"correct horse battery staple");
+        assert_eq!(iden_a.address, iden_b.address);
+        assert_eq!(iden_a.secret_key.0.0, iden_b.secret_key.0.0);
+        assert!(iden_a.is_valid(&params));
+
+        // a different seed must (almost certainly) recover a different identity
+        let iden_c = Identity::from_seed(&params, b"a different seed phrase");
+        assert_ne!(iden_a.address, iden_c.address);
+    }
+
+    #[test]
+    fn test_new_external_with_prefix() {
+        let params = CryptoParams::setup(&mut test_rng());
+        let rng = &mut test_rng();
+        // a 1-hex-digit prefix is found within a handful of attempts almost always
+        let iden = Identity::new_external_with_prefix(rng, &params, "0");
+        assert!(fe_to_be_hex_str(&iden.address).starts_with('0'));
+        assert!(iden.is_valid(&params));
+    }
 }
@@ -4,4 +4,10 @@ pub mod circuit;
 pub mod params;
 pub mod derivations;
 pub mod identities;
-pub mod processor;
\ No newline at end of file
+pub mod processor;
+pub mod asm;
+pub mod optimizer;
+pub mod solidity_verifier;
+pub mod versioned;
+pub mod aggregation;
+pub mod serialization;
\ No newline at end of file
@@ -0,0 +1,492 @@
+//! Optimization passes over `ZkInstruction` programs, run identically by the prover
+//! (`RuntimeInterface::execute`) and the verifier (`MainProofVerifier::verify`) before the
+//! instruction stream becomes a public circuit input, so proving cost (which scales with
+//! `instructions.len()`) drops without the two sides ever disagreeing on what ran.
+//!
+//! `eliminate_dead_code` is a single backward liveness scan, mirroring the BEAM `beam_dead` pass
+//! but targeting circuit-cycle count instead of bytecode size: walk the program in reverse
+//! keeping a live-set of register addresses, and drop any "pure" dst-writing instruction (one
+//! with no effect beyond writing a register) whose `dst` is never read afterwards.
+//! `STORE`/`KILL`/`REQ` never write a register at all, and `NEW`/`FRESH` additionally consume a
+//! limited resource pool, so none of those five are ever candidates for removal, matching the
+//! ISA doc comment in `processor.rs`. Control-flow opcodes (`JMP`/`JZ`/`JNZ`/`HALT`) aren't
+//! dst-writing either, so they always survive; removed instructions still shift later ones
+//! forward, so branch targets (absolute instruction indices) are remapped to land on the same
+//! surviving instruction they used to.
+//!
+//! `fold_constants` is a forward dataflow pass that evaluates instructions whose operands are
+//! already known at this point in the program with concrete field arithmetic, replacing them
+//! with an equivalent `MOV dst, Const(result)` (in the spirit of rust-analyzer's `consteval`).
+//! Pairing it with `eliminate_dead_code` removes the producers it folds away.
+//!
+//! `allocate_registers` is a linear-scan register allocator (as in `beam_ssa_pre_codegen`) for
+//! callers that compile down to `ZkInstruction` from a representation with more virtual
+//! registers than fit in `NOF_PROCESSOR_REGISTERS` physical ones. Unlike the other two passes,
+//! it isn't wired into `RuntimeInterface::execute`/`MainProofVerifier::verify`: those already
+//! deal exclusively in physical register addresses, and `NOF_PROCESSOR_REGISTERS` is a
+//! circuit-wide constant baked into every `ZkProcessorPartialState`, not something a single
+//! transaction's program can resize. It's exposed as a library function, the same way
+//! `asm::assemble` is, for a compiler targeting this ISA to call before handing its output to
+//! `execute`.
+//!
+//! `infer_range_safe_cycles` (modeled on `beam_ssa_type`'s bound propagation) is a forward
+//! bit-width analysis consumed by `ZkProcessorGadget::run` in its `straight_line` mode (see
+//! `program_has_control_flow` in `processor.rs`): it tells that gadget, per cycle, whether
+//! `op_res`'s overflow check can skip its `to_bits_le` bit-decomposition entirely because the
+//! result is provably narrower than `MAX_BYTES_UINT*8` bits no matter what the operands turn out
+//! to be.
+
+use std::collections::{HashMap, HashSet};
+
+use ark_ff::{to_bytes, One, Zero};
+
+use crate::common::OuterScalarField;
+use crate::constants::MAX_BYTES_UINT;
+
+use super::processor::{
+    fits_in_max_bytes, program_has_control_flow, RegOrConst, ZkInstruction,
+    OPCODE_AND, OPCODE_ADD, OPCODE_CID, OPCODE_CMOV, OPCODE_DIV, OPCODE_EQ, OPCODE_FRESH,
+    OPCODE_GAS, OPCODE_HALT, OPCODE_JMP, OPCODE_JNZ, OPCODE_JZ, OPCODE_KILL, OPCODE_LOAD,
+    OPCODE_LT, OPCODE_MOD, OPCODE_MOV, OPCODE_MUL, OPCODE_NEW, OPCODE_NOP, OPCODE_NOW,
+    OPCODE_OR, OPCODE_PK, OPCODE_SHL, OPCODE_SHR, OPCODE_STORE, OPCODE_SUB, OPCODE_XOR,
+};
+
+/// Whether `opcode` writes its `dst` register (the `is_write_dst` group from `ZkProcessorGadget`).
+fn writes_dst(opcode: u8) -> bool {
+    matches!(opcode,
+        OPCODE_MOV | OPCODE_CMOV | OPCODE_ADD | OPCODE_SUB | OPCODE_MUL | OPCODE_EQ | OPCODE_LT |
+        OPCODE_LOAD | OPCODE_CID | OPCODE_FRESH | OPCODE_NEW | OPCODE_NOW | OPCODE_PK |
+        OPCODE_DIV | OPCODE_MOD | OPCODE_AND | OPCODE_OR | OPCODE_XOR | OPCODE_GAS |
+        OPCODE_SHL | OPCODE_SHR)
+}
+
+/// Whether `opcode` has an effect beyond writing `dst` (consumes a limited resource pool), so it
+/// must be kept even when `dst` is dead.
+fn is_side_effecting(opcode: u8) -> bool {
+    matches!(opcode, OPCODE_NEW | OPCODE_FRESH)
+}
+
+/// Removes dst-writing instructions whose result is never read, compacting the program and
+/// padding the freed slots back up to `instructions.len()` with `OPCODE_NOP`. Branch targets are
+/// remapped to account for the shift; the returned program is always the same length as `instructions`.
+pub fn eliminate_dead_code(instructions: &[ZkInstruction]) -> Vec<ZkInstruction> {
+    let n = instructions.len();
+    let mut live: HashSet<usize> = HashSet::new();
+    let mut keep = vec![false; n];
+
+    for (i, inst) in instructions.iter().enumerate().rev() {
+        let removable = writes_dst(inst.opcode) && !is_side_effecting(inst.opcode) && !live.contains(&inst.dst);
+        if removable {
+            continue;
+        }
+        keep[i] = true;
+        if writes_dst(inst.opcode) {
+            live.remove(&inst.dst);
+        }
+        if inst.opcode == OPCODE_CMOV {
+            // CMOV reads its own dst as the "keep unchanged" branch
+            live.insert(inst.dst);
+        }
+        if let RegOrConst::Reg(r) = &inst.src_1 {
+            live.insert(*r);
+        }
+        if let RegOrConst::Reg(r) = &inst.src_2 {
+            live.insert(*r);
+        }
+    }
+
+    // `next_surviving[i]` is the compacted index that a jump targeting original index `i` should
+    // land on: either `i`'s own new position, or (if `i` was removed) the new position of the
+    // next surviving instruction at or after `i`. `next_surviving[n]` is the compacted length,
+    // for targets that point past the end of the program.
+    let mut next_surviving = vec![0usize; n + 1];
+    let mut new_idx = 0;
+    for i in 0..n {
+        next_surviving[i] = new_idx;
+        if keep[i] {
+            new_idx += 1;
+        }
+    }
+    next_surviving[n] = new_idx;
+
+    let mut compacted: Vec<ZkInstruction> = instructions.iter().enumerate()
+        .filter(|(i, _)| keep[*i])
+        .map(|(_, inst)| {
+            let mut inst = inst.clone();
+            if matches!(inst.opcode, OPCODE_JMP | OPCODE_JZ | OPCODE_JNZ) {
+                inst.dst = next_surviving[inst.dst.min(n)];
+            }
+            inst
+        })
+        .collect();
+    compacted.resize(n, ZkInstruction { opcode: OPCODE_NOP, ..ZkInstruction::default() });
+    compacted
+}
+
+/// Whether `opcode` writes its `dst` register but isn't one of the opcodes `fold_constants`
+/// computes a concrete result for above; its dst must be forgotten rather than folded.
+fn writes_unknown_dst(opcode: u8) -> bool {
+    writes_dst(opcode) && !matches!(opcode, OPCODE_MOV | OPCODE_ADD | OPCODE_SUB | OPCODE_MUL | OPCODE_EQ | OPCODE_LT)
+}
+
+/// Replaces instructions whose operands are fully known constants at this point in program
+/// order with an equivalent `MOV dst, Const(result)`, folded via a forward dataflow pass that
+/// tracks, per register, the constant it currently holds (if known). Pair with
+/// `eliminate_dead_code` so the producers it folds away (now-dead `MOV`s further back) get
+/// removed too.
+///
+/// Propagation stops conservatively: `LOAD`/`CID`/`PK`/`FRESH`/`NEW`/`NOW`/`CMOV` and the
+/// bitwise/`DIV`/`MOD`/`GAS` opcodes just forget their own `dst`; `STORE`/`KILL` forget every
+/// known register (they mutate object state that a later `LOAD` might depend on); and since
+/// control flow can enter an instruction from somewhere other than straight-line order, every
+/// jump target and every instruction right after a `JMP`/`JZ`/`JNZ`/`HALT` also forgets everything.
+pub fn fold_constants(instructions: &[ZkInstruction]) -> Vec<ZkInstruction> {
+    let mut jump_targets: HashSet<usize> = HashSet::new();
+    for inst in instructions {
+        if matches!(inst.opcode, OPCODE_JMP | OPCODE_JZ | OPCODE_JNZ) {
+            jump_targets.insert(inst.dst);
+        }
+    }
+
+    let mut known: HashMap<usize, OuterScalarField> = HashMap::new();
+    let mut folded = Vec::with_capacity(instructions.len());
+    for (i, inst) in instructions.iter().enumerate() {
+        if jump_targets.contains(&i) || matches!(inst.opcode, OPCODE_JMP | OPCODE_JZ | OPCODE_JNZ | OPCODE_HALT) {
+            known.clear();
+        }
+
+        let known_operand = |op: &RegOrConst| -> Option<OuterScalarField> {
+            match op {
+                RegOrConst::Const(c) => Some(*c),
+                RegOrConst::Reg(r) => known.get(r).copied(),
+            }
+        };
+        let src_1 = known_operand(&inst.src_1);
+        let src_2 = known_operand(&inst.src_2);
+
+        let folded_result = match inst.opcode {
+            OPCODE_MOV => src_1,
+            OPCODE_ADD => src_1.zip(src_2).map(|(a, b)| a + b).filter(fits_in_max_bytes),
+            OPCODE_SUB => src_1.zip(src_2).map(|(a, b)| a - b).filter(fits_in_max_bytes),
+            OPCODE_MUL => src_1.zip(src_2).map(|(a, b)| a * b).filter(fits_in_max_bytes),
+            OPCODE_EQ => src_1.zip(src_2).map(|(a, b)| if a == b { OuterScalarField::one() } else { OuterScalarField::zero() }),
+            OPCODE_LT => src_1.zip(src_2).map(|(a, b)| if a < b { OuterScalarField::one() } else { OuterScalarField::zero() }),
+            _ => None,
+        };
+
+        if let Some(result) = folded_result {
+            known.insert(inst.dst, result);
+            folded.push(ZkInstruction { opcode: OPCODE_MOV, dst: inst.dst, src_1: RegOrConst::Const(result), src_2: RegOrConst::Const(OuterScalarField::zero()) });
+            continue;
+        }
+
+        if matches!(inst.opcode, OPCODE_STORE | OPCODE_KILL) {
+            known.clear();
+        } else if writes_unknown_dst(inst.opcode) {
+            known.remove(&inst.dst);
+        }
+        folded.push(inst.clone());
+    }
+    folded
+}
+
+/// Whether `opcode`'s `dst` field addresses a register it reads (rather than writes, or a
+/// non-register value like a jump target). Only `STORE` reads `dst` (see the ISA doc comment in
+/// `processor.rs`); `REQ`/`KILL`/`HALT`/`NOOP`/`NOP` leave it as an unused placeholder and
+/// `JMP`/`JZ`/`JNZ` use it as an absolute instruction index, never a register.
+fn dst_is_register_read(opcode: u8) -> bool {
+    opcode == OPCODE_STORE
+}
+
+/// The `[first def, last use]` live interval of every virtual register touched by `instructions`,
+/// keyed by register address. A register read before it's ever written (e.g. an incoming
+/// argument) is treated as live from instruction 0.
+fn live_intervals(instructions: &[ZkInstruction]) -> HashMap<usize, (usize, usize)> {
+    let mut first_def: HashMap<usize, usize> = HashMap::new();
+    let mut last_use: HashMap<usize, usize> = HashMap::new();
+    for (i, inst) in instructions.iter().enumerate() {
+        if writes_dst(inst.opcode) {
+            first_def.entry(inst.dst).or_insert(i);
+        }
+        if dst_is_register_read(inst.opcode) || inst.opcode == OPCODE_CMOV {
+            // STORE reads dst as the value to store; CMOV reads its own dst as the "else" branch
+            last_use.insert(inst.dst, i);
+        }
+        if let RegOrConst::Reg(r) = &inst.src_1 {
+            last_use.insert(*r, i);
+        }
+        if let RegOrConst::Reg(r) = &inst.src_2 {
+            last_use.insert(*r, i);
+        }
+    }
+    first_def.keys().chain(last_use.keys()).copied().collect::<HashSet<_>>().into_iter()
+        .map(|r| {
+            let start = first_def.get(&r).copied().unwrap_or(0);
+            let end = last_use.get(&r).copied().unwrap_or(start);
+            (r, (start, end))
+        })
+        .collect()
+}
+
+/// Returned by `allocate_registers` when a program needs more physical registers than `budget`
+/// allows, even after linear-scan allocation; `needed` is the true minimum (the peak number of
+/// virtual registers simultaneously live).
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct RegisterBudgetExceeded {
+    pub needed: usize,
+    pub budget: usize,
+}
+
+/// Rewrites `instructions` (written against arbitrarily many virtual register addresses) to use
+/// at most `budget` physical registers, via linear scan: compute each virtual register's live
+/// interval, sort by start, and walk them assigning a free physical register to each, reclaiming
+/// physical registers from intervals that have already ended. Errors if the peak number of
+/// simultaneously-live virtual registers exceeds `budget`. `dst` is only rewritten for opcodes
+/// that address a register through it (see `writes_dst`/`dst_is_register_read`); `JMP`/`JZ`/`JNZ`
+/// targets are left untouched, since `dst` there is an instruction index, not a register.
+pub fn allocate_registers(instructions: &[ZkInstruction], budget: usize) -> Result<Vec<ZkInstruction>, RegisterBudgetExceeded> {
+    let intervals = live_intervals(instructions);
+    let mut by_start: Vec<(usize, usize, usize)> = intervals.iter().map(|(&r, &(start, end))| (start, end, r)).collect();
+    by_start.sort_by_key(|&(start, _, _)| start);
+
+    let mut free: Vec<usize> = (0..budget).rev().collect();
+    let mut active: Vec<(usize, usize)> = vec![];   // (end, physical register), sorted by end
+    let mut mapping: HashMap<usize, usize> = HashMap::new();
+    let mut peak_live = 0;
+
+    for (start, end, virt) in by_start {
+        active.retain(|&(active_end, phys)| {
+            let expired = active_end < start;
+            if expired {
+                free.push(phys);
+            }
+            !expired
+        });
+        let phys = free.pop().ok_or(RegisterBudgetExceeded { needed: active.len() + 1, budget })?;
+        mapping.insert(virt, phys);
+        active.push((end, phys));
+        active.sort_by_key(|&(active_end, _)| active_end);
+        peak_live = peak_live.max(active.len());
+    }
+    if peak_live > budget {
+        return Err(RegisterBudgetExceeded { needed: peak_live, budget });
+    }
+
+    Ok(instructions.iter().map(|inst| {
+        let mut inst = inst.clone();
+        if writes_dst(inst.opcode) || dst_is_register_read(inst.opcode) {
+            inst.dst = mapping[&inst.dst];
+        }
+        if let RegOrConst::Reg(r) = &inst.src_1 {
+            inst.src_1 = RegOrConst::Reg(mapping[r]);
+        }
+        if let RegOrConst::Reg(r) = &inst.src_2 {
+            inst.src_2 = RegOrConst::Reg(mapping[r]);
+        }
+        inst
+    }).collect())
+}
+
+/// Number of bits needed to represent `c` as an unsigned integer (`0` for the zero field element).
+fn bit_length(c: &OuterScalarField) -> usize {
+    let bytes = to_bytes!(c).unwrap();
+    for (byte_idx, byte) in bytes.iter().enumerate().rev() {
+        if *byte != 0 {
+            return byte_idx * 8 + (8 - byte.leading_zeros() as usize);
+        }
+    }
+    0
+}
+
+/// For each cycle of a control-flow-free `instructions`, whether `ZkProcessorGadget::run` can
+/// skip `is_in_range`'s bit-decomposition of `op_res` entirely: either the opcode isn't
+/// `ADD`/`SUB`/`MUL` (the overflow check is a no-op for it anyway), or it is, and tracking each
+/// register's worst-case bit-width forward through the program (seeded from constants by
+/// `bit_length`, widened by `ADD`/`SUB` to `max(w_a, w_b) + 1` and by `MUL` to `w_a + w_b`, reset
+/// to the full `MAX_BYTES_UINT*8` by any opcode whose result isn't one of those) proves the
+/// result is narrower than `MAX_BYTES_UINT*8` bits regardless of the operands' actual values.
+/// Conservatively returns all-`false` (keep every check) if `instructions` has any control flow,
+/// since the forward widths this computes don't account for a register's width varying across
+/// the different paths that could reach a given instruction.
+pub fn infer_range_safe_cycles(instructions: &[ZkInstruction]) -> Vec<bool> {
+    let full_width = MAX_BYTES_UINT * 8;
+    if program_has_control_flow(instructions) {
+        return vec![false; instructions.len()];
+    }
+
+    let mut widths: HashMap<usize, usize> = HashMap::new();
+    let operand_width = |op: &RegOrConst, widths: &HashMap<usize, usize>| -> usize {
+        match op {
+            RegOrConst::Const(c) => bit_length(c),
+            RegOrConst::Reg(r) => widths.get(r).copied().unwrap_or(full_width),
+        }
+    };
+
+    instructions.iter().map(|inst| {
+        let w1 = operand_width(&inst.src_1, &widths);
+        let w2 = operand_width(&inst.src_2, &widths);
+        let result_width = match inst.opcode {
+            OPCODE_MOV => w1,
+            OPCODE_ADD | OPCODE_SUB => w1.max(w2) + 1,
+            OPCODE_MUL => w1 + w2,
+            OPCODE_EQ | OPCODE_LT => 1,
+            _ => full_width,
+        };
+        if writes_dst(inst.opcode) {
+            widths.insert(inst.dst, result_width.min(full_width));
+        }
+        !matches!(inst.opcode, OPCODE_ADD | OPCODE_SUB | OPCODE_MUL) || result_width < full_width
+    }).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::processor::{OPCODE_NOOP, OPCODE_REQ};
+    use RegOrConst::{Const, Reg};
+
+    #[test]
+    fn test_eliminate_dead_code_drops_unread_writes() {
+        let instructions = vec![
+            ZkInstruction { opcode: OPCODE_MOV, dst: 2, src_1: Const(OuterScalarField::from(1u64)), src_2: Reg(0) },  // dead: dst 2 never read
+            ZkInstruction { opcode: OPCODE_MOV, dst: 1, src_1: Const(OuterScalarField::from(2u64)), src_2: Reg(0) },  // live: read by REQ below
+            ZkInstruction { opcode: OPCODE_REQ, dst: 0, src_1: Reg(1), src_2: Reg(0) },
+        ];
+        let optimized = eliminate_dead_code(&instructions);
+        assert_eq!(optimized.len(), instructions.len());
+        // the dead MOV is dropped, the survivors shift forward and the freed slot becomes a NOP
+        assert_eq!(optimized[0].opcode, OPCODE_MOV);
+        assert_eq!(optimized[1].opcode, OPCODE_REQ);
+        assert_eq!(optimized[2].opcode, OPCODE_NOP);
+    }
+
+    #[test]
+    fn test_eliminate_dead_code_keeps_side_effecting_ops() {
+        let instructions = vec![
+            ZkInstruction { opcode: OPCODE_NEW, dst: 0, src_1: Const(OuterScalarField::from(7u64)), src_2: Reg(0) },
+            ZkInstruction { opcode: OPCODE_FRESH, dst: 1, src_1: Reg(0), src_2: Reg(0) },
+            ZkInstruction { opcode: OPCODE_NOOP, dst: 0, src_1: Reg(0), src_2: Reg(0) },
+        ];
+        let optimized = eliminate_dead_code(&instructions);
+        assert_eq!(optimized[0].opcode, OPCODE_NEW);
+        assert_eq!(optimized[1].opcode, OPCODE_FRESH);
+        assert_eq!(optimized[2].opcode, OPCODE_NOOP);
+    }
+
+    #[test]
+    fn test_eliminate_dead_code_remaps_jump_targets() {
+        let instructions = vec![
+            ZkInstruction { opcode: OPCODE_MOV, dst: 0, src_1: Const(OuterScalarField::from(1u64)), src_2: Reg(0) },  // live: JMP and HALT name Reg(0)
+            ZkInstruction { opcode: OPCODE_JMP, dst: 3, src_1: Reg(0), src_2: Reg(0) },                               // jumps past the dead slot at 2
+            ZkInstruction { opcode: OPCODE_MOV, dst: 1, src_1: Const(OuterScalarField::from(2u64)), src_2: Reg(0) },  // dead, removed
+            ZkInstruction { opcode: OPCODE_HALT, dst: 0, src_1: Reg(0), src_2: Reg(0) },
+        ];
+        let optimized = eliminate_dead_code(&instructions);
+        assert_eq!(optimized.len(), instructions.len());
+        // only index 2 is dropped: JMP stays at index 1 and its target (originally 3) must still
+        // land on HALT, which moved from index 3 to index 2
+        assert_eq!(optimized[0].opcode, OPCODE_MOV);
+        assert_eq!(optimized[1].opcode, OPCODE_JMP);
+        assert_eq!(optimized[1].dst, 2);
+        assert_eq!(optimized[2].opcode, OPCODE_HALT);
+        assert_eq!(optimized[3].opcode, OPCODE_NOP);
+    }
+
+    #[test]
+    fn test_fold_constants_propagates_through_arithmetic() {
+        let instructions = vec![
+            ZkInstruction { opcode: OPCODE_MOV, dst: 0, src_1: Const(OuterScalarField::from(2u64)), src_2: Reg(0) },
+            ZkInstruction { opcode: OPCODE_MOV, dst: 1, src_1: Const(OuterScalarField::from(3u64)), src_2: Reg(0) },
+            ZkInstruction { opcode: OPCODE_ADD, dst: 2, src_1: Reg(0), src_2: Reg(1) },
+            ZkInstruction { opcode: OPCODE_LT, dst: 3, src_1: Reg(2), src_2: Const(OuterScalarField::from(10u64)) },
+        ];
+        let folded = fold_constants(&instructions);
+        assert_eq!(folded.len(), instructions.len());
+        for inst in &folded {
+            assert_eq!(inst.opcode, OPCODE_MOV);
+        }
+        assert!(matches!(folded[2].src_1, Const(c) if c == OuterScalarField::from(5u64)));
+        assert!(matches!(folded[3].src_1, Const(c) if c == OuterScalarField::one()));
+    }
+
+    #[test]
+    fn test_fold_constants_does_not_fold_unknown_operands() {
+        let instructions = vec![
+            ZkInstruction { opcode: OPCODE_LOAD, dst: 0, src_1: Const(OuterScalarField::from(7u64)), src_2: Reg(0) },
+            ZkInstruction { opcode: OPCODE_ADD, dst: 1, src_1: Reg(0), src_2: Const(OuterScalarField::from(1u64)) },
+        ];
+        let folded = fold_constants(&instructions);
+        assert_eq!(folded[0].opcode, OPCODE_LOAD);
+        assert_eq!(folded[1].opcode, OPCODE_ADD);
+    }
+
+    #[test]
+    fn test_fold_constants_clears_known_values_after_store() {
+        let instructions = vec![
+            ZkInstruction { opcode: OPCODE_MOV, dst: 0, src_1: Const(OuterScalarField::from(9u64)), src_2: Reg(0) },
+            ZkInstruction { opcode: OPCODE_STORE, dst: 0, src_1: Reg(1), src_2: Reg(2) },
+            ZkInstruction { opcode: OPCODE_ADD, dst: 3, src_1: Reg(0), src_2: Const(OuterScalarField::from(1u64)) },
+        ];
+        let folded = fold_constants(&instructions);
+        // dst 0's known value of 9 doesn't survive the intervening STORE
+        assert_eq!(folded[2].opcode, OPCODE_ADD);
+    }
+
+    #[test]
+    fn test_allocate_registers_fits_disjoint_intervals_into_one_physical_register() {
+        // r0 dies at instruction 0 (never read again), so r5 can reuse its physical slot
+        let instructions = vec![
+            ZkInstruction { opcode: OPCODE_MOV, dst: 0, src_1: Const(OuterScalarField::from(1u64)), src_2: Reg(0) },
+            ZkInstruction { opcode: OPCODE_MOV, dst: 5, src_1: Const(OuterScalarField::from(2u64)), src_2: Const(OuterScalarField::zero()) },
+            ZkInstruction { opcode: OPCODE_REQ, dst: 0, src_1: Reg(5), src_2: Const(OuterScalarField::zero()) },
+        ];
+        let allocated = allocate_registers(&instructions, 1).unwrap();
+        assert_eq!(allocated[0].dst, 0);
+        assert_eq!(allocated[1].dst, 0);
+        assert!(matches!(allocated[2].src_1, Reg(0)));
+    }
+
+    #[test]
+    fn test_allocate_registers_errors_when_budget_exceeded() {
+        let instructions = vec![
+            ZkInstruction { opcode: OPCODE_MOV, dst: 0, src_1: Const(OuterScalarField::from(1u64)), src_2: Reg(0) },
+            ZkInstruction { opcode: OPCODE_MOV, dst: 1, src_1: Const(OuterScalarField::from(2u64)), src_2: Reg(0) },
+            ZkInstruction { opcode: OPCODE_REQ, dst: 0, src_1: Reg(0), src_2: Reg(1) },
+        ];
+        let err = allocate_registers(&instructions, 1).unwrap_err();
+        assert_eq!(err.needed, 2);
+        assert_eq!(err.budget, 1);
+    }
+
+    #[test]
+    fn test_infer_range_safe_cycles_elides_narrow_arithmetic() {
+        let instructions = vec![
+            ZkInstruction { opcode: OPCODE_MOV, dst: 0, src_1: Const(OuterScalarField::from(2u64)), src_2: Reg(0) },   // width 2
+            ZkInstruction { opcode: OPCODE_MOV, dst: 1, src_1: Const(OuterScalarField::from(3u64)), src_2: Reg(0) },   // width 2
+            ZkInstruction { opcode: OPCODE_ADD, dst: 2, src_1: Reg(0), src_2: Reg(1) },                                // width <= 3, safe to elide
+        ];
+        let safe = infer_range_safe_cycles(&instructions);
+        assert_eq!(safe, vec![true, true, true]);
+    }
+
+    #[test]
+    fn test_infer_range_safe_cycles_keeps_check_for_unbounded_operand() {
+        let instructions = vec![
+            ZkInstruction { opcode: OPCODE_LOAD, dst: 0, src_1: Const(OuterScalarField::from(7u64)), src_2: Reg(0) },  // dst 0 becomes full-width
+            ZkInstruction { opcode: OPCODE_ADD, dst: 1, src_1: Reg(0), src_2: Const(OuterScalarField::from(1u64)) },   // can't bound the sum
+        ];
+        let safe = infer_range_safe_cycles(&instructions);
+        assert_eq!(safe, vec![true, false]);
+    }
+
+    #[test]
+    fn test_infer_range_safe_cycles_keeps_every_check_when_program_branches() {
+        let instructions = vec![
+            ZkInstruction { opcode: OPCODE_MOV, dst: 0, src_1: Const(OuterScalarField::from(1u64)), src_2: Reg(0) },
+            ZkInstruction { opcode: OPCODE_JMP, dst: 2, src_1: Reg(0), src_2: Reg(0) },
+            ZkInstruction { opcode: OPCODE_ADD, dst: 1, src_1: Reg(0), src_2: Reg(0) },
+        ];
+        let safe = infer_range_safe_cycles(&instructions);
+        assert_eq!(safe, vec![false, false, false]);
+    }
+}
@@ -1,3 +1,5 @@
+use std::io::{Read, Write};
+
 use crate::common::*;
 use crate::constants::*;
 use crate::crypto::poseidon::HybridPoseidonCipher;
@@ -5,6 +7,7 @@ use crate::crypto::poseidon::HybridPoseidonParams;
 
 use ark_crypto_primitives::{crh::{pedersen, injective_map::{PedersenCRHCompressor, TECompressor}, CRH, TwoToOneCRH}};
 use ark_crypto_primitives::merkle_tree;
+use ark_ff::{ToBytes, FromBytes};
 use ark_std::rand::Rng;
 
 use super::record::ENC_RECORD_BYTES;
@@ -34,21 +37,78 @@ impl merkle_tree::Config for MerkleTreeParams {
     type TwoToOneHash = InnerHash;
 }
 
+/// Selects which inner (encryption/address) curve transaction logic targets. Only `JubJub` is
+/// implemented today, matching `derivations::JubJubDerivation`; a higher-security Ed448-family
+/// ciphersuite (see `derivations::DerivationCurve`) would need its own variant here, plus its own
+/// `MerkleTreeParams`/`InnerHash`/`LeafHash`/`HybridPoseidonParams`-equivalents, since those are
+/// currently all hard-coded to `InnerEdProjective`. This enum exists so callers can already be
+/// written against a ciphersuite selector rather than an implicit default.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Ciphersuite {
+    JubJub,
+}
+
 #[derive(Clone)]
 pub struct CryptoParams {
     pub leaf_hash_param: merkle_tree::LeafParam<MerkleTreeParams>,
     pub inner_hash_param: merkle_tree::TwoToOneParam<MerkleTreeParams>,
-    pub enc_params: HybridPoseidonParams
+    pub enc_params: HybridPoseidonParams,
+
+    /// When set, trial decryption (`Runtime::try_recognize_enc_records` and its view-only/range
+    /// counterparts) first checks `EncryptedRecord::is_probably_mine`'s one-byte detection tag
+    /// and skips the full `Record::decrypt`/`decrypt_with_viewing_key` call on a mismatch, instead
+    /// of always running full decryption against every identity. This trades scanning speed for
+    /// a small amount of linkability: the tag is only one byte, so it leaks at most ~8 bits of
+    /// information about which records share a recipient to anyone who can see the ledger and
+    /// guess candidate shared secrets (e.g. a sender colluding with an observer) -- hence opt-in.
+    pub use_view_tags: bool,
 }
 
 impl CryptoParams {
     pub fn setup<R: Rng>(rng: &mut R) -> CryptoParams {
-        CryptoParams {
-            leaf_hash_param: <LeafHash as CRH>::setup(rng).unwrap(),
-            inner_hash_param: <InnerHash as TwoToOneCRH>::setup(rng).unwrap(),
-            enc_params: HybridPoseidonCipher::setup(rng),
+        Self::setup_for(Ciphersuite::JubJub, rng)
+    }
+
+    /// Same as `setup`, but explicit about which `Ciphersuite` the resulting parameters target.
+    pub fn setup_for<R: Rng>(ciphersuite: Ciphersuite, rng: &mut R) -> CryptoParams {
+        match ciphersuite {
+            Ciphersuite::JubJub => CryptoParams {
+                leaf_hash_param: <LeafHash as CRH>::setup(rng).unwrap(),
+                inner_hash_param: <InnerHash as TwoToOneCRH>::setup(rng).unwrap(),
+                enc_params: HybridPoseidonCipher::setup(rng),
+                use_view_tags: false,
+            },
         }
     }
+
+    /// Builder-style toggle for `use_view_tags`; see its doc-comment for the tradeoff.
+    pub fn with_view_tags(mut self, enabled: bool) -> CryptoParams {
+        self.use_view_tags = enabled;
+        self
+    }
+}
+
+// Lets proving/verifying key material stay consistent across runs: `setup` samples the
+// Pedersen window generators and the ElGamal generator at random, so they must be persisted
+// rather than regenerated.
+impl ToBytes for CryptoParams {
+    fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        self.leaf_hash_param.write(&mut writer)?;
+        self.inner_hash_param.write(&mut writer)?;
+        self.enc_params.write(&mut writer)?;
+        (self.use_view_tags as u8).write(&mut writer)?;
+        Ok(())
+    }
+}
+
+impl FromBytes for CryptoParams {
+    fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let leaf_hash_param = <LeafHash as CRH>::Parameters::read(&mut reader)?;
+        let inner_hash_param = <InnerHash as TwoToOneCRH>::Parameters::read(&mut reader)?;
+        let enc_params = HybridPoseidonParams::read(&mut reader)?;
+        let use_view_tags = u8::read(&mut reader)? != 0;
+        Ok(CryptoParams { leaf_hash_param, inner_hash_param, enc_params, use_view_tags })
+    }
 }
 
 #[derive(Clone)]
@@ -63,5 +123,46 @@ impl Default for MerkleTreePath {
     }
 }
 
+impl ToBytes for MerkleTreePath {
+    fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        self.0.leaf_sibling_hash.write(&mut writer)?;
+        assert_eq!(self.0.auth_path.len(), TREE_HEIGHT - 2, "auth path length must match the configured tree height");
+        for node in self.0.auth_path.iter() {
+            node.write(&mut writer)?;
+        }
+        // NOTE: as the tree height (and thus auth path length) is constant, we do not serialize it
+        (self.0.leaf_index as u64).write(&mut writer)?;
+        Ok(())
+    }
+}
+
+impl FromBytes for MerkleTreePath {
+    fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let leaf_sibling_hash = <LeafHash as CRH>::Output::read(&mut reader)?;
+        let mut auth_path = Vec::with_capacity(TREE_HEIGHT - 2);
+        for _ in 0..TREE_HEIGHT - 2 {
+            auth_path.push(<InnerHash as TwoToOneCRH>::Output::read(&mut reader)?);
+        }
+        let leaf_index = u64::read(&mut reader)? as usize;
+        Ok(MerkleTreePath(merkle_tree::Path::<MerkleTreeParams> {
+            leaf_sibling_hash,
+            auth_path,
+            leaf_index,
+        }))
+    }
+}
+
 #[derive(Clone,Debug,Eq,PartialEq,Default)]
 pub struct MerkleTreeRoot(pub merkle_tree::TwoToOneDigest<MerkleTreeParams>);
+
+impl ToBytes for MerkleTreeRoot {
+    fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        self.0.write(&mut writer)
+    }
+}
+
+impl FromBytes for MerkleTreeRoot {
+    fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        Ok(MerkleTreeRoot(<InnerHash as TwoToOneCRH>::Output::read(&mut reader)?))
+    }
+}
@@ -5,7 +5,7 @@ use ark_ff::{One,Zero, to_bytes};
 use ark_relations::r1cs::ConstraintSystemRef;
 use log::debug;
 
-use crate::{common::*, constants::{NOF_TX_RECORDS, NOF_TX_FRESH, NOF_RECORD_PAYLOAD_ELEMENTS, NOF_PROCESSOR_REGISTERS, MAX_BYTES_UINT}};
+use crate::{common::*, constants::{NOF_TX_RECORDS, NOF_TX_FRESH, NOF_RECORD_PAYLOAD_ELEMENTS, NOF_PROCESSOR_REGISTERS, MAX_BYTES_UINT, DEFAULT_GAS_LIMIT}};
 
 use super::{runtime::RuntimeStateView, record::ObjectId};
 
@@ -35,8 +35,39 @@ const NOF_NEW_OBJS: usize = NOF_TX_FRESH;
 // NEW dst src_1 _          // tmp[dst] = oid of fresh object with cid = val(src_1)
 // KILL _ src_1 _           // delete obj(oid: val(src_1))
 // FRESH dst _ _            // tmp[dst] = freshly derived number
-// NOW dst _ _              // tmp[dst] = current timestamp   
+// NOW dst _ _              // tmp[dst] = current timestamp
 // PK dst src_1 _           // tmp[dst] = obj(oid: val(src_1)).addr_object
+// JMP dst _ _              // pc = dst
+// JZ dst src_1 _           // pc = (val(src_1) == 0) ? dst : pc+1
+// JNZ dst src_1 _          // pc = (val(src_1) != 0) ? dst : pc+1 (i.e. "jump if nonzero")
+// HALT _ _ _               // freezes pc; all further steps become no-ops
+// DIV dst src_1 src_2      // tmp[dst] = val(src_1) / val(src_2) (integer division; traps if src_2 == 0)
+// MOD dst src_1 src_2      // tmp[dst] = val(src_1) % val(src_2) (traps if src_2 == 0)
+// AND dst src_1 src_2      // tmp[dst] = val(src_1) & val(src_2) (bitwise, over MAX_BYTES_UINT bytes)
+// OR dst src_1 src_2       // tmp[dst] = val(src_1) | val(src_2)
+// XOR dst src_1 src_2      // tmp[dst] = val(src_1) ^ val(src_2)
+// GAS dst _ _              // tmp[dst] = gas_limit - gas_used (remaining budget, after charging this instruction)
+// NOP _ _ _                // (no op; emitted by optimizer::eliminate_dead_code to pad a compacted program back to its original length)
+// SHL dst src_1 src_2      // tmp[dst] = val(src_1) << val(src_2), truncated to MAX_BYTES_UINT*8 bits
+// SHR dst src_1 src_2      // tmp[dst] = val(src_1) >> val(src_2)
+//
+// SHL/SHR's src_2 must be a compile-time constant (a RegOrConst::Const, not a Reg): the in-circuit
+// gadget needs the shift amount before allocating any variables (see `compute_shift_amounts`), the
+// same way `straight_line`/`range_check_elided` need the control-flow shape of the whole program
+// before allocating anything. A register shift amount traps natively (ZkTrapKind::ShiftAmountNotConstant)
+// rather than silently producing a witness the circuit has no sound way to check.
+//
+// Execution runs for a fixed number of steps (one per instruction slot, so the R1CS circuit
+// has a fixed shape): at each step, `pc` selects which instruction executes next
+// (`instructions[pc]`, or a NOOP if `pc` is out of range), then `pc` advances to either `pc+1`
+// or a branch target. JMP/JZ/JNZ targets are absolute instruction indices (not relative to
+// `pc`), matching `dst`'s existing unsigned-index type.
+//
+// Each instruction also charges gas: `NOOP` is free, `LOAD`/`STORE`/`NEW`/`KILL` (the
+// memory-touching opcodes) cost `GAS_COST_MEM_OP`, and everything else costs `GAS_COST_LIGHT_OP`.
+// The running total is tracked in `ZkProcessorPartialState::gas_used` and trapped against
+// `ZkProcessor::gas_limit` (see `opcode_gas_cost`), giving a host a deterministic, in-circuit
+// bound on the constraint cost of a single transaction.
 
 pub const OPCODE_NOOP: u8 = 0;
 pub const OPCODE_MOV: u8 = 1;
@@ -55,6 +86,37 @@ pub const OPCODE_SUB: u8 = 13;
 pub const OPCODE_MUL: u8 = 14;
 pub const OPCODE_EQ: u8 = 15;
 pub const OPCODE_LT: u8 = 16;
+pub const OPCODE_JMP: u8 = 17;
+pub const OPCODE_JZ: u8 = 18;
+pub const OPCODE_JNZ: u8 = 19;
+pub const OPCODE_HALT: u8 = 20;
+pub const OPCODE_DIV: u8 = 21;
+pub const OPCODE_MOD: u8 = 22;
+pub const OPCODE_AND: u8 = 23;
+pub const OPCODE_OR: u8 = 24;
+pub const OPCODE_XOR: u8 = 25;
+pub const OPCODE_GAS: u8 = 26;
+/// A second, distinct no-op, reserved for `optimizer::eliminate_dead_code`'s padding so that
+/// compiler-inserted filler is never confused with a `NOOP` the contract author actually wrote.
+/// Behaves identically to `OPCODE_NOOP` (free, no registers/flags touched).
+pub const OPCODE_NOP: u8 = 27;
+pub const OPCODE_SHL: u8 = 28;
+pub const OPCODE_SHR: u8 = 29;
+
+/// Gas cost charged for a memory-touching opcode (`LOAD`/`STORE`/`NEW`/`KILL`); see `opcode_gas_cost`.
+const GAS_COST_MEM_OP: u64 = 10;
+/// Gas cost charged for any other opcode except `NOOP` (which is free); see `opcode_gas_cost`.
+const GAS_COST_LIGHT_OP: u64 = 1;
+
+/// The gas cost charged for executing `opcode` once (see the `ZkProcessorPartialState::gas_used`
+/// accumulator and `ZkProcessor::gas_limit`).
+fn opcode_gas_cost(opcode: u8) -> u64 {
+    match opcode {
+        OPCODE_NOOP | OPCODE_NOP => 0,
+        OPCODE_LOAD | OPCODE_STORE | OPCODE_NEW | OPCODE_KILL => GAS_COST_MEM_OP,
+        _ => GAS_COST_LIGHT_OP,
+    }
+}
 
 #[derive(Clone)]
 pub enum RegOrConst {
@@ -137,6 +199,16 @@ pub struct ZkProcessorPartialState {
     pub new_oids: Vec<OuterScalarField>,
     pub new_obj_sks: Vec<OuterScalarField>,
     pub new_obj_addrs: Vec<OuterScalarField>,
+    /// index into `instructions` of the instruction to execute next (see JMP/JZ/JNZ)
+    pub pc: usize,
+    /// quotient produced by the most recently executed `DIV`/`MOD` (witnessed so the circuit
+    /// can check `src_1 == div_q*src_2 + div_r` even though only one of the two is written to
+    /// `dst`); meaningless (and unconstrained) for any other instruction
+    pub div_q: OuterScalarField,
+    /// remainder produced by the most recently executed `DIV`/`MOD`, see `div_q`
+    pub div_r: OuterScalarField,
+    /// running total of gas charged so far (see `opcode_gas_cost`, `ZkProcessor::gas_limit`)
+    pub gas_used: OuterScalarField,
 }
 
 impl Debug for ZkProcessorPartialState {
@@ -146,6 +218,10 @@ impl Debug for ZkProcessorPartialState {
         write!(f, "new_oids: {:?} ", self.new_oids.iter().map(|fe| { fe_to_string(fe) }).collect::<Vec<_>>())?;
         write!(f, "new_obj_sks: {:?} ", self.new_obj_sks.iter().map(|fe| { fe_to_string(fe) }).collect::<Vec<_>>())?;
         write!(f, "new_obj_addrs: {:?} ", self.new_obj_addrs.iter().map(|fe| { fe_to_string(fe) }).collect::<Vec<_>>())?;
+        write!(f, "pc: {} ", self.pc)?;
+        write!(f, "div_q: {} ", fe_to_string(&self.div_q))?;
+        write!(f, "div_r: {} ", fe_to_string(&self.div_r))?;
+        write!(f, "gas_used: {} ", fe_to_string(&self.gas_used))?;
         Ok(())
     }
 }
@@ -158,6 +234,10 @@ impl Default for ZkProcessorPartialState {
             new_oids: (0..NOF_NEW_OBJS).map(|_| OuterScalarField::default()).collect(),
             new_obj_sks: (0..NOF_NEW_OBJS).map(|_| OuterScalarField::default()).collect(),
             new_obj_addrs: (0..NOF_NEW_OBJS).map(|_| OuterScalarField::default()).collect(),
+            pc: 0,
+            div_q: OuterScalarField::default(),
+            div_r: OuterScalarField::default(),
+            gas_used: OuterScalarField::default(),
         }
     }
 }
@@ -192,10 +272,47 @@ impl Default for ZkProcessorState {
     }
 }
 
+/// An abnormal condition raised by `ZkProcessor::run`/`run_with_memory`, carrying the
+/// instruction index and opcode that triggered it. Unlike the `panic!`s this replaces, a
+/// `ZkTrap` lets a host embedding the processor (e.g. a long-lived proving service)
+/// distinguish a legitimately-failing transaction (`RequirementFailed`) from a malformed
+/// program, without unwinding the whole process.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ZkTrap {
+    pub instruction_idx: usize,
+    pub opcode: u8,
+    pub kind: ZkTrapKind,
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ZkTrapKind {
+    /// a `REQ` instruction's condition did not evaluate to `1`
+    RequirementFailed,
+    /// an arithmetic op (`ADD`/`SUB`/`MUL`) over- or underflowed past `MAX_BYTES_UINT`
+    ArithmeticOverflow,
+    /// the instruction's opcode does not match any `OPCODE_*` constant
+    UnknownOpcode,
+    /// `LOAD`/`STORE`/`CID`/`KILL`/`PK` referenced an object id not present in memory
+    ObjectNotFound,
+    /// `LinearMemory` holds more than one live object with the same object id
+    ObjectAliased,
+    /// `FRESH` (or `NEW`, for `new_oids`/`new_obj_sks`/`new_obj_addrs`) found no nonzero entry
+    /// left in `initial_state.fresh_vals` (respectively those lists) to take
+    FreshExhausted,
+    /// `NEW`/`get_new` was executed more times than `LinearMemory` has empty object slots
+    OutOfObjectSlots,
+    /// `DIV`/`MOD`'s divisor (`src_2`) evaluated to zero
+    DivisionByZero,
+    /// the running gas total (see `opcode_gas_cost`) exceeded `ZkProcessor::gas_limit`
+    GasLimitExceeded,
+    /// `SHL`/`SHR`'s `src_2` (the shift amount) was a register instead of a compile-time constant
+    ShiftAmountNotConstant,
+}
+
 pub trait Memory {
-    fn get_data(&mut self, oid: &OuterScalarField) -> ObjectData;
-    fn set_data(&mut self, oid: &OuterScalarField, data: ObjectData);
-    fn get_new(&mut self, oid: &OuterScalarField) -> ObjectData;
+    fn get_data(&mut self, instruction_idx: usize, opcode: u8, oid: &OuterScalarField) -> Result<ObjectData, ZkTrap>;
+    fn set_data(&mut self, instruction_idx: usize, opcode: u8, oid: &OuterScalarField, data: ObjectData) -> Result<(), ZkTrap>;
+    fn get_new(&mut self, instruction_idx: usize, opcode: u8, oid: &OuterScalarField) -> Result<ObjectData, ZkTrap>;
     fn get_current_obj_state(&self) -> Vec<ObjectData>;
 }
 
@@ -210,46 +327,47 @@ impl Default for LinearMemory {
 }
 
 impl LinearMemory {
-    fn find_object_idx(&self, oid: &OuterScalarField) -> usize {
+    fn find_object_idx(&self, instruction_idx: usize, opcode: u8, oid: &OuterScalarField) -> Result<usize, ZkTrap> {
         let mut found = false;
         let mut idx = 0;
         for i in 0..NOF_OBJS {
             if self.obj_data[i].is_empty.is_zero() && self.obj_data[i].object_id == *oid {
-                if found { panic!("invalid access (multiple inputs with matching object id)"); }
+                if found { return Err(ZkTrap { instruction_idx, opcode, kind: ZkTrapKind::ObjectAliased }); }
                 found = true;
                 idx = i;
             }
         }
-        if !found { panic!("invalid access (object id not found)"); }
-        idx
+        if !found { return Err(ZkTrap { instruction_idx, opcode, kind: ZkTrapKind::ObjectNotFound }); }
+        Ok(idx)
     }
 
-    fn find_next_empty_object(&self) -> usize {
+    fn find_next_empty_object(&self, instruction_idx: usize, opcode: u8) -> Result<usize, ZkTrap> {
         for i in 0..NOF_OBJS {
             if self.obj_data[i].is_empty.is_one() {
-                return i;
+                return Ok(i);
             }
         }
-         panic!("no empty object found");
+        Err(ZkTrap { instruction_idx, opcode, kind: ZkTrapKind::OutOfObjectSlots })
     }
 }
 
 impl Memory for LinearMemory {
-    fn get_data(&mut self, oid: &OuterScalarField) -> ObjectData {
-        let idx = self.find_object_idx(oid);
-        self.obj_data[idx].clone()
+    fn get_data(&mut self, instruction_idx: usize, opcode: u8, oid: &OuterScalarField) -> Result<ObjectData, ZkTrap> {
+        let idx = self.find_object_idx(instruction_idx, opcode, oid)?;
+        Ok(self.obj_data[idx].clone())
     }
 
-    fn set_data(&mut self, oid: &OuterScalarField, data: ObjectData) {
-        let idx = self.find_object_idx(oid);
+    fn set_data(&mut self, instruction_idx: usize, opcode: u8, oid: &OuterScalarField, data: ObjectData) -> Result<(), ZkTrap> {
+        let idx = self.find_object_idx(instruction_idx, opcode, oid)?;
         self.obj_data[idx] = data;
+        Ok(())
     }
 
-    fn get_new(&mut self, oid: &OuterScalarField) -> ObjectData {
-        let idx = self.find_next_empty_object();
+    fn get_new(&mut self, instruction_idx: usize, opcode: u8, oid: &OuterScalarField) -> Result<ObjectData, ZkTrap> {
+        let idx = self.find_next_empty_object(instruction_idx, opcode)?;
         self.obj_data[idx].is_empty = OuterScalarField::zero();
         self.obj_data[idx].object_id = *oid;
-        self.obj_data[idx].clone()
+        Ok(self.obj_data[idx].clone())
     }
 
     fn get_current_obj_state(&self) -> Vec<ObjectData> {
@@ -291,28 +409,30 @@ impl StateBrokerMemory {
 }
 
 impl Memory for StateBrokerMemory {
-    fn get_data(&mut self, oid: &OuterScalarField) -> ObjectData {
+    fn get_data(&mut self, instruction_idx: usize, opcode: u8, oid: &OuterScalarField) -> Result<ObjectData, ZkTrap> {
         let found = self.data_for_oid.get(oid);
         if let Some(data) = found {
-            return data.clone();
+            return Ok(data.clone());
         }
-        let record = self.prev_state_view.borrow().get_record_for_oid(oid).unwrap_or_else(|_| panic!("unknown object id {}", fe_to_be_hex_str(oid)));
+        let record = self.prev_state_view.borrow().get_record_for_oid(oid)
+            .map_err(|_| ZkTrap { instruction_idx, opcode, kind: ZkTrapKind::ObjectNotFound })?;
         let data = record.to_object_data();
         self.accessed_previous_objects.push(data.clone());
         self.data_for_oid.insert(*oid, data.clone());
-        data
+        Ok(data)
     }
 
-    fn set_data(&mut self, oid: &OuterScalarField, data: ObjectData) {
+    fn set_data(&mut self, _instruction_idx: usize, _opcode: u8, oid: &OuterScalarField, data: ObjectData) -> Result<(), ZkTrap> {
         self.data_for_oid.insert(*oid, data);
+        Ok(())
     }
 
-    fn get_new(&mut self, oid: &OuterScalarField) -> ObjectData {
+    fn get_new(&mut self, _instruction_idx: usize, _opcode: u8, oid: &OuterScalarField) -> Result<ObjectData, ZkTrap> {
         let mut data = ObjectData::default();
         data.is_empty = OuterScalarField::zero();
         data.object_id = *oid;
         self.data_for_oid.insert(*oid, data.clone());
-        data
+        Ok(data)
     }
 
     fn get_current_obj_state(&self) -> Vec<ObjectData> {
@@ -321,22 +441,86 @@ impl Memory for StateBrokerMemory {
 }
 
 
-fn ensure_no_overflow(x: OuterScalarField) -> OuterScalarField {
-    let upper_bytes = &to_bytes!(x).unwrap()[MAX_BYTES_UINT..];
-    for b in upper_bytes {
-        if *b != 0u8 {
-            panic!("arithmetic under- or overflow detected")
-        }
+/// Whether `x` fits in `MAX_BYTES_UINT` bytes, i.e. would pass the in-circuit `is_in_range` check.
+pub(crate) fn fits_in_max_bytes(x: &OuterScalarField) -> bool {
+    to_bytes!(x).unwrap()[MAX_BYTES_UINT..].iter().all(|b| *b == 0u8)
+}
+
+/// Whether `instructions` contains any control-flow opcode (`JMP`/`JZ`/`JNZ`/`HALT`, the last
+/// because it freezes `pc`). When it doesn't,
+/// `pc` can never take on any value but `cycle` (it only ever starts at `0` and advances by `1`
+/// per step), which `ZkProcessorGadget::run` uses to skip `select_instruction_at_pc`'s per-cycle,
+/// one-hot fan-out over the whole program in favor of indexing the known instruction directly.
+pub(crate) fn program_has_control_flow(instructions: &[ZkInstruction]) -> bool {
+    instructions.iter().any(|inst| matches!(inst.opcode, OPCODE_JMP | OPCODE_JZ | OPCODE_JNZ | OPCODE_HALT))
+}
+
+/// Precomputes every `SHL`/`SHR` cycle's shift amount from the plaintext `instructions` list (`0`
+/// for every other cycle), the same way `straight_line` is derived: `ZkProcessorGadget::run`
+/// needs the amount before allocating any circuit variables to fold it into a constant re-weighting
+/// of `src_1`'s bits instead of a witnessed, per-cycle value (see the SHL/SHR ISA doc comment and
+/// `shift_amount` below). Like `range_check_elided`, this is only meaningful at cycle `c` when the
+/// whole program is `straight_line` (no control flow), since only then is `instructions[c]`
+/// guaranteed to be the instruction actually executed at cycle `c` rather than some other one
+/// reached by a jump; `ZkProcessorGadget::run` only ever consults it under that condition.
+pub(crate) fn compute_shift_amounts(instructions: &[ZkInstruction]) -> Vec<usize> {
+    instructions.iter().map(|inst| match inst.opcode {
+        OPCODE_SHL | OPCODE_SHR => shift_amount(0, inst.opcode, &inst.src_2).unwrap_or(0),
+        _ => 0,
+    }).collect()
+}
+
+fn ensure_no_overflow(instruction_idx: usize, opcode: u8, x: OuterScalarField) -> Result<OuterScalarField, ZkTrap> {
+    if !fits_in_max_bytes(&x) {
+        return Err(ZkTrap { instruction_idx, opcode, kind: ZkTrapKind::ArithmeticOverflow });
+    }
+    Ok(x)
+}
+
+/// Extracts SHL/SHR's shift amount from `src_2`, trapping if it isn't a compile-time constant
+/// (see the ISA doc comment above); the in-circuit gadget (`compute_shift_amounts`) needs the
+/// same restriction to reduce the shift to a cheap re-weighted sum over `src_1`'s bits.
+fn shift_amount(instruction_idx: usize, opcode: u8, src_2: &RegOrConst) -> Result<usize, ZkTrap> {
+    match src_2 {
+        RegOrConst::Const(c) => {
+            let big: num_bigint::BigUint = (*c).into();
+            Ok(big.to_u64_digits().first().copied().unwrap_or(0) as usize)
+        },
+        RegOrConst::Reg(_) => Err(ZkTrap { instruction_idx, opcode, kind: ZkTrapKind::ShiftAmountNotConstant }),
+    }
+}
+
+/// Computes `(x / y, x % y)` as unsigned integers, trapping if `y` is zero.
+fn checked_div_mod(instruction_idx: usize, opcode: u8, x: OuterScalarField, y: OuterScalarField) -> Result<(OuterScalarField, OuterScalarField), ZkTrap> {
+    if y.is_zero() {
+        return Err(ZkTrap { instruction_idx, opcode, kind: ZkTrapKind::DivisionByZero });
     }
-    x
+    let x_int: num_bigint::BigUint = x.into();
+    let y_int: num_bigint::BigUint = y.into();
+    let q = &x_int / &y_int;
+    let r = &x_int % &y_int;
+    Ok((OuterScalarField::from(q), OuterScalarField::from(r)))
 }
 
 
-#[derive(Default)]
 pub struct ZkProcessor {
     pub instructions: Vec<ZkInstruction>,
     pub states: Vec<ZkProcessorState>,
-    pub current_time: OuterScalarField
+    pub current_time: OuterScalarField,
+    /// the gas budget a run is not allowed to exceed (see `opcode_gas_cost`, `OPCODE_GAS`);
+    /// defaults to `DEFAULT_GAS_LIMIT`, set it explicitly to run with a tighter budget
+    pub gas_limit: OuterScalarField,
+}
+
+impl Default for ZkProcessor {
+    fn default() -> Self {
+        Self {
+            instructions: Vec::default(),
+            states: Vec::default(),
+            current_time: OuterScalarField::default(),
+            gas_limit: OuterScalarField::from(DEFAULT_GAS_LIMIT),
+        }
+    }
 }
 
 impl ZkProcessor {
@@ -345,28 +529,28 @@ impl ZkProcessor {
         instructions: &[ZkInstruction],
         initial_state: ZkProcessorPartialState,
         current_time: OuterScalarField
-    ) {
+    ) -> Result<(), ZkTrap> {
         // first, run with broker memory to access current state via state_view and find linear layout
         debug!("running processor with state broker memory...");
         let mut broker = StateBrokerMemory::new(state_view);
-        self.run_with_memory(&mut broker, instructions.to_vec(), initial_state.clone(), current_time);
-        debug!("execution consumes object ids: {:?}", broker.accessed_previous_objects.iter().map(|data| fe_to_string(&data.object_id)).collect::<Vec<_>>());      
+        self.run_with_memory(&mut broker, instructions.to_vec(), initial_state.clone(), current_time)?;
+        debug!("execution consumes object ids: {:?}", broker.accessed_previous_objects.iter().map(|data| fe_to_string(&data.object_id)).collect::<Vec<_>>());
         let mut linear = broker.get_linear_memory();
 
         // then, run with linear memory to get correct intermediate states
         debug!("running processor linear memory...");
-        self.run_with_memory(&mut linear, instructions.to_vec(), initial_state, current_time);
+        self.run_with_memory(&mut linear, instructions.to_vec(), initial_state, current_time)
     }
 
-    fn get_next_nonzero(vals: &mut Vec<OuterScalarField>) -> OuterScalarField {
+    fn get_next_nonzero(instruction_idx: usize, opcode: u8, vals: &mut Vec<OuterScalarField>) -> Result<OuterScalarField, ZkTrap> {
         for v in vals.iter_mut() {
             if !v.is_zero() {
                 let val = *v;
                 *v = OuterScalarField::zero();
-                return val;
+                return Ok(val);
             }
         }
-        panic!("no values left");
+        Err(ZkTrap { instruction_idx, opcode, kind: ZkTrapKind::FreshExhausted })
     }
 
     fn run_with_memory<I: Memory>(&mut self,
@@ -374,7 +558,7 @@ impl ZkProcessor {
             instructions: Vec<ZkInstruction>,
             initial_state: ZkProcessorPartialState,
             current_time: OuterScalarField
-    ) {
+    ) -> Result<(), ZkTrap> {
         self.instructions = instructions;
         self.states.clear();
         self.current_time = current_time;
@@ -385,11 +569,27 @@ impl ZkProcessor {
                 partial: initial_state.clone()
             });
         let mut state = initial_state;
-        for inst in self.instructions.iter() {
+        let max_steps = self.instructions.len();
+        for _step in 0..max_steps {
+            let instruction_idx = state.pc;
+            let inst = self.instructions.get(instruction_idx).cloned().unwrap_or_default();
+            let opcode = inst.opcode;
+            let mut next_pc = instruction_idx + 1;
+
+            // charge gas for this instruction and trap if the running total exceeds gas_limit
+            let gas_used = state.gas_used + OuterScalarField::from(opcode_gas_cost(opcode));
+            if gas_used > self.gas_limit {
+                return Err(ZkTrap { instruction_idx, opcode, kind: ZkTrapKind::GasLimitExceeded });
+            }
+            state.gas_used = gas_used;
+
             match inst.opcode {
                 OPCODE_NOOP => {
                     debug!("NOOP");
                 },
+                OPCODE_NOP => {
+                    debug!("NOP");
+                },
                 OPCODE_MOV => {
                     let res = val(&state, &inst.src_1);
                     debug!("MOV {} {}", inst.dst, fe_to_string(&res));
@@ -405,19 +605,19 @@ impl ZkProcessor {
                     let src_1_val = val(&state, &inst.src_1);
                     let src_2_val = val(&state, &inst.src_2);
                     debug!("ADD {} {} {}", inst.dst, fe_to_string(&src_1_val), fe_to_string(&src_2_val));
-                    state.registers[inst.dst] = ensure_no_overflow(src_1_val + src_2_val);
+                    state.registers[inst.dst] = ensure_no_overflow(instruction_idx, opcode, src_1_val + src_2_val)?;
                 },
                 OPCODE_SUB => {
                     let src_1_val = val(&state, &inst.src_1);
                     let src_2_val = val(&state, &inst.src_2);
                     debug!("SUB {} {} {}", inst.dst, fe_to_string(&src_1_val), fe_to_string(&src_2_val));
-                    state.registers[inst.dst] = ensure_no_overflow(src_1_val - src_2_val);
+                    state.registers[inst.dst] = ensure_no_overflow(instruction_idx, opcode, src_1_val - src_2_val)?;
                 },
                 OPCODE_MUL => {
                     let src_1_val = val(&state, &inst.src_1);
                     let src_2_val = val(&state, &inst.src_2);
                     debug!("MUL {} {} {}", inst.dst, fe_to_string(&src_1_val), fe_to_string(&src_2_val));
-                    state.registers[inst.dst] = ensure_no_overflow(src_1_val * src_2_val);
+                    state.registers[inst.dst] = ensure_no_overflow(instruction_idx, opcode, src_1_val * src_2_val)?;
                 },
                 OPCODE_EQ => {
                     let src_1_val = val(&state, &inst.src_1);
@@ -431,10 +631,12 @@ impl ZkProcessor {
                     debug!("LT {} {} {}", inst.dst, fe_to_string(&src_1_val), fe_to_string(&src_2_val));
                     state.registers[inst.dst] = if src_1_val < src_2_val { OuterScalarField::one() } else { OuterScalarField::zero() };
                 },
-                OPCODE_REQ => { 
+                OPCODE_REQ => {
                     let cond_val = val(&state, &inst.src_1);
                     debug!("REQ {}", fe_to_string(&cond_val));
-                    if cond_val != OuterScalarField::one() { panic!("requirement failed")}
+                    if cond_val != OuterScalarField::one() {
+                        return Err(ZkTrap { instruction_idx, opcode, kind: ZkTrapKind::RequirementFailed });
+                    }
                 },
                 OPCODE_LOAD => {
                     let oid = val(&state, &inst.src_1);
@@ -442,7 +644,7 @@ impl ZkProcessor {
                     let digits = field.to_u32_digits();
                     let field = if digits.is_empty() { 0usize } else { digits[0] as usize };
                     debug!("LOAD {} {} {}", inst.dst, fe_to_be_hex_str(&oid), field);
-                    let data = memory.get_data(&oid);
+                    let data = memory.get_data(instruction_idx, opcode, &oid)?;
                     state.registers[inst.dst] = data.payload[field];
                 },
                 OPCODE_STORE => {
@@ -451,41 +653,41 @@ impl ZkProcessor {
                     let digits = field.to_u32_digits();
                     let field = if digits.is_empty() { 0usize } else { digits[0] as usize };
                     debug!("STORE {} {} {}", inst.dst, fe_to_be_hex_str(&oid), field);
-                    let mut data = memory.get_data(&oid);
+                    let mut data = memory.get_data(instruction_idx, opcode, &oid)?;
                     data.payload[field] = state.registers[inst.dst];
-                    memory.set_data(&oid, data);
+                    memory.set_data(instruction_idx, opcode, &oid, data)?;
                 },
                 OPCODE_CID => {
                     let oid = val(&state, &inst.src_1);
                     debug!("CID {} {}", inst.dst, fe_to_be_hex_str(&oid));
-                    let data = memory.get_data(&oid);
+                    let data = memory.get_data(instruction_idx, opcode, &oid)?;
                     state.registers[inst.dst] = data.contract_id;
                 },
                 OPCODE_FRESH => {
-                    let val = Self::get_next_nonzero(&mut state.fresh_vals);
+                    let val = Self::get_next_nonzero(instruction_idx, opcode, &mut state.fresh_vals)?;
                     debug!("FRESH {}", inst.dst);
                     state.registers[inst.dst] = val;
                 },
                 OPCODE_KILL => {
                     let oid = val(&state, &inst.src_1);
                     debug!("KILL {}", fe_to_be_hex_str(&oid));
-                    let mut data = memory.get_data(&oid);
+                    let mut data = memory.get_data(instruction_idx, opcode, &oid)?;
                     data.is_empty = OuterScalarField::one();
-                    memory.set_data(&oid, data);
+                    memory.set_data(instruction_idx, opcode, &oid, data)?;
                 },
                 OPCODE_NEW => {
                     let cid = val(&state, &inst.src_1);
                     debug!("NEW {} {}", inst.dst, fe_to_string(&cid));
-                    let oid = Self::get_next_nonzero(&mut state.new_oids);
-                    let sk = Self::get_next_nonzero(&mut state.new_obj_sks);
-                    let addr = Self::get_next_nonzero(&mut state.new_obj_addrs);
-                    let mut data = memory.get_new(&oid);
+                    let oid = Self::get_next_nonzero(instruction_idx, opcode, &mut state.new_oids)?;
+                    let sk = Self::get_next_nonzero(instruction_idx, opcode, &mut state.new_obj_sks)?;
+                    let addr = Self::get_next_nonzero(instruction_idx, opcode, &mut state.new_obj_addrs)?;
+                    let mut data = memory.get_new(instruction_idx, opcode, &oid)?;
                     state.registers[inst.dst] = oid;
                     data.contract_id = cid;
                     data.object_id = oid;
                     data.sk_object = sk;
                     data.addr_object = addr;
-                    memory.set_data(&oid, data);
+                    memory.set_data(instruction_idx, opcode, &oid, data)?;
                 },
                 OPCODE_NOW => {
                     debug!("NOW {}", inst.dst);
@@ -494,18 +696,95 @@ impl ZkProcessor {
                 OPCODE_PK => {
                     let oid = val(&state, &inst.src_1);
                     debug!("PK {} {}", inst.dst, fe_to_be_hex_str(&oid));
-                    let data = memory.get_data(&oid);
+                    let data = memory.get_data(instruction_idx, opcode, &oid)?;
                     state.registers[inst.dst] = data.addr_object;
-                }
-                _ => panic!("unknown opcode")
+                },
+                OPCODE_JMP => {
+                    debug!("JMP {}", inst.dst);
+                    next_pc = inst.dst;
+                },
+                OPCODE_JZ => {
+                    let cond_val = val(&state, &inst.src_1);
+                    debug!("JZ {} {}", inst.dst, fe_to_string(&cond_val));
+                    if cond_val.is_zero() { next_pc = inst.dst; }
+                },
+                OPCODE_JNZ => {
+                    let cond_val = val(&state, &inst.src_1);
+                    debug!("JNZ {} {}", inst.dst, fe_to_string(&cond_val));
+                    if !cond_val.is_zero() { next_pc = inst.dst; }
+                },
+                OPCODE_HALT => {
+                    debug!("HALT");
+                    next_pc = instruction_idx;
+                },
+                OPCODE_DIV => {
+                    let src_1_val = val(&state, &inst.src_1);
+                    let src_2_val = val(&state, &inst.src_2);
+                    debug!("DIV {} {} {}", inst.dst, fe_to_string(&src_1_val), fe_to_string(&src_2_val));
+                    let (q, r) = checked_div_mod(instruction_idx, opcode, src_1_val, src_2_val)?;
+                    state.registers[inst.dst] = q;
+                    state.div_q = q;
+                    state.div_r = r;
+                },
+                OPCODE_MOD => {
+                    let src_1_val = val(&state, &inst.src_1);
+                    let src_2_val = val(&state, &inst.src_2);
+                    debug!("MOD {} {} {}", inst.dst, fe_to_string(&src_1_val), fe_to_string(&src_2_val));
+                    let (q, r) = checked_div_mod(instruction_idx, opcode, src_1_val, src_2_val)?;
+                    state.registers[inst.dst] = r;
+                    state.div_q = q;
+                    state.div_r = r;
+                },
+                OPCODE_AND => {
+                    let src_1_val: num_bigint::BigUint = val(&state, &inst.src_1).into();
+                    let src_2_val: num_bigint::BigUint = val(&state, &inst.src_2).into();
+                    debug!("AND {} {} {}", inst.dst, src_1_val, src_2_val);
+                    state.registers[inst.dst] = OuterScalarField::from(src_1_val & src_2_val);
+                },
+                OPCODE_OR => {
+                    let src_1_val: num_bigint::BigUint = val(&state, &inst.src_1).into();
+                    let src_2_val: num_bigint::BigUint = val(&state, &inst.src_2).into();
+                    debug!("OR {} {} {}", inst.dst, src_1_val, src_2_val);
+                    state.registers[inst.dst] = OuterScalarField::from(src_1_val | src_2_val);
+                },
+                OPCODE_XOR => {
+                    let src_1_val: num_bigint::BigUint = val(&state, &inst.src_1).into();
+                    let src_2_val: num_bigint::BigUint = val(&state, &inst.src_2).into();
+                    debug!("XOR {} {} {}", inst.dst, src_1_val, src_2_val);
+                    state.registers[inst.dst] = OuterScalarField::from(src_1_val ^ src_2_val);
+                },
+                OPCODE_GAS => {
+                    debug!("GAS {}", inst.dst);
+                    state.registers[inst.dst] = self.gas_limit - state.gas_used;
+                },
+                OPCODE_SHL => {
+                    let shift = shift_amount(instruction_idx, opcode, &inst.src_2)?;
+                    let src_1_val: num_bigint::BigUint = val(&state, &inst.src_1).into();
+                    debug!("SHL {} {} {}", inst.dst, src_1_val, shift);
+                    let nbits = MAX_BYTES_UINT * 8;
+                    let mask = (num_bigint::BigUint::from(1u64) << nbits) - num_bigint::BigUint::from(1u64);
+                    let shifted = if shift >= nbits { num_bigint::BigUint::from(0u64) } else { (src_1_val << shift) & mask };
+                    state.registers[inst.dst] = OuterScalarField::from(shifted);
+                },
+                OPCODE_SHR => {
+                    let shift = shift_amount(instruction_idx, opcode, &inst.src_2)?;
+                    let src_1_val: num_bigint::BigUint = val(&state, &inst.src_1).into();
+                    debug!("SHR {} {} {}", inst.dst, src_1_val, shift);
+                    let nbits = MAX_BYTES_UINT * 8;
+                    let shifted = if shift >= nbits { num_bigint::BigUint::from(0u64) } else { src_1_val >> shift };
+                    state.registers[inst.dst] = OuterScalarField::from(shifted);
+                },
+                _ => return Err(ZkTrap { instruction_idx, opcode, kind: ZkTrapKind::UnknownOpcode })
             }
 
+            state.pc = next_pc;
             self.states.push(
                 ZkProcessorState {
                     obj_data:  memory.get_current_obj_state(),
                     partial: state.clone()
                 });
         }
+        Ok(())
     }
 
     pub fn get_instructions_var(&self, cs: ConstraintSystemRef<OuterScalarField>, mode: AllocationMode) -> ark_relations::r1cs::Result<Vec<constraints::ZkInstructionVar>> {
@@ -529,6 +808,11 @@ impl ZkProcessor {
         Ok(var)
     }
 
+    pub fn get_gas_limit_var(&self, cs: ConstraintSystemRef<OuterScalarField>, mode: AllocationMode) -> ark_relations::r1cs::Result<OuterScalarVar> {
+        let var = OuterScalarVar::new_variable(cs, || Ok(self.gas_limit), mode)?;
+        Ok(var)
+    }
+
     pub fn get_initial_state(&self) -> ZkProcessorState {
         self.states[0].clone()
     }
@@ -559,6 +843,10 @@ pub mod constraints {
         pub new_oids: Vec<OuterScalarVar>,
         pub new_obj_sks: Vec<OuterScalarVar>,
         pub new_obj_addrs: Vec<OuterScalarVar>,
+        pub pc: OuterScalarVar,
+        pub div_q: OuterScalarVar,
+        pub div_r: OuterScalarVar,
+        pub gas_used: OuterScalarVar,
     }
 
     impl AllocVar<ZkProcessorState, OuterScalarField> for ZkProcessorStateVar {
@@ -588,6 +876,10 @@ pub mod constraints {
                 new_oids: state.borrow().partial.new_oids.iter().map(|e| OuterScalarVar::new_variable(cs.clone(), || Ok(e), mode).unwrap()).collect(),
                 new_obj_sks: state.borrow().partial.new_obj_sks.iter().map(|e| OuterScalarVar::new_variable(cs.clone(), || Ok(e), mode).unwrap()).collect(),
                 new_obj_addrs: state.borrow().partial.new_obj_addrs.iter().map(|e| OuterScalarVar::new_variable(cs.clone(), || Ok(e), mode).unwrap()).collect(),
+                pc: OuterScalarVar::new_variable(cs.clone(), || Ok(OuterScalarField::from(state.borrow().partial.pc as u64)), mode).unwrap(),
+                div_q: OuterScalarVar::new_variable(cs.clone(), || Ok(state.borrow().partial.div_q), mode).unwrap(),
+                div_r: OuterScalarVar::new_variable(cs.clone(), || Ok(state.borrow().partial.div_r), mode).unwrap(),
+                gas_used: OuterScalarVar::new_variable(cs.clone(), || Ok(state.borrow().partial.gas_used), mode).unwrap(),
             })
         }
     }
@@ -622,6 +914,18 @@ pub mod constraints {
         pub op_is_new: OuterScalarVar,
         pub op_is_now: OuterScalarVar,
         pub op_is_pk: OuterScalarVar,
+        pub op_is_jmp: OuterScalarVar,
+        pub op_is_jz: OuterScalarVar,
+        pub op_is_jnz: OuterScalarVar,
+        pub op_is_halt: OuterScalarVar,
+        pub op_is_div: OuterScalarVar,
+        pub op_is_mod: OuterScalarVar,
+        pub op_is_and: OuterScalarVar,
+        pub op_is_or: OuterScalarVar,
+        pub op_is_xor: OuterScalarVar,
+        pub op_is_gas: OuterScalarVar,
+        pub op_is_shl: OuterScalarVar,
+        pub op_is_shr: OuterScalarVar,
     }
 
     impl AllocVar<ZkInstruction, OuterScalarField> for ZkInstructionVar {
@@ -664,7 +968,19 @@ pub mod constraints {
                 op_is_kill: opcode_var.is_eq(&OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(OPCODE_KILL as u64))?)?.into(),
                 op_is_new: opcode_var.is_eq(&OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(OPCODE_NEW as u64))?)?.into(),
                 op_is_now: opcode_var.is_eq(&OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(OPCODE_NOW as u64))?)?.into(),
-                op_is_pk: opcode_var.is_eq(&OuterScalarVar::new_constant(cs, OuterScalarField::from(OPCODE_PK as u64))?)?.into(),
+                op_is_pk: opcode_var.is_eq(&OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(OPCODE_PK as u64))?)?.into(),
+                op_is_jmp: opcode_var.is_eq(&OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(OPCODE_JMP as u64))?)?.into(),
+                op_is_jz: opcode_var.is_eq(&OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(OPCODE_JZ as u64))?)?.into(),
+                op_is_jnz: opcode_var.is_eq(&OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(OPCODE_JNZ as u64))?)?.into(),
+                op_is_halt: opcode_var.is_eq(&OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(OPCODE_HALT as u64))?)?.into(),
+                op_is_div: opcode_var.is_eq(&OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(OPCODE_DIV as u64))?)?.into(),
+                op_is_mod: opcode_var.is_eq(&OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(OPCODE_MOD as u64))?)?.into(),
+                op_is_and: opcode_var.is_eq(&OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(OPCODE_AND as u64))?)?.into(),
+                op_is_or: opcode_var.is_eq(&OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(OPCODE_OR as u64))?)?.into(),
+                op_is_xor: opcode_var.is_eq(&OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(OPCODE_XOR as u64))?)?.into(),
+                op_is_gas: opcode_var.is_eq(&OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(OPCODE_GAS as u64))?)?.into(),
+                op_is_shl: opcode_var.is_eq(&OuterScalarVar::new_constant(cs.clone(), OuterScalarField::from(OPCODE_SHL as u64))?)?.into(),
+                op_is_shr: opcode_var.is_eq(&OuterScalarVar::new_constant(cs, OuterScalarField::from(OPCODE_SHR as u64))?)?.into(),
             })
         }
     }
@@ -702,6 +1018,73 @@ pub mod constraints {
         Ok(x)
     }
 
+    /// Selects the instruction executed at `pc` out of the (fixed, public-shape) program
+    /// `instructions`, as the inner product of a one-hot selector over `pc` with each of the
+    /// program's columns. Besides the `is_eq`-derived selector bits summing to `1`, we also
+    /// redundantly enforce `selector[i] * (pc - i) == 0` for every `i`, exactly as the
+    /// branching design calls for, so a malicious selector witness can't pick an instruction
+    /// that doesn't actually match `pc`.
+    pub fn select_instruction_at_pc(cycle: usize, instructions: &[ZkInstructionVar], pc: &OuterScalarVar) -> ark_relations::r1cs::Result<ZkInstructionVar> {
+        let cs = &pc.cs();
+        let n = instructions.len();
+
+        let mut selector = Vec::with_capacity(n);
+        let mut sum = OuterScalarVar::zero();
+        for (i, i_var) in (0..n).map(|i| (i, const_fe(cs, i as u64))) {
+            let sel: OuterScalarVar = pc.clone().is_eq(&i_var)?.into();  dbg_var(&sel);
+            let consistent = sel.clone().mul(&pc.clone().sub(&i_var)).is_eq(&OuterScalarVar::zero())?;
+            enforce_true_with_info(&consistent, &format!("ZkProcessor - cycle {} - select_instruction_at_pc - i = {} - selector_consistent", cycle, i));
+            sum.add_assign(&sel);
+            selector.push(sel);
+        }
+        let exactly_one_selected = sum.is_eq(&OuterScalarVar::one())?;
+        enforce_true_with_info(&exactly_one_selected, &format!("ZkProcessor - cycle {} - select_instruction_at_pc - exactly_one_selected", cycle));
+
+        let select_column = |col: &dyn Fn(&ZkInstructionVar) -> OuterScalarVar| -> OuterScalarVar {
+            let mut x = OuterScalarVar::zero();
+            for (s, inst) in selector.iter().zip(instructions.iter()) {
+                x.add_assign(&s.clone().mul(&col(inst)));
+            }
+            x
+        };
+
+        Ok(ZkInstructionVar {
+            dst: select_column(&|i| i.dst.clone()),
+            src_1: select_column(&|i| i.src_1.clone()),
+            src_1_is_const: select_column(&|i| i.src_1_is_const.clone()),
+            src_2: select_column(&|i| i.src_2.clone()),
+            src_2_is_const: select_column(&|i| i.src_2_is_const.clone()),
+            op_is_mov: select_column(&|i| i.op_is_mov.clone()),
+            op_is_cmov: select_column(&|i| i.op_is_cmov.clone()),
+            op_is_add: select_column(&|i| i.op_is_add.clone()),
+            op_is_sub: select_column(&|i| i.op_is_sub.clone()),
+            op_is_mul: select_column(&|i| i.op_is_mul.clone()),
+            op_is_eq: select_column(&|i| i.op_is_eq.clone()),
+            op_is_lt: select_column(&|i| i.op_is_lt.clone()),
+            op_is_req: select_column(&|i| i.op_is_req.clone()),
+            op_is_load: select_column(&|i| i.op_is_load.clone()),
+            op_is_store: select_column(&|i| i.op_is_store.clone()),
+            op_is_cid: select_column(&|i| i.op_is_cid.clone()),
+            op_is_fresh: select_column(&|i| i.op_is_fresh.clone()),
+            op_is_kill: select_column(&|i| i.op_is_kill.clone()),
+            op_is_new: select_column(&|i| i.op_is_new.clone()),
+            op_is_now: select_column(&|i| i.op_is_now.clone()),
+            op_is_pk: select_column(&|i| i.op_is_pk.clone()),
+            op_is_jmp: select_column(&|i| i.op_is_jmp.clone()),
+            op_is_jz: select_column(&|i| i.op_is_jz.clone()),
+            op_is_jnz: select_column(&|i| i.op_is_jnz.clone()),
+            op_is_halt: select_column(&|i| i.op_is_halt.clone()),
+            op_is_div: select_column(&|i| i.op_is_div.clone()),
+            op_is_mod: select_column(&|i| i.op_is_mod.clone()),
+            op_is_and: select_column(&|i| i.op_is_and.clone()),
+            op_is_or: select_column(&|i| i.op_is_or.clone()),
+            op_is_xor: select_column(&|i| i.op_is_xor.clone()),
+            op_is_gas: select_column(&|i| i.op_is_gas.clone()),
+            op_is_shl: select_column(&|i| i.op_is_shl.clone()),
+            op_is_shr: select_column(&|i| i.op_is_shr.clone()),
+        })
+    }
+
     pub fn get_obj_field(cycle: usize,
         obj_data: &[ObjectDataVar],
         oid: &OuterScalarVar,
@@ -881,13 +1264,290 @@ pub mod constraints {
         Ok(x)
     }
 
-    pub fn is_in_range(val: &OuterScalarVar) -> ark_relations::r1cs::Result<Boolean<OuterScalarField>> {
+    /// Bit-decomposes `val` (booleanity and recomposition are enforced by `to_bits_le` itself)
+    /// and returns its low `nbits` bits together with a condition that's true iff all bits
+    /// beyond `nbits` are zero, i.e. iff `val < 2^nbits`. Shared by the arithmetic overflow
+    /// check (`is_in_range`), the `DIV`/`MOD` remainder bound, and the bitwise opcodes, which
+    /// all need to reason about a value's bit pattern up to some width.
+    pub fn enforce_range(val: &OuterScalarVar, nbits: usize) -> ark_relations::r1cs::Result<(Boolean<OuterScalarField>, Vec<Boolean<OuterScalarField>>)> {
         let bits = val.to_bits_le()?;
-        let mut ok = Boolean::TRUE;
-        for bit in &bits[MAX_BYTES_UINT*8..] {
-            ok = ok.and(&bit.not())?
+        let mut in_range = Boolean::TRUE;
+        for bit in &bits[nbits..] {
+            in_range = in_range.and(&bit.not())?
+        }
+        Ok((in_range, bits[..nbits].to_vec()))
+    }
+
+    pub fn is_in_range(val: &OuterScalarVar) -> ark_relations::r1cs::Result<Boolean<OuterScalarField>> {
+        Ok(enforce_range(val, MAX_BYTES_UINT*8)?.0)
+    }
+
+    /// In-circuit counterpart of `fe_to_signed_string`'s decode rule: proves `val` two's-complement
+    /// decodes to a value in `[-2^(8*size_bytes-1), 2^(8*size_bytes-1)-1]`. Adds the same
+    /// `2^(8*size_bytes-1)` bias the encoding is centered on, which shifts that signed window onto
+    /// the unsigned one `[0, 2^(8*size_bytes)-1]` that `enforce_range` already checks.
+    pub fn enforce_signed_range(val: &OuterScalarVar, size_bytes: usize) -> ark_relations::r1cs::Result<Boolean<OuterScalarField>> {
+        let nbits = 8 * size_bytes;
+        let bias = OuterScalarVar::new_constant(val.cs(), OuterScalarField::from(2).pow([nbits as u64 - 1]))?;
+        let shifted = val.clone().add(&bias);
+        Ok(enforce_range(&shifted, nbits)?.0)
+    }
+
+    /// Fiat-Shamir fingerprint of a `(address, value, timestamp)` memory access tuple:
+    /// `α − (address + β·value + β²·timestamp)`. `alpha`/`beta` are two challenges the verifier
+    /// is expected to allocate as public inputs, so prover and verifier commit to the same
+    /// memory-consistency check; see `GrandProduct`.
+    pub fn memory_fingerprint(
+        address: &OuterScalarVar,
+        value: &OuterScalarVar,
+        timestamp: &OuterScalarVar,
+        alpha: &OuterScalarVar,
+        beta: &OuterScalarVar,
+    ) -> OuterScalarVar {
+        let beta_sq = beta.clone().mul(beta);
+        alpha.clone().sub(&address.clone().add(&beta.clone().mul(value)).add(&beta_sq.mul(timestamp)))
+    }
+
+    /// A running product of `memory_fingerprint` values, one factor absorbed per memory access.
+    /// An offline memory-checking argument (in the style used by uniform-step zkVMs) builds one
+    /// of these over every read and one over every write (seeded with the initial memory
+    /// snapshot and extended with the final one respectively) and constrains the two equal at the
+    /// end, which is enough to prove every read returned the value most recently written to that
+    /// address — in O(1) additional work per access instead of re-scanning all of memory on
+    /// every access the way `get_obj_field`/`check_obj_data` currently do. Wiring `ZkProcessorGadget`
+    /// over to this (replacing the per-cycle `obj_data` scan with read/write fingerprint
+    /// absorption, threading `alpha`/`beta` through as new public inputs, and giving the native
+    /// interpreter in `run_with_memory` a notion of per-field access timestamps to witness) is
+    /// substantial follow-up work; this type is the first, self-contained building block of it.
+    #[derive(Clone)]
+    pub struct GrandProduct {
+        pub running_product: OuterScalarVar,
+    }
+
+    impl GrandProduct {
+        pub fn one(cs: ConstraintSystemRef<OuterScalarField>) -> Self {
+            Self { running_product: OuterScalarVar::new_constant(cs, OuterScalarField::one()).unwrap() }
+        }
+
+        pub fn absorb(&mut self, fingerprint: &OuterScalarVar) {
+            self.running_product = self.running_product.clone().mul(fingerprint);
+        }
+    }
+
+    /// A LogUp (log-derivative) lookup argument: proves a multiset of witnessed values all lie in
+    /// a fixed table by checking `Σ 1/(γ − x_i)` over the witnessed values against
+    /// `Σ m_t/(γ − t)` over the table, weighting each table entry `t` by how many times the prover
+    /// claims to have looked it up (`m_t`), for a verifier challenge `γ`. Where `enforce_range`
+    /// pays one `to_bits_le` decomposition per checked value, this pays one field division per
+    /// value and one table-sized sum shared across every value checked against the same table —
+    /// cheaper whenever many values are checked against the same small table, as `LT`'s and
+    /// `ADD`/`SUB`/`MUL`'s overflow guards would be if each limb were checked this way instead.
+    /// Wiring those over (and giving the native interpreter well-defined wrapping arithmetic to
+    /// match, instead of the `ZkTrapKind::ArithmeticOverflow` it currently raises) is substantial
+    /// follow-up work that touches most of `run`'s arithmetic opcodes; this is the reusable
+    /// argument those call sites would build on, matching the role `GrandProduct` plays for the
+    /// offline memory-checking argument above.
+    pub struct LogUpArgument {
+        gamma: OuterScalarVar,
+        witness_sum: OuterScalarVar,
+    }
+
+    impl LogUpArgument {
+        pub fn new(gamma: OuterScalarVar) -> Self {
+            let witness_sum = OuterScalarVar::zero();
+            Self { gamma, witness_sum }
+        }
+
+        /// Records one more occurrence of `value` being claimed to lie in the table.
+        pub fn check_member(&mut self, value: &OuterScalarVar) -> ark_relations::r1cs::Result<()> {
+            let term = self.gamma.clone().sub(value).inverse()?;
+            self.witness_sum = self.witness_sum.clone().add(&term);
+            Ok(())
+        }
+
+        /// Enforces the accumulated witness-side sum equals the table-side sum. `table` lists
+        /// each distinct entry once; `multiplicities[i]` is how many times `check_member` was
+        /// called with `table[i]`'s value, witnessed by the prover (the verifier doesn't know
+        /// which table entries were looked up, only that the two sums match).
+        pub fn enforce_consistent(&self, table: &[OuterScalarVar], multiplicities: &[OuterScalarVar]) -> ark_relations::r1cs::Result<()> {
+            let mut table_sum = OuterScalarVar::zero();
+            for (t, m) in table.iter().zip(multiplicities.iter()) {
+                table_sum = table_sum.add(m.clone().mul(&self.gamma.clone().sub(t).inverse()?));
+            }
+            self.witness_sum.is_eq(&table_sum)?.enforce_equal(&Boolean::TRUE)
+        }
+    }
+
+    /// Allocates the `[0, 2^bits)` lookup table `LogUpArgument` checks limb membership against,
+    /// as public constants shared by every cycle that looks values up in it.
+    pub fn range_table(cs: ConstraintSystemRef<OuterScalarField>, bits: usize) -> Vec<OuterScalarVar> {
+        (0..(1u64 << bits)).map(|v| const_fe(&cs, v)).collect()
+    }
+
+    /// `X²`'s value for the `ExtField` extension below. Whoever instantiates `ExtField` over a
+    /// given base field is responsible for checking this is actually a quadratic non-residue
+    /// there; picking a non-residue is a
+    /// one-time, field-specific fact, not something this type can verify for itself.
+    pub const EXT_FIELD_NON_RESIDUE: u64 = 7;
+
+    /// Native element `a + b·X` of the degree-2 extension `OuterScalarField[X]/(X² − NON_RESIDUE)`.
+    /// `GrandProduct`/`LogUpArgument`'s soundness error is on the order of
+    /// `trace_length / field_size` in whatever field the challenges (`alpha`/`beta`/`gamma`) live
+    /// in; with `OuterScalarField` being BLS12-381's ~255-bit scalar field, that's already
+    /// negligible for any trace `ZkProcessor` could witness, so nothing below is wired into
+    /// `ZkProcessorGadget`. This exists as the building block a future swap to a small, efficient
+    /// base field (e.g. a 64-bit prime) would need to keep those arguments sound: challenges and
+    /// accumulators would move to `ExtField`/`ExtFieldVar` while registers and `obj_data` stay in
+    /// the (now small) base field — there is no generic field-selection mechanism in this
+    /// codebase today to gate that switch on.
+    #[derive(Clone, Copy, Debug, PartialEq, Eq)]
+    pub struct ExtField {
+        pub a: OuterScalarField,
+        pub b: OuterScalarField,
+    }
+
+    impl ExtField {
+        pub fn new(a: OuterScalarField, b: OuterScalarField) -> Self {
+            Self { a, b }
+        }
+
+        pub fn zero() -> Self {
+            Self { a: OuterScalarField::zero(), b: OuterScalarField::zero() }
+        }
+
+        pub fn one() -> Self {
+            Self { a: OuterScalarField::one(), b: OuterScalarField::zero() }
+        }
+
+        pub fn add(&self, other: &Self) -> Self {
+            Self { a: self.a + other.a, b: self.b + other.b }
+        }
+
+        pub fn sub(&self, other: &Self) -> Self {
+            Self { a: self.a - other.a, b: self.b - other.b }
+        }
+
+        pub fn mul(&self, other: &Self) -> Self {
+            let non_residue = OuterScalarField::from(EXT_FIELD_NON_RESIDUE);
+            Self {
+                a: self.a * other.a + non_residue * self.b * other.b,
+                b: self.a * other.b + self.b * other.a,
+            }
+        }
+
+        /// `None` iff `self` is zero.
+        pub fn inverse(&self) -> Option<Self> {
+            let non_residue = OuterScalarField::from(EXT_FIELD_NON_RESIDUE);
+            let norm = self.a * self.a - non_residue * self.b * self.b;
+            let norm_inv = norm.inverse()?;
+            Some(Self { a: self.a * norm_inv, b: -(self.b * norm_inv) })
+        }
+    }
+
+    /// In-circuit counterpart of `ExtField`: an `(a, b)` pair of `OuterScalarVar`s representing
+    /// `a + b·X`. Mirrors `ExtField`'s arithmetic one constraint-system op at a time, so a
+    /// `GrandProduct`/`LogUpArgument` instantiated over `ExtFieldVar` challenges would look the
+    /// same as today's, just with every `OuterScalarVar` op replaced by the matching one here.
+    #[derive(Clone)]
+    pub struct ExtFieldVar {
+        pub a: OuterScalarVar,
+        pub b: OuterScalarVar,
+    }
+
+    impl ExtFieldVar {
+        pub fn constant(cs: ConstraintSystemRef<OuterScalarField>, c: ExtField) -> Self {
+            Self {
+                a: OuterScalarVar::new_constant(cs.clone(), c.a).unwrap(),
+                b: OuterScalarVar::new_constant(cs, c.b).unwrap(),
+            }
+        }
+
+        pub fn add(&self, other: &Self) -> Self {
+            Self { a: self.a.clone().add(&other.a), b: self.b.clone().add(&other.b) }
+        }
+
+        pub fn sub(&self, other: &Self) -> Self {
+            Self { a: self.a.clone().sub(&other.a), b: self.b.clone().sub(&other.b) }
+        }
+
+        pub fn mul(&self, other: &Self) -> Self {
+            let non_residue = const_fe(&self.a.cs(), EXT_FIELD_NON_RESIDUE);
+            Self {
+                a: self.a.clone().mul(&other.a).add(&non_residue.mul(&self.b).mul(&other.b)),
+                b: self.a.clone().mul(&other.b).add(&self.b.clone().mul(&other.a)),
+            }
         }
-        Ok(ok)
+
+        /// Inverts via the conjugate trick (`(a + bX)⁻¹ = (a − bX) / (a² − NON_RESIDUE·b²)`),
+        /// which keeps the whole operation to one base-field `inverse()` instead of needing to
+        /// witness-and-check the extension inverse directly.
+        pub fn inverse(&self) -> ark_relations::r1cs::Result<Self> {
+            let non_residue = const_fe(&self.a.cs(), EXT_FIELD_NON_RESIDUE);
+            let norm = self.a.clone().mul(&self.a).sub(&non_residue.mul(&self.b).mul(&self.b));
+            let norm_inv = norm.inverse()?;
+            Ok(Self {
+                a: self.a.clone().mul(&norm_inv),
+                b: OuterScalarVar::zero().sub(&self.b).mul(&norm_inv),
+            })
+        }
+    }
+
+    /// Computes `a OP b`, bit by bit, over the low `nbits` bits of `a` and `b` (per
+    /// `enforce_range`), using the standard boolean-algebra-as-field-arithmetic identities
+    /// (`a AND b = a*b`, `a OR b = a+b-ab`, `a XOR b = a+b-2ab`), then recomposes the result.
+    /// `op_is_and`/`op_is_or`/`op_is_xor` are mutually exclusive one-hot flags selecting which
+    /// identity applies; the caller is responsible for range-checking `a` and `b` themselves.
+    pub fn bitwise_op(
+        a_bits: &[Boolean<OuterScalarField>],
+        b_bits: &[Boolean<OuterScalarField>],
+        op_is_and: &OuterScalarVar,
+        op_is_or: &OuterScalarVar,
+        op_is_xor: &OuterScalarVar,
+    ) -> ark_relations::r1cs::Result<OuterScalarVar> {
+        let mut res = OuterScalarVar::zero();
+        let mut pow2 = OuterScalarVar::one();
+        let two = const_fe(&op_is_and.cs(), 2);
+        for (a_bit, b_bit) in a_bits.iter().zip(b_bits.iter()) {
+            let a_fe: OuterScalarVar = a_bit.clone().into();
+            let b_fe: OuterScalarVar = b_bit.clone().into();
+            let and_fe = a_fe.clone().mul(&b_fe);
+            let or_fe = a_fe.clone().add(&b_fe).sub(&and_fe);
+            let xor_fe = a_fe.add(&b_fe).sub(&and_fe.clone().add(&and_fe));
+            let bit_res = and_fe.mul(op_is_and).add(or_fe.mul(op_is_or)).add(xor_fe.mul(op_is_xor));
+            res.add_assign(&bit_res.mul(&pow2));
+            pow2 = pow2.mul(&two);
+        }
+        Ok(res)
+    }
+
+    /// Re-weights `bits` (a value's little-endian bit decomposition, from `enforce_range`) to
+    /// compute `value << amount` (`left`) or `value >> amount` (`!left`) for a compile-time
+    /// constant `amount`, dropping whichever bits would land outside `[0, bits.len())`. Cheaper
+    /// than a generic barrel shifter because `amount` doesn't need to be witnessed or selected
+    /// between per cycle — see the SHL/SHR ISA doc comment and `compute_shift_amounts`.
+    pub fn shift_op(cs: &ConstraintSystemRef<OuterScalarField>, bits: &[Boolean<OuterScalarField>], amount: usize, left: bool) -> OuterScalarVar {
+        let nbits = bits.len();
+        if amount >= nbits {
+            return OuterScalarVar::zero();
+        }
+        let mut res = OuterScalarVar::zero();
+        // SHL's lowest surviving bit (index 0) lands at output position `amount`, so its weight
+        // starts at 2^amount instead of 2^0; SHR's lowest surviving bit (index `amount`) lands at
+        // output position 0, so its weight starts at 2^0 as usual.
+        let mut weight = OuterScalarField::one();
+        if left {
+            for _ in 0..amount {
+                weight += weight;
+            }
+        }
+        let kept_bits = if left { &bits[..nbits - amount] } else { &bits[amount..] };
+        for bit in kept_bits {
+            let bit_fe: OuterScalarVar = bit.clone().into();
+            let weight_var = OuterScalarVar::new_constant(cs.clone(), weight).unwrap();
+            res = res.add(&bit_fe.mul(&weight_var));
+            weight += weight;
+        }
+        res
     }
 
     pub struct ZkProcessorGadget {
@@ -895,6 +1555,18 @@ pub mod constraints {
         pub instructions: Vec<ZkInstructionVar>,
         pub states: Vec<ZkProcessorStateVar>,
         pub current_time: OuterScalarVar,
+        pub gas_limit: OuterScalarVar,
+        /// set from `program_has_control_flow`: when `false`, `run` knows `pc` can only ever equal
+        /// `cycle` and skips the one-hot `select_instruction_at_pc` fan-out for a direct index.
+        pub straight_line: bool,
+        /// from `optimizer::infer_range_safe_cycles`; only consulted when `straight_line` is set.
+        /// `range_check_elided[cycle]` means `run` can skip `op_res`'s overflow bit-decomposition
+        /// that cycle because it's been proven unnecessary regardless of the operands' values.
+        pub range_check_elided: Vec<bool>,
+        /// from `compute_shift_amounts`; only consulted when `straight_line` is set, for the same
+        /// reason as `range_check_elided`. `shift_amounts[cycle]` is the compile-time-constant
+        /// shift amount `run` folds a `SHL`/`SHR` at that cycle into, `0` for every other cycle.
+        pub shift_amounts: Vec<usize>,
     }
 
     impl ZkProcessorGadget {
@@ -902,12 +1574,20 @@ pub mod constraints {
             instructions: Vec<ZkInstructionVar>,
             states: Vec<ZkProcessorStateVar>,
             current_time: OuterScalarVar,
+            gas_limit: OuterScalarVar,
+            straight_line: bool,
+            range_check_elided: Vec<bool>,
+            shift_amounts: Vec<usize>,
         ) -> ZkProcessorGadget {
             ZkProcessorGadget {
                 cs,
                 instructions,
                 states,
-                current_time
+                current_time,
+                gas_limit,
+                straight_line,
+                range_check_elided,
+                shift_amounts,
             }
         }
 
@@ -928,10 +1608,23 @@ pub mod constraints {
             let one_const = OuterScalarVar::one();
 
             for cycle in 0..self.instructions.len() {
-                let inst = &self.instructions[cycle];
                 let state = &self.states[cycle];
                 let state_next = &self.states[cycle + 1];
 
+                // select the instruction actually executed this cycle. If the program has no
+                // control flow, `pc` is provably always `cycle` (cheaply re-checked below), so we
+                // can skip the one-hot fan-out over the whole program and index it directly;
+                // otherwise fall back to the general `select_instruction_at_pc` selector.
+                let inst = if self.straight_line {
+                    let expected_pc = const_fe(&self.cs, cycle as u64);
+                    let pc_ok = state.pc.is_eq(&expected_pc)?;
+                    enforce_true_with_info(&pc_ok, &format!("ZkProcessor - cycle {} - straight_line_pc_ok", cycle));
+                    self.instructions[cycle].clone()
+                } else {
+                    select_instruction_at_pc(cycle, &self.instructions, &state.pc)?
+                };
+                let inst = &inst;
+
                 // get the value of src_1 (access register or use constant)
                 let src_1 = select_2(&inst.src_1_is_const,
                     &inst.src_1,
@@ -939,6 +1632,9 @@ pub mod constraints {
                 )?;
                 dbg_var(&src_1);
 
+                // whether src_1 is zero (for JZ/JNZ's branch condition)
+                let src_1_is_zero: OuterScalarVar = src_1.clone().is_eq(&zero_const)?.into();  dbg_var(&src_1_is_zero);
+
                 // get the value of src_2 (access register or use constant)
                 let src_2 = select_2(&inst.src_2_is_const,
                     &inst.src_2,
@@ -961,6 +1657,65 @@ pub mod constraints {
                 let new_obj_sk = get_next_nonzero(cycle, &state.new_obj_sks, &state_next.new_obj_sks, &inst.op_is_new)?;    dbg_var(&new_obj_sk);
                 let new_obj_addr = get_next_nonzero(cycle, &state.new_obj_addrs, &state_next.new_obj_addrs, &inst.op_is_new)?;    dbg_var(&new_obj_addr);
 
+                // witness the quotient/remainder produced by the most recently executed DIV/MOD
+                // (see ZkProcessorPartialState::div_q/div_r) and check src_1 == div_q*src_2 + div_r
+                let div_q = state_next.div_q.clone();  dbg_var(&div_q);
+                let div_r = state_next.div_r.clone();  dbg_var(&div_r);
+                let is_div_or_mod = inst.op_is_div.clone().add(&inst.op_is_mod);
+                let div_identity_ok = src_1.clone().is_eq(&div_q.clone().mul(&src_2).add(&div_r))?.or(&is_div_or_mod.is_zero()?)?;
+                enforce_true_with_info(&div_identity_ok, &format!("ZkProcessor - cycle {} - div_identity_ok", cycle));
+
+                // div_q itself must be in range: div_identity_ok alone lets a malicious prover pick
+                // an out-of-range div_q (with a compensating, equally out-of-range src_2 or src_1)
+                // that still satisfies the identity but overflows dst when written by DIV
+                let div_q_in_range_ok = is_in_range(&div_q)?.or(&inst.op_is_div.is_zero()?)?;
+                enforce_true_with_info(&div_q_in_range_ok, &format!("ZkProcessor - cycle {} - div_q_in_range_ok", cycle));
+
+                // div_r < src_2, via range-checking src_2 - div_r - 1; this also rules out
+                // src_2 == 0, since no div_r satisfies div_r < 0
+                let (div_r_below_src_2, _) = enforce_range(&src_2.clone().sub(&div_r).sub(&one_const), MAX_BYTES_UINT*8)?;
+                let div_r_below_src_2_ok = div_r_below_src_2.or(&is_div_or_mod.is_zero()?)?;
+                enforce_true_with_info(&div_r_below_src_2_ok, &format!("ZkProcessor - cycle {} - div_r_below_src_2_ok", cycle));
+
+                // bitwise AND/OR/XOR: range-check both operands, then combine bit by bit
+                let is_bitwise = inst.op_is_and.clone().add(&inst.op_is_or).add(&inst.op_is_xor);
+                let (src_1_in_range, src_1_bits) = enforce_range(&src_1, MAX_BYTES_UINT*8)?;
+                let (src_2_in_range, src_2_bits) = enforce_range(&src_2, MAX_BYTES_UINT*8)?;
+                let bitwise_operands_in_range_ok = src_1_in_range.clone().and(&src_2_in_range)?.or(&is_bitwise.is_zero()?)?;
+                enforce_true_with_info(&bitwise_operands_in_range_ok, &format!("ZkProcessor - cycle {} - bitwise_operands_in_range_ok", cycle));
+                let bitwise_res = bitwise_op(&src_1_bits, &src_2_bits, &inst.op_is_and, &inst.op_is_or, &inst.op_is_xor)?;  dbg_var(&bitwise_res);
+
+                // SHL/SHR: range-check src_1 (reusing its bit decomposition from above), then
+                // re-weight those bits by the compile-time-constant shift amount `run` was built
+                // with (see `compute_shift_amounts`); only trustworthy when `straight_line`, same
+                // as `range_check_elided` (see its doc comment on `ZkProcessorGadget`)
+                let is_shift = inst.op_is_shl.clone().add(&inst.op_is_shr);
+                let shift_src_1_in_range_ok = src_1_in_range.or(&is_shift.is_zero()?)?;
+                enforce_true_with_info(&shift_src_1_in_range_ok, &format!("ZkProcessor - cycle {} - shift_src_1_in_range_ok", cycle));
+                let shift_amount = if self.straight_line { self.shift_amounts.get(cycle).copied().unwrap_or(0) } else { 0 };
+                let shl_res = shift_op(&self.cs, &src_1_bits, shift_amount, true);  dbg_var(&shl_res);
+                let shr_res = shift_op(&self.cs, &src_1_bits, shift_amount, false);  dbg_var(&shr_res);
+
+                // charge gas for the executed opcode (see opcode_gas_cost) and check the running
+                // total never exceeds gas_limit
+                let mem_op_cost = const_fe(&self.cs, GAS_COST_MEM_OP);
+                let light_op_cost = const_fe(&self.cs, GAS_COST_LIGHT_OP);
+                let is_mem_op = inst.op_is_load.clone().add(&inst.op_is_store).add(&inst.op_is_new).add(&inst.op_is_kill);
+                let is_light_op = inst.op_is_mov.clone()
+                    .add(&inst.op_is_cmov).add(&inst.op_is_add).add(&inst.op_is_sub).add(&inst.op_is_mul)
+                    .add(&inst.op_is_eq).add(&inst.op_is_lt).add(&inst.op_is_req).add(&inst.op_is_cid)
+                    .add(&inst.op_is_fresh).add(&inst.op_is_now).add(&inst.op_is_pk).add(&inst.op_is_jmp)
+                    .add(&inst.op_is_jz).add(&inst.op_is_jnz).add(&inst.op_is_halt).add(&inst.op_is_div)
+                    .add(&inst.op_is_mod).add(&inst.op_is_and).add(&inst.op_is_or).add(&inst.op_is_xor)
+                    .add(&inst.op_is_gas).add(&inst.op_is_shl).add(&inst.op_is_shr);
+                let gas_cost = is_mem_op.mul(&mem_op_cost).add(&is_light_op.mul(&light_op_cost));  dbg_var(&gas_cost);
+                let gas_used_next_expected = state.gas_used.clone().add(&gas_cost);
+                enforce_true_with_info(&state_next.gas_used.is_eq(&gas_used_next_expected)?, &format!("ZkProcessor - cycle {} - gas_used_ok", cycle));
+                // remaining budget after charging this instruction, witnessed by GAS (see op_res below)
+                let gas_remaining = self.gas_limit.clone().sub(&state_next.gas_used);  dbg_var(&gas_remaining);
+                let (gas_within_limit, _) = enforce_range(&gas_remaining, MAX_BYTES_UINT*8)?;
+                enforce_true_with_info(&gas_within_limit, &format!("ZkProcessor - cycle {} - gas_within_limit", cycle));
+
                 // check whether condition (stored at src_1) is true (for CMOV and REQ)
                 let condition_ok: OuterScalarVar = src_1.clone().is_eq(&one_const)?.into(); dbg_var(&condition_ok);
 
@@ -988,15 +1743,26 @@ pub mod constraints {
                     .add(obj_field.mul(&inst.op_is_load.clone().add(&inst.op_is_cid).add(&inst.op_is_pk)))
                     .add(fresh_val.mul(&inst.op_is_fresh))
                     .add(new_oid.clone().mul(&inst.op_is_new))
-                    .add(self.current_time.clone().mul(&inst.op_is_now));
+                    .add(self.current_time.clone().mul(&inst.op_is_now))
+                    .add(div_q.mul(&inst.op_is_div))
+                    .add(div_r.mul(&inst.op_is_mod))
+                    .add(bitwise_res.mul(&is_bitwise))
+                    .add(shl_res.mul(&inst.op_is_shl))
+                    .add(shr_res.mul(&inst.op_is_shr))
+                    .add(gas_remaining.mul(&inst.op_is_gas));
                 dbg_var(&op_res);
 
-                // ensure operation did not result in an under- or overflow for ADD, MUL, SUB
-                let op_res_in_range = is_in_range(&op_res)?;
-                let op_res_ok = op_res_in_range.or(
-                    &inst.op_is_add.clone().add(&inst.op_is_sub).add(&inst.op_is_mul).is_zero()?
-                )?;
-                enforce_true_with_info(&op_res_ok, &format!("ZkProcessor - cycle {} - op_res_ok", cycle));
+                // ensure operation did not result in an under- or overflow for ADD, MUL, SUB;
+                // skipped entirely (no bit-decomposition gadget emitted) on straight-line cycles
+                // `infer_range_safe_cycles` has proven provably in range regardless of operands
+                let range_check_elided = self.straight_line && self.range_check_elided.get(cycle).copied().unwrap_or(false);
+                if !range_check_elided {
+                    let op_res_in_range = is_in_range(&op_res)?;
+                    let op_res_ok = op_res_in_range.or(
+                        &inst.op_is_add.clone().add(&inst.op_is_sub).add(&inst.op_is_mul).is_zero()?
+                    )?;
+                    enforce_true_with_info(&op_res_ok, &format!("ZkProcessor - cycle {} - op_res_ok", cycle));
+                }
 
                 // check whether the current operation modifies the dst register (arithmetics, moves, LOAD, CID, FRESH, NEW)
                 let is_write_dst = inst.op_is_mov.clone()
@@ -1011,7 +1777,15 @@ pub mod constraints {
                     .add(&inst.op_is_fresh)
                     .add(&inst.op_is_new)
                     .add(&inst.op_is_now)
-                    .add(&inst.op_is_pk);
+                    .add(&inst.op_is_pk)
+                    .add(&inst.op_is_div)
+                    .add(&inst.op_is_mod)
+                    .add(&inst.op_is_and)
+                    .add(&inst.op_is_or)
+                    .add(&inst.op_is_xor)
+                    .add(&inst.op_is_gas)
+                    .add(&inst.op_is_shl)
+                    .add(&inst.op_is_shr);
                 dbg_var(&is_write_dst);
                 
                 // check correct update of dst (changed for dst-modifying operations, unchanged for all others)
@@ -1033,6 +1807,19 @@ pub mod constraints {
                 let field_or_cid = src_1.mul(&inst.op_is_new).add(src_2.mul(&one_const.clone().sub(&inst.op_is_new)));
                 check_obj_data(cycle, &state.obj_data, &state_next.obj_data, &oid, &field_or_cid, &dst, &new_obj_sk, &new_obj_addr, &inst.op_is_store, &inst.op_is_kill, &inst.op_is_new)?;
 
+                // compute the next pc: `dst` for JMP, `dst` or pc+1 for JZ/JNZ depending on
+                // whether src_1 is zero (computed above), frozen at pc for HALT, pc+1 otherwise
+                let branch_taken = inst.op_is_jmp.clone()
+                    .add(inst.op_is_jz.clone().mul(&src_1_is_zero))
+                    .add(inst.op_is_jnz.clone().mul(&one_const.clone().sub(&src_1_is_zero)));
+                dbg_var(&branch_taken);
+                let advance = one_const.clone().sub(&branch_taken).sub(&inst.op_is_halt);
+                let pc_next_expected = branch_taken.mul(&inst.dst)
+                    .add(inst.op_is_halt.clone().mul(&state.pc))
+                    .add(advance.mul(&state.pc.clone().add(&one_const)));
+                dbg_var(&pc_next_expected);
+                enforce_true_with_info(&state_next.pc.is_eq(&pc_next_expected)?, &format!("ZkProcessor - cycle {} - pc_next_ok", cycle));
+
                 dbg_ensure_satisfied(&self.cs, &format!("processor - cycle {}", cycle));
             }
             Ok(())
@@ -1056,13 +1843,17 @@ mod tests {
         let initial_state = ZkProcessorPartialState::default();
         let instructions: Vec<_> = (0..NOF_PROCESSOR_CYCLES).map(|_| ZkInstruction::default()).collect();
         let mut memory = LinearMemory::default();
-        processor.run_with_memory(&mut memory, instructions, initial_state, OuterScalarField::from(77));
+        processor.run_with_memory(&mut memory, instructions, initial_state, OuterScalarField::from(77)).unwrap();
 
         let cs: ConstraintSystemRef<OuterScalarField> = ConstraintSystem::new_ref();
         let gadget = ZkProcessorGadget::new(cs.clone(),
             processor.get_instructions_var(cs.clone(), AllocationMode::Witness).unwrap(),
             processor.get_states_var(cs.clone(), AllocationMode::Witness).unwrap(),
-            processor.get_current_time_var(cs.clone(), AllocationMode::Input).unwrap());
+            processor.get_current_time_var(cs.clone(), AllocationMode::Input).unwrap(),
+            processor.get_gas_limit_var(cs.clone(), AllocationMode::Input).unwrap(),
+            !program_has_control_flow(&processor.instructions),
+            crate::infrastructure::optimizer::infer_range_safe_cycles(&processor.instructions),
+            compute_shift_amounts(&processor.instructions));
         gadget.run().unwrap();
         assert!(cs.is_satisfied().unwrap());
         println!("number of registers:  {}", NOF_PROCESSOR_REGISTERS);
@@ -1096,7 +1887,7 @@ mod tests {
         ];
         // ---------------
         let mut memory = LinearMemory::default();
-        processor.run_with_memory(&mut memory, instructions, initial_state, OuterScalarField::from(77));
+        processor.run_with_memory(&mut memory, instructions, initial_state, OuterScalarField::from(77)).unwrap();
 
         let res = processor.get_result_state();
         assert_eq!(res.partial.registers[0], OuterScalarField::from(40u64));
@@ -1110,7 +1901,11 @@ mod tests {
         let gadget = ZkProcessorGadget::new(cs.clone(),
             processor.get_instructions_var(cs.clone(), AllocationMode::Witness).unwrap(),
             processor.get_states_var(cs.clone(), AllocationMode::Witness).unwrap(),
-            processor.get_current_time_var(cs.clone(), AllocationMode::Input).unwrap());
+            processor.get_current_time_var(cs.clone(), AllocationMode::Input).unwrap(),
+            processor.get_gas_limit_var(cs.clone(), AllocationMode::Input).unwrap(),
+            !program_has_control_flow(&processor.instructions),
+            crate::infrastructure::optimizer::infer_range_safe_cycles(&processor.instructions),
+            compute_shift_amounts(&processor.instructions));
         gadget.run().unwrap();
         assert!(cs.is_satisfied().unwrap());
     }
@@ -1127,7 +1922,7 @@ mod tests {
    
         ];
         let mut memory = LinearMemory::default();
-        processor.run_with_memory(&mut memory, instructions, initial_state, OuterScalarField::from(77));
+        processor.run_with_memory(&mut memory, instructions, initial_state, OuterScalarField::from(77)).unwrap();
     }
 
     #[test]
@@ -1169,7 +1964,7 @@ mod tests {
             ZkInstruction { opcode: OPCODE_PK, dst: 0, src_1: Const(OuterScalarField::from(222)), src_2: Reg(0)},   
         ];
         // ---------------
-        processor.run_with_memory(&mut memory, instructions, initial_state, OuterScalarField::from(77));
+        processor.run_with_memory(&mut memory, instructions, initial_state, OuterScalarField::from(77)).unwrap();
 
         let res = processor.get_result_state();
         assert_eq!(res.obj_data[0].payload[1], OuterScalarField::from(9));
@@ -1187,7 +1982,11 @@ mod tests {
         let gadget = ZkProcessorGadget::new(cs.clone(),
             processor.get_instructions_var(cs.clone(), AllocationMode::Witness).unwrap(),
             processor.get_states_var(cs.clone(), AllocationMode::Witness).unwrap(),
-            processor.get_current_time_var(cs.clone(), AllocationMode::Input).unwrap());
+            processor.get_current_time_var(cs.clone(), AllocationMode::Input).unwrap(),
+            processor.get_gas_limit_var(cs.clone(), AllocationMode::Input).unwrap(),
+            !program_has_control_flow(&processor.instructions),
+            crate::infrastructure::optimizer::infer_range_safe_cycles(&processor.instructions),
+            compute_shift_amounts(&processor.instructions));
         gadget.run().unwrap();
         assert!(cs.is_satisfied().unwrap());
     }
@@ -1206,7 +2005,7 @@ mod tests {
             ZkInstruction { opcode: OPCODE_FRESH, dst: 1, src_1: Reg(0), src_2: Reg(0) },
         ];
         // ---------------
-        processor.run_with_memory(&mut memory, instructions, initial_state, OuterScalarField::from(77));
+        processor.run_with_memory(&mut memory, instructions, initial_state, OuterScalarField::from(77)).unwrap();
 
         let res = processor.get_result_state();
         assert_eq!(res.partial.registers[0], OuterScalarField::from(6u64));
@@ -1216,8 +2015,186 @@ mod tests {
         let gadget = ZkProcessorGadget::new(cs.clone(),
             processor.get_instructions_var(cs.clone(), AllocationMode::Witness).unwrap(),
             processor.get_states_var(cs.clone(), AllocationMode::Witness).unwrap(),
-            processor.get_current_time_var(cs.clone(), AllocationMode::Input).unwrap());
+            processor.get_current_time_var(cs.clone(), AllocationMode::Input).unwrap(),
+            processor.get_gas_limit_var(cs.clone(), AllocationMode::Input).unwrap(),
+            !program_has_control_flow(&processor.instructions),
+            crate::infrastructure::optimizer::infer_range_safe_cycles(&processor.instructions),
+            compute_shift_amounts(&processor.instructions));
+        gadget.run().unwrap();
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn test_processor_bitwise_and_shift() {
+        let mut processor = ZkProcessor::default();
+        let mut initial_state = ZkProcessorPartialState::default();
+        // --- args ---
+        initial_state.registers[5] = OuterScalarField::from(12u64); // 0b1100
+        // ------------
+
+        // --- program ---
+        let instructions = vec![
+            ZkInstruction { opcode: OPCODE_MOV, dst: 0, src_1: Const(OuterScalarField::from(10u64)), src_2: Reg(0) }, // 0b1010
+            ZkInstruction { opcode: OPCODE_AND, dst: 1, src_1: Reg(5), src_2: Reg(0) },
+            ZkInstruction { opcode: OPCODE_OR, dst: 2, src_1: Reg(5), src_2: Reg(0) },
+            ZkInstruction { opcode: OPCODE_XOR, dst: 3, src_1: Reg(5), src_2: Reg(0) },
+            ZkInstruction { opcode: OPCODE_SHL, dst: 4, src_1: Reg(5), src_2: Const(OuterScalarField::from(2u64)) },
+            ZkInstruction { opcode: OPCODE_SHR, dst: 6, src_1: Reg(5), src_2: Const(OuterScalarField::from(2u64)) },
+        ];
+        // ---------------
+        let mut memory = LinearMemory::default();
+        processor.run_with_memory(&mut memory, instructions, initial_state, OuterScalarField::from(77)).unwrap();
+
+        let res = processor.get_result_state();
+        assert_eq!(res.partial.registers[1], OuterScalarField::from(8u64));  // 12 & 10
+        assert_eq!(res.partial.registers[2], OuterScalarField::from(14u64)); // 12 | 10
+        assert_eq!(res.partial.registers[3], OuterScalarField::from(6u64));  // 12 ^ 10
+        assert_eq!(res.partial.registers[4], OuterScalarField::from(48u64)); // 12 << 2
+        assert_eq!(res.partial.registers[6], OuterScalarField::from(3u64));  // 12 >> 2
+
+        let cs: ConstraintSystemRef<OuterScalarField> = ConstraintSystem::new_ref();
+        let gadget = ZkProcessorGadget::new(cs.clone(),
+            processor.get_instructions_var(cs.clone(), AllocationMode::Witness).unwrap(),
+            processor.get_states_var(cs.clone(), AllocationMode::Witness).unwrap(),
+            processor.get_current_time_var(cs.clone(), AllocationMode::Input).unwrap(),
+            processor.get_gas_limit_var(cs.clone(), AllocationMode::Input).unwrap(),
+            !program_has_control_flow(&processor.instructions),
+            crate::infrastructure::optimizer::infer_range_safe_cycles(&processor.instructions),
+            compute_shift_amounts(&processor.instructions));
         gadget.run().unwrap();
         assert!(cs.is_satisfied().unwrap());
     }
+
+    #[test]
+    fn test_processor_shift_by_register_traps() {
+        let mut processor = ZkProcessor::default();
+        let mut initial_state = ZkProcessorPartialState::default();
+        initial_state.registers[5] = OuterScalarField::from(12u64);
+        initial_state.registers[1] = OuterScalarField::from(2u64);
+        let instructions = vec![
+            ZkInstruction { opcode: OPCODE_SHL, dst: 0, src_1: Reg(5), src_2: Reg(1) },
+        ];
+        let mut memory = LinearMemory::default();
+        let err = processor.run_with_memory(&mut memory, instructions, initial_state, OuterScalarField::from(77)).unwrap_err();
+        assert_eq!(err.kind, ZkTrapKind::ShiftAmountNotConstant);
+    }
+
+    #[test]
+    fn test_grand_product_matches_for_consistent_read_write_sequence() {
+        let cs: ConstraintSystemRef<OuterScalarField> = ConstraintSystem::new_ref();
+        let alpha = OuterScalarVar::new_input(cs.clone(), || Ok(OuterScalarField::from(1000u64))).unwrap();
+        let beta = OuterScalarVar::new_input(cs.clone(), || Ok(OuterScalarField::from(7u64))).unwrap();
+        let var = |x: u64| OuterScalarVar::new_witness(cs.clone(), || Ok(OuterScalarField::from(x))).unwrap();
+
+        // address 3 initialized to 0 at time 0, written to 9 at time 1, then read back at time 2;
+        // every access reads the tuple it finds and writes a fresh one stamped with its own time
+        let mut write_set = GrandProduct::one(cs.clone());
+        write_set.absorb(&memory_fingerprint(&var(3), &var(0), &var(0), &alpha, &beta));  // initial memory
+        write_set.absorb(&memory_fingerprint(&var(3), &var(9), &var(1), &alpha, &beta));  // the STORE
+        write_set.absorb(&memory_fingerprint(&var(3), &var(9), &var(2), &alpha, &beta));  // the LOAD's write-back
+
+        let mut read_set = GrandProduct::one(cs.clone());
+        read_set.absorb(&memory_fingerprint(&var(3), &var(0), &var(0), &alpha, &beta));    // the STORE's read of the old value
+        read_set.absorb(&memory_fingerprint(&var(3), &var(9), &var(1), &alpha, &beta));    // the subsequent LOAD
+        read_set.absorb(&memory_fingerprint(&var(3), &var(9), &var(2), &alpha, &beta));    // final memory (unchanged since)
+
+        read_set.running_product.is_eq(&write_set.running_product).unwrap().enforce_equal(&Boolean::TRUE).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn test_grand_product_mismatches_for_a_skipped_write() {
+        let cs: ConstraintSystemRef<OuterScalarField> = ConstraintSystem::new_ref();
+        let alpha = OuterScalarVar::new_input(cs.clone(), || Ok(OuterScalarField::from(1000u64))).unwrap();
+        let beta = OuterScalarVar::new_input(cs.clone(), || Ok(OuterScalarField::from(7u64))).unwrap();
+        let var = |x: u64| OuterScalarVar::new_witness(cs.clone(), || Ok(OuterScalarField::from(x))).unwrap();
+
+        let mut write_set = GrandProduct::one(cs.clone());
+        write_set.absorb(&memory_fingerprint(&var(3), &var(0), &var(0), &alpha, &beta));
+
+        // a LOAD claiming to observe a value that was never actually written
+        let mut read_set = GrandProduct::one(cs.clone());
+        read_set.absorb(&memory_fingerprint(&var(3), &var(9), &var(1), &alpha, &beta));
+
+        assert_ne!(read_set.running_product.value().unwrap(), write_set.running_product.value().unwrap());
+    }
+
+    #[test]
+    fn test_enforce_signed_range_accepts_in_window_values() {
+        let cs: ConstraintSystemRef<OuterScalarField> = ConstraintSystem::new_ref();
+        let pos = OuterScalarVar::new_witness(cs.clone(), || Ok(signed_fe_from_i128(100))).unwrap();
+        let neg = OuterScalarVar::new_witness(cs.clone(), || Ok(signed_fe_from_i128(-100))).unwrap();
+
+        enforce_signed_range(&pos, 1).unwrap().enforce_equal(&Boolean::TRUE).unwrap();
+        enforce_signed_range(&neg, 1).unwrap().enforce_equal(&Boolean::TRUE).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn test_enforce_signed_range_rejects_out_of_window_value() {
+        let cs: ConstraintSystemRef<OuterScalarField> = ConstraintSystem::new_ref();
+        // 200 doesn't fit in a signed 1-byte window ([-128, 127])
+        let out_of_range = OuterScalarVar::new_witness(cs.clone(), || Ok(signed_fe_from_i128(200))).unwrap();
+
+        let in_range = enforce_signed_range(&out_of_range, 1).unwrap();
+        assert!(!in_range.value().unwrap());
+    }
+
+    #[test]
+    fn test_logup_argument_accepts_values_within_table() {
+        let cs: ConstraintSystemRef<OuterScalarField> = ConstraintSystem::new_ref();
+        let gamma = OuterScalarVar::new_input(cs.clone(), || Ok(OuterScalarField::from(1000u64))).unwrap();
+        let table = range_table(cs.clone(), 3);   // [0, 8)
+
+        let mut arg = LogUpArgument::new(gamma);
+        let witnessed = [3u64, 5, 3];
+        for v in witnessed {
+            arg.check_member(&OuterScalarVar::new_witness(cs.clone(), || Ok(OuterScalarField::from(v))).unwrap()).unwrap();
+        }
+        let multiplicities: Vec<_> = (0u64..8).map(|t| {
+            let count = witnessed.iter().filter(|&&v| v == t).count() as u64;
+            OuterScalarVar::new_witness(cs.clone(), || Ok(OuterScalarField::from(count))).unwrap()
+        }).collect();
+
+        arg.enforce_consistent(&table, &multiplicities).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn test_logup_argument_rejects_value_outside_table() {
+        let cs: ConstraintSystemRef<OuterScalarField> = ConstraintSystem::new_ref();
+        let gamma = OuterScalarVar::new_input(cs.clone(), || Ok(OuterScalarField::from(1000u64))).unwrap();
+        let table = range_table(cs.clone(), 3);   // [0, 8)
+
+        let mut arg = LogUpArgument::new(gamma);
+        arg.check_member(&OuterScalarVar::new_witness(cs.clone(), || Ok(OuterScalarField::from(8u64))).unwrap()).unwrap();
+        // claim zero uses of every table entry, which can't actually balance the sums
+        let multiplicities: Vec<_> = (0u64..8).map(|_| OuterScalarVar::new_witness(cs.clone(), || Ok(OuterScalarField::zero())).unwrap()).collect();
+
+        arg.enforce_consistent(&table, &multiplicities).unwrap();
+        assert!(!cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn test_ext_field_native_and_var_inverse_agree_and_invert() {
+        let x = ExtField::new(OuterScalarField::from(3u64), OuterScalarField::from(5u64));
+        let x_inv = x.inverse().unwrap();
+        assert_eq!(x.mul(&x_inv), ExtField::one());
+
+        let cs: ConstraintSystemRef<OuterScalarField> = ConstraintSystem::new_ref();
+        let x_var = ExtFieldVar::constant(cs.clone(), x);
+        let x_inv_var = x_var.inverse().unwrap();
+        assert_eq!(x_inv_var.a.value().unwrap(), x_inv.a);
+        assert_eq!(x_inv_var.b.value().unwrap(), x_inv.b);
+
+        let one = x_var.mul(&x_inv_var);
+        one.a.is_eq(&OuterScalarVar::one()).unwrap().enforce_equal(&Boolean::TRUE).unwrap();
+        one.b.is_eq(&OuterScalarVar::zero()).unwrap().enforce_equal(&Boolean::TRUE).unwrap();
+        assert!(cs.is_satisfied().unwrap());
+    }
+
+    #[test]
+    fn test_ext_field_zero_has_no_inverse() {
+        assert!(ExtField::zero().inverse().is_none());
+    }
 }
\ No newline at end of file
@@ -1,11 +1,14 @@
 use std::io::{Read, Write};
 use ark_crypto_primitives::encryption::elgamal;
-use ark_ff::{ToBytes, FromBytes};
+use ark_ff::{ToBytes, FromBytes, to_bytes};
 use ark_std::{One, Zero};
 use std::fmt::Debug;
 use rand::Rng;
-use crate::common::{InnerEdScalarField, fe_to_be_hex_str};
+use zeroize::Zeroize;
+use crate::common::{InnerEdScalarField, fe_to_be_hex_str, fe_from_be_hex_str, FeFromLeBytesConverter, FeConverter};
 use crate::crypto::elgamal_ext::ExtSecretKey;
+use crate::crypto::elgamal_dleq::{ElGamalDleq, DecryptionProof};
+use crate::infrastructure::identities::ViewingKey;
 use crate::crypto::poseidon::{HybridPoseidonParams, HybridPoseidonCipher, HybridPoseidonCiphertext, PoseidonCiphertext};
 use crate::{common::{InnerEdAffine, OuterScalarField, InnerEdProjective}};
 use crate::constants::*;
@@ -15,8 +18,62 @@ use super::processor::ObjectData;
 pub type Serial = [u8; SN_BYTES];
 pub type ObjectId = OuterScalarField;
 
-/// The number of field elements required to represent a record
-pub const RECORD_CHUNKS: usize = 6 + NOF_RECORD_PAYLOAD_ELEMENTS;
+/// The number of bytes carried by a record's optional memo.
+pub const MEMO_BYTES: usize = 512;
+
+/// The number of plaintext bytes packed into a single field element chunk of the memo
+/// (31 bytes fit into an `OuterScalarField`, which is slightly below 32 bytes wide).
+const MEMO_CHUNK_BYTES: usize = 31;
+
+/// The number of field elements required to pack the memo, including a leading
+/// presence byte used to distinguish "no memo" from a present, all-zero memo.
+pub const RECORD_MEMO_ELEMENTS: usize = (1 + MEMO_BYTES + MEMO_CHUNK_BYTES - 1) / MEMO_CHUNK_BYTES;
+
+/// An opaque, application-defined memo attached to a `Record`.
+/// Travels confidentially alongside the record, encrypted under the same hybrid scheme.
+#[derive(Clone, PartialEq, Eq)]
+pub struct Memo(pub Box<[u8; MEMO_BYTES]>);
+
+impl Debug for Memo {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Memo({})", hex::encode(self.0.as_ref()))
+    }
+}
+
+/// Packs an (optional) memo into `RECORD_MEMO_ELEMENTS` field elements.
+fn pack_memo(memo: &Option<Memo>) -> [OuterScalarField; RECORD_MEMO_ELEMENTS] {
+    let mut bytes = vec![0u8; RECORD_MEMO_ELEMENTS * MEMO_CHUNK_BYTES];
+    if let Some(memo) = memo {
+        bytes[0] = 1;
+        bytes[1..1 + MEMO_BYTES].copy_from_slice(memo.0.as_ref());
+    }
+    let mut elems = [OuterScalarField::zero(); RECORD_MEMO_ELEMENTS];
+    for i in 0..RECORD_MEMO_ELEMENTS {
+        let chunk = &bytes[i * MEMO_CHUNK_BYTES..(i + 1) * MEMO_CHUNK_BYTES];
+        elems[i] = FeConverter::from_le_bytes(chunk).expect("memo chunk always fits a field element");
+    }
+    elems
+}
+
+/// Reverses `pack_memo`, recovering `None` if no memo was present.
+fn unpack_memo(elems: &[OuterScalarField]) -> Option<Memo> {
+    assert_eq!(elems.len(), RECORD_MEMO_ELEMENTS);
+    let mut bytes = vec![0u8; RECORD_MEMO_ELEMENTS * MEMO_CHUNK_BYTES];
+    for (i, elem) in elems.iter().enumerate() {
+        let elem_bytes = to_bytes!(elem).unwrap();
+        bytes[i * MEMO_CHUNK_BYTES..(i + 1) * MEMO_CHUNK_BYTES].copy_from_slice(&elem_bytes[..MEMO_CHUNK_BYTES]);
+    }
+    if bytes[0] == 0 {
+        return None;
+    }
+    let mut data = [0u8; MEMO_BYTES];
+    data.copy_from_slice(&bytes[1..1 + MEMO_BYTES]);
+    Some(Memo(Box::new(data)))
+}
+
+/// The number of field elements required to represent a record, including the trailing
+/// diversifier element (see `Record::diversifier`).
+pub const RECORD_CHUNKS: usize = 6 + NOF_RECORD_PAYLOAD_ELEMENTS + RECORD_MEMO_ELEMENTS + 1;
 
 /// The number of field elements required to represent a record, padded to a multiple of 3
 pub const RECORD_CHUNKS_PADDED: usize = ((RECORD_CHUNKS + 2) / 3) * 3;    // round up to nearest multiple of 3
@@ -46,6 +103,16 @@ pub struct Record {
 
     /// The payload of this object, including all fields except the owner address
     pub payload: [OuterScalarField; NOF_RECORD_PAYLOAD_ELEMENTS],
+
+    /// An optional, opaque application-defined memo (e.g. a payment reference or tag),
+    /// encrypted together with the rest of the record.
+    pub memo: Option<Memo>,
+
+    /// The diversifier `addr_owner` was derived under (see
+    /// `derivations::derive_diversified_addr`). Zero means `addr_owner` is the plain,
+    /// reconstructable `derivations::get_addr_for_pk` address; any other value makes
+    /// `addr_owner` one of many unlinkable diversified addresses for the same owner key.
+    pub diversifier: OuterScalarField,
 }
 
 impl Debug for Record {
@@ -59,17 +126,126 @@ impl Debug for Record {
             .field("addr_object", &fe_to_be_hex_str(&self.addr_object))
             .field("addr_owner", &fe_to_be_hex_str(&self.addr_owner))
             .field("payload", &p)
+            .field("memo", &self.memo)
+            .field("diversifier", &fe_to_be_hex_str(&self.diversifier))
             .finish()
     }
 }
 
 pub type EncRandomness = (InnerEdScalarField, InnerEdAffine);
 
+impl Drop for Record {
+    fn drop(&mut self) {
+        self.serial_nonce.zeroize();
+        self.sk_object.zeroize();
+    }
+}
+
+/// Holds the field elements decrypted from an `EncryptedRecord` only as long as needed,
+/// scrubbing them from memory once the wrapper goes out of scope.
+struct ZeroizingFieldVec(Vec<OuterScalarField>);
+
+impl Drop for ZeroizingFieldVec {
+    fn drop(&mut self) {
+        self.0.zeroize();
+    }
+}
+
+/// A `Record` wrapped to make the caller's intent to scrub its secret fields on drop
+/// explicit at the type level. See `Record::into_zeroizing`.
+pub struct ZeroizingRecord(pub Record);
+
+/// A watch-only view of a decrypted record, returned by `Record::decrypt_with_viewing_key`.
+/// Omits `serial_nonce` and `sk_object`, the fields needed to consume the record or to
+/// decrypt records owned by it, so holding one grants no spend authority.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct WatchOnlyRecord {
+    pub contract_id: OuterScalarField,
+    pub object_id: OuterScalarField,
+    pub addr_object: OuterScalarField,
+    pub addr_owner: OuterScalarField,
+    pub payload: [OuterScalarField; NOF_RECORD_PAYLOAD_ELEMENTS],
+    pub memo: Option<Memo>,
+    pub diversifier: OuterScalarField,
+}
+
+impl WatchOnlyRecord {
+    pub fn is_dummy(&self) -> bool {
+        self.object_id.is_zero()
+    }
+}
+
+/// A verifiable, selective disclosure of a record's `contract_id`, `addr_owner`, and chosen
+/// payload fields, built by `Record::open` and checkable by anyone holding only the record's
+/// public `EncryptedRecord` (from the ledger) and the claimed owner's public key -- no secret
+/// key or viewing key changes hands. `proof` is a `crypto::elgamal_dleq::ElGamalDleq` proof
+/// that `shared_key` is indeed the one committed to by the `EncryptedRecord`'s ElGamal key
+/// part, so `verify` can trust `shared_key` without the owner ever revealing their secret key.
+///
+/// NOTE: this is DPC-style auditability, not a hiding selective disclosure: `shared_key` alone
+/// decrypts every field of the record, disclosed or not (see
+/// `HybridPoseidonCipher::decrypt_with_shared_key`), so `verify` necessarily recovers the whole
+/// plaintext to check it. `contract_id`/`addr_owner`/`disclosed_payload` only record which
+/// fields the discloser is vouching for -- a verifier who runs `verify` learns the rest of the
+/// record too, and should be trusted accordingly.
+#[derive(Clone, Debug)]
+pub struct RecordOpening {
+    pub object_id: OuterScalarField,
+    pub contract_id: OuterScalarField,
+    pub addr_owner: OuterScalarField,
+    pub disclosed_payload: Vec<(usize, OuterScalarField)>,
+    pub shared_key: InnerEdAffine,
+    pub proof: DecryptionProof,
+}
+
+impl RecordOpening {
+    /// Verifies this opening against the `enc_record` published on the ledger and the claimed
+    /// `owner_pk`: checks `proof` ties `shared_key` to `enc_record`'s ElGamal key part under
+    /// `owner_pk`, then decrypts the data part with `shared_key` and checks the disclosed
+    /// fields against the result.
+    pub fn verify(&self, enc_record: &EncryptedRecord, owner_pk: &InnerEdAffine, params: &HybridPoseidonParams) -> bool {
+        let (c1, c2) = enc_record.0.key_part;
+        if !ElGamalDleq::verify(params, owner_pk, &c1, &c2, &self.shared_key, &self.proof) {
+            return false;
+        }
+        let data = match HybridPoseidonCipher::decrypt_with_shared_key(
+            params,
+            &self.shared_key,
+            enc_record.0.data_part.nonce,
+            &enc_record.0.data_part.elems,
+            enc_record.0.data_part.msg_len,
+            &[],
+        ) {
+            Ok(data) => data,
+            Err(_) => return false,
+        };
+        let record = Record::from_plaintext_chunks(&data);
+        if record.contract_id != self.contract_id || record.object_id != self.object_id || record.addr_owner != self.addr_owner {
+            return false;
+        }
+        self.disclosed_payload.iter().all(|&(i, v)| record.payload[i] == v)
+    }
+}
+
+impl std::ops::Deref for ZeroizingRecord {
+    type Target = Record;
+
+    fn deref(&self) -> &Record {
+        &self.0
+    }
+}
+
 impl Record {
     pub fn is_dummy(&self) -> bool {
         self.object_id.is_zero()
     }
 
+    /// Packs this record's (optional) memo into `RECORD_MEMO_ELEMENTS` field elements,
+    /// in the same layout appended to the `data` vector by `encrypt()`.
+    pub fn memo_elems(&self) -> [OuterScalarField; RECORD_MEMO_ELEMENTS] {
+        pack_memo(&self.memo)
+    }
+
     pub fn encrypt<R: Rng>(&self, pk: &elgamal::PublicKey<InnerEdProjective>, params: &HybridPoseidonParams, rng: &mut R) -> (EncryptedRecord, EncRandomness) {
         let mut data = vec![
            self.serial_nonce,
@@ -80,17 +256,29 @@ impl Record {
            self.addr_owner
         ];
         data.extend_from_slice(&self.payload);
+        data.extend_from_slice(&pack_memo(&self.memo));
+        data.push(self.diversifier);
         assert_eq!(data.len(), RECORD_CHUNKS);
-        let (cipher, rand, shared_key) = HybridPoseidonCipher::encrypt_hybrid(params, pk, &data, rng);
-        (EncryptedRecord(cipher), (rand, shared_key))
+        let (cipher, rand, shared_key) = HybridPoseidonCipher::encrypt_hybrid(params, pk, &data, &[], rng);
+        let tag = HybridPoseidonCipher::derive_detection_tag(params, &shared_key);
+        (EncryptedRecord(cipher, tag), (rand, shared_key))
     }
 
     pub fn decrypt(enc_record: &EncryptedRecord, sk: &ExtSecretKey<InnerEdProjective>, params: &HybridPoseidonParams) -> Result<Record, ()> {
-        let data = HybridPoseidonCipher::decrypt_hybrid(params, &enc_record.0, &sk.0)?;
+        let data = ZeroizingFieldVec(HybridPoseidonCipher::decrypt_hybrid(params, &enc_record.0, &sk.0, &[])?);
+        Ok(Self::from_plaintext_chunks(&data.0))
+    }
+
+    /// Unpacks a record's plaintext chunk vector (the layout `encrypt` produces) into a
+    /// `Record`. Shared by `decrypt` (which recovers the chunks via the owner's secret key) and
+    /// `RecordOpening::verify` (which recovers them via a disclosed `shared_key` instead).
+    fn from_plaintext_chunks(data: &[OuterScalarField]) -> Record {
         assert_eq!(data.len(), RECORD_CHUNKS);
         let mut payload = [OuterScalarField::default(); NOF_RECORD_PAYLOAD_ELEMENTS];
-        payload.copy_from_slice(&data[RECORD_CHUNKS-NOF_RECORD_PAYLOAD_ELEMENTS..]);
-        Ok(Record {
+        payload.copy_from_slice(&data[6..6 + NOF_RECORD_PAYLOAD_ELEMENTS]);
+        let memo = unpack_memo(&data[RECORD_CHUNKS - 1 - RECORD_MEMO_ELEMENTS..RECORD_CHUNKS - 1]);
+        let diversifier = data[RECORD_CHUNKS - 1];
+        Record {
             serial_nonce: data[0],
             contract_id: data[1],
             object_id: data[2],
@@ -98,9 +286,61 @@ impl Record {
             addr_object: data[4],
             addr_owner: data[5],
             payload,
+            memo,
+            diversifier,
+        }
+    }
+
+    /// Wraps this record so that its secret-bearing fields (`serial_nonce`, `sk_object`)
+    /// are guaranteed to be scrubbed from memory once the wrapper is dropped, for callers
+    /// that want this made explicit at the type level (e.g. short-lived decrypted records).
+    pub fn into_zeroizing(self) -> ZeroizingRecord {
+        ZeroizingRecord(self)
+    }
+
+    /// Decrypts a record using only an incoming viewing key, recovering everything needed
+    /// to read the object's state while keeping spend-critical material (`serial_nonce`,
+    /// `sk_object`) out of reach of watch-only callers.
+    pub fn decrypt_with_viewing_key(enc_record: &EncryptedRecord, vk: &ViewingKey, params: &HybridPoseidonParams) -> Result<WatchOnlyRecord, ()> {
+        let record = Record::decrypt(enc_record, vk.as_ext_secret_key(), params)?;
+        Ok(WatchOnlyRecord {
+            contract_id: record.contract_id,
+            object_id: record.object_id,
+            addr_object: record.addr_object,
+            addr_owner: record.addr_owner,
+            payload: record.payload,
+            memo: record.memo.clone(),
+            diversifier: record.diversifier,
         })
     }
 
+    /// Builds a `RecordOpening` disclosing `contract_id`, `addr_owner`, and the payload elements
+    /// at `payload_indices`, provable against `enc_record` by anyone holding `owner_pk` alone --
+    /// see `RecordOpening` for what this does and doesn't hide. `sk` is the owner's secret key
+    /// (used only to construct the proof; never itself disclosed).
+    pub fn open<R: Rng>(
+        &self,
+        enc_record: &EncryptedRecord,
+        sk: &ExtSecretKey<InnerEdProjective>,
+        owner_pk: &InnerEdAffine,
+        params: &HybridPoseidonParams,
+        payload_indices: &[usize],
+        rng: &mut R,
+    ) -> RecordOpening {
+        let (c1, c2) = enc_record.0.key_part;
+        let shared_key = elgamal::ElGamal::<InnerEdProjective>::decrypt(&params.elgamal_params, &sk.0, &(c1, c2)).unwrap();
+        let proof = ElGamalDleq::prove(params, sk, owner_pk, &c1, &c2, &shared_key, rng);
+        let disclosed_payload = payload_indices.iter().map(|&i| (i, self.payload[i])).collect();
+        RecordOpening {
+            object_id: self.object_id,
+            contract_id: self.contract_id,
+            addr_owner: self.addr_owner,
+            disclosed_payload,
+            shared_key,
+            proof,
+        }
+    }
+
     pub fn to_object_data(&self) -> ObjectData {
         // dedicated position of owner address
         let mut payload = vec![self.addr_owner];
@@ -131,6 +371,12 @@ impl Record {
             addr_object: data.addr_object,
             addr_owner: data.payload[0],
             payload,
+            memo: None,
+            // the processor only tracks `addr_owner`, not which diversifier it was derived
+            // under; zero is the reserved "plain, reconstructable address" diversifier, which
+            // is what every on-chain-assigned owner address (another object's `addr_object`)
+            // actually is.
+            diversifier: OuterScalarField::zero(),
         }
     }
 }
@@ -144,7 +390,9 @@ impl Default for Record {
             sk_object: OuterScalarField::zero(),
             addr_object: OuterScalarField::zero(),
             addr_owner: OuterScalarField::zero(),
-            payload: [OuterScalarField::zero(); NOF_RECORD_PAYLOAD_ELEMENTS]
+            payload: [OuterScalarField::zero(); NOF_RECORD_PAYLOAD_ELEMENTS],
+            memo: None,
+            diversifier: OuterScalarField::zero(),
         }
     }
 }
@@ -160,6 +408,14 @@ impl ToBytes for Record {
         for p in self.payload.iter() {
             p.write(&mut writer)?;
         }
+        match &self.memo {
+            Some(memo) => {
+                1u8.write(&mut writer)?;
+                writer.write_all(memo.0.as_ref())?;
+            }
+            None => 0u8.write(&mut writer)?,
+        }
+        self.diversifier.write(&mut writer)?;
         Ok(())
     }
 }
@@ -176,6 +432,15 @@ impl FromBytes for Record {
         for i in 0..NOF_RECORD_PAYLOAD_ELEMENTS {
             payload[i] = OuterScalarField::read(&mut reader)?;
         }
+        let has_memo = u8::read(&mut reader)?;
+        let memo = if has_memo == 1 {
+            let mut bytes = [0u8; MEMO_BYTES];
+            reader.read_exact(&mut bytes)?;
+            Some(Memo(Box::new(bytes)))
+        } else {
+            None
+        };
+        let diversifier = OuterScalarField::read(&mut reader)?;
         Ok(
             Record {
                 serial_nonce,
@@ -184,14 +449,18 @@ impl FromBytes for Record {
                 sk_object,
                 addr_object,
                 addr_owner,
-                payload
+                payload,
+                memo,
+                diversifier,
             }
         )
     }
 }
 
+/// `HybridPoseidonCiphertext` plus a cheap, non-secret ownership-detection `tag`
+/// (see `Record::encrypt`/`EncryptedRecord::is_probably_mine`).
 #[derive(Clone, Debug, PartialEq, Eq)]
-pub struct EncryptedRecord(pub HybridPoseidonCiphertext);
+pub struct EncryptedRecord(pub HybridPoseidonCiphertext, pub OuterScalarField);
 
 impl Default for EncryptedRecord {
     fn default() -> Self {
@@ -203,11 +472,35 @@ impl Default for EncryptedRecord {
                 nonce: Default::default(),
                 msg_len: RECORD_CHUNKS
             },
-        })
+        }, Default::default())
+    }
+}
+
+impl EncryptedRecord {
+    /// Cheaply tests whether `sk` is likely the owner of this record, without running the
+    /// full hybrid decryption. Recomputes the ElGamal shared key (the single scalar mult
+    /// that `Record::decrypt` would have to do anyway) and compares the resulting detection
+    /// tag. Never false-negatives; a false positive just falls through to `Record::decrypt`.
+    pub fn is_probably_mine(&self, sk: &ExtSecretKey<InnerEdProjective>, params: &HybridPoseidonParams) -> bool {
+        let shared_key = match elgamal::ElGamal::<InnerEdProjective>::decrypt(&params.elgamal_params, &sk.0, &self.0.key_part) {
+            Ok(shared_key) => shared_key,
+            Err(_) => return false,
+        };
+        let tag = HybridPoseidonCipher::derive_detection_tag(params, &shared_key);
+        tag == self.1
+    }
+
+    /// ABI-encodes this record's field-element encoding (the same one `MainProofVerifier::verify`
+    /// folds into its public input vector) as a flat `uint256[]` calldata blob, via
+    /// `solidity_verifier::encode_public_inputs_calldata`.
+    pub fn to_calldata(&self) -> Vec<u8> {
+        use ark_ff::ToConstraintField;
+        use crate::infrastructure::solidity_verifier::encode_public_inputs_calldata;
+        encode_public_inputs_calldata(&self.to_field_elements().unwrap())
     }
 }
 
-pub const ENC_RECORD_BYTES: usize = FE_BYTES * (5 + RECORD_CHUNKS_PADDED + 1);
+pub const ENC_RECORD_BYTES: usize = FE_BYTES * (6 + RECORD_CHUNKS_PADDED + 1);
 
 impl ToBytes for EncryptedRecord {
     fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
@@ -217,6 +510,7 @@ impl ToBytes for EncryptedRecord {
         for i in 0..RECORD_CHUNKS_PADDED+1 {   // +1 as ciphertext has one additional chunk
             self.0.data_part.elems[i].write(&mut writer)?;
         }
+        self.1.write(&mut writer)?;
         // NOTE: as message length (RECORD_CHUNKS) is constant, so we do not serialize it
         Ok(())
     }
@@ -231,6 +525,7 @@ impl FromBytes for EncryptedRecord {
         for _ in 0..RECORD_CHUNKS_PADDED+1 {   // +1 as ciphertext has one additional chunk
             elems.push(OuterScalarField::read(&mut reader)?);
         }
+        let tag = OuterScalarField::read(&mut reader)?;
         Ok(EncryptedRecord(HybridPoseidonCiphertext {
             key_part: (key_part_0, key_part_1),
             data_part: PoseidonCiphertext {
@@ -238,7 +533,247 @@ impl FromBytes for EncryptedRecord {
                 nonce,
                 msg_len: RECORD_CHUNKS,
             },
-        }))
+        }, tag))
+    }
+}
+
+impl crate::infrastructure::versioned::VersionedBinary for EncryptedRecord {
+    const MAGIC: [u8; 4] = *b"ZENC";
+
+    fn write_body<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        self.write(writer)
+    }
+
+    fn read_body<R: Read>(reader: R) -> std::io::Result<Self> {
+        Self::read(reader)
+    }
+}
+
+/// Manual `serde` support for `Record` and `EncryptedRecord`.
+///
+/// Ark's `ToBytes`/`FromBytes` impls above are the canonical wire format, but they
+/// aren't usable from `serde`-based tooling (JSON APIs, config files, ...). Each field
+/// element is encoded as a big-endian hex string (reusing `fe_to_be_hex_str`/
+/// `fe_from_be_hex_str`), which stays reasonably compact under `bincode` (a
+/// length-prefixed byte string) while remaining human-readable under `serde_json`.
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct RecordHex {
+        serial_nonce: String,
+        contract_id: String,
+        object_id: String,
+        sk_object: String,
+        addr_object: String,
+        addr_owner: String,
+        payload: Vec<String>,
+        memo: Option<String>,
+        diversifier: String,
+    }
+
+    impl From<&Record> for RecordHex {
+        fn from(record: &Record) -> Self {
+            RecordHex {
+                serial_nonce: fe_to_be_hex_str(&record.serial_nonce),
+                contract_id: fe_to_be_hex_str(&record.contract_id),
+                object_id: fe_to_be_hex_str(&record.object_id),
+                sk_object: fe_to_be_hex_str(&record.sk_object),
+                addr_object: fe_to_be_hex_str(&record.addr_object),
+                addr_owner: fe_to_be_hex_str(&record.addr_owner),
+                payload: record.payload.iter().map(fe_to_be_hex_str).collect(),
+                memo: record.memo.as_ref().map(|memo| hex::encode(memo.0.as_ref())),
+                diversifier: fe_to_be_hex_str(&record.diversifier),
+            }
+        }
+    }
+
+    impl TryFrom<RecordHex> for Record {
+        type Error = String;
+
+        fn try_from(hex: RecordHex) -> Result<Self, Self::Error> {
+            if hex.payload.len() != NOF_RECORD_PAYLOAD_ELEMENTS {
+                return Err(format!("expected {} payload elements, got {}", NOF_RECORD_PAYLOAD_ELEMENTS, hex.payload.len()));
+            }
+            let mut payload = [OuterScalarField::zero(); NOF_RECORD_PAYLOAD_ELEMENTS];
+            for (i, elem) in hex.payload.iter().enumerate() {
+                payload[i] = fe_from_be_hex_str(elem);
+            }
+            let memo = match hex.memo {
+                Some(memo_hex) => {
+                    let bytes = hex::decode(memo_hex).map_err(|e| e.to_string())?;
+                    if bytes.len() != MEMO_BYTES {
+                        return Err(format!("expected {} memo bytes, got {}", MEMO_BYTES, bytes.len()));
+                    }
+                    let mut data = [0u8; MEMO_BYTES];
+                    data.copy_from_slice(&bytes);
+                    Some(Memo(Box::new(data)))
+                }
+                None => None,
+            };
+            Ok(Record {
+                serial_nonce: fe_from_be_hex_str(&hex.serial_nonce),
+                contract_id: fe_from_be_hex_str(&hex.contract_id),
+                object_id: fe_from_be_hex_str(&hex.object_id),
+                sk_object: fe_from_be_hex_str(&hex.sk_object),
+                addr_object: fe_from_be_hex_str(&hex.addr_object),
+                addr_owner: fe_from_be_hex_str(&hex.addr_owner),
+                payload,
+                memo,
+                diversifier: fe_from_be_hex_str(&hex.diversifier),
+            })
+        }
+    }
+
+    impl Serialize for Record {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            RecordHex::from(self).serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for Record {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let hex = RecordHex::deserialize(deserializer)?;
+            Record::try_from(hex).map_err(serde::de::Error::custom)
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct EncryptedRecordHex {
+        key_part_0_x: String,
+        key_part_0_y: String,
+        key_part_1_x: String,
+        key_part_1_y: String,
+        nonce: String,
+        elems: Vec<String>,
+        tag: String,
+    }
+
+    impl From<&EncryptedRecord> for EncryptedRecordHex {
+        fn from(enc_record: &EncryptedRecord) -> Self {
+            EncryptedRecordHex {
+                key_part_0_x: fe_to_be_hex_str(&enc_record.0.key_part.0.x),
+                key_part_0_y: fe_to_be_hex_str(&enc_record.0.key_part.0.y),
+                key_part_1_x: fe_to_be_hex_str(&enc_record.0.key_part.1.x),
+                key_part_1_y: fe_to_be_hex_str(&enc_record.0.key_part.1.y),
+                nonce: fe_to_be_hex_str(&enc_record.0.data_part.nonce),
+                elems: enc_record.0.data_part.elems.iter().map(fe_to_be_hex_str).collect(),
+                tag: fe_to_be_hex_str(&enc_record.1),
+            }
+        }
+    }
+
+    impl From<EncryptedRecordHex> for EncryptedRecord {
+        fn from(hex: EncryptedRecordHex) -> Self {
+            let key_part_0 = InnerEdAffine::new(fe_from_be_hex_str(&hex.key_part_0_x), fe_from_be_hex_str(&hex.key_part_0_y));
+            let key_part_1 = InnerEdAffine::new(fe_from_be_hex_str(&hex.key_part_1_x), fe_from_be_hex_str(&hex.key_part_1_y));
+            let nonce = fe_from_be_hex_str(&hex.nonce);
+            let elems = hex.elems.iter().map(|e| fe_from_be_hex_str(e)).collect();
+            let msg_len = RECORD_CHUNKS;
+            let tag = fe_from_be_hex_str(&hex.tag);
+            EncryptedRecord(HybridPoseidonCiphertext {
+                key_part: (key_part_0, key_part_1),
+                data_part: PoseidonCiphertext { elems, nonce, msg_len },
+            }, tag)
+        }
+    }
+
+    impl Serialize for EncryptedRecord {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            EncryptedRecordHex::from(self).serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for EncryptedRecord {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let hex = EncryptedRecordHex::deserialize(deserializer)?;
+            Ok(EncryptedRecord::from(hex))
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use ark_std::test_rng;
+        use ark_std::UniformRand;
+        use ark_crypto_primitives::encryption::{elgamal, AsymmetricEncryptionScheme};
+
+        use crate::infrastructure::derivations::get_addr_for_pk;
+
+        use super::*;
+
+        fn get_record() -> Record {
+            let mut rng = test_rng();
+            let mut payload = [OuterScalarField::zero(); NOF_RECORD_PAYLOAD_ELEMENTS];
+            for elem in payload.iter_mut() {
+                *elem = OuterScalarField::rand(&mut rng);
+            }
+            Record {
+                serial_nonce: OuterScalarField::rand(&mut rng),
+                contract_id: OuterScalarField::rand(&mut rng),
+                object_id: OuterScalarField::rand(&mut rng),
+                sk_object: OuterScalarField::rand(&mut rng),
+                addr_object: OuterScalarField::rand(&mut rng),
+                addr_owner: OuterScalarField::rand(&mut rng),
+                payload,
+                memo: Some(Memo(Box::new([0x42u8; MEMO_BYTES]))),
+                diversifier: OuterScalarField::rand(&mut rng),
+            }
+        }
+
+        #[test]
+        fn test_record_serde_json_round_trip() {
+            let record = get_record();
+            let json = serde_json::to_string(&record).unwrap();
+            let check_record: Record = serde_json::from_str(&json).unwrap();
+            assert_eq!(record, check_record);
+        }
+
+        #[test]
+        fn test_record_bincode_round_trip() {
+            let record = get_record();
+            let bytes = bincode::serialize(&record).unwrap();
+            let check_record: Record = bincode::deserialize(&bytes).unwrap();
+            assert_eq!(record, check_record);
+        }
+
+        #[test]
+        fn test_encrypted_record_serde_round_trip() {
+            let mut rng = test_rng();
+            let params = HybridPoseidonCipher::setup(&mut rng);
+            let (pk, _) = elgamal::ElGamal::<InnerEdProjective>::keygen(&params.elgamal_params, &mut rng).unwrap();
+
+            let mut record = get_record();
+            record.addr_owner = get_addr_for_pk(&pk);
+            let enc_record = record.encrypt(&pk, &params, &mut rng).0;
+
+            let json = serde_json::to_string(&enc_record).unwrap();
+            let check_enc_record: EncryptedRecord = serde_json::from_str(&json).unwrap();
+            assert_eq!(enc_record, check_enc_record);
+
+            let bytes = bincode::serialize(&enc_record).unwrap();
+            let check_enc_record: EncryptedRecord = bincode::deserialize(&bytes).unwrap();
+            assert_eq!(enc_record, check_enc_record);
+        }
+
+        #[test]
+        fn test_encrypted_record_versioned_bincode_round_trip() {
+            use crate::infrastructure::versioned::VersionedBincode;
+
+            let mut rng = test_rng();
+            let params = HybridPoseidonCipher::setup(&mut rng);
+            let (pk, _) = elgamal::ElGamal::<InnerEdProjective>::keygen(&params.elgamal_params, &mut rng).unwrap();
+
+            let mut record = get_record();
+            record.addr_owner = get_addr_for_pk(&pk);
+            let enc_record = record.encrypt(&pk, &params, &mut rng).0;
+
+            let bytes = enc_record.to_bincode();
+            let check_enc_record = EncryptedRecord::from_bincode(&bytes).unwrap();
+            assert_eq!(enc_record, check_enc_record);
+        }
     }
 }
 
@@ -266,6 +801,7 @@ mod tests {
         let sk_object = OuterScalarField::rand(&mut rng);
         let addr_object = OuterScalarField::rand(&mut rng);
         let addr_owner = OuterScalarField::rand(&mut rng);
+        let diversifier = OuterScalarField::rand(&mut rng);
 
         Record {
             serial_nonce,
@@ -274,7 +810,9 @@ mod tests {
             sk_object,
             addr_object,
             addr_owner,
-            payload
+            payload,
+            memo: None,
+            diversifier,
         }
     }
 
@@ -320,6 +858,29 @@ mod tests {
         assert_eq!(record, check_record);
     }
 
+    #[test]
+    fn test_decrypt_with_viewing_key() {
+        let mut rng = test_rng();
+
+        let params = HybridPoseidonCipher::setup(&mut rng);
+        let (pk, sk) = elgamal::ElGamal::<InnerEdProjective>::keygen(&params.elgamal_params, &mut rng).unwrap();
+        let sk = ExtSecretKey(sk);
+        let vk = ViewingKey::derive(&sk);
+
+        let mut record = get_record();
+        record.addr_owner = get_addr_for_pk(&pk);
+        let enc_record = record.encrypt(&pk, &params, &mut rng).0;
+
+        let watch_only = Record::decrypt_with_viewing_key(&enc_record, &vk, &params).unwrap();
+        assert_eq!(watch_only.contract_id, record.contract_id);
+        assert_eq!(watch_only.object_id, record.object_id);
+        assert_eq!(watch_only.addr_object, record.addr_object);
+        assert_eq!(watch_only.addr_owner, record.addr_owner);
+        assert_eq!(watch_only.payload, record.payload);
+        assert_eq!(watch_only.memo, record.memo);
+        assert_eq!(watch_only.diversifier, record.diversifier);
+    }
+
     #[test]
     fn test_decrypt_poseidon_garbage() {
         let mut rng = test_rng();
@@ -335,4 +896,54 @@ mod tests {
         let res = Record::decrypt(&enc_record, &sk_2, &params);     // using wrong key, giving garbage
         assert!(res.is_err());
     }
+
+    #[test]
+    fn test_is_probably_mine() {
+        let mut rng = test_rng();
+
+        let params = HybridPoseidonCipher::setup(&mut rng);
+        let (pk, sk) = elgamal::ElGamal::<InnerEdProjective>::keygen(&params.elgamal_params, &mut rng).unwrap();
+        let sk = ExtSecretKey(sk);
+        let (_, sk_2) = elgamal::ElGamal::<InnerEdProjective>::keygen(&params.elgamal_params, &mut rng).unwrap();
+        let sk_2 = ExtSecretKey(sk_2);
+
+        let mut record = get_record();
+        record.addr_owner = get_addr_for_pk(&pk);
+        let enc_record = record.encrypt(&pk, &params, &mut rng).0;
+
+        assert!(enc_record.is_probably_mine(&sk, &params));
+        assert!(!enc_record.is_probably_mine(&sk_2, &params));
+    }
+
+    #[test]
+    fn test_memo_to_from_bytes() {
+        let mut record = get_record();
+        let mut memo_bytes = [0u8; MEMO_BYTES];
+        memo_bytes[0] = 0xab;
+        memo_bytes[MEMO_BYTES - 1] = 0xcd;
+        record.memo = Some(Memo(Box::new(memo_bytes)));
+
+        let bytes = to_bytes!(record).unwrap();
+        let check_record = Record::read(bytes.as_slice()).unwrap();
+        assert_eq!(record, check_record);
+    }
+
+    #[test]
+    fn test_encrypt_decrypt_poseidon_record_with_memo() {
+        let mut rng = test_rng();
+
+        let params = HybridPoseidonCipher::setup(&mut rng);
+        let (pk, sk) = elgamal::ElGamal::<InnerEdProjective>::keygen(&params.elgamal_params, &mut rng).unwrap();
+        let sk = ExtSecretKey(sk);
+
+        let mut record = get_record();
+        record.addr_owner = get_addr_for_pk(&pk);
+        let mut memo_bytes = [0u8; MEMO_BYTES];
+        memo_bytes[10] = 0x42;
+        record.memo = Some(Memo(Box::new(memo_bytes)));
+
+        let enc_record = record.encrypt(&pk, &params, &mut rng).0;
+        let check_record = Record::decrypt(&enc_record, &sk, &params).unwrap();
+        assert_eq!(record, check_record);
+    }
 }
\ No newline at end of file
@@ -1,17 +1,23 @@
 use std::cell::RefCell;
 use std::collections::BTreeMap;
 use std::collections::BTreeSet;
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Write};
+use std::path::Path;
 use std::rc::Rc;
 use ark_crypto_primitives::encryption::elgamal::SecretKey;
-use ark_ff::to_bytes;
+use ark_ff::{to_bytes, FromBytes, ToBytes};
 use ark_gm17::ProvingKey;
 use ark_std::UniformRand;
 use ark_std::Zero;
 use log::debug;
 use rand::{Rng, CryptoRng};
+use rayon::prelude::*;
 use crate::crypto::elgamal_ext::derive_pk_from_sk;
-use crate::crypto::sparse_merkle_tree::SparseMerkleTree;
-use crate::infrastructure::identities::Identity;
+use crate::crypto::sparse_merkle_tree::{CheckpointId, SparseMerkleTree};
+use crate::crypto::rln::{self, RlnShare};
+use crate::crypto::spent_serials::{SmtNonMembershipPath, SpentSerialsCheckpointId, SpentSerialsSmt};
+use crate::infrastructure::identities::{Identity, IdentityKind, ViewOnlyIdentity, ViewingKey};
 
 use crate::common::*;
 use crate::constants::*;
@@ -22,6 +28,7 @@ use crate::infrastructure::derivations::*;
 use crate::time_measure;
 
 use super::circuit::{generate_main_proof, MainProof};
+use super::optimizer::{eliminate_dead_code, fold_constants};
 use super::params::{CryptoParams, MerkleTreeParams};
 use super::processor::{ZkInstruction, ZkProcessor, ZkProcessorPartialState, ZkProcessorState};
 
@@ -40,6 +47,9 @@ pub struct InRecordContext {
     pub encrypted: EncryptedRecord,
     /// the merkle tree path for the input record
     pub path: MerkleTreePath,
+    /// the spent-serials non-membership path proving `sn` was not already spent (see
+    /// `crypto::spent_serials`)
+    pub spent_serials_path: SmtNonMembershipPath,
 }
 
 impl InRecordContext {
@@ -58,7 +68,8 @@ impl Default for InRecordContext {
             sn: Default::default(),
             plaintext: Default::default(),  // this is a dummy record by default
             encrypted: Default::default(),
-            path: Default::default()
+            path: Default::default(),
+            spent_serials_path: Default::default()
         }
     }
 }
@@ -91,6 +102,9 @@ impl Default for OutRecordContext {
 pub struct ProofContext {
     /// the merkle tree path root indicating the state on which this transaction is based
     pub merkle_root: MerkleTreeRoot,
+    /// the root of the spent-serials accumulator (see `crypto::spent_serials`) the input records'
+    /// non-membership paths are proven against
+    pub spent_serials_root: OuterScalarField,
 
     /// the address of the transaction sender
     pub sender_address: OuterScalarField,
@@ -113,6 +127,8 @@ pub struct ProofContext {
     pub processor_instructions: [ZkInstruction; NOF_PROCESSOR_CYCLES],
     /// the current time for the processor
     pub processor_current_time: OuterScalarField,
+    /// the gas budget the processor ran with (see `ZkProcessor::gas_limit`)
+    pub processor_gas_limit: OuterScalarField,
     /// the intermediate states of the processor
     pub processor_states: [ZkProcessorState; NOF_PROCESSOR_CYCLES + 1],
 
@@ -132,6 +148,7 @@ impl ProofContext {
         // NOTE: this function must initialize the proof context with the correct sizes, as it is used for the circuit setup phase
         ProofContext {
             merkle_root: Default::default(),
+            spent_serials_root: Default::default(),
             sender_address: Default::default(),
             sender_sk_bytes: [0u8; FE_BYTES],
             unique_seed: Default::default(),
@@ -141,6 +158,7 @@ impl ProofContext {
             called_function_id: Default::default(),
             processor_instructions: [(); NOF_PROCESSOR_CYCLES].map(|_| ZkInstruction::default()),
             processor_current_time: Default::default(),
+            processor_gas_limit: Default::default(),
             processor_states: [(); NOF_PROCESSOR_CYCLES + 1].map(|_| ZkProcessorState::default()),
             rand_oid: Default::default(),
             rand_sk: Default::default(),
@@ -151,12 +169,14 @@ impl ProofContext {
 
     pub fn new<R: Rng>(rng: &mut R,
                merkle_root: MerkleTreeRoot,
+               spent_serials_root: OuterScalarField,
                crypto_params: CryptoParams,
                sender_address: OuterScalarField
-    ) -> ProofContext {    
+    ) -> ProofContext {
         // prepare proof context
         let mut ctx = ProofContext::default_with_params(crypto_params);
         ctx.merkle_root = merkle_root;
+        ctx.spent_serials_root = spent_serials_root;
         ctx.sender_address = sender_address;
 
         // set random unique seed
@@ -179,13 +199,14 @@ impl ProofContext {
         ctx
     }
 
-    pub fn set_input_and_decrypt(&mut self, idx: usize, sk: ExtSecretKey<InnerEdProjective>, encrypted: EncryptedRecord, path: MerkleTreePath) {
+    pub fn set_input_and_decrypt(&mut self, idx: usize, sk: ExtSecretKey<InnerEdProjective>, encrypted: EncryptedRecord, path: MerkleTreePath, spent_serials_path: SmtNonMembershipPath) {
         let rec = &mut self.in_records[idx];
         rec.sk_serialized.copy_from_slice(to_bytes!(sk).unwrap().as_slice());
         rec.sk = sk;
         rec.encrypted = encrypted.clone();
         rec.plaintext = Record::decrypt(&encrypted, &rec.sk, &self.crypto_params.enc_params).unwrap();
         rec.path = path;
+        rec.spent_serials_path = spent_serials_path;
         rec.derive_and_set_serial();
     }
 
@@ -201,6 +222,8 @@ impl ProofContext {
             }
         }
 
+        // only valid for a plain (zero-diversifier) `addr_owner`, which is all that
+        // `execute()` currently produces; see `Record::diversifier`
         rec.pk_owner = get_pk_for_addr(&out_record.addr_owner);
         let res = out_record.encrypt(&rec.pk_owner, &self.crypto_params.enc_params, rng);
         rec.plaintext = out_record;
@@ -250,6 +273,7 @@ impl ProofContext {
     }
 }
 
+#[derive(Clone)]
 pub struct ObjectInfo {
     /// The leaf index of this object in the state Merkle tree
     pub leaf_idx: usize,
@@ -261,6 +285,42 @@ pub struct ObjectInfo {
     pub serial_number: Serial
 }
 
+/// Like `ObjectInfo`, but for an object recognized through a `ViewOnlyIdentity` rather than a
+/// spend-capable `Identity`: there is no `serial_number`, since deriving one needs `sk_object`,
+/// which a viewing key alone cannot recover (see `Record::decrypt_with_viewing_key`). A
+/// consequence: a view-only watcher cannot tell when such an object is later consumed --
+/// `try_recognize_published_serial` only ever matches `known_objects`.
+#[derive(Clone)]
+pub struct ViewOnlyObjectInfo {
+    /// The leaf index of this object in the state Merkle tree
+    pub leaf_idx: usize,
+
+    /// The identity of this object's owner
+    pub owner_identity: Rc<ViewOnlyIdentity>,
+}
+
+/// Undo information for one `sync_tx` call, recorded by `RuntimeStateView::checkpoint` and popped
+/// in reverse by `RuntimeStateView::rewind` -- the `Runtime::sync_tx`/`rewind_to_tx` counterpart
+/// of the chain-reorg checkpoint/rewind model shielded-pool wallets use to recover when the
+/// remote ledger drops previously synced transactions.
+struct TxDelta {
+    /// checkpoint id into `merkle_tree` (see `SparseMerkleTree::checkpoint`), undoing every leaf
+    /// update this transaction made so `get_root()` recomputes to the earlier root
+    tree_checkpoint: CheckpointId,
+    /// checkpoint id into `spent_serials` (see `SpentSerialsSmt::checkpoint`), undoing every
+    /// serial this transaction marked spent
+    spent_serials_checkpoint: SpentSerialsCheckpointId,
+    /// leaf indices appended to `tree_leaves` by this transaction (always fresh keys, since
+    /// leaves are appended in order, so there is no prior value to restore -- just remove them)
+    added_leaves: Vec<usize>,
+    /// every `known_objects` mutation this transaction made (recognizing a new/updated object,
+    /// or removing one whose serial was recognized as spent), in chronological order, paired
+    /// with the entry's previous value (`None` if it was not present before)
+    known_objects_log: Vec<(ObjectId, Option<ObjectInfo>)>,
+    /// same as `known_objects_log`, but for `known_view_only_objects`
+    known_view_only_objects_log: Vec<(ObjectId, Option<ViewOnlyObjectInfo>)>,
+}
+
 pub struct RuntimeStateView {
     /// The crypto parameters to be used in this runtime.
     crypto_params: CryptoParams,
@@ -271,23 +331,153 @@ pub struct RuntimeStateView {
     /// A local copy of the remote ledger Merkle tree
     pub merkle_tree: SparseMerkleTree<MerkleTreeParams>,
 
+    /// A local copy of the remote spent-serials accumulator (see `crypto::spent_serials`)
+    pub spent_serials: SpentSerialsSmt,
+
     /// Mapping of Merkle tree leaf indices to leaf data
     pub tree_leaves: BTreeMap<usize, EncryptedRecord>,
-    
+
     /// Mapping object ids to object information for all known objects
     pub known_objects: BTreeMap<ObjectId, ObjectInfo>,
+
+    /// Reverse index of `known_objects`, from serial number to object id, kept in sync with
+    /// `known_objects` by `set_known_object_in`/`rewind` so `try_recognize_published_serial`
+    /// can look up which object (if any) a just-published serial spends in `O(log n)` instead
+    /// of scanning every known object.
+    serial_to_object: BTreeMap<Serial, ObjectId>,
+
+    /// Mapping object ids to object information for objects recognized only through a
+    /// `ViewOnlyIdentity` (see `ViewOnlyObjectInfo`)
+    pub known_view_only_objects: BTreeMap<ObjectId, ViewOnlyObjectInfo>,
+
+    /// The number of Merkle tree leaves trial-decryption has processed so far (always equal to
+    /// `tree_leaves.len()` right after a `sync_tx`/`sync_range` call returns, since both only
+    /// ever trial-decrypt the leaves they just appended). Exposed so callers can confirm sync
+    /// progress without relying on `tree_leaves.len()` directly.
+    ///
+    /// NOTE: registering an identity via `register_identity`/`register_view_only_identity`
+    /// does not rescan leaves added before `last_scanned_leaf_idx` -- there is no API to replay
+    /// already-synced transactions, so an identity only recognizes records from transactions
+    /// synced after it was registered.
+    pub last_scanned_leaf_idx: usize,
+
+    /// One `TxDelta` per synced transaction, in sync order; see `rewind`.
+    checkpoints: Vec<TxDelta>,
 }
 
 impl RuntimeStateView {
     pub fn new(crypto_params: CryptoParams) -> RuntimeStateView {
         let tree = SparseMerkleTree::new(&crypto_params.leaf_hash_param, &crypto_params.inner_hash_param, TREE_HEIGHT);
+        let spent_serials = SpentSerialsSmt::new(crypto_params.enc_params.clone());
 
         RuntimeStateView {
             crypto_params,
             nof_synced_tx: 0,
             merkle_tree: tree,
+            spent_serials,
             tree_leaves: BTreeMap::new(),
-            known_objects: BTreeMap::new()
+            known_objects: BTreeMap::new(),
+            serial_to_object: BTreeMap::new(),
+            known_view_only_objects: BTreeMap::new(),
+            last_scanned_leaf_idx: 0,
+            checkpoints: Vec::new(),
+        }
+    }
+
+    /// Starts recording a new transaction's undo delta; called by `Runtime::sync_tx` right
+    /// before applying transaction `tx_idx`.
+    fn checkpoint(&mut self) {
+        let tree_checkpoint = self.merkle_tree.checkpoint();
+        let spent_serials_checkpoint = self.spent_serials.checkpoint();
+        self.checkpoints.push(TxDelta {
+            tree_checkpoint,
+            spent_serials_checkpoint,
+            added_leaves: Vec::new(),
+            known_objects_log: Vec::new(),
+            known_view_only_objects_log: Vec::new(),
+        });
+    }
+
+    /// Records that the leaf at `leaf_idx` was appended by the transaction currently being
+    /// synced (see `checkpoint`).
+    fn record_added_leaf(&mut self, leaf_idx: usize) {
+        self.checkpoints.last_mut().expect("record_added_leaf called outside a checkpoint").added_leaves.push(leaf_idx);
+    }
+
+    /// Sets (or removes, if `info` is `None`) the `known_objects` entry for `oid`, recording the
+    /// previous value in the transaction currently being synced's delta (see `checkpoint`) so it
+    /// can be restored by `rewind`.
+    fn set_known_object(&mut self, oid: ObjectId, info: Option<ObjectInfo>) {
+        let checkpoint_idx = self.checkpoints.len().checked_sub(1).expect("set_known_object called outside a checkpoint");
+        self.set_known_object_in(checkpoint_idx, oid, info);
+    }
+
+    /// Same as `set_known_object`, but targets an explicit checkpoint rather than always the most
+    /// recently opened one. `Runtime::sync_range` needs this: it opens one checkpoint per
+    /// transaction in the batch up front (so each still gets its own `TxDelta`), then runs a
+    /// single parallel trial-decryption pass over the whole batch afterwards, by which point the
+    /// originating transaction's checkpoint is no longer the last one on the stack.
+    fn set_known_object_in(&mut self, checkpoint_idx: usize, oid: ObjectId, info: Option<ObjectInfo>) {
+        let prev = match &info {
+            Some(info) => self.known_objects.insert(oid, info.clone()),
+            None => self.known_objects.remove(&oid),
+        };
+        if let Some(prev_info) = &prev {
+            self.serial_to_object.remove(&prev_info.serial_number);
+        }
+        if let Some(info) = &info {
+            self.serial_to_object.insert(info.serial_number, oid);
+        }
+        self.checkpoints[checkpoint_idx].known_objects_log.push((oid, prev));
+    }
+
+    /// Same as `set_known_object`, but for `known_view_only_objects`.
+    fn set_known_view_only_object(&mut self, oid: ObjectId, info: Option<ViewOnlyObjectInfo>) {
+        let checkpoint_idx = self.checkpoints.len().checked_sub(1).expect("set_known_view_only_object called outside a checkpoint");
+        self.set_known_view_only_object_in(checkpoint_idx, oid, info);
+    }
+
+    /// Same as `set_known_object_in`, but for `known_view_only_objects`.
+    fn set_known_view_only_object_in(&mut self, checkpoint_idx: usize, oid: ObjectId, info: Option<ViewOnlyObjectInfo>) {
+        let prev = match info {
+            Some(info) => self.known_view_only_objects.insert(oid, info),
+            None => self.known_view_only_objects.remove(&oid),
+        };
+        self.checkpoints[checkpoint_idx].known_view_only_objects_log.push((oid, prev));
+    }
+
+    /// Restores the mirror to the state it was in right after `target_nof_synced_tx`
+    /// transactions had been synced, undoing every later transaction's delta in reverse. The key
+    /// invariant: `rewind(k)` followed by re-`sync_tx`-ing transactions `k..` reproduces a
+    /// byte-identical `get_root()` and `known_objects` to the state before the rewind.
+    pub fn rewind(&mut self, target_nof_synced_tx: usize) {
+        assert!(target_nof_synced_tx <= self.nof_synced_tx, "cannot rewind forward");
+        while self.nof_synced_tx > target_nof_synced_tx {
+            let delta = self.checkpoints.pop().expect("checkpoint stack out of sync with nof_synced_tx");
+            for (oid, prev) in delta.known_objects_log.into_iter().rev() {
+                if let Some(cur) = self.known_objects.get(&oid) {
+                    self.serial_to_object.remove(&cur.serial_number);
+                }
+                match prev {
+                    Some(info) => {
+                        self.serial_to_object.insert(info.serial_number, oid);
+                        self.known_objects.insert(oid, info);
+                    }
+                    None => { self.known_objects.remove(&oid); }
+                }
+            }
+            for (oid, prev) in delta.known_view_only_objects_log.into_iter().rev() {
+                match prev {
+                    Some(info) => { self.known_view_only_objects.insert(oid, info); }
+                    None => { self.known_view_only_objects.remove(&oid); }
+                }
+            }
+            for leaf_idx in delta.added_leaves {
+                self.tree_leaves.remove(&leaf_idx);
+            }
+            self.spent_serials.rewind(delta.spent_serials_checkpoint);
+            self.merkle_tree.rewind(delta.tree_checkpoint);
+            self.nof_synced_tx -= 1;
         }
     }
 
@@ -310,15 +500,129 @@ impl RuntimeStateView {
         Ok(record)
     }
 
+    /// Like `get_record_for_oid`, but for an object only `known_view_only_objects` (recognized
+    /// via a `ViewOnlyIdentity`) -- decrypts with the viewing key alone, so the result omits
+    /// `serial_nonce`/`sk_object` (see `WatchOnlyRecord`).
+    pub fn get_view_only_record_for_oid(&self, oid: &ObjectId) -> Result<WatchOnlyRecord, ()> {
+        assert!(!oid.is_zero());
+        let info = self.known_view_only_objects.get(oid).ok_or(())?;
+        let owner = &info.owner_identity;
+        let enc_record = self.tree_leaves.get(&info.leaf_idx).unwrap();
+        let record = Record::decrypt_with_viewing_key(&enc_record, &owner.viewing_key, &self.crypto_params.enc_params).unwrap();
+        assert_eq!(&record.object_id, oid);
+        Ok(record)
+    }
+
     pub fn get_root(&self) -> MerkleTreeRoot {
         MerkleTreeRoot(self.merkle_tree.root())
     }
+
+    pub fn get_spent_serials_root(&self) -> OuterScalarField {
+        self.spent_serials.root()
+    }
+
+    /// Serializes enough of this mirror to `load` it back and resume `sync_tx`/`sync_range` from
+    /// `nof_synced_tx` without rescanning from transaction 0: `nof_synced_tx`, `tree_leaves`, and
+    /// `known_objects` (by leaf index, owner address, and serial number -- the owner identity
+    /// itself is re-resolved on `load` against whatever identities it's given, see
+    /// `Runtime::save_state`). The Merkle tree's interior nodes are not serialized; `load` cheaply
+    /// recomputes all of them from `tree_leaves` via `SparseMerkleTree::update_batch`, then checks
+    /// the result against the root digest stored here, so a corrupted or stale file is rejected
+    /// rather than silently resumed from.
+    ///
+    /// NOTE: the spent-serials accumulator, the per-transaction `TxDelta` undo log, and
+    /// `known_view_only_objects` are not part of this snapshot, so a loaded `RuntimeStateView` can
+    /// sync forward from `nof_synced_tx` but cannot `rewind_to_tx` below it, and a reloaded
+    /// `Runtime` will not recognize previously-seen view-only balances until its view-only
+    /// identities re-sync from scratch.
+    pub fn save<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        (self.nof_synced_tx as u64).write(&mut writer)?;
+        self.get_root().write(&mut writer)?;
+
+        (self.merkle_tree.height() as u64).write(&mut writer)?;
+        (self.tree_leaves.len() as u64).write(&mut writer)?;
+        for (leaf_idx, enc_record) in self.tree_leaves.iter() {
+            (*leaf_idx as u64).write(&mut writer)?;
+            enc_record.write(&mut writer)?;
+        }
+
+        (self.known_objects.len() as u64).write(&mut writer)?;
+        for (oid, info) in self.known_objects.iter() {
+            oid.write(&mut writer)?;
+            (info.leaf_idx as u64).write(&mut writer)?;
+            info.owner_identity.address.write(&mut writer)?;
+            writer.write_all(&info.serial_number)?;
+        }
+        Ok(())
+    }
+
+    /// Reads a snapshot written by `save`, rebuilding the Merkle tree from its leaves and
+    /// resolving each `known_objects` entry's owner against `identities` (typically
+    /// `Runtime::identities`, already populated by `Runtime::load_state` from the same wallet
+    /// file). Fails if the reconstructed root doesn't match the stored one, or if an object's
+    /// owner address isn't among `identities`.
+    pub fn load<R: Read>(mut reader: R, crypto_params: CryptoParams, identities: &BTreeMap<OuterScalarField, Rc<Identity>>) -> std::io::Result<RuntimeStateView> {
+        let nof_synced_tx = u64::read(&mut reader)? as usize;
+        let stored_root = MerkleTreeRoot::read(&mut reader)?;
+
+        let height = u64::read(&mut reader)? as usize;
+        let nof_leaves = u64::read(&mut reader)? as usize;
+        let mut tree_leaves = BTreeMap::new();
+        let mut entries = Vec::with_capacity(nof_leaves);
+        for _ in 0..nof_leaves {
+            let leaf_idx = u64::read(&mut reader)? as usize;
+            let enc_record = EncryptedRecord::read(&mut reader)?;
+            entries.push((leaf_idx as u128, enc_record.clone()));
+            tree_leaves.insert(leaf_idx, enc_record);
+        }
+
+        let mut merkle_tree = SparseMerkleTree::new(&crypto_params.leaf_hash_param, &crypto_params.inner_hash_param, height);
+        merkle_tree.update_batch(&entries);
+        if MerkleTreeRoot(merkle_tree.root()) != stored_root {
+            return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "reconstructed Merkle root does not match the stored root; snapshot is corrupt or out of date"));
+        }
+
+        let nof_known_objects = u64::read(&mut reader)? as usize;
+        let mut known_objects = BTreeMap::new();
+        let mut serial_to_object = BTreeMap::new();
+        for _ in 0..nof_known_objects {
+            let oid = ObjectId::read(&mut reader)?;
+            let leaf_idx = u64::read(&mut reader)? as usize;
+            let owner_address = OuterScalarField::read(&mut reader)?;
+            let mut serial_number: Serial = [0u8; SN_BYTES];
+            reader.read_exact(&mut serial_number)?;
+            let owner_identity = identities.get(&owner_address).cloned().ok_or_else(|| std::io::Error::new(
+                std::io::ErrorKind::InvalidData,
+                format!("unknown owner identity with address {}; register it before loading this snapshot", fe_to_be_hex_str(&owner_address)),
+            ))?;
+            serial_to_object.insert(serial_number, oid);
+            known_objects.insert(oid, ObjectInfo { leaf_idx, owner_identity, serial_number });
+        }
+
+        let spent_serials = SpentSerialsSmt::new(crypto_params.enc_params.clone());
+        let last_scanned_leaf_idx = tree_leaves.len();
+        Ok(RuntimeStateView {
+            crypto_params,
+            nof_synced_tx,
+            merkle_tree,
+            spent_serials,
+            tree_leaves,
+            known_objects,
+            serial_to_object,
+            known_view_only_objects: BTreeMap::new(),
+            last_scanned_leaf_idx,
+            checkpoints: Vec::new(),
+        })
+    }
 }
 
 pub struct ExecutionResult {
     /// the merkle tree root of the state containing the consumed records
     pub merkle_tree_root: MerkleTreeRoot,
 
+    /// the spent-serials accumulator root the consumed records' non-membership was proven against
+    pub spent_serials_root: OuterScalarField,
+
     /// the current timestamp used for the transaction,
     pub current_time: OuterScalarField,
 
@@ -334,6 +638,10 @@ pub struct ExecutionResult {
     /// a unique seed used to derive fresh values
     pub unique_seed: [u8; RAND_BYTES],
 
+    /// this transaction's RLN rate-limiting share (see crypto::rln); pass to
+    /// `MainProofVerifier::verify` alongside the proof
+    pub rln_share: RlnShare,
+
     /// the return value of the top-level function call
     pub return_value: OuterScalarField
 }
@@ -351,6 +659,10 @@ pub struct Runtime<R: Rng + CryptoRng> {
     /// maps addresses to known identities
     pub identities: BTreeMap<OuterScalarField, Rc<Identity>>,
 
+    /// maps addresses to known view-only identities (see `ViewOnlyIdentity`), registered via
+    /// `register_view_only_identity`
+    pub view_identities: BTreeMap<OuterScalarField, Rc<ViewOnlyIdentity>>,
+
     /// The outer prover key to be used in this runtime.
     /// Can be set to `None` for debugging purposes, in which case proofs are not generated.
     pub proving_key: Option<ProvingKey<OuterPairing>>
@@ -364,6 +676,7 @@ impl<R: Rng + CryptoRng> Runtime<R> {
             rand,
             ledger_state_view: Rc::new(RefCell::new(RuntimeStateView::new(crypto_params))),
             identities: BTreeMap::new(),
+            view_identities: BTreeMap::new(),
             proving_key
         }
     }
@@ -379,23 +692,79 @@ impl<R: Rng + CryptoRng> Runtime<R> {
         let nof_synced = self.get_nof_synced_tx();
         if tx_idx == nof_synced {
             debug!("synchronizing transaction index {}", tx_idx);
+            self.ledger_state_view.borrow_mut().checkpoint();
             for serial in published_serials.iter() {
                 self.try_recognize_published_serial(serial);
+                self.ledger_state_view.borrow_mut().spent_serials.insert(serial);
             }
             let mut idx_and_records = vec![];
             for enc_record in published_records {
                 let leaf_idx = self.ledger_state_view.borrow().tree_leaves.len();
                 self.ledger_state_view.borrow_mut().merkle_tree.update(leaf_idx as u128, enc_record);
                 self.ledger_state_view.borrow_mut().tree_leaves.insert(leaf_idx, enc_record.clone());
+                self.ledger_state_view.borrow_mut().record_added_leaf(leaf_idx);
                 idx_and_records.push((leaf_idx, enc_record.clone()));
             }
+            self.try_recognize_enc_records_view_only(&idx_and_records);
             self.try_recognize_enc_records(idx_and_records);
             self.ledger_state_view.borrow_mut().nof_synced_tx += 1;
+            let mut view = self.ledger_state_view.borrow_mut();
+            view.last_scanned_leaf_idx = view.tree_leaves.len();
         } else if tx_idx > nof_synced {
             panic!("transaction index too high: synchronize transaction {} first", nof_synced);
         }
     }
 
+    /// Rewinds the local mirror back to the state it was in right after `tx_idx` transactions
+    /// had been synced, undoing every later transaction recorded by `sync_tx` -- for recovering
+    /// from a remote chain reorg that dropped previously synced transactions. Re-`sync_tx`-ing
+    /// transactions `tx_idx..` afterwards reproduces a byte-identical mirror to the one before
+    /// the reorg (see `RuntimeStateView::rewind`).
+    pub fn rewind_to_tx(&mut self, tx_idx: usize) {
+        self.ledger_state_view.borrow_mut().rewind(tx_idx);
+    }
+
+    /// Synchronizes a contiguous range of transactions in one call: the fast path for bootstrapping
+    /// a wallet against a long history, where calling `sync_tx` once per transaction makes trial
+    /// decryption an `O(txs × keys)` serial scan. `txs[i]` is the `(published_serials,
+    /// published_records)` pair for transaction `start_idx + i`, in the same shape `sync_tx` takes;
+    /// `start_idx` must equal `get_nof_synced_tx()`, exactly like `sync_tx`.
+    ///
+    /// Every transaction's leaves are still appended one at a time (so each gets its own
+    /// `TxDelta`, and `rewind_to_tx` keeps working exactly as it does after a run of `sync_tx`
+    /// calls), but the expensive part -- trial-decrypting every newly-added record against every
+    /// registered identity -- runs once, in parallel (rayon), over the whole range's records
+    /// afterwards, instead of once per transaction.
+    pub fn sync_range(&mut self, start_idx: usize, txs: &[(Vec<Serial>, Vec<EncryptedRecord>)]) {
+        let nof_synced = self.get_nof_synced_tx();
+        assert_eq!(start_idx, nof_synced, "transaction index too high or too low: synchronize transaction {} first", nof_synced);
+
+        let mut idx_and_records = vec![];
+        for (offset, (published_serials, published_records)) in txs.iter().enumerate() {
+            debug!("synchronizing transaction index {} (batched)", start_idx + offset);
+            self.ledger_state_view.borrow_mut().checkpoint();
+            let checkpoint_idx = self.ledger_state_view.borrow().checkpoints.len() - 1;
+
+            for serial in published_serials.iter() {
+                self.try_recognize_published_serial(serial);
+                self.ledger_state_view.borrow_mut().spent_serials.insert(serial);
+            }
+            for enc_record in published_records {
+                let leaf_idx = self.ledger_state_view.borrow().tree_leaves.len();
+                self.ledger_state_view.borrow_mut().merkle_tree.update(leaf_idx as u128, enc_record);
+                self.ledger_state_view.borrow_mut().tree_leaves.insert(leaf_idx, enc_record.clone());
+                self.ledger_state_view.borrow_mut().record_added_leaf(leaf_idx);
+                idx_and_records.push((leaf_idx, enc_record.clone(), checkpoint_idx));
+            }
+            self.ledger_state_view.borrow_mut().nof_synced_tx += 1;
+        }
+
+        self.try_recognize_enc_records_view_only_range(&idx_and_records);
+        self.try_recognize_enc_records_range(idx_and_records);
+        let mut view = self.ledger_state_view.borrow_mut();
+        view.last_scanned_leaf_idx = view.tree_leaves.len();
+    }
+
     /// Registers an identity.
     pub fn register_identity(&mut self, iden: Identity) {
         // check whether identity is correct (e.g., matches the configured crypto parameters)
@@ -409,6 +778,78 @@ impl<R: Rng + CryptoRng> Runtime<R> {
         self.identities.insert(iden.address, Rc::new(iden));
     }
 
+    /// Registers an identity for watch-only access: `try_recognize_enc_records`/
+    /// `get_record_for_oid` still recognize and decrypt its incoming records (so its balance is
+    /// visible), but `execute` refuses to use it as a `sender_address`, so importing it cannot
+    /// expose spend authority over the identity's funds. `iden` is converted to
+    /// `IdentityKind::WatchOnly` regardless of its incoming `kind`.
+    pub fn register_watch_identity(&mut self, iden: Identity) {
+        self.register_identity(iden.as_watch_only());
+    }
+
+    /// Registers an identity known only by its incoming viewing key (see `ViewOnlyIdentity`):
+    /// `sync_tx`/`sync_range` will recognize and decrypt its records into
+    /// `known_view_only_objects`, making its balance visible via `get_view_only_state`, but --
+    /// unlike `register_watch_identity` -- there is no `secret_key` at all here, so this
+    /// identity can never derive a serial number (it cannot tell when its own records are
+    /// later consumed) and could not be used as an `execute` sender even if one tried.
+    pub fn register_view_only_identity(&mut self, view_only: ViewOnlyIdentity) {
+        debug!("registered view-only identity with address {}, public key ({}, {})",
+            fe_to_be_hex_str(&view_only.address),
+            fe_to_be_hex_str(&view_only.public_key.x),
+            fe_to_be_hex_str(&view_only.public_key.y));
+        self.view_identities.insert(view_only.address, Rc::new(view_only));
+    }
+
+    /// Persists the registered identities and the synced ledger mirror so a wallet file can be
+    /// `load_state`d back into a fresh `Runtime` later, resuming from `get_nof_synced_tx()`
+    /// instead of rescanning from transaction 0.
+    pub fn save_state<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        (self.identities.len() as u64).write(&mut writer)?;
+        for iden in self.identities.values() {
+            iden.write(&mut writer)?;
+        }
+        self.ledger_state_view.borrow().save(writer)
+    }
+
+    /// Reads a wallet file written by `save_state`, re-validating every identity against
+    /// `crypto_params` via `Identity::from_bytes_checked` (the bytes may come from an untrusted
+    /// source) before handing them to `RuntimeStateView::load` to resolve `known_objects` owners.
+    pub fn load_state<Re: Read>(mut reader: Re, crypto_params: CryptoParams, proving_key: Option<ProvingKey<OuterPairing>>, rand: RefCell<R>) -> std::io::Result<Runtime<R>> {
+        let nof_identities = u64::read(&mut reader)? as usize;
+        let mut identities = BTreeMap::new();
+        for _ in 0..nof_identities {
+            let iden = Identity::from_bytes_checked(&mut reader, &crypto_params)?
+                .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "identity in wallet file does not match the configured crypto parameters"))?;
+            identities.insert(iden.address, Rc::new(iden));
+        }
+
+        let ledger_state_view = RuntimeStateView::load(reader, crypto_params.clone(), &identities)?;
+
+        Ok(Runtime {
+            crypto_params,
+            rand,
+            ledger_state_view: Rc::new(RefCell::new(ledger_state_view)),
+            identities,
+            view_identities: BTreeMap::new(),
+            proving_key,
+        })
+    }
+
+    /// `save_state`, writing to a freshly created (or truncated) file at `path` instead of an
+    /// arbitrary `Write`r -- the entry point wallet-file persistence is expected to use.
+    pub fn save_to_path<P: AsRef<Path>>(&self, path: P) -> std::io::Result<()> {
+        let file = File::create(path)?;
+        self.save_state(BufWriter::new(file))
+    }
+
+    /// `load_state`, reading from a file at `path` instead of an arbitrary `Read`er -- the
+    /// counterpart of `save_to_path`.
+    pub fn load_from_path<P: AsRef<Path>>(path: P, crypto_params: CryptoParams, proving_key: Option<ProvingKey<OuterPairing>>, rand: RefCell<R>) -> std::io::Result<Runtime<R>> {
+        let file = File::open(path)?;
+        Runtime::load_state(BufReader::new(file), crypto_params, proving_key, rand)
+    }
+
     /// Returns the current state of object with object id `oid`.
     /// The object and it's owner's identity must be known to the runtime.
     pub fn get_state(&self, oid: ObjectId) -> Result<Record, ()> {
@@ -416,6 +857,35 @@ impl<R: Rng + CryptoRng> Runtime<R> {
         Ok(record)
     }
 
+    /// Like `get_state`, but also returns a `RecordOpening` disclosing `contract_id`,
+    /// `addr_owner`, and the payload elements at `payload_indices` -- see `Record::open` and
+    /// `RecordOpening` for what the resulting proof does and doesn't hide. The owner's secret
+    /// key is used to build the proof but never leaves this function.
+    pub fn get_state_with_opening(&self, oid: ObjectId, payload_indices: &[usize]) -> Result<(Record, RecordOpening), ()> {
+        let (enc_record, owner) = {
+            let view = self.ledger_state_view.borrow();
+            let info = view.known_objects.get(&oid).ok_or(())?;
+            (view.tree_leaves.get(&info.leaf_idx).unwrap().clone(), info.owner_identity.clone())
+        };
+        let record = Record::decrypt(&enc_record, &owner.secret_key, &self.crypto_params.enc_params).unwrap();
+        assert_eq!(record.object_id, oid);
+        let opening = record.open(
+            &enc_record,
+            &owner.secret_key,
+            &owner.public_key,
+            &self.crypto_params.enc_params,
+            payload_indices,
+            &mut *self.rand.borrow_mut(),
+        );
+        Ok((record, opening))
+    }
+
+    /// Like `get_state`, but for an object recognized only through a `ViewOnlyIdentity`; see
+    /// `RuntimeStateView::get_view_only_record_for_oid`.
+    pub fn get_view_only_state(&self, oid: ObjectId) -> Result<WatchOnlyRecord, ()> {
+        self.ledger_state_view.borrow().get_view_only_record_for_oid(&oid)
+    }
+
     /// Executes the given program with given arguments for the current mirror of the ledger state.
     /// - The first argument is the address of the transaction sender.
     /// - The mirror should be updated using `sync_tx` before this function is called.
@@ -432,10 +902,12 @@ impl<R: Rng + CryptoRng> Runtime<R> {
         dbg_sync_immediately: bool
     ) -> ExecutionResult {
         let root = self.ledger_state_view.borrow().get_root();
+        let spent_serials_root = self.ledger_state_view.borrow().get_spent_serials_root();
         let consumed_serials: Vec<_>;
         let mut new_records;
         let proof;
         let unique_seed;
+        let rln_share;
         let result_state;
         {
             // enforce sender address is a user address
@@ -444,11 +916,12 @@ impl<R: Rng + CryptoRng> Runtime<R> {
             assert!(is_external_account(&sender_address), "sender address (argument 0) must be an external user account address");
 
             let rand: &mut R = &mut self.rand.borrow_mut();
-            let mut ctx = ProofContext::new(rand, root.clone(), self.crypto_params.clone(), sender_address);
+            let mut ctx = ProofContext::new(rand, root.clone(), spent_serials_root, self.crypto_params.clone(), sender_address);
 
             // get sender identity and sender secret key
             let sender_ident = self.try_get_identity_for_addr(&sender_address)
                 .unwrap_or_else(|| panic!("no secret key registered for sender address {}", &fe_to_be_hex_str(&sender_address)));
+            assert!(!sender_ident.is_watch_only(), "sender address {} is registered watch-only (no spend authority); register it with register_identity instead of register_watch_identity to author transactions", &fe_to_be_hex_str(&sender_address));
             ctx.sender_sk_bytes.copy_from_slice(&to_bytes!(sender_ident.secret_key.0.0).unwrap());
 
             debug!("using sender identity with address {}, public key ({}, {}), secret key {}",
@@ -473,15 +946,21 @@ impl<R: Rng + CryptoRng> Runtime<R> {
             ctx.called_class_id = called_class_id;
             ctx.called_function_id = called_function_id;
 
-            // pad program with NOOPs
+            // pad program with NOOPs, then fold constants and eliminate dead code
+            // (MainProofVerifier::verify re-derives the same padded, optimized instructions from
+            // the caller-supplied program, so the public instruction stream stays identical on
+            // both sides)
             assert!(program.len() <= NOF_PROCESSOR_CYCLES, "too many instructions (got: {}, max: {})", program.len(), NOF_PROCESSOR_CYCLES);
             ctx.processor_instructions[0..program.len()].clone_from_slice(&program);
+            ctx.processor_instructions.clone_from_slice(&fold_constants(&ctx.processor_instructions));
+            ctx.processor_instructions.clone_from_slice(&eliminate_dead_code(&ctx.processor_instructions));
 
             // run processor
             let mut processor = ZkProcessor::default();
-            time_measure!("run_processor", processor.run(self.ledger_state_view.clone(), &ctx.processor_instructions, initial_proc_state, ctx.processor_current_time) );
+            time_measure!("run_processor", processor.run(self.ledger_state_view.clone(), &ctx.processor_instructions, initial_proc_state, ctx.processor_current_time).unwrap() );
 
             // store intermediate states to context
+            ctx.processor_gas_limit = processor.gas_limit;
             ctx.processor_states.clone_from_slice(&processor.states);
 
             // prepare all inputs for context
@@ -492,7 +971,10 @@ impl<R: Rng + CryptoRng> Runtime<R> {
                     assert_ne!(data.object_id, OuterScalarField::zero());
                     debug!("input record object id: {}", fe_to_string(&data.object_id));
                     let (enc_record, path, sk) = self.ledger_state_view.borrow().get_enc_record_with_path_and_sk(&data.object_id).unwrap();
-                    ctx.set_input_and_decrypt(i, sk, enc_record, path)
+                    let serial = self.ledger_state_view.borrow().known_objects[&data.object_id].serial_number;
+                    let spent_serials_path = self.ledger_state_view.borrow().spent_serials.witness(&serial)
+                        .expect("input record's serial number is already in the spent-serials accumulator (double spend)");
+                    ctx.set_input_and_decrypt(i, sk, enc_record, path, spent_serials_path)
                 }
             }
 
@@ -505,6 +987,9 @@ impl<R: Rng + CryptoRng> Runtime<R> {
                 if !record.is_dummy() {
                     debug!("output record object id: {}", fe_to_string(&record.object_id));
                     debug!("{:?}", record);
+                    // `Record::from_object_data` always sets `diversifier` to zero (the
+                    // processor has no notion of it), so `addr_owner` here is always the
+                    // plain, reconstructable address (see `derivations::derive_diversified_addr`)
                     let pk_owner = try_get_pk_for_addr(&record.addr_owner);
                     assert!(pk_owner.is_some() && pk_owner.unwrap().is_on_curve() && pk_owner.unwrap().is_in_correct_subgroup_assuming_on_curve(),
                         "invalid owner address for object {}; did the program correctly store the owner?",  fe_to_be_hex_str(&record.object_id));
@@ -523,6 +1008,12 @@ impl<R: Rng + CryptoRng> Runtime<R> {
             consumed_serials = ctx.in_records.iter().map(|rec| rec.sn).collect();
             unique_seed = ctx.unique_seed;
 
+            // compute this transaction's RLN rate-limiting share (see circuit::MainProofCircuit's
+            // "rln_rate_limit" constraints and crypto::rln) before ctx is consumed by proving
+            let tx_seed: OuterScalarField = fe_from_le_bytes_mod_order(&ctx.unique_seed);
+            let sk_fe: OuterScalarField = fe_from_le_bytes_mod_order(&ctx.sender_sk_bytes);
+            rln_share = rln::evaluate_share(&self.crypto_params.enc_params, sk_fe, ctx.processor_current_time, tx_seed);
+
             // generate proof
             time_measure!("generate_proof", proof = generate_main_proof(rand, &self.proving_key, ctx));
         }   // end borrowing of self.rand
@@ -537,11 +1028,13 @@ impl<R: Rng + CryptoRng> Runtime<R> {
 
         ExecutionResult {
             merkle_tree_root: root,
+            spent_serials_root,
             current_time,
             consumed_serials,
             new_records,
             proof,
             unique_seed,
+            rln_share,
             return_value: result_state.partial.registers[return_register],
         }
     }
@@ -551,6 +1044,32 @@ impl<R: Rng + CryptoRng> Runtime<R> {
         iden.cloned()
     }
 
+    /// Trial-decrypts newly appended records against every registered `ViewOnlyIdentity`,
+    /// populating `known_view_only_objects`. Unlike `try_recognize_enc_records`, this is a single
+    /// pass, not a fixpoint: `WatchOnlyRecord` omits `sk_object`, so a view-only identity can
+    /// never learn of a new object identity one of its own records owns, and so can never
+    /// recognize records addressed to that child object either.
+    fn try_recognize_enc_records_view_only(&mut self, idx_and_records: &[(usize, EncryptedRecord)]) {
+        for (idx, enc_record) in idx_and_records.iter() {
+            for (_, ident) in self.view_identities.iter() {
+                if self.crypto_params.use_view_tags
+                    && !enc_record.is_probably_mine(ident.viewing_key.as_ext_secret_key(), &self.crypto_params.enc_params)
+                {
+                    continue;
+                }
+                let res = Record::decrypt_with_viewing_key(enc_record, &ident.viewing_key, &self.crypto_params.enc_params);
+                if let Ok(record) = res {
+                    if record.is_dummy() {
+                        break;
+                    }
+                    let info = ViewOnlyObjectInfo { leaf_idx: *idx, owner_identity: ident.clone() };
+                    self.ledger_state_view.borrow_mut().set_known_view_only_object(record.object_id, Some(info));
+                    break;
+                }
+            }
+        }
+    }
+
     fn try_recognize_enc_records(&mut self, idx_and_records: Vec<(usize, EncryptedRecord)>) {
         // as new records may be mutual owners of each other, need to iterate multiple times
         let mut found_new_identity = true;
@@ -563,6 +1082,11 @@ impl<R: Rng + CryptoRng> Runtime<R> {
                     continue;
                 }
                 for (_, ident) in self.identities.iter() {
+                    if self.crypto_params.use_view_tags
+                        && !enc_record.is_probably_mine(&ident.secret_key, &self.crypto_params.enc_params)
+                    {
+                        continue;
+                    }
                     let res = Record::decrypt(enc_record, &ident.secret_key, &self.crypto_params.enc_params);
                     if let Ok(record) = res {
                         recognized.insert(idx);
@@ -582,7 +1106,7 @@ impl<R: Rng + CryptoRng> Runtime<R> {
                             owner_identity: ident.clone(),
                             serial_number
                         };
-                        self.ledger_state_view.borrow_mut().known_objects.insert(record.object_id, info);
+                        self.ledger_state_view.borrow_mut().set_known_object(record.object_id, Some(info));
         
                         // remember new object identity for registration, if not known yet
                         if !self.identities.contains_key(&record.addr_object) {
@@ -592,6 +1116,7 @@ impl<R: Rng + CryptoRng> Runtime<R> {
                                 secret_key: ExtSecretKey(SecretKey(FeConverter::to_smaller(&record.sk_object).unwrap())),
                                 public_key: get_pk_for_addr(&record.addr_object),
                                 address: record.addr_object,
+                                kind: IdentityKind::Spend,
                             };
                             new_identities.push(obj_iden);
                         }
@@ -607,16 +1132,109 @@ impl<R: Rng + CryptoRng> Runtime<R> {
         }      
     }
 
-    fn try_recognize_published_serial(&mut self, serial: &Serial) {
-        let mut found_obj: Option<ObjectId> = None;
-        for info in self.ledger_state_view.borrow().known_objects.iter() {
-            if serial == &info.1.serial_number {
-                found_obj = Some(*info.0);
-                break;
+    /// `sync_range`'s counterpart to `try_recognize_enc_records_view_only`: same single-pass
+    /// scan, but over a batch of transactions, each entry carrying the checkpoint index its
+    /// leaf was added under (see `set_known_view_only_object_in`).
+    fn try_recognize_enc_records_view_only_range(&mut self, idx_and_records: &[(usize, EncryptedRecord, usize)]) {
+        for (idx, enc_record, checkpoint_idx) in idx_and_records.iter() {
+            for (_, ident) in self.view_identities.iter() {
+                if self.crypto_params.use_view_tags
+                    && !enc_record.is_probably_mine(ident.viewing_key.as_ext_secret_key(), &self.crypto_params.enc_params)
+                {
+                    continue;
+                }
+                let res = Record::decrypt_with_viewing_key(enc_record, &ident.viewing_key, &self.crypto_params.enc_params);
+                if let Ok(record) = res {
+                    if record.is_dummy() {
+                        break;
+                    }
+                    let info = ViewOnlyObjectInfo { leaf_idx: *idx, owner_identity: ident.clone() };
+                    self.ledger_state_view.borrow_mut().set_known_view_only_object_in(*checkpoint_idx, record.object_id, Some(info));
+                    break;
+                }
+            }
+        }
+    }
+
+    /// `try_recognize_enc_records`'s counterpart for `sync_range`: `idx_and_records` pairs every
+    /// newly-added leaf with the checkpoint index of the transaction that added it (see
+    /// `RuntimeStateView::set_known_object_in`), and the same multi-pass fixpoint (new records can
+    /// be mutual owners of each other) runs here too -- only the trial decryption within each pass
+    /// is parallelized. Rayon tasks must be `Send`/`Sync`, so each pass snapshots `self.identities`
+    /// into plain `Identity` values (not the `Rc<Identity>`s the map actually holds, since
+    /// `RuntimeStateView` is otherwise single-threaded by design) and tries them in the snapshot's
+    /// order within a task, so the "first matching identity wins" behavior of the serial scan is
+    /// unchanged.
+    fn try_recognize_enc_records_range(&mut self, idx_and_records: Vec<(usize, EncryptedRecord, usize)>) {
+        let mut found_new_identity = true;
+        let mut recognized = BTreeSet::new();
+        while found_new_identity {
+            found_new_identity = false;
+
+            let identities: Vec<Identity> = self.identities.values().map(|ident| (**ident).clone()).collect();
+            let crypto_params = self.crypto_params.clone();
+
+            let matches: Vec<(usize, usize, Identity, Record)> = idx_and_records
+                .par_iter()
+                .filter(|(leaf_idx, _, _)| !recognized.contains(leaf_idx))
+                .filter_map(|(leaf_idx, enc_record, checkpoint_idx)| {
+                    identities.iter().find_map(|ident| {
+                        if crypto_params.use_view_tags && !enc_record.is_probably_mine(&ident.secret_key, &crypto_params.enc_params) {
+                            return None;
+                        }
+                        Record::decrypt(enc_record, &ident.secret_key, &crypto_params.enc_params).ok()
+                            .map(|record| (*leaf_idx, *checkpoint_idx, ident.clone(), record))
+                    })
+                })
+                .collect();
+
+            let mut new_identities = vec![];
+            for (leaf_idx, checkpoint_idx, ident, record) in matches {
+                recognized.insert(leaf_idx);
+                if record.is_dummy() {
+                    // dummy records do not have to be remembered
+                    continue;
+                }
+
+                // derive serial number (such that we can later observe once this record is consumed)
+                let mut sk_serialized = [0u8; SERIALIZED_SK_BYTES];
+                sk_serialized.copy_from_slice(to_bytes!(&ident.secret_key).unwrap().as_slice());
+                let serial_number = derive_sn_from_nonce(&record.serial_nonce, &sk_serialized);
+
+                // update known objects
+                let owner_identity = self.identities.get(&ident.address).expect("identity snapshot diverged from self.identities").clone();
+                let info = ObjectInfo {
+                    leaf_idx,
+                    owner_identity,
+                    serial_number
+                };
+                self.ledger_state_view.borrow_mut().set_known_object_in(checkpoint_idx, record.object_id, Some(info));
+
+                // remember new object identity for registration, if not known yet
+                if !self.identities.contains_key(&record.addr_object) {
+                    assert!(!is_external_account(&record.addr_object));
+                    let obj_iden = Identity {
+                        is_external_account: false,
+                        secret_key: ExtSecretKey(SecretKey(FeConverter::to_smaller(&record.sk_object).unwrap())),
+                        public_key: get_pk_for_addr(&record.addr_object),
+                        address: record.addr_object,
+                        kind: IdentityKind::Spend,
+                    };
+                    new_identities.push(obj_iden);
+                }
+            }
+            // register newly observed identities
+            for obj_iden in new_identities {
+                found_new_identity = true;
+                self.register_identity(obj_iden);
             }
         }
+    }
+
+    fn try_recognize_published_serial(&mut self, serial: &Serial) {
+        let found_obj = self.ledger_state_view.borrow().serial_to_object.get(serial).copied();
         if let Some(oid) = found_obj {
-            self.ledger_state_view.borrow_mut().known_objects.remove(&oid);
+            self.ledger_state_view.borrow_mut().set_known_object(oid, None);
         }
     }
 }
@@ -629,7 +1247,7 @@ mod tests {
     use ark_std::{Zero, test_rng};
     use rand::prelude::StdRng;
     use crate::crypto::elgamal_ext::derive_pk_from_sk;
-    use crate::infrastructure::circuit::{setup_main_proof_circuit, MainProofVerifier};
+    use crate::infrastructure::circuit::{setup_main_proof_circuit, MainProofVerifier, ProofVerificationRequest};
     use crate::infrastructure::identities::Identity;
     use crate::infrastructure::processor::{OPCODE_STORE, OPCODE_LOAD, OPCODE_KILL, OPCODE_NEW, OPCODE_ADD, OPCODE_MOV, RegOrConst, OPCODE_NOOP};
     use super::*;
@@ -666,7 +1284,9 @@ mod tests {
             sk_object: FeConverter::to_larger(&sk_object),
             addr_object,
             addr_owner: *addr_owner,
-            payload
+            payload,
+            memo: None,
+            diversifier: OuterScalarField::zero(),
         };
         let enc_record = record.encrypt(&get_pk_for_addr(addr_owner), &params.enc_params, rng).0;
 
@@ -737,6 +1357,59 @@ mod tests {
         assert_eq!(state, record_2);
     }
 
+    #[test]
+    fn test_runtime_get_state_with_opening() {
+        let rng = RefCell::new(test_rng());
+        let (record_1, _, runtime, _, addr, params, _) = init_runtime(rng, false);
+        let owner_pk = get_pk_for_addr(&addr);
+
+        let (state, opening) = runtime.get_state_with_opening(record_1.object_id, &[0, 1]).unwrap();
+        assert_eq!(state, record_1);
+        assert_eq!(opening.disclosed_payload, vec![(0, record_1.payload[0]), (1, record_1.payload[1])]);
+
+        let enc_record = runtime.ledger_state_view.borrow().tree_leaves.get(&0).unwrap().clone();
+        assert!(opening.verify(&enc_record, &owner_pk, &params.enc_params));
+
+        // a bogus disclosed payload value must not verify
+        let mut bad_opening = opening.clone();
+        bad_opening.disclosed_payload[0].1 = OuterScalarField::from(0xdead_beefu64);
+        assert!(!bad_opening.verify(&enc_record, &owner_pk, &params.enc_params));
+    }
+
+    #[test]
+    fn test_runtime_recognizes_view_only_identity_records() {
+        let mut rng = test_rng();
+        let params = CryptoParams::setup(&mut rng);
+        let iden = Identity::new_external(&mut rng, &params);
+        let (record, enc_record) = get_record(&mut rng, &iden.address, &params);
+
+        let mut runtime: Runtime<StdRng> = Runtime::new(params, None, RefCell::new(rng));
+        runtime.register_view_only_identity(iden.view_only());
+        runtime.sync_tx(0, &[], &[enc_record]);
+
+        let watch_only = runtime.get_view_only_state(record.object_id).unwrap();
+        assert_eq!(watch_only.object_id, record.object_id);
+        assert_eq!(watch_only.payload, record.payload);
+        assert!(!runtime.ledger_state_view.borrow().known_objects.contains_key(&record.object_id));
+    }
+
+    #[test]
+    fn test_runtime_recognizes_records_with_view_tags_enabled() {
+        let rng = RefCell::new(test_rng());
+        let (record_1, record_2, mut runtime, _, addr, params, _) = init_runtime(rng, false);
+        runtime.crypto_params.use_view_tags = true;
+
+        let state = runtime.get_state(record_1.object_id).unwrap();
+        assert_eq!(state, record_1);
+        let state = runtime.get_state(record_2.object_id).unwrap();
+        assert_eq!(state, record_2);
+
+        // a freshly-synced record is still recognized with the fast-rejection tag check enabled
+        let (record_3, enc_record_3) = get_record(&mut *runtime.rand.borrow_mut(), &addr, &params);
+        runtime.sync_tx(1, &[], &[enc_record_3]);
+        assert_eq!(runtime.get_state(record_3.object_id).unwrap(), record_3);
+    }
+
     #[test]
     fn test_runtime_execute_store_load() {
         let rng = RefCell::new(test_rng());
@@ -783,6 +1456,21 @@ mod tests {
         assert!(!runtime.ledger_state_view.borrow().known_objects.contains_key(&record_2.object_id));
     }
 
+    #[test]
+    #[should_panic(expected = "watch-only")]
+    fn test_runtime_execute_rejects_watch_only_sender() {
+        let mut rng = test_rng();
+        let params = CryptoParams::setup(&mut rng);
+        let iden = Identity::new_external(&mut rng, &params);
+        let addr = iden.address;
+
+        let mut runtime: Runtime<StdRng> = Runtime::new(params, None, RefCell::new(rng));
+        runtime.register_watch_identity(iden);
+
+        let current_time = OuterScalarField::from(777);
+        runtime.execute(OuterScalarField::from(123), OuterScalarField::from(7), vec![ZkInstruction::default()], vec![addr, OuterScalarField::from(0)], 0, current_time, false);
+    }
+
     #[test]
     fn test_runtime_execute_new() {
         let rng = RefCell::new(test_rng());
@@ -870,10 +1558,80 @@ mod tests {
         assert!(res.proof.is_some());
 
         let verifier = MainProofVerifier::new(verifier_key.unwrap());
-        let ok = verifier.verify(&res.unique_seed, &res.merkle_tree_root, &res.consumed_serials, &res.new_records, called_class_id, called_function_id, &program, current_time, res.proof.as_ref().unwrap());
+        let ok = verifier.verify(&res.unique_seed, &res.merkle_tree_root, res.spent_serials_root, &res.consumed_serials, &res.new_records, called_class_id, called_function_id, &program, current_time, &res.rln_share, res.proof.as_ref().unwrap());
         assert!(ok);
 
-        let ok = verifier.verify(&[99u8; RAND_BYTES], &res.merkle_tree_root, &res.consumed_serials, &res.new_records, called_class_id, called_function_id, &program, current_time, res.proof.as_ref().unwrap());
+        let ok = verifier.verify(&[99u8; RAND_BYTES], &res.merkle_tree_root, res.spent_serials_root, &res.consumed_serials, &res.new_records, called_class_id, called_function_id, &program, current_time, &res.rln_share, res.proof.as_ref().unwrap());
         assert!(!ok);
     }
+
+    #[test]
+    #[ignore]
+    fn test_runtime_real_proof_batch_verify() {
+        let rng = RefCell::new(test_rng());
+        let (_, _, mut runtime, _, addr, _, verifier_key) = init_runtime(rng, true);
+        let current_time = OuterScalarField::from(777);
+        let called_class_id = OuterScalarField::from(123);
+        let called_function_id = OuterScalarField::from(7);
+
+        let program: Vec<_> = (0..NOF_PROCESSOR_CYCLES)
+            .map(|_| ZkInstruction { opcode: OPCODE_NOOP, dst: 0, src_1: RegOrConst::Reg(0), src_2: RegOrConst::Reg(0)})
+            .collect();
+        let res_1 = runtime.execute(called_class_id, called_function_id, program.clone(), vec![addr.clone()], 0, current_time, false);
+        let res_2 = runtime.execute(called_class_id, called_function_id, program.clone(), vec![addr], 1, current_time, false);
+        assert!(res_1.proof.is_some() && res_2.proof.is_some());
+
+        let verifier = MainProofVerifier::new(verifier_key.unwrap());
+        let to_request = |res: &ExecutionResult| ProofVerificationRequest {
+            unique_seed: res.unique_seed,
+            merkle_tree_root: res.merkle_tree_root.clone(),
+            spent_serials_root: res.spent_serials_root,
+            consumed_serials: &res.consumed_serials,
+            new_records: &res.new_records,
+            called_class_id,
+            called_function_id,
+            instructions: &program,
+            current_time,
+            rln_share: &res.rln_share,
+            proof: res.proof.as_ref().unwrap(),
+        };
+
+        assert_eq!(verifier.verify_batch(&[to_request(&res_1), to_request(&res_2)]), Ok(()));
+
+        // tamper with the second request's unique_seed so only that one fails
+        let mut bad_req_2 = to_request(&res_2);
+        bad_req_2.unique_seed = [99u8; RAND_BYTES];
+        assert_eq!(verifier.verify_batch(&[to_request(&res_1), bad_req_2]), Err(vec![1]));
+    }
+
+    #[test]
+    fn test_runtime_save_and_load_from_path() {
+        let rng = RefCell::new(test_rng());
+        let (record_1, _, runtime, _, _, params, _) = init_runtime(rng, false);
+        let oid = record_1.object_id;
+
+        let path = std::env::temp_dir().join(format!("zapper_test_runtime_{}.bin", std::process::id()));
+        runtime.save_to_path(&path).unwrap();
+
+        let loaded = Runtime::load_from_path(&path, params, None, RefCell::new(test_rng())).unwrap();
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(loaded.get_nof_synced_tx(), runtime.get_nof_synced_tx());
+        assert_eq!(loaded.get_state(oid).unwrap().object_id, oid);
+    }
+
+    #[test]
+    fn test_runtime_load_from_path_rejects_truncated_file() {
+        let rng = RefCell::new(test_rng());
+        let (_, _, runtime, _, _, params, _) = init_runtime(rng, false);
+
+        let path = std::env::temp_dir().join(format!("zapper_test_runtime_corrupt_{}.bin", std::process::id()));
+        runtime.save_to_path(&path).unwrap();
+        let bytes = std::fs::read(&path).unwrap();
+        std::fs::write(&path, &bytes[..bytes.len() / 4]).unwrap();
+
+        let result = Runtime::load_from_path(&path, params, None, RefCell::new(test_rng()));
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_err());
+    }
 }
\ No newline at end of file
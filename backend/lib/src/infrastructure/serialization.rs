@@ -0,0 +1,536 @@
+//! Stable wire/storage formats for values that otherwise have none: `Serial`, the derived object
+//! ids and fresh values (`OuterScalarField` elements produced by `derivations::try_derive_fresh_*`),
+//! the `(rand, sk)` output of `derivations::derive_fresh_object_sk`, `CryptoParams`, and
+//! `Transaction` (the portable form of `runtime::ExecutionResult`).
+//!
+//! Each gets a `VersionedBinary` impl (see `versioned`), so `to_bytes`/`from_bytes`/`to_hex`/
+//! `from_hex` are available for all of them, plus `VersionedBincode`'s `to_bincode`/`from_bincode`
+//! for the ones that also carry `serde` support (`CryptoParams` and `Transaction` don't, for the
+//! same reason `MainProof` doesn't -- see `versioned`'s module doc comment). Field elements go
+//! through `DerivedFieldElement`, which round-trips them via `FeConverter::from_le_bytes`'s
+//! canonical little-endian encoding, rejecting anything that isn't the exact minimal byte string
+//! for some element of the field -- so a serialized object id or fresh value always deserializes
+//! back to the exact `OuterScalarField` it was encoded from.
+
+use std::convert::TryFrom;
+use std::io::{Read, Write};
+
+use ark_ff::{FromBytes, ToBytes};
+
+use crate::common::*;
+use crate::constants::*;
+use crate::crypto::rln::RlnShare;
+
+use super::circuit::MainProof;
+use super::params::{CryptoParams, MerkleTreeRoot};
+use super::record::{EncryptedRecord, Serial};
+use super::runtime::ExecutionResult;
+use super::versioned::VersionedBinary;
+
+/// A field element produced by one of `derivations`'s `try_derive_fresh_*` functions (an object
+/// id or a fresh value), wrapped so it can implement `VersionedBinary`/`serde` directly: bare
+/// `OuterScalarField` is a foreign type, so those impls can't live on it without this wrapper.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DerivedFieldElement(pub OuterScalarField);
+
+impl ToBytes for DerivedFieldElement {
+    fn write<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        self.0.write(writer)
+    }
+}
+
+impl FromBytes for DerivedFieldElement {
+    fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let mut bytes = [0u8; FE_BYTES];
+        reader.read_exact(&mut bytes)?;
+        FeConverter::from_le_bytes(&bytes)
+            .map(DerivedFieldElement)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "not a canonical field element"))
+    }
+}
+
+impl VersionedBinary for DerivedFieldElement {
+    const MAGIC: [u8; 4] = *b"FELT";
+
+    fn write_body<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        self.write(writer)
+    }
+
+    fn read_body<R: Read>(reader: R) -> std::io::Result<Self> {
+        Self::read(reader)
+    }
+}
+
+impl VersionedBinary for Serial {
+    const MAGIC: [u8; 4] = *b"SERL";
+
+    fn write_body<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writer.write_all(self)
+    }
+
+    fn read_body<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let mut bytes = [0u8; SN_BYTES];
+        reader.read_exact(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// The `(rand, sk)` output of `derivations::derive_fresh_object_sk`: `rand` is the randomness the
+/// derivation was run with (so it can be replayed or re-checked), `sk` is the resulting object
+/// secret key.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct FreshObjectSk {
+    pub rand: [u8; RAND_BYTES],
+    pub sk: InnerEdScalarField,
+}
+
+impl From<([u8; RAND_BYTES], InnerEdScalarField)> for FreshObjectSk {
+    fn from((rand, sk): ([u8; RAND_BYTES], InnerEdScalarField)) -> Self {
+        FreshObjectSk { rand, sk }
+    }
+}
+
+impl ToBytes for FreshObjectSk {
+    fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        writer.write_all(&self.rand)?;
+        self.sk.write(&mut writer)
+    }
+}
+
+impl FromBytes for FreshObjectSk {
+    fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let mut rand = [0u8; RAND_BYTES];
+        reader.read_exact(&mut rand)?;
+        let mut sk_bytes = [0u8; SERIALIZED_SK_BYTES];
+        reader.read_exact(&mut sk_bytes)?;
+        let sk = FeConverter::from_le_bytes(&sk_bytes)
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "not a canonical field element"))?;
+        Ok(FreshObjectSk { rand, sk })
+    }
+}
+
+impl VersionedBinary for FreshObjectSk {
+    const MAGIC: [u8; 4] = *b"FOSK";
+
+    fn write_body<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        self.write(writer)
+    }
+
+    fn read_body<R: Read>(reader: R) -> std::io::Result<Self> {
+        Self::read(reader)
+    }
+}
+
+// `CryptoParams` already has a hand-rolled `ToBytes`/`FromBytes` (see `params.rs`); it just needs
+// the magic/version framing. Like `MainProof`, its fields have no `serde` support, so it only
+// gets `VersionedBinary`, not `VersionedBincode`.
+impl VersionedBinary for CryptoParams {
+    const MAGIC: [u8; 4] = *b"PRMS";
+
+    fn write_body<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        self.write(writer)
+    }
+
+    fn read_body<R: Read>(reader: R) -> std::io::Result<Self> {
+        Self::read(reader)
+    }
+}
+
+/// The portable, on-the-wire counterpart of `ExecutionResult`: everything a relayer or an
+/// on-chain verifier needs to check and apply a transaction, with the `VersionedBinary` framing
+/// (magic + version + length) so a buffer produced for one `CryptoParams`/circuit layout can't be
+/// silently mis-parsed by a verifier built against a different one. `proof` is not optional here
+/// -- `ExecutionResult::proof` is only ever `None` for local debugging (see its doc comment), and
+/// a transaction with no proof isn't submittable -- so `ExecutionResult::serialize` rejects that
+/// case up front rather than producing a blob no verifier could check.
+#[derive(Clone)]
+pub struct Transaction {
+    pub merkle_tree_root: MerkleTreeRoot,
+    pub spent_serials_root: OuterScalarField,
+    pub current_time: OuterScalarField,
+    pub consumed_serials: Vec<Serial>,
+    pub new_records: Vec<EncryptedRecord>,
+    pub proof: MainProof,
+    pub unique_seed: [u8; RAND_BYTES],
+    pub rln_share: RlnShare,
+    pub return_value: OuterScalarField,
+}
+
+impl TryFrom<&ExecutionResult> for Transaction {
+    type Error = String;
+
+    fn try_from(result: &ExecutionResult) -> Result<Self, Self::Error> {
+        let proof = result.proof.clone().ok_or("ExecutionResult has no proof to serialize (was it produced by a debug Runtime with no proving key?)")?;
+        Ok(Transaction {
+            merkle_tree_root: result.merkle_tree_root.clone(),
+            spent_serials_root: result.spent_serials_root,
+            current_time: result.current_time,
+            consumed_serials: result.consumed_serials.clone(),
+            new_records: result.new_records.clone(),
+            proof,
+            unique_seed: result.unique_seed,
+            rln_share: result.rln_share,
+            return_value: result.return_value,
+        })
+    }
+}
+
+impl ExecutionResult {
+    /// Encodes this result as a portable `Transaction` blob, ready for submission to a relayer
+    /// or on-chain verifier. Panics if `self.proof` is `None` (i.e. this `ExecutionResult` came
+    /// from a debug `Runtime` with no proving key) -- such a result was never meant to leave the
+    /// local process, so there is nothing meaningful to serialize.
+    pub fn serialize(&self) -> Vec<u8> {
+        Transaction::try_from(self).expect("cannot serialize an ExecutionResult with no proof").to_bytes()
+    }
+}
+
+impl Transaction {
+    /// Decodes a `Transaction` previously produced by `ExecutionResult::serialize`. Rejects
+    /// truncated buffers and buffers produced for a different type or a different wire-format
+    /// version (see `VersionedBinary`).
+    pub fn deserialize(bytes: &[u8]) -> std::io::Result<Transaction> {
+        Self::from_bytes(bytes)
+    }
+}
+
+impl ToBytes for Transaction {
+    fn write<W: Write>(&self, mut writer: W) -> std::io::Result<()> {
+        self.merkle_tree_root.write(&mut writer)?;
+        self.spent_serials_root.write(&mut writer)?;
+        self.current_time.write(&mut writer)?;
+        (self.consumed_serials.len() as u64).write(&mut writer)?;
+        for serial in self.consumed_serials.iter() {
+            writer.write_all(serial)?;
+        }
+        (self.new_records.len() as u64).write(&mut writer)?;
+        for record in self.new_records.iter() {
+            record.write(&mut writer)?;
+        }
+        self.proof.write(&mut writer)?;
+        writer.write_all(&self.unique_seed)?;
+        self.rln_share.write(&mut writer)?;
+        self.return_value.write(&mut writer)?;
+        Ok(())
+    }
+}
+
+impl FromBytes for Transaction {
+    fn read<R: Read>(mut reader: R) -> std::io::Result<Self> {
+        let merkle_tree_root = MerkleTreeRoot::read(&mut reader)?;
+        let spent_serials_root = OuterScalarField::read(&mut reader)?;
+        let current_time = OuterScalarField::read(&mut reader)?;
+        let nof_consumed_serials = u64::read(&mut reader)? as usize;
+        let mut consumed_serials = Vec::with_capacity(nof_consumed_serials);
+        for _ in 0..nof_consumed_serials {
+            let mut serial: Serial = [0u8; SN_BYTES];
+            reader.read_exact(&mut serial)?;
+            consumed_serials.push(serial);
+        }
+        let nof_new_records = u64::read(&mut reader)? as usize;
+        let mut new_records = Vec::with_capacity(nof_new_records);
+        for _ in 0..nof_new_records {
+            new_records.push(EncryptedRecord::read(&mut reader)?);
+        }
+        let proof = MainProof::read(&mut reader)?;
+        let mut unique_seed = [0u8; RAND_BYTES];
+        reader.read_exact(&mut unique_seed)?;
+        let rln_share = RlnShare::read(&mut reader)?;
+        let return_value = OuterScalarField::read(&mut reader)?;
+        Ok(Transaction {
+            merkle_tree_root,
+            spent_serials_root,
+            current_time,
+            consumed_serials,
+            new_records,
+            proof,
+            unique_seed,
+            rln_share,
+            return_value,
+        })
+    }
+}
+
+impl VersionedBinary for Transaction {
+    const MAGIC: [u8; 4] = *b"ZTXN";
+
+    fn write_body<W: Write>(&self, writer: W) -> std::io::Result<()> {
+        self.write(writer)
+    }
+
+    fn read_body<R: Read>(reader: R) -> std::io::Result<Self> {
+        Self::read(reader)
+    }
+}
+
+#[cfg(feature = "serde")]
+mod serde_impl {
+    use std::convert::TryFrom;
+
+    use ark_ff::to_bytes;
+    use serde::{Serialize, Deserialize, Serializer, Deserializer};
+
+    use super::*;
+
+    #[derive(Serialize, Deserialize)]
+    struct DerivedFieldElementHex(String);
+
+    impl From<&DerivedFieldElement> for DerivedFieldElementHex {
+        fn from(elem: &DerivedFieldElement) -> Self {
+            DerivedFieldElementHex(hex::encode(to_bytes!(elem.0).unwrap()))
+        }
+    }
+
+    impl TryFrom<DerivedFieldElementHex> for DerivedFieldElement {
+        type Error = String;
+
+        fn try_from(hex: DerivedFieldElementHex) -> Result<Self, Self::Error> {
+            let bytes = hex::decode(&hex.0).map_err(|e| e.to_string())?;
+            if bytes.len() != FE_BYTES {
+                return Err(format!("expected {} bytes, got {}", FE_BYTES, bytes.len()));
+            }
+            FeConverter::from_le_bytes(&bytes)
+                .map(DerivedFieldElement)
+                .ok_or_else(|| "not a canonical field element".to_string())
+        }
+    }
+
+    impl Serialize for DerivedFieldElement {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            DerivedFieldElementHex::from(self).serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for DerivedFieldElement {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let hex = DerivedFieldElementHex::deserialize(deserializer)?;
+            DerivedFieldElement::try_from(hex).map_err(serde::de::Error::custom)
+        }
+    }
+
+    #[derive(Serialize, Deserialize)]
+    struct FreshObjectSkHex {
+        rand: String,
+        sk: String,
+    }
+
+    impl From<&FreshObjectSk> for FreshObjectSkHex {
+        fn from(fresh: &FreshObjectSk) -> Self {
+            FreshObjectSkHex {
+                rand: hex::encode(fresh.rand),
+                sk: hex::encode(to_bytes!(fresh.sk).unwrap()),
+            }
+        }
+    }
+
+    impl TryFrom<FreshObjectSkHex> for FreshObjectSk {
+        type Error = String;
+
+        fn try_from(hex: FreshObjectSkHex) -> Result<Self, Self::Error> {
+            let rand_bytes = hex::decode(&hex.rand).map_err(|e| e.to_string())?;
+            if rand_bytes.len() != RAND_BYTES {
+                return Err(format!("expected {} rand bytes, got {}", RAND_BYTES, rand_bytes.len()));
+            }
+            let mut rand = [0u8; RAND_BYTES];
+            rand.copy_from_slice(&rand_bytes);
+
+            let sk_bytes = hex::decode(&hex.sk).map_err(|e| e.to_string())?;
+            if sk_bytes.len() != SERIALIZED_SK_BYTES {
+                return Err(format!("expected {} sk bytes, got {}", SERIALIZED_SK_BYTES, sk_bytes.len()));
+            }
+            let sk = FeConverter::from_le_bytes(&sk_bytes).ok_or("not a canonical field element")?;
+
+            Ok(FreshObjectSk { rand, sk })
+        }
+    }
+
+    impl Serialize for FreshObjectSk {
+        fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+            FreshObjectSkHex::from(self).serialize(serializer)
+        }
+    }
+
+    impl<'de> Deserialize<'de> for FreshObjectSk {
+        fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+            let hex = FreshObjectSkHex::deserialize(deserializer)?;
+            FreshObjectSk::try_from(hex).map_err(serde::de::Error::custom)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ark_ec::PairingEngine;
+    use ark_gm17::Proof;
+    use ark_std::{test_rng, UniformRand};
+
+    use super::super::derivations::derive_fresh_object_sk;
+    use super::super::runtime::ExecutionResult;
+    use super::*;
+
+    // a, b, c default to the point at infinity here -- exercising the `Transaction` framing
+    // doesn't need a real proof, only `MainProof`'s existing ToBytes/FromBytes encoding (see the
+    // identical rationale for `circuit::tests::dummy_main_proof`)
+    fn dummy_main_proof() -> MainProof {
+        MainProof(Proof::<OuterPairing> {
+            a: <OuterPairing as PairingEngine>::G1Affine::default(),
+            b: <OuterPairing as PairingEngine>::G2Affine::default(),
+            c: <OuterPairing as PairingEngine>::G1Affine::default(),
+        })
+    }
+
+    fn dummy_execution_result(rng: &mut impl rand::Rng) -> ExecutionResult {
+        ExecutionResult {
+            merkle_tree_root: MerkleTreeRoot::default(),
+            spent_serials_root: OuterScalarField::rand(rng),
+            current_time: OuterScalarField::rand(rng),
+            consumed_serials: vec![[1u8; SN_BYTES], [2u8; SN_BYTES]],
+            new_records: vec![EncryptedRecord::default()],
+            proof: Some(dummy_main_proof()),
+            unique_seed: [3u8; RAND_BYTES],
+            rln_share: RlnShare { x: OuterScalarField::rand(rng), y: OuterScalarField::rand(rng), internal_nullifier: OuterScalarField::rand(rng) },
+            return_value: OuterScalarField::rand(rng),
+        }
+    }
+
+    #[test]
+    fn test_serial_round_trips_through_versioned_binary() {
+        let serial: Serial = [7u8; SN_BYTES];
+        let bytes = serial.to_bytes();
+        assert_eq!(Serial::from_bytes(&bytes).unwrap(), serial);
+        let hex = serial.to_hex();
+        assert_eq!(Serial::from_hex(&hex).unwrap(), serial);
+    }
+
+    #[test]
+    fn test_serial_rejects_truncated_buffer() {
+        let bytes = [7u8; SN_BYTES].to_bytes();
+        assert!(Serial::from_bytes(&bytes[..bytes.len() - 1]).is_err());
+    }
+
+    #[test]
+    fn test_derived_field_element_round_trips_through_versioned_binary() {
+        let mut rng = test_rng();
+        let elem = DerivedFieldElement(OuterScalarField::rand(&mut rng));
+        let bytes = elem.to_bytes();
+        assert_eq!(DerivedFieldElement::from_bytes(&bytes).unwrap(), elem);
+        let hex = elem.to_hex();
+        assert_eq!(DerivedFieldElement::from_hex(&hex).unwrap(), elem);
+    }
+
+    #[test]
+    fn test_derived_field_element_rejects_non_canonical_bytes() {
+        // the all-0xff byte string (2^(8*FE_BYTES) - 1, the largest value FE_BYTES bytes can hold)
+        // is larger than any element of the field, so it must be rejected, not reduced
+        let max_plus_one = [0xffu8; FE_BYTES];
+        assert!(FeConverter::from_le_bytes(&max_plus_one).is_none());
+        assert!(DerivedFieldElement::from_bytes(&max_plus_one).is_err());
+    }
+
+    #[test]
+    fn test_fresh_object_sk_round_trips_through_versioned_binary() {
+        let mut rng = test_rng();
+        let fresh = FreshObjectSk { rand: [3u8; RAND_BYTES], sk: InnerEdScalarField::rand(&mut rng) };
+        let bytes = fresh.to_bytes();
+        assert_eq!(FreshObjectSk::from_bytes(&bytes).unwrap(), fresh);
+        let hex = fresh.to_hex();
+        assert_eq!(FreshObjectSk::from_hex(&hex).unwrap(), fresh);
+    }
+
+    #[test]
+    fn test_crypto_params_round_trips_through_versioned_binary() {
+        let mut rng = test_rng();
+        let params = CryptoParams::setup(&mut rng);
+        let bytes = params.to_bytes();
+        let roundtripped = CryptoParams::from_bytes(&bytes).unwrap();
+        assert_eq!(roundtripped.to_bytes(), bytes);
+    }
+
+    #[cfg(feature = "serde")]
+    mod serde_tests {
+        use ark_std::{test_rng, UniformRand};
+
+        use crate::infrastructure::versioned::VersionedBincode;
+
+        use super::*;
+
+        #[test]
+        fn test_derived_field_element_round_trips_through_serde_and_bincode() {
+            let mut rng = test_rng();
+            let elem = DerivedFieldElement(OuterScalarField::rand(&mut rng));
+
+            let json = serde_json::to_string(&elem).unwrap();
+            let parsed: DerivedFieldElement = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed, elem);
+
+            let bincode = elem.to_bincode();
+            assert_eq!(DerivedFieldElement::from_bincode(&bincode).unwrap(), elem);
+        }
+
+        #[test]
+        fn test_fresh_object_sk_round_trips_through_serde_and_bincode() {
+            let mut rng = test_rng();
+            let fresh = FreshObjectSk { rand: [9u8; RAND_BYTES], sk: InnerEdScalarField::rand(&mut rng) };
+
+            let json = serde_json::to_string(&fresh).unwrap();
+            let parsed: FreshObjectSk = serde_json::from_str(&json).unwrap();
+            assert_eq!(parsed, fresh);
+
+            let bincode = fresh.to_bincode();
+            assert_eq!(FreshObjectSk::from_bincode(&bincode).unwrap(), fresh);
+        }
+    }
+
+    #[test]
+    fn test_derive_fresh_object_sk_output_converts_into_wrapper() {
+        let mut rng = test_rng();
+        let params = CryptoParams::setup(&mut rng);
+        let unique_seed = [1u8; RAND_BYTES];
+        let (rand, sk) = derive_fresh_object_sk(&mut rng, &params, 0u8, &unique_seed);
+        let fresh = FreshObjectSk::from((rand, sk));
+        assert_eq!(fresh.rand, rand);
+        assert_eq!(fresh.sk, sk);
+    }
+
+    #[test]
+    fn test_rln_share_round_trips_through_to_bytes() {
+        let mut rng = test_rng();
+        let share = RlnShare { x: OuterScalarField::rand(&mut rng), y: OuterScalarField::rand(&mut rng), internal_nullifier: OuterScalarField::rand(&mut rng) };
+        let bytes = ark_ff::to_bytes!(share).unwrap();
+        let check = RlnShare::read(bytes.as_slice()).unwrap();
+        assert_eq!(check, share);
+    }
+
+    #[test]
+    fn test_execution_result_serializes_into_transaction_round_trip() {
+        let mut rng = test_rng();
+        let result = dummy_execution_result(&mut rng);
+
+        let bytes = result.serialize();
+        let tx = Transaction::deserialize(&bytes).unwrap();
+
+        assert_eq!(tx.merkle_tree_root, result.merkle_tree_root);
+        assert_eq!(tx.spent_serials_root, result.spent_serials_root);
+        assert_eq!(tx.current_time, result.current_time);
+        assert_eq!(tx.consumed_serials, result.consumed_serials);
+        assert_eq!(tx.new_records, result.new_records);
+        assert_eq!(tx.unique_seed, result.unique_seed);
+        assert_eq!(tx.rln_share, result.rln_share);
+        assert_eq!(tx.return_value, result.return_value);
+    }
+
+    #[test]
+    fn test_execution_result_with_no_proof_cannot_be_serialized() {
+        let mut rng = test_rng();
+        let mut result = dummy_execution_result(&mut rng);
+        result.proof = None;
+
+        assert!(Transaction::try_from(&result).is_err());
+    }
+
+    #[test]
+    fn test_transaction_rejects_wrong_magic() {
+        let bytes = [7u8; SN_BYTES].to_bytes();
+        assert!(Transaction::from_bytes(&bytes).is_err());
+    }
+}
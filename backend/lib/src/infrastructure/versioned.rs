@@ -0,0 +1,176 @@
+//! Self-describing framing for this crate's hand-rolled `ToBytes`/`FromBytes` wire formats.
+//!
+//! `MainProof::read` (and every other `FromBytes` impl in this crate) trusts its input to already
+//! be exactly the bytes a matching `write` produced, in order: a stray byte, a truncated buffer,
+//! or bytes from an older/incompatible layout all get silently mis-parsed as whatever the next
+//! field happens to be (e.g. a truncated `MainProof` would read garbage into `Proof::b`'s `G2`
+//! coordinates instead of failing). `VersionedBinary` wraps any such type's existing raw encoding
+//! in a fixed header -- a 4-byte magic tag identifying the type, a version number, and an
+//! explicit body length -- so `from_reader`/`from_hex` can reject a mismatched or truncated
+//! buffer up front instead of parsing past its end.
+//!
+//! The header adds a constant 16 bytes; the body itself is still exactly the type's existing
+//! `ToBytes` encoding, so this isn't a new serialization format, just a safety wrapper around the
+//! one this crate already has. Types that also derive `serde::Serialize`/`Deserialize` (see
+//! `record::serde_impl`) get the same framing around a `bincode` body via `VersionedBincode`,
+//! for a more compact alternative to hex/JSON tooling encodings. `MainProof` and the GM17
+//! `ProvingKey`/`VerifyingKey` wrappers don't get a `VersionedBincode` impl: their fields are raw
+//! curve points with no `serde` support in this version of arkworks, so `ToBytes`'s hand-rolled
+//! encoding (wrapped by `VersionedBinary`) is the only compact format available for them.
+
+use std::io::{self, Read, Write};
+
+fn invalid_data(msg: impl Into<String>) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, msg.into())
+}
+
+fn write_versioned<W: Write>(mut writer: W, magic: &[u8; 4], version: u32, body: &[u8]) -> io::Result<()> {
+    writer.write_all(magic)?;
+    writer.write_all(&version.to_le_bytes())?;
+    writer.write_all(&(body.len() as u64).to_le_bytes())?;
+    writer.write_all(body)?;
+    Ok(())
+}
+
+fn read_versioned<R: Read>(mut reader: R, expected_magic: &[u8; 4], expected_version: u32) -> io::Result<Vec<u8>> {
+    let mut magic = [0u8; 4];
+    reader.read_exact(&mut magic).map_err(|_| invalid_data("truncated buffer: missing magic header"))?;
+    if &magic != expected_magic {
+        return Err(invalid_data(format!("wrong magic header {:?}, expected {:?}", magic, expected_magic)));
+    }
+    let mut version_bytes = [0u8; 4];
+    reader.read_exact(&mut version_bytes).map_err(|_| invalid_data("truncated buffer: missing version"))?;
+    let version = u32::from_le_bytes(version_bytes);
+    if version != expected_version {
+        return Err(invalid_data(format!("unsupported version {}, expected {}", version, expected_version)));
+    }
+    let mut len_bytes = [0u8; 8];
+    reader.read_exact(&mut len_bytes).map_err(|_| invalid_data("truncated buffer: missing body length"))?;
+    let len = u64::from_le_bytes(len_bytes) as usize;
+    let mut body = vec![0u8; len];
+    reader.read_exact(&mut body).map_err(|_| invalid_data("truncated buffer: body shorter than declared length"))?;
+    Ok(body)
+}
+
+/// Wraps `T`'s existing `ToBytes`/`FromBytes` encoding in a magic/version/length header. `MAGIC`
+/// identifies the type (so a `VerifyingKey` buffer can't be silently read as a `MainProof`);
+/// `VERSION` identifies the body layout, to be bumped whenever that layout changes incompatibly.
+pub trait VersionedBinary: Sized {
+    const MAGIC: [u8; 4];
+    const VERSION: u32 = 1;
+
+    fn write_body<W: Write>(&self, writer: W) -> io::Result<()>;
+    fn read_body<R: Read>(reader: R) -> io::Result<Self>;
+
+    fn to_writer<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        let mut body = vec![];
+        self.write_body(&mut body)?;
+        write_versioned(&mut writer, &Self::MAGIC, Self::VERSION, &body)
+    }
+
+    fn from_reader<R: Read>(reader: R) -> io::Result<Self> {
+        let body = read_versioned(reader, &Self::MAGIC, Self::VERSION)?;
+        Self::read_body(body.as_slice())
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut out = vec![];
+        self.to_writer(&mut out).expect("writing to a Vec is infallible");
+        out
+    }
+
+    fn from_bytes(bytes: &[u8]) -> io::Result<Self> {
+        Self::from_reader(bytes)
+    }
+
+    fn to_hex(&self) -> String {
+        hex::encode(self.to_bytes())
+    }
+
+    fn from_hex(s: &str) -> io::Result<Self> {
+        let bytes = hex::decode(s).map_err(|e| invalid_data(e.to_string()))?;
+        Self::from_bytes(&bytes)
+    }
+}
+
+/// A more compact alternative to `VersionedBinary::to_hex`/`from_hex` for types that also have
+/// `serde` support (see the module doc comment for why not every `VersionedBinary` type qualifies).
+#[cfg(feature = "serde")]
+pub trait VersionedBincode: VersionedBinary + serde::Serialize + serde::de::DeserializeOwned {
+    fn to_bincode(&self) -> Vec<u8> {
+        let body = bincode::serialize(self).expect("bincode serialization of an in-memory value is infallible");
+        let mut out = vec![];
+        write_versioned(&mut out, &Self::MAGIC, Self::VERSION, &body).expect("writing to a Vec is infallible");
+        out
+    }
+
+    fn from_bincode(bytes: &[u8]) -> io::Result<Self> {
+        let body = read_versioned(bytes, &Self::MAGIC, Self::VERSION)?;
+        bincode::deserialize(&body).map_err(|e| invalid_data(e.to_string()))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<T: VersionedBinary + serde::Serialize + serde::de::DeserializeOwned> VersionedBincode for T {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[derive(Debug, PartialEq, Eq)]
+    struct Dummy(u64);
+
+    impl VersionedBinary for Dummy {
+        const MAGIC: [u8; 4] = *b"DUMY";
+
+        fn write_body<W: Write>(&self, mut writer: W) -> io::Result<()> {
+            writer.write_all(&self.0.to_le_bytes())
+        }
+
+        fn read_body<R: Read>(mut reader: R) -> io::Result<Self> {
+            let mut bytes = [0u8; 8];
+            reader.read_exact(&mut bytes)?;
+            Ok(Dummy(u64::from_le_bytes(bytes)))
+        }
+    }
+
+    #[test]
+    fn test_versioned_binary_round_trips() {
+        let dummy = Dummy(0x1122334455667788);
+        let bytes = dummy.to_bytes();
+        assert_eq!(Dummy::from_bytes(&bytes).unwrap(), dummy);
+
+        let hex = dummy.to_hex();
+        assert_eq!(Dummy::from_hex(&hex).unwrap(), dummy);
+    }
+
+    #[test]
+    fn test_versioned_binary_rejects_wrong_magic() {
+        struct OtherMagic;
+        impl VersionedBinary for OtherMagic {
+            const MAGIC: [u8; 4] = *b"OTHR";
+            fn write_body<W: Write>(&self, _writer: W) -> io::Result<()> { Ok(()) }
+            fn read_body<R: Read>(_reader: R) -> io::Result<Self> { Ok(OtherMagic) }
+        }
+
+        let dummy_bytes = Dummy(42).to_bytes();
+        assert!(Dummy::from_bytes(&dummy_bytes).is_ok());
+        assert!(OtherMagic::from_bytes(&dummy_bytes).is_err());
+    }
+
+    #[test]
+    fn test_versioned_binary_rejects_truncated_buffer() {
+        let bytes = Dummy(42).to_bytes();
+        for truncate_at in 0..bytes.len() {
+            assert!(Dummy::from_bytes(&bytes[..truncate_at]).is_err(), "expected truncation at {} to fail", truncate_at);
+        }
+    }
+
+    #[test]
+    fn test_versioned_binary_rejects_wrong_version() {
+        let mut bytes = Dummy(42).to_bytes();
+        // version is the 4 bytes right after the 4-byte magic
+        bytes[4] = bytes[4].wrapping_add(1);
+        assert!(Dummy::from_bytes(&bytes).is_err());
+    }
+}
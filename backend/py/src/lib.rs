@@ -3,8 +3,11 @@ use ark_gm17::{ProvingKey, VerifyingKey};
 use pyo3::prelude::*;
 use rand::prelude::ThreadRng;
 use std::cell::RefCell;
-use ark_ff::{to_bytes, FromBytes};
-use zapper_backend_lib::{common::*, infrastructure::{params::{CryptoParams, MerkleTreeParams, MerkleTreeRoot}, runtime::{Runtime, ExecutionResult}, processor::{self, RegOrConst}, record::{Record, EncryptedRecord}, identities::Identity, circuit::{setup_main_proof_circuit, MainProofVerifier, MainProof}}, common::OuterScalarField, crypto::{sparse_merkle_tree::SparseMerkleTree}, constants::{TREE_HEIGHT, SN_BYTES}};
+use std::io::{self, Read, Write};
+use std::convert::TryInto;
+use ark_ff::{to_bytes, ToBytes, FromBytes, Zero, One};
+use blake2::{Blake2s, Digest};
+use zapper_backend_lib::{common::*, infrastructure::{params::{CryptoParams, MerkleTreeParams, MerkleTreeRoot}, runtime::{Runtime, ExecutionResult}, processor::{self, RegOrConst}, record::{Record, EncryptedRecord}, identities::Identity, circuit::{setup_main_proof_circuit, MainProofVerifier, MainProof}}, common::OuterScalarField, crypto::{sparse_merkle_tree::SparseMerkleTree}, constants::{TREE_HEIGHT, SN_BYTES, MAX_BYTES_UINT}};
 use pyo3::create_exception;
 
 create_exception!(zapper_backend, ZapperBackendError, pyo3::exceptions::PyException);
@@ -14,6 +17,10 @@ fn zapper_backend(_py: Python, m: &PyModule) -> PyResult<()> {
     m.add_function(wrap_pyfunction!(enable_logging, m)?)?;
     m.add_function(wrap_pyfunction!(trusted_setup, m)?)?;
     m.add_function(wrap_pyfunction!(new_user_account, m)?)?;
+    m.add_function(wrap_pyfunction!(new_user_account_from_seed, m)?)?;
+    m.add_function(wrap_pyfunction!(new_user_account_with_prefix, m)?)?;
+    m.add_function(wrap_pyfunction!(encode_argument, m)?)?;
+    m.add_function(wrap_pyfunction!(decode_field, m)?)?;
     m.add_class::<RuntimeInterface>()?;
     m.add_class::<ObjectState>()?;
     m.add_class::<KeyPair>()?;
@@ -58,6 +65,137 @@ fn convert_arguments(orig: Vec<String>) -> Vec<OuterScalarField> {
     orig.iter().map(|arg| fe_from_be_hex_str(arg)).collect()
 }
 
+/// Fallible counterpart of `fe_from_be_hex_str`: rejects malformed hex and out-of-range values
+/// with a `ZapperBackendError` instead of panicking, for PyO3 entry points that take hex directly
+/// from SDK callers rather than from this crate's own trusted internal encodings.
+fn try_fe_from_be_hex_str(hex_str: &str) -> PyResult<OuterScalarField> {
+    let bytes = hex::decode(hex_str).map_err(|e| ZapperBackendError::new_err(format!("invalid hex string '{}': {}", hex_str, e)))?;
+    let num = num_bigint::BigUint::from_bytes_be(&bytes);
+    let max: num_bigint::BigUint = (OuterScalarField::zero() - OuterScalarField::one()).into();
+    if num > max {
+        return Err(ZapperBackendError::new_err(format!("hex value '{}' is too large to fit in a field element", hex_str)));
+    }
+    Ok(OuterScalarField::from(num))
+}
+
+/// Accepts either this crate's usual big-endian hex encoding or a plain Python int, so a
+/// plain-field-valued parameter (e.g. `current_time`) can be passed as a native integer instead
+/// of a hand-formatted hex string, while still accepting the hex form existing callers already use.
+#[derive(FromPyObject)]
+enum FieldArg {
+    Hex(String),
+    Int(i128),
+}
+
+impl FieldArg {
+    fn to_field(&self) -> PyResult<OuterScalarField> {
+        match self {
+            FieldArg::Hex(s) => try_fe_from_be_hex_str(s),
+            FieldArg::Int(n) => {
+                if *n < 0 {
+                    return Err(ZapperBackendError::new_err(format!("expected a non-negative integer, got {}", n)));
+                }
+                Ok(OuterScalarField::from(*n as u128))
+            }
+        }
+    }
+}
+
+/// Which native Python type `encode_argument`/`decode_field` should read or produce -- the typed
+/// alternative to callers hand-picking a hex encoding convention for each processor argument.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum ArgKind {
+    Int,
+    Bool,
+    Field,
+    Address,
+    Bytes,
+}
+
+impl ArgKind {
+    fn parse(kind: &str) -> PyResult<ArgKind> {
+        match kind {
+            "int" => Ok(ArgKind::Int),
+            "bool" => Ok(ArgKind::Bool),
+            "field" => Ok(ArgKind::Field),
+            "address" => Ok(ArgKind::Address),
+            "bytes" => Ok(ArgKind::Bytes),
+            _ => Err(ZapperBackendError::new_err(format!("unknown argument kind '{}', expected one of: int, bool, field, address, bytes", kind))),
+        }
+    }
+}
+
+/// Native Python value accepted by `encode_argument`. `Field`/`Address`/`Bytes` kinds take an
+/// already-hex-encoded string (this crate's usual convention for byte-ish values), since those
+/// don't have a lossless native Python representation; `Int`/`Bool` take the obvious native type.
+/// `Bool` is listed before `Int` because Python's `bool` is itself an `int` subclass, and PyO3
+/// tries `FromPyObject` variants in declaration order.
+#[derive(FromPyObject)]
+enum ArgValue {
+    Bool(bool),
+    Int(i128),
+    Hex(String),
+}
+
+/// Encodes `value` (tagged by `kind`) into this crate's big-endian hex field-element encoding,
+/// validating that `value` actually matches and fits the declared kind instead of panicking like
+/// `fe_from_be_hex_str`/`convert_arguments` do on malformed or out-of-range input.
+#[pyfunction]
+fn encode_argument(value: ArgValue, kind: &str) -> PyResult<String> {
+    let kind = ArgKind::parse(kind)?;
+    let fe = match (kind, &value) {
+        (ArgKind::Int, ArgValue::Int(n)) => {
+            if *n < 0 {
+                return Err(ZapperBackendError::new_err("'int' arguments must be non-negative"));
+            }
+            let max = (num_bigint::BigUint::from(1u8) << (MAX_BYTES_UINT * 8)) - num_bigint::BigUint::from(1u8);
+            let num = num_bigint::BigUint::from(*n as u128);
+            if num > max {
+                return Err(ZapperBackendError::new_err(format!("'int' value {} does not fit in {} bytes", n, MAX_BYTES_UINT)));
+            }
+            OuterScalarField::from(num)
+        }
+        (ArgKind::Bool, ArgValue::Bool(b)) => OuterScalarField::from(*b as u8),
+        (ArgKind::Field, ArgValue::Hex(s)) | (ArgKind::Address, ArgValue::Hex(s)) => try_fe_from_be_hex_str(s)?,
+        (ArgKind::Bytes, ArgValue::Hex(s)) => try_fe_from_be_hex_str(s)?,
+        _ => return Err(ZapperBackendError::new_err(format!("value does not match declared kind '{:?}'", kind))),
+    };
+    Ok(fe_to_be_hex_str(&fe))
+}
+
+/// Decodes a big-endian hex field element (tagged by `kind`) back into a native Python value, the
+/// inverse of `encode_argument`.
+#[pyfunction]
+fn decode_field(hex_str: String, kind: &str) -> PyResult<PyObject> {
+    let kind = ArgKind::parse(kind)?;
+    let fe = try_fe_from_be_hex_str(&hex_str)?;
+    let num: num_bigint::BigUint = fe.into();
+    Python::with_gil(|py| match kind {
+        ArgKind::Int => {
+            let max = num_bigint::BigUint::from(1u8) << (MAX_BYTES_UINT * 8);
+            if num >= max {
+                return Err(ZapperBackendError::new_err(format!("field value {} does not fit in a {}-byte 'int'", num, MAX_BYTES_UINT)));
+            }
+            let n: u128 = num.try_into().expect("already checked against MAX_BYTES_UINT*8 bits");
+            Ok((n as i128).into_py(py))
+        }
+        ArgKind::Bool => {
+            if num > num_bigint::BigUint::from(1u8) {
+                return Err(ZapperBackendError::new_err("field value is not 0 or 1, cannot decode as 'bool'"));
+            }
+            Ok((num == num_bigint::BigUint::from(1u8)).into_py(py))
+        }
+        ArgKind::Field | ArgKind::Address | ArgKind::Bytes => Ok(fe_to_be_hex_str(&fe).into_py(py)),
+    })
+}
+
+/// Masks a hex-encoded secret for human-facing display, keeping only a short fingerprint so a
+/// `__str__`/`__repr__` can't leak key material into logs, notebooks, or tracebacks by accident.
+fn redact_hex_secret(hex: &str) -> String {
+    let fingerprint_len = 6.min(hex.len());
+    format!("***{}", &hex[hex.len() - fingerprint_len..])
+}
+
 #[pyclass(name="ObjectState")]
 struct ObjectState {
     #[pyo3(get, set)]
@@ -90,6 +228,24 @@ impl ObjectState {
 #[pymethods]
 impl ObjectState {
     fn __str__(self_: PyRef<Self>) -> String {
+        format!(
+            "{{\n contract_id: {}\n object_id: {}\n sk_object: {}\n addr_object: {}\n addr_owner: {}\n payload: {:?}\n}}",
+            self_.contract_id,
+            self_.object_id,
+            redact_hex_secret(&self_.sk_object),
+            self_.addr_object,
+            self_.addr_owner,
+            self_.payload
+        )
+    }
+
+    fn __repr__(self_: PyRef<Self>) -> String {
+        ObjectState::__str__(self_)
+    }
+
+    /// Returns the same human-readable form as `__str__`, but with `sk_object` in the clear.
+    /// Only call this when you deliberately need to display the raw secret.
+    fn reveal(self_: PyRef<Self>) -> String {
         format!(
             "{{\n contract_id: {}\n object_id: {}\n sk_object: {}\n addr_object: {}\n addr_owner: {}\n payload: {:?}\n}}",
             self_.contract_id,
@@ -126,11 +282,169 @@ impl KeyPair {
     }
 }
 
+#[pymethods]
+impl KeyPair {
+    fn __str__(self_: PyRef<Self>) -> String {
+        format!(
+            "{{\n secret_key: {}\n public_key: {:?}\n address: {}\n}}",
+            redact_hex_secret(&self_.secret_key),
+            self_.public_key,
+            self_.address
+        )
+    }
+
+    fn __repr__(self_: PyRef<Self>) -> String {
+        KeyPair::__str__(self_)
+    }
+
+    /// Returns the same human-readable form as `__str__`, but with `secret_key` in the clear.
+    /// Only call this when you deliberately need to display the raw secret.
+    fn reveal(self_: PyRef<Self>) -> String {
+        format!(
+            "{{\n secret_key: {}\n public_key: {:?}\n address: {}\n}}",
+            self_.secret_key,
+            self_.public_key,
+            self_.address
+        )
+    }
+}
+
+/// Semantic version embedded in every serialized `ExportedCryptoParams` artifact. `load` refuses
+/// to deserialize a file whose `major` differs from this build's own, since a circuit layout
+/// change between majors would otherwise silently produce a prover/verifier pairing that looks
+/// valid but proves nothing meaningful.
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+struct SpecVersion {
+    major: u16,
+    minor: u16,
+    patch: u16,
+}
+
+impl SpecVersion {
+    fn current() -> SpecVersion {
+        SpecVersion {
+            major: env!("CARGO_PKG_VERSION_MAJOR").parse().expect("CARGO_PKG_VERSION_MAJOR is a valid u16"),
+            minor: env!("CARGO_PKG_VERSION_MINOR").parse().expect("CARGO_PKG_VERSION_MINOR is a valid u16"),
+            patch: env!("CARGO_PKG_VERSION_PATCH").parse().expect("CARGO_PKG_VERSION_PATCH is a valid u16"),
+        }
+    }
+
+    fn write<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.major.to_le_bytes())?;
+        writer.write_all(&self.minor.to_le_bytes())?;
+        writer.write_all(&self.patch.to_le_bytes())
+    }
+
+    fn read<R: Read>(mut reader: R) -> io::Result<SpecVersion> {
+        let mut buf = [0u8; 2];
+        reader.read_exact(&mut buf)?;
+        let major = u16::from_le_bytes(buf);
+        reader.read_exact(&mut buf)?;
+        let minor = u16::from_le_bytes(buf);
+        reader.read_exact(&mut buf)?;
+        let patch = u16::from_le_bytes(buf);
+        Ok(SpecVersion { major, minor, patch })
+    }
+}
+
+/// Canonical hash of a `CryptoParams`'s `ToBytes` encoding, used to tie a saved prover/verifier
+/// key pair to the exact `general_params` they were generated from (see `ExportedCryptoParams`).
+fn hash_general_params(general_params: &CryptoParams) -> [u8; 32] {
+    let mut hasher = Blake2s::new();
+    hasher.update(&to_bytes![general_params].expect("serializing CryptoParams is infallible"));
+    let mut out = [0u8; 32];
+    out.copy_from_slice(&hasher.finalize());
+    out
+}
+
+fn write_length_prefixed<W: Write>(mut writer: W, bytes: &[u8]) -> io::Result<()> {
+    writer.write_all(&(bytes.len() as u64).to_le_bytes())?;
+    writer.write_all(bytes)
+}
+
+fn read_length_prefixed(reader: &mut &[u8]) -> io::Result<Vec<u8>> {
+    let mut len_bytes = [0u8; 8];
+    reader.read_exact(&mut len_bytes)?;
+    let len = u64::from_le_bytes(len_bytes) as usize;
+    if reader.len() < len {
+        return Err(io::Error::new(io::ErrorKind::InvalidData, "truncated crypto params file: field shorter than its declared length"));
+    }
+    let (field, rest) = reader.split_at(len);
+    *reader = rest;
+    Ok(field.to_vec())
+}
+
+fn write_optional_key<T: ToBytes, W: Write>(mut writer: W, key: &Option<T>) -> io::Result<()> {
+    match key {
+        Some(k) => {
+            writer.write_all(&[1])?;
+            write_length_prefixed(writer, &to_bytes![k].expect("serializing a GM17 key is infallible"))
+        }
+        None => writer.write_all(&[0]),
+    }
+}
+
+fn read_optional_key<T: FromBytes>(reader: &mut &[u8]) -> io::Result<Option<T>> {
+    let mut flag = [0u8; 1];
+    reader.read_exact(&mut flag)?;
+    if flag[0] == 0 {
+        return Ok(None);
+    }
+    let bytes = read_length_prefixed(reader)?;
+    Ok(Some(T::read(bytes.as_slice())?))
+}
+
 #[pyclass(name="CryptoParameters")]
 struct ExportedCryptoParams {
     general_params: CryptoParams,
     prover_key: Option<ProvingKey<OuterPairing>>,
-    verifier_key: Option<VerifyingKey<OuterPairing>>
+    verifier_key: Option<VerifyingKey<OuterPairing>>,
+    params_hash: [u8; 32]
+}
+
+#[pymethods]
+impl ExportedCryptoParams {
+    #[pyo3(text_signature = "(self, path)")]
+    fn save(&self, path: String) -> PyResult<()> {
+        let mut out = vec![];
+        SpecVersion::current().write(&mut out).map_err(|e| ZapperBackendError::new_err(format!("could not write version header: {}", e)))?;
+        out.write_all(&self.params_hash).map_err(|e| ZapperBackendError::new_err(format!("could not write params hash: {}", e)))?;
+        write_length_prefixed(&mut out, &to_bytes![self.general_params].map_err(|e| ZapperBackendError::new_err(format!("could not serialize general params: {}", e)))?)
+            .map_err(|e| ZapperBackendError::new_err(format!("could not write general params: {}", e)))?;
+        write_optional_key(&mut out, &self.prover_key).map_err(|e| ZapperBackendError::new_err(format!("could not write prover key: {}", e)))?;
+        write_optional_key(&mut out, &self.verifier_key).map_err(|e| ZapperBackendError::new_err(format!("could not write verifier key: {}", e)))?;
+        std::fs::write(path, out).map_err(|e| ZapperBackendError::new_err(format!("could not write crypto params file: {}", e)))
+    }
+
+    #[staticmethod]
+    #[pyo3(text_signature = "(path)")]
+    fn load(path: String) -> PyResult<ExportedCryptoParams> {
+        let bytes = std::fs::read(path).map_err(|e| ZapperBackendError::new_err(format!("could not read crypto params file: {}", e)))?;
+        let mut reader = bytes.as_slice();
+        let version = SpecVersion::read(&mut reader).map_err(|e| ZapperBackendError::new_err(format!("could not read version header: {}", e)))?;
+        if version.major != SpecVersion::current().major {
+            return Err(ZapperBackendError::new_err(format!("incompatible crypto params file: saved with spec version {:?}, this build is {:?}", version, SpecVersion::current())));
+        }
+        let mut params_hash = [0u8; 32];
+        reader.read_exact(&mut params_hash).map_err(|e| ZapperBackendError::new_err(format!("could not read params hash: {}", e)))?;
+        let general_params_bytes = read_length_prefixed(&mut reader).map_err(|e| ZapperBackendError::new_err(format!("could not read general params: {}", e)))?;
+        let general_params = CryptoParams::read(general_params_bytes.as_slice()).map_err(|e| ZapperBackendError::new_err(format!("could not deserialize general params: {}", e)))?;
+        if hash_general_params(&general_params) != params_hash {
+            return Err(ZapperBackendError::new_err("crypto params file is corrupted: general params do not match the embedded hash"));
+        }
+        let prover_key = read_optional_key(&mut reader).map_err(|e| ZapperBackendError::new_err(format!("could not deserialize prover key: {}", e)))?;
+        let verifier_key = read_optional_key(&mut reader).map_err(|e| ZapperBackendError::new_err(format!("could not deserialize verifier key: {}", e)))?;
+        Ok(ExportedCryptoParams { general_params, prover_key, verifier_key, params_hash })
+    }
+}
+
+/// Fails if `crypto_params`'s stored `general_params` no longer matches its embedded
+/// `params_hash`, so a prover key can never end up silently paired with mismatched params.
+fn check_params_hash(crypto_params: &ExportedCryptoParams) -> PyResult<()> {
+    if hash_general_params(&crypto_params.general_params) != crypto_params.params_hash {
+        return Err(ZapperBackendError::new_err("crypto params hash mismatch: prover/verifier key may be paired with mismatched general params"));
+    }
+    Ok(())
 }
 
 #[pyfunction(dbg_no_circuit_setup = "false")]
@@ -147,10 +461,12 @@ fn trusted_setup(dbg_no_circuit_setup: bool) -> ExportedCryptoParams {
         prover_key = Some(keys.0);
         verifier_key = Some(keys.1);
     }
+    let params_hash = hash_general_params(&general_params);
     ExportedCryptoParams {
         general_params,
         prover_key,
-        verifier_key
+        verifier_key,
+        params_hash
     }
 }
 
@@ -162,6 +478,20 @@ fn new_user_account(crypto_params: PyRef<ExportedCryptoParams>) -> PyResult<KeyP
     Ok(key_pair)
 }
 
+#[pyfunction]
+fn new_user_account_from_seed(crypto_params: PyRef<ExportedCryptoParams>, seed_hex: String) -> PyResult<KeyPair> {
+    let seed = hex::decode(&seed_hex).map_err(|e| ZapperBackendError::new_err(format!("invalid seed hex: {}", e)))?;
+    let identity = Identity::from_seed(&crypto_params.general_params, &seed);
+    Ok(KeyPair::from_identity(&identity))
+}
+
+#[pyfunction]
+fn new_user_account_with_prefix(crypto_params: PyRef<ExportedCryptoParams>, hex_prefix: String) -> PyResult<KeyPair> {
+    let rng_borrowed = &mut rand::thread_rng();
+    let identity = Identity::new_external_with_prefix(rng_borrowed, &crypto_params.general_params, &hex_prefix);
+    Ok(KeyPair::from_identity(&identity))
+}
+
 #[pyclass(name="ExecutionResult")]
 struct ExportedExecutionResult {
     #[pyo3(get, set)]
@@ -205,6 +535,7 @@ struct RuntimeInterface {
 impl RuntimeInterface {
     #[new]
     fn new(crypto_params: PyRef<ExportedCryptoParams>) -> PyResult<RuntimeInterface> {
+        check_params_hash(&crypto_params)?;
         let rng = RefCell::new(rand::thread_rng());
         let params = crypto_params.general_params.clone();
         let runtime = Runtime::new(params.clone(), crypto_params.prover_key.clone(), rng);
@@ -214,18 +545,23 @@ impl RuntimeInterface {
         })
     }
 
-    #[args(dbg_sync_immediately = "false")]
-    #[pyo3(text_signature = "(self, program, arguments, current_time, dbg_sync_immediately)")]
+    #[args(dbg_sync_immediately = "false", typed_arguments = "None")]
+    #[pyo3(text_signature = "(self, called_class_id, called_function_id, program, arguments, return_register, current_time, dbg_sync_immediately, typed_arguments)")]
     fn execute(mut self_: PyRefMut<Self>,
         called_class_id: String,
         called_function_id: String,
         program: Vec<Instruction>,
         arguments: Vec<String>,
         return_register: usize,
-        current_time: String,
-        dbg_sync_immediately: bool
+        current_time: FieldArg,
+        dbg_sync_immediately: bool,
+        typed_arguments: Option<Vec<(ArgValue, String)>>
     ) -> PyResult<ExportedExecutionResult> {
-        let res = self_.runtime.execute(fe_from_be_hex_str(&called_class_id), fe_from_be_hex_str(&called_function_id), convert_instructions(program), convert_arguments(arguments), return_register, fe_from_be_hex_str(&current_time), dbg_sync_immediately);
+        let arguments = match typed_arguments {
+            Some(typed) => typed.into_iter().map(|(value, kind)| encode_argument(value, &kind)).collect::<PyResult<Vec<_>>>()?,
+            None => arguments,
+        };
+        let res = self_.runtime.execute(fe_from_be_hex_str(&called_class_id), fe_from_be_hex_str(&called_function_id), convert_instructions(program), convert_arguments(arguments), return_register, current_time.to_field()?, dbg_sync_immediately);
         Ok(ExportedExecutionResult::from(res))
     }
 
@@ -250,6 +586,38 @@ impl RuntimeInterface {
         Ok(key_pair)
     }
 
+    #[pyo3(text_signature = "(self, seed_hex)")]
+    fn new_user_account_from_seed(mut self_: PyRefMut<Self>, seed_hex: String) -> PyResult<KeyPair> {
+        let seed = hex::decode(&seed_hex).map_err(|e| ZapperBackendError::new_err(format!("invalid seed hex: {}", e)))?;
+        let identity = Identity::from_seed(&self_.params, &seed);
+        let key_pair = KeyPair::from_identity(&identity);
+        self_.runtime.register_identity(identity);
+        Ok(key_pair)
+    }
+
+    #[pyo3(text_signature = "(self, hex_prefix)")]
+    fn new_user_account_with_prefix(mut self_: PyRefMut<Self>, hex_prefix: String) -> PyResult<KeyPair> {
+        let identity;
+        {
+            let rng_borrowed: &mut ThreadRng = &mut self_.runtime.rand.borrow_mut();
+            identity = Identity::new_external_with_prefix(rng_borrowed, &self_.params, &hex_prefix);
+        }
+        let key_pair = KeyPair::from_identity(&identity);
+        self_.runtime.register_identity(identity);
+        Ok(key_pair)
+    }
+
+    /// Re-derives a previously backed-up account from its recovery seed/phrase and re-registers
+    /// it, so a lost `KeyPair` can be recovered without needing the runtime's persisted state.
+    #[pyo3(text_signature = "(self, seed_hex)")]
+    fn recover_user_account(mut self_: PyRefMut<Self>, seed_hex: String) -> PyResult<KeyPair> {
+        let seed = hex::decode(&seed_hex).map_err(|e| ZapperBackendError::new_err(format!("invalid seed hex: {}", e)))?;
+        let identity = Identity::from_seed(&self_.params, &seed);
+        let key_pair = KeyPair::from_identity(&identity);
+        self_.runtime.register_identity(identity);
+        Ok(key_pair)
+    }
+
     #[pyo3(text_signature = "(self, keys)")]
     fn register_account(mut self_: PyRefMut<Self>, keys: PyRef<KeyPair>) -> PyResult<()> {
         self_.runtime.register_identity(keys.to_identity());
@@ -282,6 +650,22 @@ impl RuntimeInterface {
         self_.runtime.sync_tx(tx_idx, &serials, &records);
         Ok(())
     }
+
+    #[pyo3(text_signature = "(self, path)")]
+    fn save_to_path(self_: PyRef<Self>, path: String) -> PyResult<()> {
+        self_.runtime.save_to_path(path).map_err(|e| ZapperBackendError::new_err(format!("could not save runtime state: {}", e)))
+    }
+
+    #[staticmethod]
+    #[pyo3(text_signature = "(crypto_params, path)")]
+    fn load_from_path(crypto_params: PyRef<ExportedCryptoParams>, path: String) -> PyResult<RuntimeInterface> {
+        check_params_hash(&crypto_params)?;
+        let rng = RefCell::new(rand::thread_rng());
+        let params = crypto_params.general_params.clone();
+        let runtime = Runtime::load_from_path(path, params.clone(), crypto_params.prover_key.clone(), rng)
+            .map_err(|e| ZapperBackendError::new_err(format!("could not load runtime state: {}", e)))?;
+        Ok(RuntimeInterface { runtime, params })
+    }
 }
 
 #[pyclass(name="MerkleTree",unsendable)]   // NOTE: the class will panic if accessed from different thread
@@ -331,6 +715,7 @@ struct VerifierInterface {
 impl VerifierInterface {
     #[new]
     fn new(crypto_params: PyRef<ExportedCryptoParams>) -> PyResult<VerifierInterface> {
+        check_params_hash(&crypto_params)?;
         if let Some(verifier_key) = &crypto_params.verifier_key {
             return Ok(VerifierInterface {
                 verifier: MainProofVerifier::new(verifier_key.clone())
@@ -347,7 +732,7 @@ impl VerifierInterface {
         called_class_id: String,
         called_function_id: String,
         instructions: Vec<Instruction>,
-        current_time: String,
+        current_time: FieldArg,
         proof: String
     ) -> PyResult<bool> {
         let unique_seed = decode_hex_byte_array(&unique_seed);
@@ -357,7 +742,7 @@ impl VerifierInterface {
         let instructions = convert_instructions(instructions);
         let called_class_id = fe_from_be_hex_str(&called_class_id);
         let called_function_id = fe_from_be_hex_str(&called_function_id);
-        let current_time = fe_from_be_hex_str(&current_time);
+        let current_time = current_time.to_field()?;
         let proof = MainProof::read(hex::decode(proof).unwrap().as_slice()).unwrap();
         let res = self_.verifier.verify(&unique_seed, &merkle_tree_root, &consumed_serials, &new_records, called_class_id, called_function_id, &instructions, current_time, &proof);
         Ok(res)